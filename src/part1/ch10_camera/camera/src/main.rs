@@ -0,0 +1,238 @@
+#![allow(unreachable_code, unused_variables, unused_imports)]
+
+use std::f32::consts::FRAC_PI_4;
+use std::io::Cursor;
+use std::mem::{size_of, size_of_val};
+use std::sync::Arc;
+
+use anyhow::Result;
+use gl::*;
+use gl::types::*;
+use glutin::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+use glutin::window::CursorGrabMode;
+use image::DynamicImage;
+use image::io::Reader as ImgReader;
+use nalgebra::{Matrix4, Point3, Rotation3, Translation3, Vector3};
+
+use learnopengl_utils as utils;
+use utils::app::{App, AppConfig, AppContext};
+use utils::camera::{Camera, CameraController, FpsController, FreeFlyController, OrbitController, Projection};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+
+const CUBE_POSITIONS: [[f32; 3]; 10] = [
+    [0.0, 0.0, 0.0],
+    [2.0, 5.0, -15.0],
+    [-1.5, -2.2, -2.5],
+    [-3.8, -2.0, -12.3],
+    [2.4, -0.4, -3.5],
+    [-1.7, 3.0, -7.5],
+    [1.3, -2.0, -2.5],
+    [1.5, 2.0, -2.5],
+    [1.5, 0.2, -1.5],
+    [-1.3, 1.0, -1.5],
+];
+
+struct CameraSample {
+    shader: utils::Shader<'static>,
+    camera: Camera,
+    controller: Box<dyn CameraController>,
+}
+
+impl CameraSample {
+    /// `1` walks, `2` orbits the first cube and `3` flies, the mouse is grabbed except when orbiting.
+    fn switch_controller(&mut self, ctx: &mut AppContext, key: VirtualKeyCode) {
+        let (controller, grab): (Box<dyn CameraController>, bool) = match key {
+            VirtualKeyCode::Key1 => (Box::new(FpsController::default()), true),
+            VirtualKeyCode::Key2 => (Box::new(OrbitController::default()), false),
+            VirtualKeyCode::Key3 => (Box::new(FreeFlyController::default()), true),
+            _ => return,
+        };
+        self.controller = controller;
+        self.camera.position = Point3::new(0.0, 0.0, 3.0);
+        self.camera.look_at(&Point3::origin(), &Vector3::y());
+
+        if let Some(window) = ctx.gl.window() {
+            let mode = if grab { CursorGrabMode::Confined } else { CursorGrabMode::None };
+            window.set_cursor_grab(mode).ok();
+            window.set_cursor_visible(!grab);
+        }
+    }
+}
+
+impl App for CameraSample {
+    fn init(ctx: &mut AppContext) -> Result<Self> {
+        ctx.input.set_actions(utils::camera::camera_actions());
+
+        let stride = 5;
+        let vertices: &[f32] = &[
+            // position        // texture coords
+            -0.5, -0.5, -0.5,  0.0, 0.0,
+             0.5, -0.5, -0.5,  1.0, 0.0,
+             0.5,  0.5, -0.5,  1.0, 1.0,
+             0.5,  0.5, -0.5,  1.0, 1.0,
+            -0.5,  0.5, -0.5,  0.0, 1.0,
+            -0.5, -0.5, -0.5,  0.0, 0.0,
+
+            -0.5, -0.5,  0.5,  0.0, 0.0,
+             0.5, -0.5,  0.5,  1.0, 0.0,
+             0.5,  0.5,  0.5,  1.0, 1.0,
+             0.5,  0.5,  0.5,  1.0, 1.0,
+            -0.5,  0.5,  0.5,  0.0, 1.0,
+            -0.5, -0.5,  0.5,  0.0, 0.0,
+
+            -0.5,  0.5,  0.5,  1.0, 0.0,
+            -0.5,  0.5, -0.5,  1.0, 1.0,
+            -0.5, -0.5, -0.5,  0.0, 1.0,
+            -0.5, -0.5, -0.5,  0.0, 1.0,
+            -0.5, -0.5,  0.5,  0.0, 0.0,
+            -0.5,  0.5,  0.5,  1.0, 0.0,
+
+             0.5,  0.5,  0.5,  1.0, 0.0,
+             0.5,  0.5, -0.5,  1.0, 1.0,
+             0.5, -0.5, -0.5,  0.0, 1.0,
+             0.5, -0.5, -0.5,  0.0, 1.0,
+             0.5, -0.5,  0.5,  0.0, 0.0,
+             0.5,  0.5,  0.5,  1.0, 0.0,
+
+            -0.5, -0.5, -0.5,  0.0, 1.0,
+             0.5, -0.5, -0.5,  1.0, 1.0,
+             0.5, -0.5,  0.5,  1.0, 0.0,
+             0.5, -0.5,  0.5,  1.0, 0.0,
+            -0.5, -0.5,  0.5,  0.0, 0.0,
+            -0.5, -0.5, -0.5,  0.0, 1.0,
+
+            -0.5,  0.5, -0.5,  0.0, 1.0,
+             0.5,  0.5, -0.5,  1.0, 1.0,
+             0.5,  0.5,  0.5,  1.0, 0.0,
+             0.5,  0.5,  0.5,  1.0, 0.0,
+            -0.5,  0.5,  0.5,  0.0, 0.0,
+            -0.5,  0.5, -0.5,  0.0, 1.0,
+        ];
+        let mut vbo = 0;
+        let mut vao = 0;
+        unsafe {
+            GenBuffers(1, &mut vbo);
+            assert_ne!(vbo, 0);
+            GenVertexArrays(1, &mut vao);
+            assert_ne!(vao, 0);
+
+            BindVertexArray(vao);
+            BindBuffer(ARRAY_BUFFER, vbo);
+            BufferData(
+                ARRAY_BUFFER,
+                size_of_val(vertices) as _,
+                vertices.as_ptr() as *const _,
+                STATIC_DRAW);
+
+            VertexAttribPointer(
+                0, 3, FLOAT, FALSE,
+                stride * size_of::<f32>() as i32,
+                std::ptr::null(),
+            );
+            EnableVertexAttribArray(0);
+            VertexAttribPointer(
+                1, 2, FLOAT, FALSE,
+                stride * size_of::<f32>() as i32,
+                (3 * size_of::<f32>()) as *const _,
+            );
+            EnableVertexAttribArray(1);
+
+            Enable(DEPTH_TEST);
+        }
+
+        fn load_texture(data: &[u8]) -> Result<GLuint> {
+            let img = ImgReader::new(Cursor::new(data))
+                .with_guessed_format()?
+                .decode()?
+                .flipv();  // flip the image so that fit into the opengl coordination.
+            let (w, h) = (img.width(), img.height());
+            let format = match img {
+                DynamicImage::ImageRgb8(_) => RGB,
+                DynamicImage::ImageRgba8(_) => RGBA,
+                _ => { unimplemented!() }
+            };
+
+            let mut tex = 0;
+            unsafe {
+                GenTextures(1, &mut tex);
+                assert_ne!(tex, 0);
+
+                BindTexture(TEXTURE_2D, tex);
+                TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR_MIPMAP_LINEAR as _);
+                TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as _);
+                TexImage2D(
+                    TEXTURE_2D, 0, format as _,
+                    w as _, h as _, 0,
+                    format, UNSIGNED_BYTE,
+                    img.as_bytes().as_ptr() as *const _,
+                );
+                GenerateMipmap(TEXTURE_2D);
+            }
+
+            Ok(tex)
+        }
+
+        let tex1 = load_texture(include_bytes!("textures/wall.jpg"))?;
+        let tex2 = load_texture(include_bytes!("textures/awesomeface.png"))?;
+
+        unsafe {
+            ActiveTexture(TEXTURE0);
+            BindTexture(TEXTURE_2D, tex1);
+
+            ActiveTexture(TEXTURE1);
+            BindTexture(TEXTURE_2D, tex2);
+        }
+
+        let shader = utils::Shader::new(
+            include_str!("shaders/shader1.vs.glsl"),
+            include_str!("shaders/shader1.fg.glsl"),
+        );
+
+        shader.activate().ok();
+        shader.set_i32("texture1", (0, )).ok();
+        shader.set_i32("texture2", (1, )).ok();
+
+        let camera = Camera::new(Point3::new(0.0, 0.0, 3.0), Projection::perspective(FRAC_PI_4, ctx.aspect_ratio(), 0.1, 100.0));
+        let mut sample = Self { shader, camera, controller: Box::new(FpsController::default()) };
+        sample.switch_controller(ctx, VirtualKeyCode::Key1);
+        Ok(sample)
+    }
+
+    fn update(&mut self, ctx: &mut AppContext, dt: f32) {
+        self.controller.update(&mut self.camera, &ctx.input, dt);
+    }
+
+    fn render(&mut self, _ctx: &mut AppContext) {
+        self.shader.set_mat4fv("view_projection", Arc::new(self.camera.view_projection())).unwrap();
+
+        for (i, position) in CUBE_POSITIONS.iter().enumerate() {
+            let angle = (20.0 * i as f32).to_radians();
+            let axis = Vector3::new(1.0, 0.3, 0.5).normalize();
+            let model = Translation3::new(position[0], position[1], position[2]).to_homogeneous()
+                * Rotation3::new(axis * angle).to_homogeneous();
+
+            self.shader.set_mat4fv("model", Arc::new(model)).unwrap();
+            unsafe {
+                utils::gl_check!(DrawArrays(TRIANGLES, 0, 36));
+            }
+        }
+    }
+
+    fn on_event(&mut self, ctx: &mut AppContext, event: &WindowEvent) {
+        if let WindowEvent::KeyboardInput {
+            input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. }, ..
+        } = event {
+            self.switch_controller(ctx, *key);
+        }
+    }
+
+    fn on_resize(&mut self, _ctx: &mut AppContext, width: u32, height: u32) {
+        self.camera.projection.set_aspect(width as f32 / height.max(1) as f32);
+    }
+}
+
+fn main() -> Result<()> {
+    utils::app::run::<CameraSample>(AppConfig::new(WIDTH, HEIGHT).with_title("Camera"))
+}
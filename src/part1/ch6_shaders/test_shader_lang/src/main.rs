@@ -6,182 +6,128 @@ use std::ops::Add;
 use std::thread;
 use std::time::{Duration, Instant};
 
+use anyhow::Result;
 use chrono::{Timelike, TimeZone};
 use gl::*;
-use glutin::event::{Event, WindowEvent};
-use glutin::event_loop::ControlFlow;
+use gl::types::*;
 
 use learnopengl_utils as utils;
+use utils::app::{App, AppConfig, AppContext};
 
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 600;
 
-fn main() {
-    let (windowed_context, el) = utils::init(WIDTH, HEIGHT, true);
+struct ShaderLang {
+    shader_program: GLuint,
+    vao: GLuint,
+}
 
-    let mut nr_attributes = 0;
-    unsafe {
-        gl::GetIntegerv(MAX_VERTEX_ATTRIBS, &mut nr_attributes);
+impl App for ShaderLang {
+    fn init(_ctx: &mut AppContext) -> Result<Self> {
+        let info = unsafe { utils::ContextInfo::query() };
+        println!("{info}");
 
         // Draw in debug line mode
-        #[cfg(debug_assertions)] {
-            // gl::PolygonMode(FRONT_AND_BACK, LINE);
+        // unsafe { gl::PolygonMode(FRONT_AND_BACK, LINE); }
+
+        let vertices: &[f32] = &[
+            0.0, 0.5, 0.0,
+            0.5, -0.5, 0.0,
+            -0.5, -0.5, 0.0,
+        ];
+        let mut vbo = 0;
+        unsafe {
+            GenBuffers(1, &mut vbo);
+            BindBuffer(ARRAY_BUFFER, vbo);
+            BufferData(
+                ARRAY_BUFFER,
+                std::mem::size_of_val(vertices) as _,
+                vertices.as_ptr() as *const _,
+                STATIC_DRAW);
         }
-    }
-    dbg!(nr_attributes);
-
-    let vertices: &[f32] = &[
-        0.0, 0.5, 0.0,
-        0.5, -0.5, 0.0,
-        -0.5, -0.5, 0.0,
-    ];
-    let mut vbo = 0;
-    unsafe {
-        GenBuffers(1, &mut vbo);
-        BindBuffer(ARRAY_BUFFER, vbo);
-        BufferData(
-            ARRAY_BUFFER,
-            std::mem::size_of_val(vertices) as _,
-            vertices.as_ptr() as *const _,
-            STATIC_DRAW);
-    }
-    assert_ne!(vbo, 0);
-
-    let elements: &[u32] = &[
-        0, 1, 2,
-    ];
-    let mut ebo = 0;
-    unsafe {
-        GenBuffers(1, &mut ebo);
-        BindBuffer(ELEMENT_ARRAY_BUFFER, ebo);
-        BufferData(
-            ELEMENT_ARRAY_BUFFER,
-            std::mem::size_of_val(elements) as _,
-            elements.as_ptr() as *const _,
-            STATIC_DRAW);
-    }
-    assert_ne!(ebo, 0);
-
-    let mut vao = 0;
-    unsafe {
-        GenVertexArrays(1, &mut vao);
-        BindVertexArray(vao);
-
-        BindBuffer(ARRAY_BUFFER, vbo);
-        BindBuffer(ELEMENT_ARRAY_BUFFER, ebo);
-
-        VertexAttribPointer(
-            0, 3, FLOAT, FALSE,
-            0,
-            std::ptr::null(),
-        );
-        EnableVertexAttribArray(0);
-    }
-    assert_ne!(vao, 0);
-
-
-    let vertex_shader = utils::compile_shader(
-        include_str!("shaders/example_shader.glsl"), VERTEX_SHADER)
-        .expect("Shader compile failed");
-
-    let frag_shader = utils::compile_shader(
-        include_str!("shaders/uniform.glsl"), FRAGMENT_SHADER)
-        .expect("Shader compile failed");
+        assert_ne!(vbo, 0);
+
+        let elements: &[u32] = &[
+            0, 1, 2,
+        ];
+        let mut ebo = 0;
+        unsafe {
+            GenBuffers(1, &mut ebo);
+            BindBuffer(ELEMENT_ARRAY_BUFFER, ebo);
+            BufferData(
+                ELEMENT_ARRAY_BUFFER,
+                std::mem::size_of_val(elements) as _,
+                elements.as_ptr() as *const _,
+                STATIC_DRAW);
+        }
+        assert_ne!(ebo, 0);
+
+        let mut vao = 0;
+        unsafe {
+            GenVertexArrays(1, &mut vao);
+            BindVertexArray(vao);
+
+            BindBuffer(ARRAY_BUFFER, vbo);
+            BindBuffer(ELEMENT_ARRAY_BUFFER, ebo);
+
+            VertexAttribPointer(
+                0, 3, FLOAT, FALSE,
+                0,
+                std::ptr::null(),
+            );
+            EnableVertexAttribArray(0);
+        }
+        assert_ne!(vao, 0);
 
-    let shader_program = unsafe { CreateProgram() };
-    unsafe {
-        AttachShader(shader_program, vertex_shader);
-        AttachShader(shader_program, frag_shader);
 
-        LinkProgram(shader_program);
+        let vertex_shader = utils::compile_shader(
+            include_str!("shaders/example_shader.glsl"), VERTEX_SHADER)
+            .expect("Shader compile failed");
 
-        utils::get_status_and_output(
-            gl::GetProgramiv,
-            gl::GetProgramInfoLog,
-            shader_program,
-            LINK_STATUS).unwrap();
-    }
+        let frag_shader = utils::compile_shader(
+            include_str!("shaders/uniform.glsl"), FRAGMENT_SHADER)
+            .expect("Shader compile failed");
 
+        let shader_program = unsafe { CreateProgram() };
+        unsafe {
+            AttachShader(shader_program, vertex_shader);
+            AttachShader(shader_program, frag_shader);
 
-    let mut frames = 0;
-    let mut events = 0;
-    let base = chrono::Utc::now().timestamp_millis();
-    let mut timer = base;
+            LinkProgram(shader_program);
 
-    el.run(move |event, _, control_flow| {
-        // dbg!(&event);
-        *control_flow = ControlFlow::Wait;
+            utils::get_status_and_output(
+                gl::GetProgramiv,
+                gl::GetProgramInfoLog,
+                shader_program,
+                LINK_STATUS).unwrap();
+        }
 
-        events += 1;
+        Ok(Self { shader_program, vao })
+    }
 
-        let now2 = chrono::Utc::now();
+    fn render(&mut self, ctx: &mut AppContext) {
+        let interval = 2.0;
+        let t = ctx.clock.elapsed() % interval;
+        let t = t / interval * 2.0 * PI as f32;
+        let t = t.sin();
 
-        if now2.timestamp_millis() - timer >= 1000 {
-            timer = now2.timestamp_millis();
+        unsafe {
+            UseProgram(self.shader_program);
 
-            println!("{frames} {events}");
+            let varname = CString::new("ourColor").unwrap();
+            let vertex_color_loc = gl::GetUniformLocation(
+                self.shader_program,
+                varname.as_ptr());
+            assert_ne!(vertex_color_loc, -1);
 
-            frames = 0;
-            events = 0;
-        }
+            Uniform4f(vertex_color_loc, 1.0 - t, t, 0.5 + t * 0.5, 1.0);
 
-        match event {
-            Event::RedrawEventsCleared => {
-                frames += 1;
-
-                let interval = 2000;
-                let dt = (chrono::Utc::now().timestamp_millis() - base) % interval;
-                let dt = dt as f32 / (interval as f32) * 2.0 * PI as f32;
-                let dt = (dt as f32).sin();
-
-                unsafe {
-                    ClearColor(0.3, 0.3, 0.3, 1.0);
-                    Clear(COLOR_BUFFER_BIT);
-
-                    UseProgram(shader_program);
-
-                    let varname = CString::new("ourColor").unwrap();
-                    let vertex_color_loc = gl::GetUniformLocation(
-                        shader_program,
-                        varname.as_ptr());
-                    assert_ne!(vertex_color_loc, -1);
-
-                    Uniform4f(vertex_color_loc, 1.0 - dt, dt, 0.5 + dt * 0.5, 1.0);
-
-                    gl::BindVertexArray(vao);
-                    DrawElements(TRIANGLES, 3, UNSIGNED_INT, std::ptr::null());
-                }
-                windowed_context.swap_buffers().ok();
-            }
-            Event::MainEventsCleared => {
-            }
-            Event::RedrawRequested(..) => {
-                unsafe {
-                    ClearColor(0.3, 0.3, 0.3, 1.0);
-                    Clear(COLOR_BUFFER_BIT);
-                }
-                windowed_context.swap_buffers().ok();
-            }
-            Event::LoopDestroyed => (),
-            Event::WindowEvent { event, .. } => {
-                match event {
-                    WindowEvent::Resized(size) => {
-                        unsafe {
-                            gl::Viewport(0, 0, size.width as _, size.height as _);
-                        }
-                    }
-                    WindowEvent::CloseRequested => {
-                        dbg!(&event);
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                    _ => (),
-                }
-            }
-            _ => (),
+            gl::BindVertexArray(self.vao);
+            DrawElements(TRIANGLES, 3, UNSIGNED_INT, std::ptr::null());
         }
-
-        *control_flow = ControlFlow::Poll;
-    });
+    }
 }
 
+fn main() -> Result<()> {
+    utils::app::run::<ShaderLang>(AppConfig::new(WIDTH, HEIGHT))
+}
@@ -19,256 +19,205 @@ use image::DynamicImage;
 use image::io::Reader as ImgReader;
 
 use learnopengl_utils as utils;
+use utils::app::{App, AppConfig, AppContext};
+use utils::input::ActionMap;
 
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 600;
+/// Change of the mix rate per press of a mix key.
+const MIX_STEP: f32 = 0.1;
 
-fn main() -> Result<()> {
-    let (windowed_context, el) = utils::init(WIDTH, HEIGHT, true);
-
-
-    let tex_coords: &[f32] = &[
-        0.0, 0.0,
-        1.0, 0.0,
-        0.5, 1.0,
-    ];
-    /*
-        GL_REPEAT: Repeats the texture image
-        GL_MIRRORED_REPEAT: Same as GL_REPEAT but mirrors the texture image
-        GL_CLAMP_TO_EDGE: Clamps the coordinates between 0 and 1.
-        GL_CLAMP_TO_BORDER: Coordinates outside the range are now given
-            a user-specified border color.
-     */
-    unsafe {
-        gl::TexParameteri(TEXTURE_2D, TEXTURE_WRAP_S, MIRRORED_REPEAT as _);
-        // minifying, scale down
-        gl::TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as _);
-        // magnifying, scale up
-        gl::TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as _);
-
-        //mipmaps
-        gl::TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR_MIPMAP_LINEAR as _);
-        // No use, because mipmaps not used in scale up
-        // gl::TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR_MIPMAP_LINEAR as _);
-    }
-
-    let stride = 8;
-    let vertices: &[f32] = &[
-        // position       // colors        // texture coords
-        0.5, 0.5, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0,  // top right
-        0.5, -0.5, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0,  // bottom right
-        -0.5, -0.5, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0,  // bottom left
-        -0.5, 0.5, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0,  // top left
-    ];
-    let mut vbo = 0;
-    unsafe {
-        GenBuffers(1, &mut vbo);
-        assert_ne!(vbo, 0);
-        BindBuffer(ARRAY_BUFFER, vbo);
-        BufferData(
-            ARRAY_BUFFER,
-            size_of_val(vertices) as _,
-            vertices.as_ptr() as *const _,
-            STATIC_DRAW);
-    }
-
-    let elements: &[u32] = &[
-        0, 1, 3,
-        1, 2, 3,
-    ];
-    let mut ebo = 0;
-    unsafe {
-        GenBuffers(1, &mut ebo);
-        assert_ne!(ebo, 0);
-        BindBuffer(ELEMENT_ARRAY_BUFFER, ebo);
-        BufferData(
-            ELEMENT_ARRAY_BUFFER,
-            size_of_val(elements) as _,
-            elements.as_ptr() as *const _,
-            STATIC_DRAW);
-    }
-
-    let mut vao = 0;
-    unsafe {
-        GenVertexArrays(1, &mut vao);
-        assert_ne!(vao, 0);
-        BindVertexArray(vao);
-
-        BindBuffer(ARRAY_BUFFER, vbo);
-        BindBuffer(ELEMENT_ARRAY_BUFFER, ebo);
+struct TextureMix {
+    shader: utils::Shader<'static>,
+    mix_rate: f32,
+}
 
-        VertexAttribPointer(
-            0, 3, FLOAT, FALSE,
-            stride * size_of::<f32>() as i32,
-            std::ptr::null(),
-        );
-        EnableVertexAttribArray(0);
-        VertexAttribPointer(
-            1, 3, FLOAT, FALSE,
-            stride * size_of::<f32>() as i32,
-            (3 * size_of::<f32>()) as *const _,
-        );
-        EnableVertexAttribArray(1);
-        VertexAttribPointer(
-            2, 2, FLOAT, FALSE,
-            stride * size_of::<f32>() as i32,
-            (6 * size_of::<f32>()) as *const _,
-        );
-        EnableVertexAttribArray(2);
-    }
+impl App for TextureMix {
+    fn init(ctx: &mut AppContext) -> Result<Self> {
+        ctx.input.set_actions(ActionMap::parse(include_str!("actions.cfg"))?);
+
+        let tex_coords: &[f32] = &[
+            0.0, 0.0,
+            1.0, 0.0,
+            0.5, 1.0,
+        ];
+        /*
+            GL_REPEAT: Repeats the texture image
+            GL_MIRRORED_REPEAT: Same as GL_REPEAT but mirrors the texture image
+            GL_CLAMP_TO_EDGE: Clamps the coordinates between 0 and 1.
+            GL_CLAMP_TO_BORDER: Coordinates outside the range are now given
+                a user-specified border color.
+         */
+        unsafe {
+            gl::TexParameteri(TEXTURE_2D, TEXTURE_WRAP_S, MIRRORED_REPEAT as _);
+            // minifying, scale down
+            gl::TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as _);
+            // magnifying, scale up
+            gl::TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as _);
+
+            //mipmaps
+            gl::TexParameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR_MIPMAP_LINEAR as _);
+            // No use, because mipmaps not used in scale up
+            // gl::TexParameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR_MIPMAP_LINEAR as _);
+        }
 
-    fn load_texture(data: &[u8]) -> Result<GLuint> {
-        let img = ImgReader::new(Cursor::new(data))
-            .with_guessed_format()?
-            .decode()?
-            .flipv();  // flip the image so that fit into the opengl coordination.
-        let w = img.width();
-        let h = img.height();
-        let format = match img {
-            DynamicImage::ImageRgb8(_) => {
-                dbg!("rgb");
-                RGB
-            }
-            DynamicImage::ImageRgba8(_) => {
-                dbg!("rgba");
-                RGBA
-            }
-            _ => { unimplemented!() }
-        };
+        let stride = 8;
+        let vertices: &[f32] = &[
+            // position       // colors        // texture coords
+            0.5, 0.5, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0,  // top right
+            0.5, -0.5, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0,  // bottom right
+            -0.5, -0.5, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0,  // bottom left
+            -0.5, 0.5, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0,  // top left
+        ];
+        let mut vbo = 0;
+        unsafe {
+            GenBuffers(1, &mut vbo);
+            assert_ne!(vbo, 0);
+            BindBuffer(ARRAY_BUFFER, vbo);
+            BufferData(
+                ARRAY_BUFFER,
+                size_of_val(vertices) as _,
+                vertices.as_ptr() as *const _,
+                STATIC_DRAW);
+        }
 
+        let elements: &[u32] = &[
+            0, 1, 3,
+            1, 2, 3,
+        ];
+        let mut ebo = 0;
+        unsafe {
+            GenBuffers(1, &mut ebo);
+            assert_ne!(ebo, 0);
+            BindBuffer(ELEMENT_ARRAY_BUFFER, ebo);
+            BufferData(
+                ELEMENT_ARRAY_BUFFER,
+                size_of_val(elements) as _,
+                elements.as_ptr() as *const _,
+                STATIC_DRAW);
+        }
 
-        let mut tex = 0;
+        let mut vao = 0;
         unsafe {
-            GenTextures(1, &mut tex);
-            assert_ne!(tex, 0);
+            GenVertexArrays(1, &mut vao);
+            assert_ne!(vao, 0);
+            BindVertexArray(vao);
 
-            BindTexture(TEXTURE_2D, tex);
+            BindBuffer(ARRAY_BUFFER, vbo);
+            BindBuffer(ELEMENT_ARRAY_BUFFER, ebo);
 
-            TexImage2D(
-                // generate a texture_2d target
-                TEXTURE_2D,
-                // mipmap level, base level is 0
-                0,
-                // store the image in RGB format
-                format as _,
-                // image size
-                w as _, h as _,
-                // always be 0
-                0,
-                // source image type in RGB with unsigned bytes
-                format, UNSIGNED_BYTE,
-                // source pointer
-                img.as_bytes().as_ptr() as *const _,
+            VertexAttribPointer(
+                0, 3, FLOAT, FALSE,
+                stride * size_of::<f32>() as i32,
+                std::ptr::null(),
+            );
+            EnableVertexAttribArray(0);
+            VertexAttribPointer(
+                1, 3, FLOAT, FALSE,
+                stride * size_of::<f32>() as i32,
+                (3 * size_of::<f32>()) as *const _,
             );
-            GenerateMipmap(TEXTURE_2D);
+            EnableVertexAttribArray(1);
+            VertexAttribPointer(
+                2, 2, FLOAT, FALSE,
+                stride * size_of::<f32>() as i32,
+                (6 * size_of::<f32>()) as *const _,
+            );
+            EnableVertexAttribArray(2);
         }
 
-        Ok(tex)
-    }
-
-    let tex1 = load_texture(include_bytes!("textures/wall.jpg"))?;
-    let tex2 = load_texture(include_bytes!("textures/awesomeface.png"))?;
-
-    unsafe {
-        ActiveTexture(TEXTURE0);
-        BindTexture(TEXTURE_2D, tex1);
-
-        ActiveTexture(TEXTURE1);
-        BindTexture(TEXTURE_2D, tex2);
-    }
-
-
-    let shader = utils::Shader::new(
-        include_str!("shaders/shader1.vs.glsl"),
-        include_str!("shaders/shader1.fg.glsl"),
-    );
-
-
-    shader.activate().ok();
-    shader.set_i32("texture1", (0, )).ok();
-    shader.set_i32("texture2", (1, )).ok();
-
-
-    let mut frames = 0;
-    let mut events = 0;
-    let mut timer = chrono::Utc::now().timestamp_millis();
-
-    let mut mix_rate = 0.5f32;
-
-    el.run(move |event, _, control_flow| {
-        // dbg!(&event);
-        *control_flow = ControlFlow::Wait;
-
-        events += 1;
+        fn load_texture(data: &[u8]) -> Result<GLuint> {
+            let img = ImgReader::new(Cursor::new(data))
+                .with_guessed_format()?
+                .decode()?
+                .flipv();  // flip the image so that fit into the opengl coordination.
+            let w = img.width();
+            let h = img.height();
+            let format = match img {
+                DynamicImage::ImageRgb8(_) => {
+                    dbg!("rgb");
+                    RGB
+                }
+                DynamicImage::ImageRgba8(_) => {
+                    dbg!("rgba");
+                    RGBA
+                }
+                _ => { unimplemented!() }
+            };
+
+
+            let mut tex = 0;
+            unsafe {
+                GenTextures(1, &mut tex);
+                assert_ne!(tex, 0);
+
+                BindTexture(TEXTURE_2D, tex);
+
+                TexImage2D(
+                    // generate a texture_2d target
+                    TEXTURE_2D,
+                    // mipmap level, base level is 0
+                    0,
+                    // store the image in RGB format
+                    format as _,
+                    // image size
+                    w as _, h as _,
+                    // always be 0
+                    0,
+                    // source image type in RGB with unsigned bytes
+                    format, UNSIGNED_BYTE,
+                    // source pointer
+                    img.as_bytes().as_ptr() as *const _,
+                );
+                GenerateMipmap(TEXTURE_2D);
+            }
 
-        let now2 = chrono::Utc::now();
+            Ok(tex)
+        }
 
-        if now2.timestamp_millis() - timer >= 1000 {
-            timer = now2.timestamp_millis();
+        let tex1 = load_texture(include_bytes!("textures/wall.jpg"))?;
+        let tex2 = load_texture(include_bytes!("textures/awesomeface.png"))?;
 
-            println!("frames:{frames}/s  events:{events}/s");
+        unsafe {
+            ActiveTexture(TEXTURE0);
+            BindTexture(TEXTURE_2D, tex1);
 
-            frames = 0;
-            events = 0;
+            ActiveTexture(TEXTURE1);
+            BindTexture(TEXTURE_2D, tex2);
         }
 
 
-        match event {
-            Event::RedrawEventsCleared => unsafe {
-                frames += 1;
-
-                ClearColor(0.3, 0.3, 0.3, 1.0);
-                Clear(COLOR_BUFFER_BIT);
+        let shader = utils::Shader::new(
+            include_str!("shaders/shader1.vs.glsl"),
+            include_str!("shaders/shader1.fg.glsl"),
+        );
 
-                shader.set_f32("mix_rate", (mix_rate, )).ok();
 
-                DrawElements(TRIANGLES, 6, UNSIGNED_INT, std::ptr::null());
+        shader.activate().ok();
+        shader.set_i32("texture1", (0, )).ok();
+        shader.set_i32("texture2", (1, )).ok();
 
-                windowed_context.swap_buffers().ok();
-            }
-            Event::MainEventsCleared => {}
-            Event::RedrawRequested(..) => unsafe {
-                ClearColor(0.3, 0.3, 0.3, 1.0);
-                Clear(COLOR_BUFFER_BIT);
+        Ok(Self { shader, mix_rate: 0.5 })
+    }
 
-                windowed_context.swap_buffers().ok();
-            }
-            Event::LoopDestroyed => (),
-            Event::WindowEvent { event, .. } => {
-                match event {
-                    WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode: Some(key), .. }, .. } => {
-                        match (key, state) {
-                            (VirtualKeyCode::Escape, ElementState::Released) => {
-                                *control_flow = ControlFlow::ExitWithCode(0);
-                            }
-                            (VirtualKeyCode::Up, ElementState::Pressed) => {
-                                mix_rate = f32::min(mix_rate + 0.1, 1.0);
-                            }
-                            (VirtualKeyCode::Down, ElementState::Pressed) => {
-                                mix_rate = f32::max(mix_rate - 0.1, 0.0);
-                            }
-                            _ => {}
-                        };
-                    }
-                    WindowEvent::Resized(size) => unsafe {
-                        gl::Viewport(0, 0, size.width as _, size.height as _);
-                    }
-                    WindowEvent::CloseRequested => {
-                        dbg!(&event);
-                        *control_flow = ControlFlow::Exit;
-                        return;
-                    }
-                    _ => (),
-                }
-            }
-            _ => (),
+    fn update(&mut self, ctx: &mut AppContext, _dt: f32) {
+        if ctx.input.action_pressed("mix_up") {
+            self.mix_rate = f32::min(self.mix_rate + MIX_STEP, 1.0);
         }
-
-        if *control_flow == ControlFlow::Wait {
-            *control_flow = ControlFlow::Poll;
+        if ctx.input.action_pressed("mix_down") {
+            self.mix_rate = f32::max(self.mix_rate - MIX_STEP, 0.0);
         }
-    });
+    }
+
+    fn render(&mut self, _ctx: &mut AppContext) {
+        self.shader.set_f32("mix_rate", (self.mix_rate, )).ok();
 
-    Ok(())
+        unsafe {
+            DrawElements(TRIANGLES, 6, UNSIGNED_INT, std::ptr::null());
+        }
+    }
 }
 
+fn main() -> Result<()> {
+    utils::app::run::<TextureMix>(AppConfig::new(WIDTH, HEIGHT))
+}
@@ -0,0 +1,40 @@
+//! Replays recorded sessions headless and compares the last frame with a golden image.
+//!
+//! A missing golden image fails the test, `LEARNOPENGL_BLESS=1` writes it.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use learnopengl_utils as utils;
+
+fn replay(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let capture = std::env::temp_dir().join(format!("p1_ch7_{name}.png"));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_p1_ch7_ex_textures_unit_test"))
+        .env(utils::context::HEADLESS_ENV, "1")
+        .env(utils::app::REPLAY_ENV, dir.join("replays").join(format!("{name}.json")))
+        .env(utils::app::CAPTURE_ENV, &capture)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    capture
+}
+
+#[test]
+#[ignore = "needs a display to create the GL context"]
+fn mix_up_matches_golden() {
+    let capture = replay("mix_up");
+    let actual = image::open(capture).unwrap().to_rgba8();
+
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/mix_up.png");
+    utils::capture::check_golden(&actual, golden, 2, 0.001, utils::capture::bless_from_env()).unwrap();
+}
+
+#[test]
+fn replays_load() {
+    let recording = utils::input::recording::Recording::load(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replays/mix_up.json")).unwrap();
+    assert_eq!((recording.width, recording.height), (800, 600));
+    assert_eq!(recording.frames.len(), 60);
+}
@@ -0,0 +1,48 @@
+//! Traces headless runs of the sample and diffs their gl calls.
+//!
+//! Run with `cargo test --features gl-trace -- --ignored`, without the feature no trace is written.
+
+use std::path::Path;
+use std::process::Command;
+
+use learnopengl_utils as utils;
+use utils::trace::{DiffMode, Mismatch, Trace};
+
+const FRAMES: usize = 10;
+
+fn trace(name: &str, replay: Option<&str>) -> Trace {
+    let path = std::env::temp_dir().join(format!("p1_ch7_trace_{name}.txt"));
+    std::fs::remove_file(&path).ok();
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_p1_ch7_ex_textures_unit_test"));
+    command
+        .env(utils::context::HEADLESS_ENV, "1")
+        .env(utils::app::FRAMES_ENV, FRAMES.to_string())
+        .env(utils::trace::TRACE_ENV, &path)
+        .env(utils::trace::TRACE_FRAMES_ENV, FRAMES.to_string());
+    if let Some(replay) = replay {
+        let replay = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replays").join(format!("{replay}.json"));
+        command.env(utils::app::REPLAY_ENV, replay);
+    }
+    assert!(command.status().unwrap().success());
+
+    let mut trace = Trace::load(&path).expect("no trace written, is the gl-trace feature on?");
+    // the GPU timer polls its queries until the driver has the result
+    trace.retain(|call| !call.name.starts_with("GetQueryObject"));
+    trace
+}
+
+#[test]
+#[ignore = "needs a display to create the GL context and the gl-trace feature"]
+fn runs_differ_only_in_the_mix_rate() {
+    let idle = trace("idle", None);
+    assert_eq!(idle.frames.len(), FRAMES);
+    assert!(idle.diff(&trace("idle_again", None), DiffMode::IgnorePointers).is_empty());
+
+    // the replay presses `mix_up` on its second frame
+    let mix_up = trace("mix_up", Some("mix_up"));
+    assert!(idle.diff(&mix_up, DiffMode::NamesOnly).is_empty());
+    let mismatches = idle.diff(&mix_up, DiffMode::IgnorePointers);
+    assert!(!mismatches.is_empty());
+    assert!(mismatches.iter().all(|m| matches!(m, Mismatch::Changed { left, .. } if left.name == "Uniform1f")), "{mismatches:#?}");
+}
@@ -0,0 +1,78 @@
+fn main() {
+    #[cfg(any(feature = "gl-check", feature = "gl-trace"))]
+    shim::generate().unwrap();
+}
+
+/// One `extern "system"` function per gl entry point, forwarding to the driver and handing
+/// the call to `loader::after_call`, see `src/loader/mod.rs`.
+#[cfg(any(feature = "gl-check", feature = "gl-trace"))]
+mod shim {
+    use std::fs::File;
+    use std::io::{BufWriter, Result, Write};
+    use std::path::Path;
+
+    use gl_generator::{Api, Cmd, Fallbacks, Profile, Registry};
+
+    /// `*const GLchar` arguments without a length next to them are NUL terminated names,
+    /// like the uniform of `GetUniformLocation`.
+    fn is_name(cmd: &Cmd, ty: &str) -> bool {
+        ty == "*const types::GLchar" && !cmd.params.iter().any(|p| p.ident == "length" || p.ident == "bufSize")
+    }
+
+    pub fn generate() -> Result<()> {
+        let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("gl_shim.rs");
+        let mut dest = BufWriter::new(File::create(out)?);
+
+        // the same registry the `gl` crate is generated from
+        let registry = Registry::new(Api::Gl, (4, 5), Profile::Core, Fallbacks::All, []);
+        let cmds: Vec<&Cmd> = registry.cmds.iter().collect();
+
+        writeln!(dest, "pub const COUNT: usize = {};", cmds.len())?;
+        writeln!(dest, "static REAL: [AtomicPtr<c_void>; COUNT] = [UNLOADED; COUNT];")?;
+        writeln!(dest, "pub fn store(slot: usize, real: *const c_void) {{ REAL[slot].store(real as *mut _, Ordering::Relaxed) }}")?;
+
+        for (slot, cmd) in cmds.iter().enumerate() {
+            let name = &cmd.proto.ident;
+            let params: Vec<String> = cmd.params.iter().map(|p| format!("{}: {}", p.ident, p.ty)).collect();
+            let types: Vec<&str> = cmd.params.iter().map(|p| p.ty.as_ref()).collect();
+            let idents: Vec<&str> = cmd.params.iter().map(|p| p.ident.as_str()).collect();
+            let args: Vec<String> = cmd.params.iter()
+                .map(|p| if is_name(cmd, &p.ty) { format!("&Name({})", p.ident) } else { format!("&{}", p.ident) })
+                .collect();
+            let ret = &cmd.proto.ty;
+
+            if name == "GetError" {
+                writeln!(dest, "pub unsafe fn call_get_error() -> types::GLenum {{
+    let real: extern \"system\" fn() -> types::GLenum = std::mem::transmute(REAL[{slot}].load(Ordering::Relaxed));
+    real()
+}}")?;
+            }
+            writeln!(dest, "unsafe extern \"system\" fn {name}({params}) -> {ret} {{
+    let real: extern \"system\" fn({types}) -> {ret} = std::mem::transmute(REAL[{slot}].load(Ordering::Relaxed));
+    let result = real({idents});
+    crate::loader::after_call(\"{name}\", &[{args}], &result);
+    result
+}}",
+                     params = params.join(", "),
+                     types = types.join(", "),
+                     idents = idents.join(", "),
+                     args = args.join(", "))?;
+        }
+
+        writeln!(dest, "/// The slot and shim of the gl function `symbol`, fallbacks included.")?;
+        writeln!(dest, "pub fn lookup(symbol: &str) -> Option<(usize, *const c_void)> {{")?;
+        writeln!(dest, "    match symbol {{")?;
+        for (slot, cmd) in cmds.iter().enumerate() {
+            let name = &cmd.proto.ident;
+            let mut symbols = vec![format!("\"gl{name}\"")];
+            if let Some(aliases) = registry.aliases.get(name) {
+                symbols.extend(aliases.iter().map(|alias| format!("\"gl{alias}\"")));
+            }
+            writeln!(dest, "        {} => Some(({slot}, {name} as *const c_void)),", symbols.join(" | "))?;
+        }
+        writeln!(dest, "        _ => None,")?;
+        writeln!(dest, "    }}")?;
+        writeln!(dest, "}}")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! `#[derive(Std140)]` and `#[derive(Std430)]` for `learnopengl_utils::uniform::Std140`
+//! and `learnopengl_utils::storage::Std430`.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident, Lit, Meta, NestedMeta, Result};
+
+/// Lay out a struct with named fields like a GLSL struct in a `std140` block.
+///
+/// `#[std140(rename_all = "camelCase")]` on the struct and `#[std140(rename = "name")]`
+/// on a field set the GLSL names used to check the layout against a program.
+#[proc_macro_derive(Std140, attributes(std140))]
+pub fn derive_std140(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input, &Layout::STD140).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// Lay out a struct like a GLSL struct in a `std430` buffer block, with the same
+/// attributes as `Std140` under `#[std430(...)]`.
+#[proc_macro_derive(Std430, attributes(std430))]
+pub fn derive_std430(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input, &Layout::STD430).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// The trait to implement, where its helpers live and the attribute to read.
+struct Layout {
+    name: &'static str,
+    module: &'static str,
+    write: &'static str,
+    read: &'static str,
+    attribute: &'static str,
+}
+
+impl Layout {
+    const STD140: Self = Self { name: "Std140", module: "uniform", write: "write_std140", read: "read_std140", attribute: "std140" };
+    const STD430: Self = Self { name: "Std430", module: "storage", write: "write_std430", read: "read_std430", attribute: "std430" };
+}
+
+fn expand(input: DeriveInput, layout: &Layout) -> Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, format!("{} can not be derived for generic structs", layout.name)));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(name, format!("{} needs named fields", layout.name))),
+        },
+        _ => return Err(Error::new_spanned(name, format!("{} can only be derived for structs", layout.name))),
+    };
+    if fields.is_empty() {
+        return Err(Error::new_spanned(name, "GLSL structs can not be empty"));
+    }
+
+    let camel_case = match attribute(&input.attrs, layout.attribute, "rename_all")? {
+        None => false,
+        Some(case) if case == "camelCase" => true,
+        Some(case) => return Err(Error::new(Span::call_site(), format!("unsupported rename_all = \"{case}\", only \"camelCase\" is"))),
+    };
+
+    let module = Ident::new(layout.module, Span::call_site());
+    let krate = quote!(::learnopengl_utils::#module);
+    let layout_trait = Ident::new(layout.name, Span::call_site());
+    let write = Ident::new(layout.write, Span::call_site());
+    let read = Ident::new(layout.read, Span::call_site());
+    let idents: Vec<_> = fields.iter().map(|f| f.ident.clone().unwrap()).collect();
+    let types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+    let mut glsl_names = vec![];
+    for (field, ident) in fields.iter().zip(&idents) {
+        let rust_name = ident.to_string();
+        glsl_names.push(match attribute(&field.attrs, layout.attribute, "rename")? {
+            Some(rename) => rename,
+            None if camel_case => to_camel_case(&rust_name),
+            None => rust_name,
+        });
+    }
+    let indices: Vec<_> = (0..fields.len()).collect();
+    let count = fields.len();
+
+    let members = quote! {
+        [#( (<#types as #krate::#layout_trait>::ALIGN, <#types as #krate::#layout_trait>::SIZE) ),*]
+    };
+
+    Ok(quote! {
+        impl #krate::#layout_trait for #name {
+            const ALIGN: usize = #krate::struct_align(&#members);
+            const SIZE: usize = #krate::struct_size(&#members);
+
+            fn #write(&self, out: &mut [u8]) {
+                let offsets = #krate::struct_offsets::<#count>(&#members);
+                #(
+                    #krate::#layout_trait::#write(
+                        &self.#idents,
+                        &mut out[offsets[#indices]..offsets[#indices] + <#types as #krate::#layout_trait>::SIZE],
+                    );
+                )*
+            }
+
+            fn #read(bytes: &[u8]) -> Self {
+                let offsets = #krate::struct_offsets::<#count>(&#members);
+                Self {
+                    #(
+                        #idents: <#types as #krate::#layout_trait>::#read(
+                            &bytes[offsets[#indices]..offsets[#indices] + <#types as #krate::#layout_trait>::SIZE],
+                        ),
+                    )*
+                }
+            }
+
+            fn fields() -> ::std::vec::Vec<#krate::LayoutField> {
+                let offsets = #krate::struct_offsets::<#count>(&#members);
+                let mut fields = ::std::vec::Vec::new();
+                #(
+                    #krate::push_member(&mut fields, #glsl_names, offsets[#indices], <#types as #krate::#layout_trait>::fields());
+                )*
+                fields
+            }
+        }
+    })
+}
+
+/// The string of `#[std140(key = "value")]`, or `#[std430(...)]` for `name == "std430"`.
+fn attribute(attrs: &[syn::Attribute], name: &str, key: &str) -> Result<Option<String>> {
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident(name)) {
+        let Meta::List(list) = attr.parse_meta()? else {
+            return Err(Error::new_spanned(attr, format!("expected #[{name}(...)]")));
+        };
+        for nested in &list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident(key) => match &pair.lit {
+                    Lit::Str(value) => return Ok(Some(value.value())),
+                    lit => return Err(Error::new_spanned(lit, "expected a string")),
+                },
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("rename") || pair.path.is_ident("rename_all") => {}
+                other => return Err(Error::new_spanned(other, format!("unknown {name} attribute"))),
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn to_camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = !out.is_empty();
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,360 @@
+//! A small application framework owning the event loop, so a sample only implements [`App`].
+
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::Result;
+use gl::types::*;
+use glutin::dpi::PhysicalSize;
+use glutin::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use glutin::event_loop::ControlFlow;
+
+use crate::context::{ContextConfig, GlContext};
+use crate::input::recording::{Player, Recorder, Recording};
+use crate::input::{InputDevice, InputEvent, InputState};
+use crate::stats::{FrameStats, GpuTimer, StatsOverlay};
+use crate::time::{Clock, FixedTimestep, FrameLimiter, ManualTime};
+
+/// Number of frames a headless run renders before exiting, there is no window to close.
+pub const FRAMES_ENV: &str = "LEARNOPENGL_FRAMES";
+/// Show the stats overlay from the start, it can always be toggled with F3.
+pub const STATS_ENV: &str = "LEARNOPENGL_STATS";
+/// Write the frame stats history as CSV to this file on exit.
+pub const STATS_CSV_ENV: &str = "LEARNOPENGL_STATS_CSV";
+/// Record the input events and frame times of the session to this file on exit.
+pub const RECORD_ENV: &str = "LEARNOPENGL_RECORD";
+/// Replay a recorded session instead of reading live input.
+pub const REPLAY_ENV: &str = "LEARNOPENGL_REPLAY";
+/// Save the last frame as an image, for runs that end on their own.
+pub const CAPTURE_ENV: &str = "LEARNOPENGL_CAPTURE";
+
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub context: ContextConfig,
+    pub clear_color: (f32, f32, f32, f32),
+    /// Bits passed to `glClear` before [`App::render`], `0` to leave the buffers alone.
+    pub clear_mask: GLbitfield,
+    pub exit_on_escape: bool,
+    /// Exit after this many frames, defaults to `LEARNOPENGL_FRAMES`, or a single frame when headless.
+    pub max_frames: Option<u64>,
+    /// Rate of [`App::fixed_update`], disabled when `None`.
+    pub fixed_update_hz: Option<f32>,
+    /// Sleep to cap the frame rate, on top of vsync.
+    pub max_fps: Option<f32>,
+    pub show_stats: bool,
+    pub stats_csv: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    /// Frame times and input come from the recording, the run ends with it.
+    pub replay: Option<PathBuf>,
+    /// Screenshot of the frame that hits [`max_frames`](Self::max_frames) or ends the replay.
+    pub capture: Option<PathBuf>,
+}
+
+impl AppConfig {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            context: ContextConfig::new(width, height, true),
+            clear_color: (0.3, 0.3, 0.3, 1.0),
+            clear_mask: gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT,
+            exit_on_escape: true,
+            max_frames: std::env::var(FRAMES_ENV).ok().and_then(|n| n.parse().ok()),
+            fixed_update_hz: None,
+            max_fps: None,
+            show_stats: std::env::var(STATS_ENV).is_ok_and(|v| v != "0"),
+            stats_csv: std::env::var_os(STATS_CSV_ENV).map(PathBuf::from),
+            record: std::env::var_os(RECORD_ENV).map(PathBuf::from),
+            replay: std::env::var_os(REPLAY_ENV).map(PathBuf::from),
+            capture: std::env::var_os(CAPTURE_ENV).map(PathBuf::from),
+        }
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.context.title = title.to_owned();
+        self
+    }
+
+    /// Ask for a newer context than the default 3.3, e.g. `(4, 3)` for [`compute`](crate::compute).
+    pub fn with_gl_version(mut self, major: u8, minor: u8) -> Self {
+        self.context.gl_version = (major, minor);
+        self
+    }
+}
+
+/// State shared with every [`App`] hook.
+pub struct AppContext {
+    pub gl: GlContext,
+    /// Index of the frame being rendered.
+    pub frame: u64,
+    pub clock: Clock,
+    /// Interpolation factor between the last two fixed updates, `0` without fixed updates.
+    pub alpha: f32,
+    pub stats: FrameStats,
+    /// Keyboard and mouse state of this frame, reset after [`App::render`].
+    pub input: InputState,
+    exit: bool,
+}
+
+impl AppContext {
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.gl.size()
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        let size = self.size();
+        size.width as f32 / size.height.max(1) as f32
+    }
+
+    /// Leave the event loop once the current event is handled.
+    pub fn exit(&mut self) {
+        self.exit = true;
+    }
+}
+
+pub trait App: Sized {
+    /// Create the gl resources, the context is current and loaded.
+    fn init(ctx: &mut AppContext) -> Result<Self>;
+
+    /// Advance the simulation by exactly `dt` seconds, see [`AppConfig::fixed_update_hz`].
+    fn fixed_update(&mut self, _ctx: &mut AppContext, _dt: f32) {}
+
+    /// Advance the simulation by `dt` seconds, called once per frame before [`App::render`].
+    fn update(&mut self, _ctx: &mut AppContext, _dt: f32) {}
+
+    /// Draw the frame, the buffers are already cleared and are swapped afterwards.
+    fn render(&mut self, ctx: &mut AppContext);
+
+    /// Every window event, after the driver handled resizing and exiting and updated [`AppContext::input`].
+    ///
+    /// Input events are not replayed here, read [`AppContext::input`] for input that replays.
+    fn on_event(&mut self, _ctx: &mut AppContext, _event: &WindowEvent) {}
+
+    /// The viewport already follows the new size.
+    fn on_resize(&mut self, _ctx: &mut AppContext, _width: u32, _height: u32) {}
+}
+
+/// Create the context and run `A` until the window is closed.
+///
+/// Only returns if loading the replay or [`App::init`] fails, the event loop exits the process.
+pub fn run<A: App + 'static>(config: AppConfig) -> Result<()> {
+    let mut player = config.replay.as_ref().map(Recording::load).transpose()?.map(Player::new).transpose()?;
+
+    let (gl, el) = GlContext::create(&config.context);
+
+    let max_frames = match (config.max_frames, &player) {
+        (Some(max), Some(player)) => Some(max.min(player.frame_count() as u64)),
+        (None, Some(player)) => Some(player.frame_count() as u64),
+        (None, None) if gl.is_headless() => Some(1),
+        (max_frames, _) => max_frames,
+    };
+
+    if let Some(player) = &player {
+        let (recording, size) = (player.recording(), gl.size());
+        if (recording.width, recording.height) != (size.width, size.height) {
+            log::warn!("replaying a {}x{} recording at {}x{}, frames will differ",
+                recording.width, recording.height, size.width, size.height);
+        }
+    }
+    let mut recorder = config.record.is_some().then(|| Recorder::new(gl.size().width, gl.size().height));
+
+    let mut ctx = AppContext {
+        gl,
+        frame: 0,
+        clock: Clock::new(),
+        alpha: 0.0,
+        stats: FrameStats::default(),
+        input: InputState::new(),
+        exit: false,
+    };
+    let mut app = A::init(&mut ctx)?;
+
+    // Loading is not part of the first frame. A replay advances time by the recorded frame times.
+    let replay_time = ManualTime::new();
+    ctx.clock = match player {
+        Some(_) => Clock::with_source(replay_time.clone()),
+        None => Clock::new(),
+    };
+
+    let mut gpu_timer = GpuTimer::new();
+    // GPU timings differ between runs, a replay would not render the same frames with the overlay.
+    let mut overlay = (config.show_stats && player.is_none()).then(StatsOverlay::new);
+
+    let mut fixed = config.fixed_update_hz.map(FixedTimestep::new);
+    let mut limiter = config.max_fps.map(FrameLimiter::new);
+
+    let mut devices: Vec<Box<dyn InputDevice>> = vec![];
+    #[cfg(feature = "gamepad")]
+    match crate::input::gamepad::GilrsDevice::new() {
+        Ok(gamepad) => devices.push(Box::new(gamepad)),
+        Err(err) => log::warn!("no gamepad support: {err:#}"),
+    }
+
+    let mut timer = Instant::now();
+
+    el.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+
+        if timer.elapsed().as_secs() >= 1 {
+            timer = Instant::now();
+            log::debug!("{}", ctx.stats);
+        }
+
+        match event {
+            Event::RedrawEventsCleared => {
+                let replayed = player.as_mut().and_then(|player| player.next_frame());
+                if let Some(frame) = replayed {
+                    replay_time.advance(frame.duration());
+                }
+
+                let dt = ctx.clock.tick();
+                if ctx.frame > 0 {
+                    ctx.stats.record_frame(ctx.clock.raw_dt());
+                }
+
+                match replayed {
+                    Some(frame) => {
+                        for event in &frame.events {
+                            ctx.input.handle(&event.event);
+                        }
+                    }
+                    None => {
+                        let mut events = vec![];
+                        for device in &mut devices {
+                            device.poll(&mut events);
+                        }
+                        for event in events {
+                            handle_input(&mut ctx, &mut recorder, event);
+                        }
+                    }
+                }
+                if let Some(recorder) = &mut recorder {
+                    recorder.end_frame(ctx.clock.raw_dt());
+                }
+
+                let start = Instant::now();
+                if let Some(fixed) = &mut fixed {
+                    for _ in 0..fixed.accumulate(dt) {
+                        app.fixed_update(&mut ctx, fixed.step());
+                    }
+                    ctx.alpha = fixed.alpha();
+                }
+
+                app.update(&mut ctx, dt);
+                ctx.stats.record_phase("update", ms_since(start));
+
+                let start = Instant::now();
+                gpu_timer.begin();
+                unsafe {
+                    let (r, g, b, a) = config.clear_color;
+                    crate::gl_check!(gl::ClearColor(r, g, b, a));
+                    if config.clear_mask != 0 {
+                        crate::gl_check!(gl::Clear(config.clear_mask));
+                    }
+                }
+
+                app.render(&mut ctx);
+                if let Some(gpu_ms) = gpu_timer.end() {
+                    ctx.stats.record_gpu(gpu_ms);
+                }
+                ctx.stats.record_phase("render", ms_since(start));
+
+                if let Some(overlay) = &overlay {
+                    let size = ctx.size();
+                    overlay.draw(&ctx.stats, size.width, size.height);
+                }
+
+                let last_frame = max_frames.is_some_and(|max| ctx.frame + 1 >= max);
+                if let (true, Some(path)) = (last_frame, &config.capture) {
+                    let size = ctx.size();
+                    let image = unsafe { crate::capture::read_pixels(size.width, size.height) };
+                    if let Err(err) = image.save(path) {
+                        log::error!("saving capture {}: {err}", path.display());
+                    }
+                }
+
+                ctx.gl.swap_buffers().ok();
+                ctx.input.end_frame();
+
+                if let Some(limiter) = &mut limiter {
+                    limiter.wait(&ctx.clock);
+                }
+
+                ctx.frame += 1;
+
+                if last_frame {
+                    ctx.exit();
+                }
+            }
+            Event::WindowEvent { event, .. } => {
+                let input = InputEvent::from_window_event(&event);
+                if let (None, Some(input)) = (&player, input) {
+                    handle_input(&mut ctx, &mut recorder, input);
+                }
+
+                match event {
+                    WindowEvent::Resized(size) => {
+                        ctx.gl.resize(size);
+                        app.on_resize(&mut ctx, size.width, size.height);
+                    }
+                    WindowEvent::CloseRequested => ctx.exit(),
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Released,
+                            virtual_keycode: Some(VirtualKeyCode::Escape), ..
+                        }, ..
+                    } if config.exit_on_escape => ctx.exit(),
+                    WindowEvent::KeyboardInput {
+                        input: KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F3), ..
+                        }, ..
+                    } if player.is_none() => {
+                        overlay = match overlay.take() {
+                            Some(_) => None,
+                            None => Some(StatsOverlay::new()),
+                        };
+                    }
+                    _ => (),
+                }
+
+                // Live input would make a replay diverge from the recording.
+                if player.is_none() || input.is_none() {
+                    app.on_event(&mut ctx, &event);
+                }
+            }
+            Event::DeviceEvent { event, .. } => {
+                if let (None, Some(event)) = (&player, InputEvent::from_device_event(&event)) {
+                    handle_input(&mut ctx, &mut recorder, event);
+                }
+            }
+            Event::LoopDestroyed => {
+                if let (Some(path), Some(recorder)) = (&config.record, &recorder) {
+                    match recorder.recording().save(path) {
+                        Ok(()) => log::info!("recorded {} frames to {}", recorder.recording().frames.len(), path.display()),
+                        Err(err) => log::error!("{err:#}"),
+                    }
+                }
+                if let Some(path) = &config.stats_csv {
+                    if let Err(err) = ctx.stats.save_csv(path) {
+                        log::error!("{err:#}");
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        if ctx.exit {
+            *control_flow = ControlFlow::Exit;
+        }
+    });
+}
+
+fn handle_input(ctx: &mut AppContext, recorder: &mut Option<Recorder>, event: InputEvent) {
+    if let Some(recorder) = recorder {
+        recorder.record(event);
+    }
+    ctx.input.handle(&event);
+}
+
+fn ms_since(start: Instant) -> f32 {
+    start.elapsed().as_secs_f32() * 1000.0
+}
@@ -0,0 +1,576 @@
+//! Bounding volumes, view frustums and rays, for culling and mouse picking.
+//!
+//! Planes keep their normals pointing inside, so positive distances are in front of them.
+
+use nalgebra::{Matrix4, Point3, Unit, Vector3, Vector4};
+
+use crate::mesh::MeshData;
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// `None` without any point.
+    pub fn from_points<'a>(points: impl IntoIterator<Item=&'a Point3<f32>>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, p| Self::new(aabb.min.inf(p), aabb.max.sup(p))))
+    }
+
+    pub fn from_mesh(mesh: &MeshData) -> Option<Self> {
+        Self::from_points(&mesh.positions)
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    /// Half of the size along each axis.
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) / 2.0
+    }
+
+    pub fn contains(&self, p: &Point3<f32>) -> bool {
+        (0..3).all(|i| self.min[i] <= p[i] && p[i] <= self.max[i])
+    }
+
+    /// Touching boxes intersect.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+
+    pub fn merged(&self, other: &Aabb) -> Self {
+        Self::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point3::new(a.x, a.y, a.z), Point3::new(b.x, a.y, a.z),
+            Point3::new(a.x, b.y, a.z), Point3::new(b.x, b.y, a.z),
+            Point3::new(a.x, a.y, b.z), Point3::new(b.x, a.y, b.z),
+            Point3::new(a.x, b.y, b.z), Point3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    /// The box around this one after an affine transform, like a model matrix.
+    ///
+    /// Rotations grow the box, it still contains every transformed corner.
+    pub fn transformed(&self, m: &Matrix4<f32>) -> Self {
+        let center = m.transform_point(&self.center());
+        let linear = m.fixed_slice::<3, 3>(0, 0).abs();
+        let half = linear * self.half_extents();
+        Self::new(center - half, center + half)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: Point3<f32>, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Centered on the box around the points, which is close to but not the smallest sphere.
+    pub fn from_points<'a>(points: impl IntoIterator<Item=&'a Point3<f32>> + Clone) -> Option<Self> {
+        let center = Aabb::from_points(points.clone())?.center();
+        let radius = points.into_iter().map(|p| nalgebra::distance(&center, p)).fold(0.0, f32::max);
+        Some(Self::new(center, radius))
+    }
+
+    pub fn from_mesh(mesh: &MeshData) -> Option<Self> {
+        Self::from_points(&mesh.positions)
+    }
+
+    pub fn contains(&self, p: &Point3<f32>) -> bool {
+        nalgebra::distance_squared(&self.center, p) <= self.radius * self.radius
+    }
+
+    pub fn intersects(&self, other: &Sphere) -> bool {
+        let radii = self.radius + other.radius;
+        nalgebra::distance_squared(&self.center, &other.center) <= radii * radii
+    }
+
+    /// The sphere after an affine transform, non-uniform scaling uses the largest factor.
+    pub fn transformed(&self, m: &Matrix4<f32>) -> Self {
+        let scale = (0..3).map(|i| m.fixed_slice::<3, 1>(0, i).norm()).fold(0.0, f32::max);
+        Self::new(m.transform_point(&self.center), self.radius * scale)
+    }
+}
+
+/// Points `p` with `normal.dot(p) + d == 0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub d: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vector3<f32>, d: f32) -> Self {
+        Self { normal, d }
+    }
+
+    pub fn from_point_normal(point: &Point3<f32>, normal: &Unit<Vector3<f32>>) -> Self {
+        Self::new(normal.into_inner(), -normal.dot(&point.coords))
+    }
+
+    /// Normalized `(a, b, c, d)`. A zero normal is a plane at infinity,
+    /// with every point in front of it.
+    pub fn from_coefficients(v: &Vector4<f32>) -> Self {
+        let normal = v.xyz();
+        let length = normal.norm();
+        if length < 1e-6 {
+            return Self::new(Vector3::zeros(), f32::INFINITY);
+        }
+        Self::new(normal / length, v.w / length)
+    }
+
+    pub fn signed_distance(&self, p: &Point3<f32>) -> f32 {
+        self.normal.dot(&p.coords) + self.d
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Containment {
+    Outside,
+    Intersecting,
+    Inside,
+}
+
+/// The volume a camera sees, as six planes facing inside.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far.
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extract the planes from a view-projection matrix with OpenGL's `[-1, 1]` clip depth.
+    /// A projection matrix alone gives the frustum in camera space.
+    pub fn from_matrix(m: &Matrix4<f32>) -> Self {
+        let row = |i: usize| m.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self::from_rows(&x, &y, &(w + z), &(w - z), &w)
+    }
+
+    /// Like [`from_matrix`](Self::from_matrix) for a `[0, 1]` clip depth, as used with
+    /// `glClipControl` and reversed z. The near and far planes swap with reversed z.
+    pub fn from_matrix_zero_to_one(m: &Matrix4<f32>) -> Self {
+        let row = |i: usize| m.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self::from_rows(&x, &y, &z, &(w - z), &w)
+    }
+
+    fn from_rows(x: &Vector4<f32>, y: &Vector4<f32>, near: &Vector4<f32>, far: &Vector4<f32>, w: &Vector4<f32>) -> Self {
+        Self {
+            planes: [
+                Plane::from_coefficients(&(w + x)),
+                Plane::from_coefficients(&(w - x)),
+                Plane::from_coefficients(&(w + y)),
+                Plane::from_coefficients(&(w - y)),
+                Plane::from_coefficients(near),
+                Plane::from_coefficients(far),
+            ],
+        }
+    }
+
+    pub fn contains(&self, p: &Point3<f32>) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(p) >= 0.0)
+    }
+
+    pub fn classify_sphere(&self, sphere: &Sphere) -> Containment {
+        let mut containment = Containment::Inside;
+        for plane in &self.planes {
+            let distance = plane.signed_distance(&sphere.center);
+            if distance < -sphere.radius {
+                return Containment::Outside;
+            }
+            if distance < sphere.radius {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+
+    /// Conservative: a box near a corner of the frustum can be reported as intersecting
+    /// while it is just outside, which only costs a draw call.
+    pub fn classify_aabb(&self, aabb: &Aabb) -> Containment {
+        let (center, half) = (aabb.center(), aabb.half_extents());
+        let mut containment = Containment::Inside;
+        for plane in &self.planes {
+            // projection of the box onto the plane normal
+            let radius = plane.normal.abs().dot(&half);
+            let distance = plane.signed_distance(&center);
+            if distance < -radius {
+                return Containment::Outside;
+            }
+            if distance < radius {
+                containment = Containment::Intersecting;
+            }
+        }
+        containment
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.classify_sphere(sphere) != Containment::Outside
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.classify_aabb(aabb) != Containment::Outside
+    }
+}
+
+/// Where a ray hits a triangle: `p = (1 - u - v) * a + u * b + v * c`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    pub t: f32,
+    pub u: f32,
+    pub v: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshHit {
+    pub t: f32,
+    /// Index of the triangle, the indices at `3 * triangle`.
+    pub triangle: usize,
+    pub u: f32,
+    pub v: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Unit<Vector3<f32>>,
+}
+
+impl Ray {
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Self {
+        Self { origin, direction: Unit::new_normalize(direction) }
+    }
+
+    /// From the point at clip depth `from_z` towards the one at `to_z` under the cursor,
+    /// `x` and `y` in normalized device coordinates.
+    pub fn unproject(inverse_view_projection: &Matrix4<f32>, x: f32, y: f32, from_z: f32, to_z: f32) -> Self {
+        let from = inverse_view_projection.transform_point(&Point3::new(x, y, from_z));
+        let to = inverse_view_projection.transform_point(&Point3::new(x, y, to_z));
+        Self::new(from, to - from)
+    }
+
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.direction.into_inner() * t
+    }
+
+    /// Distance to where the ray enters the box, `0` when it starts inside.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        slab(&self.origin, &self.direction, aabb)
+    }
+
+    /// Distance to where the ray enters the sphere, `0` when it starts inside.
+    pub fn intersect_sphere(&self, sphere: &Sphere) -> Option<f32> {
+        let to_center = sphere.center - self.origin;
+        let along = to_center.dot(&self.direction);
+        let off_axis = to_center.norm_squared() - along * along;
+        let r2 = sphere.radius * sphere.radius;
+        if off_axis > r2 {
+            return None;
+        }
+        let half_chord = (r2 - off_axis).sqrt();
+        let (near, far) = (along - half_chord, along + half_chord);
+        (far >= 0.0).then_some(near.max(0.0))
+    }
+
+    /// `None` when parallel or behind the origin.
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let facing = plane.normal.dot(&self.direction);
+        if facing.abs() < 1e-8 {
+            return None;
+        }
+        let t = -plane.signed_distance(&self.origin) / facing;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Möller-Trumbore, hits both faces.
+    pub fn intersect_triangle(&self, triangle: &[Point3<f32>; 3]) -> Option<TriangleHit> {
+        triangle_hit(&self.origin, &self.direction, triangle)
+    }
+
+    /// Closest triangle of `mesh` placed with `model`, distances are in world units.
+    pub fn intersect_mesh(&self, mesh: &MeshData, model: &Matrix4<f32>) -> Option<MeshHit> {
+        let inverse = model.try_inverse()?;
+        // not renormalized, so `t` along the local ray is the same as along the world one
+        let origin = inverse.transform_point(&self.origin);
+        let direction = inverse.transform_vector(&self.direction);
+
+        slab(&origin, &direction, &Aabb::from_mesh(mesh)?)?;
+        mesh.triangles()
+            .enumerate()
+            .filter_map(|(triangle, positions)| {
+                triangle_hit(&origin, &direction, &positions)
+                    .map(|hit| MeshHit { t: hit.t, triangle, u: hit.u, v: hit.v })
+            })
+            .min_by(|a, b| a.t.total_cmp(&b.t))
+    }
+}
+
+fn slab(origin: &Point3<f32>, direction: &Vector3<f32>, aabb: &Aabb) -> Option<f32> {
+    let (mut near, mut far) = (0.0f32, f32::INFINITY);
+    for i in 0..3 {
+        if direction[i].abs() < 1e-8 {
+            if origin[i] < aabb.min[i] || origin[i] > aabb.max[i] {
+                return None;
+            }
+            continue;
+        }
+        let inv = 1.0 / direction[i];
+        let (t0, t1) = ((aabb.min[i] - origin[i]) * inv, (aabb.max[i] - origin[i]) * inv);
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+        if near > far {
+            return None;
+        }
+    }
+    Some(near)
+}
+
+fn triangle_hit(origin: &Point3<f32>, direction: &Vector3<f32>, [a, b, c]: &[Point3<f32>; 3]) -> Option<TriangleHit> {
+    let (ab, ac) = (b - a, c - a);
+    let p = direction.cross(&ac);
+    let det = ab.dot(&p);
+    if det.abs() < 1e-8 {
+        return None;
+    }
+    let inv = 1.0 / det;
+
+    let s = origin - a;
+    let u = s.dot(&p) * inv;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&ab);
+    let v = direction.dot(&q) * inv;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(&q) * inv;
+    (t >= 0.0).then_some(TriangleHit { t, u, v })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use nalgebra::{Perspective3, UnitQuaternion};
+
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn bounds_from_mesh() {
+        let cube = MeshData::cube();
+        let aabb = Aabb::from_mesh(&cube).unwrap();
+        assert_eq!(aabb, Aabb::new(Point3::new(-0.5, -0.5, -0.5), Point3::new(0.5, 0.5, 0.5)));
+        assert_eq!(aabb.corners().iter().filter(|c| aabb.contains(c)).count(), 8);
+
+        let sphere = Sphere::from_mesh(&cube).unwrap();
+        assert_eq!(sphere.center, Point3::origin());
+        assert!(close(sphere.radius, 0.75f32.sqrt()));
+        assert!(cube.positions.iter().all(|p| sphere.contains(p)));
+
+        assert_eq!(Aabb::from_mesh(&MeshData::default()), None);
+        assert_eq!(Sphere::from_mesh(&MeshData::default()), None);
+    }
+
+    #[test]
+    fn aabb_overlap_and_merge() {
+        let a = unit_box();
+        let b = Aabb::new(Point3::new(1.0, 0.0, 0.0), Point3::new(3.0, 2.0, 2.0));
+        let c = Aabb::new(Point3::new(1.5, 0.0, 0.0), Point3::new(3.0, 2.0, 2.0));
+        assert!(a.intersects(&b) && b.intersects(&a));
+        assert!(!a.intersects(&c) && !c.intersects(&a));
+        assert_eq!(a.merged(&c), Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(3.0, 2.0, 2.0)));
+        assert!(!a.contains(&Point3::new(0.0, 1.5, 0.0)));
+
+        let s = Sphere::new(Point3::new(3.0, 0.0, 0.0), 1.0);
+        assert!(s.intersects(&Sphere::new(Point3::origin(), 2.0)));
+        assert!(!s.intersects(&Sphere::new(Point3::origin(), 1.9)));
+    }
+
+    #[test]
+    fn bounds_follow_transforms() {
+        let model = Matrix4::new_translation(&Vector3::new(10.0, 0.0, 0.0))
+            * UnitQuaternion::from_axis_angle(&Vector3::y_axis(), FRAC_PI_4).to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(2.0, 1.0, 1.0));
+
+        let aabb = unit_box().transformed(&model);
+        assert!(close(aabb.center().x, 10.0));
+        // every transformed corner is inside, and the box is as tight as the corners allow
+        let corners = unit_box().corners().map(|c| model.transform_point(&c));
+        assert!(corners.iter().all(|c| aabb.contains(&(c - (c - aabb.center()) * 1e-4))));
+        let tight = Aabb::from_points(&corners).unwrap();
+        assert!((tight.min - aabb.min).norm() < 1e-4 && (tight.max - aabb.max).norm() < 1e-4);
+
+        let sphere = Sphere::new(Point3::new(1.0, 0.0, 0.0), 1.0).transformed(&model);
+        assert!(close(sphere.radius, 2.0));
+        assert!(close(nalgebra::distance(&sphere.center, &Point3::new(10.0, 0.0, 0.0)), 2.0));
+    }
+
+    #[test]
+    fn planes_face_inside() {
+        let plane = Plane::from_point_normal(&Point3::new(0.0, 2.0, 0.0), &Vector3::y_axis());
+        assert_eq!(plane.signed_distance(&Point3::new(5.0, 5.0, 5.0)), 3.0);
+        assert_eq!(Plane::from_coefficients(&Vector4::new(0.0, 0.0, 2.0, 4.0)), Plane::new(Vector3::z(), 2.0));
+
+        let infinite = Plane::from_coefficients(&Vector4::new(0.0, 0.0, 0.0, 1.0));
+        assert!(infinite.signed_distance(&Point3::new(0.0, 0.0, -1e30)) > 0.0);
+    }
+
+    #[test]
+    fn frustum_planes_from_projection() {
+        let projection = Perspective3::new(1.0, std::f32::consts::FRAC_PI_2, 1.0, 10.0).to_homogeneous();
+        let frustum = Frustum::from_matrix(&projection);
+
+        let [left, right, bottom, top, near, far] = frustum.planes;
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((left.normal - Vector3::new(diagonal, 0.0, -diagonal)).norm() < 1e-5 && close(left.d, 0.0));
+        assert!((right.normal - Vector3::new(-diagonal, 0.0, -diagonal)).norm() < 1e-5);
+        assert!((bottom.normal - Vector3::new(0.0, diagonal, -diagonal)).norm() < 1e-5);
+        assert!((top.normal - Vector3::new(0.0, -diagonal, -diagonal)).norm() < 1e-5);
+        assert!((near.normal + Vector3::z()).norm() < 1e-5 && close(near.d, -1.0));
+        assert!((far.normal - Vector3::z()).norm() < 1e-5 && close(far.d, 10.0));
+
+        assert!(frustum.contains(&Point3::new(0.0, 0.0, -5.0)));
+        assert!(frustum.contains(&Point3::new(4.9, 0.0, -5.0)));
+        assert!(!frustum.contains(&Point3::new(5.1, 0.0, -5.0)));
+        assert!(!frustum.contains(&Point3::new(0.0, 0.0, -0.5)));
+        assert!(!frustum.contains(&Point3::new(0.0, 0.0, -11.0)));
+        assert!(!frustum.contains(&Point3::new(0.0, 0.0, 5.0)));
+    }
+
+    #[test]
+    fn frustum_with_zero_to_one_depth() {
+        // reversed z with an infinite far plane: depth = near / distance
+        let mut projection = Matrix4::zeros();
+        projection[(0, 0)] = 1.0;
+        projection[(1, 1)] = 1.0;
+        projection[(2, 3)] = 1.0;
+        projection[(3, 2)] = -1.0;
+        let frustum = Frustum::from_matrix_zero_to_one(&projection);
+
+        assert!(frustum.contains(&Point3::new(0.0, 0.0, -1e6)));
+        assert!(frustum.contains(&Point3::new(0.0, 0.0, -1.0)));
+        assert!(!frustum.contains(&Point3::new(0.0, 0.0, -0.9)));
+    }
+
+    #[test]
+    fn frustum_culling() {
+        let view = Matrix4::look_at_rh(&Point3::new(0.0, 0.0, 5.0), &Point3::origin(), &Vector3::y());
+        let projection = Perspective3::new(1.0, std::f32::consts::FRAC_PI_2, 1.0, 10.0).to_homogeneous();
+        let frustum = Frustum::from_matrix(&(projection * view));
+
+        let at = |x: f32, y: f32, z: f32| Aabb::new(Point3::new(x - 0.5, y - 0.5, z - 0.5), Point3::new(x + 0.5, y + 0.5, z + 0.5));
+        assert_eq!(frustum.classify_aabb(&at(0.0, 0.0, 0.0)), Containment::Inside);
+        // straddling the right plane x = 5 - z at z = 0
+        assert_eq!(frustum.classify_aabb(&at(5.0, 0.0, 0.0)), Containment::Intersecting);
+        assert_eq!(frustum.classify_aabb(&at(7.0, 0.0, 0.0)), Containment::Outside);
+        // behind the camera and past the far plane
+        assert_eq!(frustum.classify_aabb(&at(0.0, 0.0, 6.0)), Containment::Outside);
+        assert_eq!(frustum.classify_aabb(&at(0.0, 0.0, -5.0)), Containment::Intersecting);
+        assert_eq!(frustum.classify_aabb(&at(0.0, 0.0, -6.0)), Containment::Outside);
+        assert!(frustum.intersects_aabb(&at(0.0, 4.5, 0.0)));
+        assert!(!frustum.intersects_aabb(&at(0.0, -7.0, 0.0)));
+
+        let sphere = |x: f32, radius: f32| Sphere::new(Point3::new(x, 0.0, 0.0), radius);
+        assert_eq!(frustum.classify_sphere(&sphere(0.0, 1.0)), Containment::Inside);
+        assert_eq!(frustum.classify_sphere(&sphere(5.0, 1.0)), Containment::Intersecting);
+        assert_eq!(frustum.classify_sphere(&sphere(7.0, 1.0)), Containment::Outside);
+        assert!(frustum.intersects_sphere(&sphere(0.0, 100.0)));
+    }
+
+    #[test]
+    fn rays_against_bounds() {
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0));
+        assert_eq!(ray.direction.into_inner(), Vector3::x());
+        assert_eq!(ray.at(2.0), Point3::new(-3.0, 0.0, 0.0));
+
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(4.0));
+        assert_eq!(Ray::new(Point3::origin(), Vector3::x()).intersect_aabb(&unit_box()), Some(0.0));
+        assert_eq!(Ray::new(Point3::new(-5.0, 2.0, 0.0), Vector3::x()).intersect_aabb(&unit_box()), None);
+        assert_eq!(Ray::new(Point3::new(5.0, 0.0, 0.0), Vector3::x()).intersect_aabb(&unit_box()), None);
+        let diagonal = Ray::new(Point3::new(-3.0, -3.0, -3.0), Vector3::repeat(1.0));
+        assert!(close(diagonal.intersect_aabb(&unit_box()).unwrap(), 2.0 * 3.0f32.sqrt()));
+
+        let sphere = Sphere::new(Point3::origin(), 1.0);
+        assert_eq!(ray.intersect_sphere(&sphere), Some(4.0));
+        assert_eq!(Ray::new(Point3::origin(), Vector3::y()).intersect_sphere(&sphere), Some(0.0));
+        assert_eq!(Ray::new(Point3::new(-5.0, 1.1, 0.0), Vector3::x()).intersect_sphere(&sphere), None);
+        assert_eq!(Ray::new(Point3::new(5.0, 0.0, 0.0), Vector3::x()).intersect_sphere(&sphere), None);
+
+        let ground = Plane::from_point_normal(&Point3::origin(), &Vector3::y_axis());
+        assert_eq!(Ray::new(Point3::new(1.0, 3.0, 1.0), -Vector3::y()).intersect_plane(&ground), Some(3.0));
+        assert_eq!(Ray::new(Point3::new(1.0, 3.0, 1.0), Vector3::y()).intersect_plane(&ground), None);
+        assert_eq!(Ray::new(Point3::new(1.0, 3.0, 1.0), Vector3::x()).intersect_plane(&ground), None);
+    }
+
+    #[test]
+    fn rays_against_triangles() {
+        let triangle = [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)];
+        let hit = Ray::new(Point3::new(0.25, 0.5, 2.0), -Vector3::z()).intersect_triangle(&triangle).unwrap();
+        assert!(close(hit.t, 2.0) && close(hit.u, 0.25) && close(hit.v, 0.5));
+        // both faces, but not behind, beside or along the triangle
+        assert!(Ray::new(Point3::new(0.25, 0.25, -2.0), Vector3::z()).intersect_triangle(&triangle).is_some());
+        assert!(Ray::new(Point3::new(0.25, 0.25, -2.0), -Vector3::z()).intersect_triangle(&triangle).is_none());
+        assert!(Ray::new(Point3::new(0.6, 0.6, 2.0), -Vector3::z()).intersect_triangle(&triangle).is_none());
+        assert!(Ray::new(Point3::new(-1.0, 0.5, 0.0), Vector3::x()).intersect_triangle(&triangle).is_none());
+    }
+
+    #[test]
+    fn rays_against_meshes() {
+        let cube = MeshData::cube();
+        let model = Matrix4::new_translation(&Vector3::new(0.0, 0.0, -10.0)) * Matrix4::new_scaling(2.0);
+
+        let hit = Ray::new(Point3::origin(), -Vector3::z()).intersect_mesh(&cube, &model).unwrap();
+        assert!(close(hit.t, 9.0));
+        // the +z face comes fifth in `MeshData::cube`
+        assert!(hit.triangle / 2 == 4);
+
+        let from_inside = Ray::new(Point3::new(0.0, 0.0, -10.0), Vector3::x()).intersect_mesh(&cube, &model).unwrap();
+        assert!(close(from_inside.t, 1.0));
+        assert!(Ray::new(Point3::origin(), Vector3::z()).intersect_mesh(&cube, &model).is_none());
+        assert!(Ray::new(Point3::new(1.1, 0.0, 0.0), -Vector3::z()).intersect_mesh(&cube, &model).is_none());
+        assert!(Ray::new(Point3::origin(), -Vector3::z()).intersect_mesh(&cube, &Matrix4::zeros()).is_none());
+    }
+
+    #[test]
+    fn rays_through_the_screen() {
+        let view = Matrix4::look_at_rh(&Point3::new(0.0, 0.0, 5.0), &Point3::origin(), &Vector3::y());
+        let projection = Perspective3::new(1.0, std::f32::consts::FRAC_PI_2, 1.0, 10.0).to_homogeneous();
+        let inverse = (projection * view).try_inverse().unwrap();
+
+        let center = Ray::unproject(&inverse, 0.0, 0.0, -1.0, 1.0);
+        assert!((center.origin - Point3::new(0.0, 0.0, 4.0)).norm() < 1e-4);
+        assert!((center.direction.into_inner() + Vector3::z()).norm() < 1e-5);
+
+        // the right edge of a 90 degree view
+        let edge = Ray::unproject(&inverse, 1.0, 0.0, -1.0, 1.0);
+        assert!((edge.direction.into_inner() - Vector3::new(1.0, 0.0, -1.0).normalize()).norm() < 1e-5);
+    }
+}
@@ -0,0 +1,491 @@
+//! A camera with projections and controllers for first person, orbiting and free flight.
+
+use std::f32::consts::FRAC_PI_2;
+
+use glutin::event::MouseButton;
+use nalgebra::{Isometry3, Matrix4, Orthographic3, Perspective3, Point3, UnitQuaternion, Vector3};
+
+use crate::bounds::{Frustum, Ray};
+use crate::input::{ActionMap, InputState};
+
+/// Default bindings of the actions the controllers read.
+pub const CAMERA_ACTIONS: &str = "
+forward = W, PadLeftStickY+
+back = S, PadLeftStickY-
+left = A, PadLeftStickX-
+right = D, PadLeftStickX+
+up = Space, PadRightBumper
+down = LControl, PadLeftBumper
+roll_left = Q, PadDPadLeft
+roll_right = E, PadDPadRight
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// `far: None` puts the far plane at infinity.
+    ///
+    /// With `reversed_z` depth goes from `1` at the near plane to `0` at the far plane,
+    /// which needs `glClipControl(GL_LOWER_LEFT, GL_ZERO_TO_ONE)`, clearing depth to `0`
+    /// and `GL_GREATER` to spread the float precision evenly.
+    Perspective { fov_y: f32, aspect: f32, near: f32, far: Option<f32>, reversed_z: bool },
+    /// `height` world units fit the viewport vertically.
+    Orthographic { height: f32, aspect: f32, near: f32, far: f32 },
+}
+
+impl Projection {
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Self::Perspective { fov_y, aspect, near, far: Some(far), reversed_z: false }
+    }
+
+    pub fn infinite(fov_y: f32, aspect: f32, near: f32) -> Self {
+        Self::Perspective { fov_y, aspect, near, far: None, reversed_z: false }
+    }
+
+    pub fn reversed_z(fov_y: f32, aspect: f32, near: f32, far: Option<f32>) -> Self {
+        Self::Perspective { fov_y, aspect, near, far, reversed_z: true }
+    }
+
+    pub fn orthographic(height: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Self::Orthographic { height, aspect, near, far }
+    }
+
+    pub fn set_aspect(&mut self, new_aspect: f32) {
+        match self {
+            Self::Perspective { aspect, .. } | Self::Orthographic { aspect, .. } => *aspect = new_aspect,
+        }
+    }
+
+    pub fn near(&self) -> f32 {
+        match *self {
+            Self::Perspective { near, .. } | Self::Orthographic { near, .. } => near,
+        }
+    }
+
+    /// `None` for an infinite far plane.
+    pub fn far(&self) -> Option<f32> {
+        match *self {
+            Self::Perspective { far, .. } => far,
+            Self::Orthographic { far, .. } => Some(far),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        match *self {
+            Self::Perspective { fov_y, aspect, near, far, reversed_z } => {
+                let f = 1.0 / (fov_y / 2.0).tan();
+                let mut m = Matrix4::zeros();
+                m[(0, 0)] = f / aspect;
+                m[(1, 1)] = f;
+                m[(3, 2)] = -1.0;
+
+                match (far, reversed_z) {
+                    (Some(far), false) => return Perspective3::new(aspect, fov_y, near, far).to_homogeneous(),
+                    // the limit of the finite matrix as far goes to infinity
+                    (None, false) => {
+                        m[(2, 2)] = -1.0;
+                        m[(2, 3)] = -2.0 * near;
+                    }
+                    (Some(far), true) => {
+                        m[(2, 2)] = near / (far - near);
+                        m[(2, 3)] = far * near / (far - near);
+                    }
+                    (None, true) => {
+                        m[(2, 3)] = near;
+                    }
+                }
+                m
+            }
+            Self::Orthographic { height, aspect, near, far } => {
+                let (w, h) = (height * aspect / 2.0, height / 2.0);
+                Orthographic3::new(-w, w, -h, h, near, far).to_homogeneous()
+            }
+        }
+    }
+}
+
+/// Looks down its local `-z` with `+y` up, like OpenGL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    pub position: Point3<f32>,
+    pub orientation: UnitQuaternion<f32>,
+    pub projection: Projection,
+}
+
+impl Camera {
+    pub fn new(position: Point3<f32>, projection: Projection) -> Self {
+        Self { position, orientation: UnitQuaternion::identity(), projection }
+    }
+
+    /// Turn towards `target`, `up` must not be parallel to the view direction.
+    pub fn look_at(&mut self, target: &Point3<f32>, up: &Vector3<f32>) {
+        let view = Isometry3::look_at_rh(&self.position, target, up);
+        self.orientation = view.rotation.inverse();
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        self.orientation * -Vector3::z()
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.orientation * Vector3::x()
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.orientation * Vector3::y()
+    }
+
+    /// World to camera space.
+    pub fn view(&self) -> Matrix4<f32> {
+        Isometry3::from_parts(self.position.coords.into(), self.orientation).inverse().to_homogeneous()
+    }
+
+    pub fn projection(&self) -> Matrix4<f32> {
+        self.projection.matrix()
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.projection() * self.view()
+    }
+
+    fn reversed_z(&self) -> bool {
+        matches!(self.projection, Projection::Perspective { reversed_z: true, .. })
+    }
+
+    /// What the camera sees in world space, for culling.
+    pub fn frustum(&self) -> Frustum {
+        if self.reversed_z() {
+            Frustum::from_matrix_zero_to_one(&self.view_projection())
+        } else {
+            Frustum::from_matrix(&self.view_projection())
+        }
+    }
+
+    /// World space ray under `cursor` in window pixels, top left first like
+    /// [`InputState::cursor`], starting on the near plane.
+    pub fn ray(&self, cursor: (f32, f32), viewport: (f32, f32)) -> Ray {
+        let x = 2.0 * cursor.0 / viewport.0 - 1.0;
+        let y = 1.0 - 2.0 * cursor.1 / viewport.1;
+        let inverse = self.view_projection().try_inverse().unwrap_or_else(Matrix4::identity);
+        // the far plane can be at infinity, aim at a depth halfway instead
+        if self.reversed_z() {
+            Ray::unproject(&inverse, x, y, 1.0, 0.5)
+        } else {
+            Ray::unproject(&inverse, x, y, -1.0, 0.0)
+        }
+    }
+}
+
+/// Moves a camera from the input of each frame.
+pub trait CameraController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, dt: f32);
+}
+
+/// The actions the controllers read, see [`CAMERA_ACTIONS`].
+pub fn camera_actions() -> ActionMap {
+    ActionMap::parse(CAMERA_ACTIONS).unwrap()
+}
+
+/// Movement on the action axes: `x` right, `y` up, `z` forward, each in `[-1, 1]`.
+fn movement(input: &InputState) -> Vector3<f32> {
+    let axis = |pos: &str, neg: &str| input.action_value(pos) - input.action_value(neg);
+    Vector3::new(axis("right", "left"), axis("up", "down"), axis("forward", "back"))
+}
+
+/// Mouse motion plus the right stick, in pixels.
+fn look(input: &InputState, stick_speed: f32, dt: f32) -> (f32, f32) {
+    let (mx, my) = input.mouse_motion();
+    let (sx, sy) = input.gamepad().right_stick();
+    // Stick up looks up, the mouse moving up has a negative delta.
+    (mx + sx * stick_speed * dt, my - sy * stick_speed * dt)
+}
+
+/// First person: yaw around world up, pitch clamped short of straight up or down.
+#[derive(Debug, Clone)]
+pub struct FpsController {
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Units per second.
+    pub speed: f32,
+    /// Radians per pixel.
+    pub sensitivity: f32,
+    /// Pixels per second the right stick counts as at full tilt.
+    pub stick_speed: f32,
+    pub max_pitch: f32,
+}
+
+impl Default for FpsController {
+    fn default() -> Self {
+        Self { yaw: 0.0, pitch: 0.0, speed: 2.5, sensitivity: 0.002, stick_speed: 800.0, max_pitch: 89f32.to_radians() }
+    }
+}
+
+impl FpsController {
+    /// Turn by a mouse movement of `dx`, `dy` pixels.
+    pub fn rotate(&mut self, dx: f32, dy: f32) {
+        self.yaw -= dx * self.sensitivity;
+        self.pitch = (self.pitch - dy * self.sensitivity).clamp(-self.max_pitch, self.max_pitch);
+    }
+
+    pub fn orientation(&self) -> UnitQuaternion<f32> {
+        UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw)
+            * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), self.pitch)
+    }
+}
+
+impl CameraController for FpsController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, dt: f32) {
+        let (dx, dy) = look(input, self.stick_speed, dt);
+        self.rotate(dx, dy);
+        camera.orientation = self.orientation();
+
+        // Walk on the ground plane wherever the camera looks.
+        let yaw = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw);
+        let m = movement(input);
+        let direction = yaw * Vector3::new(m.x, 0.0, -m.z) + Vector3::y() * m.y;
+        camera.position += direction * self.speed * dt;
+    }
+}
+
+/// Orbits `target` while the left button is held, the wheel zooms.
+#[derive(Debug, Clone)]
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Radians per pixel.
+    pub sensitivity: f32,
+    pub stick_speed: f32,
+    /// Each wheel line scales the distance by `1 - zoom_speed`.
+    pub zoom_speed: f32,
+}
+
+impl Default for OrbitController {
+    fn default() -> Self {
+        Self {
+            target: Point3::origin(),
+            distance: 5.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: 0.5,
+            max_distance: 100.0,
+            sensitivity: 0.005,
+            stick_speed: 800.0,
+            zoom_speed: 0.1,
+        }
+    }
+}
+
+impl OrbitController {
+    pub fn rotate(&mut self, dx: f32, dy: f32) {
+        let max_pitch = FRAC_PI_2 - 0.01;
+        self.yaw -= dx * self.sensitivity;
+        self.pitch = (self.pitch - dy * self.sensitivity).clamp(-max_pitch, max_pitch);
+    }
+
+    /// Positive `lines` zoom in.
+    pub fn zoom(&mut self, lines: f32) {
+        self.distance = (self.distance * (1.0 - self.zoom_speed).powf(lines)).clamp(self.min_distance, self.max_distance);
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.orientation = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw)
+            * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), self.pitch);
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, dt: f32) {
+        let (sx, sy) = input.gamepad().right_stick();
+        let (mut dx, mut dy) = (sx * self.stick_speed * dt, -sy * self.stick_speed * dt);
+        // Dragging rotates, the cursor is not grabbed.
+        if input.button_down(MouseButton::Left) {
+            let (mx, my) = input.cursor_delta();
+            dx += mx;
+            dy += my;
+        }
+        self.rotate(dx, dy);
+
+        let m = movement(input);
+        self.zoom(input.scroll().1 + m.z * 10.0 * dt);
+        self.apply(camera);
+    }
+}
+
+/// Six degrees of freedom: turning and moving along the camera's own axes, with roll.
+#[derive(Debug, Clone)]
+pub struct FreeFlyController {
+    pub speed: f32,
+    /// Radians per pixel.
+    pub sensitivity: f32,
+    pub stick_speed: f32,
+    /// Radians per second.
+    pub roll_speed: f32,
+}
+
+impl Default for FreeFlyController {
+    fn default() -> Self {
+        Self { speed: 5.0, sensitivity: 0.002, stick_speed: 800.0, roll_speed: 1.5 }
+    }
+}
+
+impl FreeFlyController {
+    /// Rotate around the camera's own axes: yaw `dx` and pitch `dy` pixels, `roll` radians.
+    pub fn rotate(&self, camera: &mut Camera, dx: f32, dy: f32, roll: f32) {
+        let local = UnitQuaternion::from_euler_angles(-dy * self.sensitivity, -dx * self.sensitivity, roll);
+        camera.orientation *= local;
+        // Thousands of small rotations drift away from unit length.
+        camera.orientation.renormalize_fast();
+    }
+}
+
+impl CameraController for FreeFlyController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, dt: f32) {
+        let (dx, dy) = look(input, self.stick_speed, dt);
+        let roll = (input.action_value("roll_left") - input.action_value("roll_right")) * self.roll_speed * dt;
+        self.rotate(camera, dx, dy, roll);
+
+        let m = movement(input);
+        let direction = camera.right() * m.x + camera.up() * m.y + camera.forward() * m.z;
+        camera.position += direction * self.speed * dt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    use nalgebra::Vector4;
+
+    use crate::input::InputEvent;
+
+    use super::*;
+
+    /// NDC depth of a point `distance` in front of the camera.
+    fn depth(projection: &Projection, distance: f32) -> f32 {
+        let clip = projection.matrix() * Vector4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn projection_depth_ranges() {
+        let p = Projection::perspective(FRAC_PI_4, 1.5, 0.1, 100.0);
+        assert!(close(depth(&p, 0.1), -1.0) && close(depth(&p, 100.0), 1.0));
+
+        let p = Projection::infinite(FRAC_PI_4, 1.5, 0.1);
+        assert!(close(depth(&p, 0.1), -1.0) && depth(&p, 1e6) < 1.0 && close(depth(&p, 1e6), 1.0));
+
+        let p = Projection::reversed_z(FRAC_PI_4, 1.5, 0.1, Some(100.0));
+        assert!(close(depth(&p, 0.1), 1.0) && close(depth(&p, 100.0), 0.0));
+
+        let p = Projection::reversed_z(FRAC_PI_4, 1.5, 0.1, None);
+        assert!(close(depth(&p, 0.1), 1.0) && depth(&p, 1e6) > 0.0 && close(depth(&p, 1e6), 0.0));
+
+        let p = Projection::orthographic(10.0, 2.0, 0.1, 100.0);
+        let corner = p.matrix() * Vector4::new(10.0, 5.0, -0.1, 1.0);
+        assert!(close(corner.x, 1.0) && close(corner.y, 1.0) && close(corner.z, -1.0));
+    }
+
+    #[test]
+    fn view_matrix_follows_camera() {
+        let mut camera = Camera::new(Point3::new(0.0, 0.0, 5.0), Projection::perspective(FRAC_PI_4, 1.0, 0.1, 100.0));
+        camera.look_at(&Point3::new(5.0, 0.0, 5.0), &Vector3::y());
+
+        assert!((camera.forward() - Vector3::x()).norm() < 1e-5);
+        let target = camera.view().transform_point(&Point3::new(5.0, 0.0, 5.0));
+        assert!((target - Point3::new(0.0, 0.0, -5.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn frustum_and_picking_rays() {
+        for projection in [
+            Projection::perspective(FRAC_PI_2, 2.0, 0.1, 100.0),
+            Projection::infinite(FRAC_PI_2, 2.0, 0.1),
+            Projection::reversed_z(FRAC_PI_2, 2.0, 0.1, Some(100.0)),
+            Projection::reversed_z(FRAC_PI_2, 2.0, 0.1, None),
+        ] {
+            let mut camera = Camera::new(Point3::new(0.0, 0.0, 5.0), projection);
+            camera.look_at(&Point3::new(5.0, 0.0, 5.0), &Vector3::y());
+
+            let frustum = camera.frustum();
+            assert!(frustum.contains(&Point3::new(50.0, 0.0, 5.0)), "{projection:?}");
+            assert!(!frustum.contains(&Point3::new(-5.0, 0.0, 5.0)), "{projection:?}");
+            assert!(!frustum.contains(&Point3::new(0.05, 0.0, 5.0)), "{projection:?}");
+
+            let center = camera.ray((400.0, 300.0), (800.0, 600.0));
+            assert!((center.origin - Point3::new(0.1, 0.0, 5.0)).norm() < 1e-3, "{projection:?}");
+            assert!((center.direction.into_inner() - Vector3::x()).norm() < 1e-3, "{projection:?}");
+
+            // the top of a 90 degree vertical view, the window's y goes down
+            let top = camera.ray((400.0, 0.0), (800.0, 600.0));
+            assert!((top.direction.into_inner() - Vector3::new(1.0, 1.0, 0.0).normalize()).norm() < 1e-3, "{projection:?}");
+        }
+    }
+
+    #[test]
+    fn fps_pitch_is_clamped() {
+        let mut fps = FpsController::default();
+        fps.rotate(0.0, -1e6);
+        assert_eq!(fps.pitch, fps.max_pitch);
+
+        fps.rotate(FRAC_PI_2 / fps.sensitivity, 0.0);
+        let mut camera = Camera::new(Point3::origin(), Projection::infinite(1.0, 1.0, 0.1));
+        camera.orientation = fps.orientation();
+        // looking right and almost straight up
+        assert!(camera.forward().y > 0.99 && camera.forward().x > 0.0);
+        assert!(camera.right().y.abs() < 1e-5, "no roll");
+    }
+
+    #[test]
+    fn fps_walks_on_the_ground() {
+        let mut input = InputState::new();
+        input.set_actions(camera_actions());
+        input.handle(&InputEvent::Key { key: glutin::event::VirtualKeyCode::W, pressed: true });
+
+        let mut fps = FpsController { pitch: 0.5, ..Default::default() };
+        let mut camera = Camera::new(Point3::origin(), Projection::infinite(1.0, 1.0, 0.1));
+        fps.update(&mut camera, &input, 1.0);
+
+        assert!(close(camera.position.y, 0.0));
+        assert!(close(camera.position.z, -fps.speed));
+    }
+
+    #[test]
+    fn orbit_keeps_distance_to_target() {
+        let mut orbit = OrbitController { target: Point3::new(1.0, 2.0, 3.0), ..Default::default() };
+        let mut camera = Camera::new(Point3::origin(), Projection::infinite(1.0, 1.0, 0.1));
+
+        orbit.rotate(100.0, -50.0);
+        orbit.zoom(2.0);
+        orbit.apply(&mut camera);
+
+        assert!(close(orbit.distance, 5.0 * 0.81));
+        assert!(close((camera.position - orbit.target).norm(), orbit.distance));
+        let target = camera.view().transform_point(&orbit.target);
+        assert!(close(target.x, 0.0) && close(target.y, 0.0) && close(target.z, -orbit.distance));
+
+        orbit.zoom(-1000.0);
+        assert_eq!(orbit.distance, orbit.max_distance);
+    }
+
+    #[test]
+    fn free_fly_rolls_and_moves_along_its_axes() {
+        let fly = FreeFlyController::default();
+        let mut camera = Camera::new(Point3::origin(), Projection::infinite(1.0, 1.0, 0.1));
+
+        fly.rotate(&mut camera, 0.0, 0.0, FRAC_PI_2);
+        assert!((camera.up() - -Vector3::x()).norm() < 1e-5);
+        assert!((camera.forward() - -Vector3::z()).norm() < 1e-5);
+
+        // pitching up after the roll turns towards the old left
+        fly.rotate(&mut camera, 0.0, -FRAC_PI_2 / fly.sensitivity, 0.0);
+        assert!((camera.forward() - -Vector3::x()).norm() < 1e-4);
+    }
+}
@@ -0,0 +1,137 @@
+//! Screenshots of the current framebuffer and comparisons against golden images.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use image::RgbaImage;
+
+/// Set to `1` to write the golden image instead of comparing against it, after an intended change.
+pub const BLESS_ENV: &str = "LEARNOPENGL_BLESS";
+
+/// Whether `LEARNOPENGL_BLESS=1` asks to rewrite the golden images.
+pub fn bless_from_env() -> bool {
+    std::env::var(BLESS_ENV).is_ok_and(|bless| bless == "1")
+}
+
+/// Read the bound read framebuffer, top row first.
+///
+/// # Safety
+/// Please ensure that gl is loaded.
+pub unsafe fn read_pixels(width: u32, height: u32) -> RgbaImage {
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    gl::ReadPixels(0, 0, width as _, height as _, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+
+    let image = RgbaImage::from_raw(width, height, pixels).unwrap();
+    // OpenGL rows start at the bottom.
+    image::imageops::flip_vertical(&image)
+}
+
+/// Per channel differences between two images of the same size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageDiff {
+    /// Largest difference of any channel.
+    pub max: u8,
+    /// Pixels with a channel differing by more than the tolerance.
+    pub mismatched: usize,
+    pub total: usize,
+}
+
+impl ImageDiff {
+    pub fn mismatched_fraction(&self) -> f32 {
+        self.mismatched as f32 / self.total.max(1) as f32
+    }
+}
+
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Result<ImageDiff> {
+    if actual.dimensions() != expected.dimensions() {
+        bail!("image size {:?} does not match {:?}", actual.dimensions(), expected.dimensions());
+    }
+
+    let mut diff = ImageDiff { max: 0, mismatched: 0, total: actual.pixels().len() };
+    for (a, e) in actual.pixels().zip(expected.pixels()) {
+        let max = a.0.iter().zip(e.0.iter()).map(|(a, e)| a.abs_diff(*e)).max().unwrap();
+        diff.max = diff.max.max(max);
+        if max > tolerance {
+            diff.mismatched += 1;
+        }
+    }
+    Ok(diff)
+}
+
+/// Compare `actual` with the png at `golden`, allowing a few pixels to differ for
+/// rasterization differences between drivers.
+///
+/// With `bless` the golden image is written instead, a missing one is an error.
+pub fn check_golden(actual: &RgbaImage, golden: impl AsRef<Path>, tolerance: u8, max_mismatched: f32, bless: bool) -> Result<ImageDiff> {
+    let golden = golden.as_ref();
+    let total = actual.pixels().len();
+
+    if bless {
+        if let Some(dir) = golden.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        actual.save(golden).with_context(|| format!("writing golden image {}", golden.display()))?;
+        log::warn!("wrote golden image {}", golden.display());
+        return Ok(ImageDiff { max: 0, mismatched: 0, total });
+    }
+    if !golden.exists() {
+        bail!("missing golden image {}, run with {BLESS_ENV}=1 to write it", golden.display());
+    }
+
+    let expected = image::open(golden)
+        .with_context(|| format!("reading golden image {}", golden.display()))?
+        .to_rgba8();
+    let diff = compare(actual, &expected, tolerance)?;
+
+    if diff.mismatched_fraction() > max_mismatched {
+        let failed = golden.with_extension("actual.png");
+        actual.save(&failed).ok();
+        bail!("{} of {} pixels differ from {} by more than {tolerance} (max {}), see {}",
+              diff.mismatched, diff.total, golden.display(), diff.max, failed.display());
+    }
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn compares_with_tolerance() {
+        let a = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+        let mut b = a.clone();
+        b.put_pixel(0, 0, Rgba([103, 100, 100, 255]));
+        b.put_pixel(1, 0, Rgba([100, 90, 100, 255]));
+
+        let diff = compare(&a, &b, 4).unwrap();
+        assert_eq!(diff, ImageDiff { max: 10, mismatched: 1, total: 16 });
+        assert_eq!(diff.mismatched_fraction(), 1.0 / 16.0);
+
+        assert!(compare(&a, &RgbaImage::new(2, 2), 0).is_err());
+    }
+
+    #[test]
+    fn missing_golden_is_an_error() {
+        let golden = std::env::temp_dir().join("learnopengl_missing_golden.png");
+        std::fs::remove_file(&golden).ok();
+
+        let err = check_golden(&RgbaImage::new(2, 2), &golden, 0, 0.0, false).unwrap_err();
+        assert!(err.to_string().contains(BLESS_ENV));
+        assert!(!golden.exists());
+    }
+
+    #[test]
+    fn bless_writes_the_golden() {
+        let golden = std::env::temp_dir().join("learnopengl_blessed_golden.png");
+        std::fs::remove_file(&golden).ok();
+
+        let image = RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 255]));
+        check_golden(&image, &golden, 0, 0.0, true).unwrap();
+        let diff = check_golden(&image, &golden, 0, 0.0, false).unwrap();
+        assert_eq!(diff.mismatched, 0);
+        std::fs::remove_file(&golden).ok();
+    }
+}
@@ -0,0 +1,214 @@
+//! Compute shaders, needs OpenGL 4.3 or `GL_ARB_compute_shader` with
+//! `GL_ARB_shader_storage_buffer_object`.
+//!
+//! `init` and [`AppConfig`](crate::app::AppConfig) ask for a 3.3 context, ask for more with
+//! [`AppConfig::with_gl_version`](crate::app::AppConfig::with_gl_version).
+
+use std::ffi::CString;
+
+use anyhow::{anyhow, bail, Result};
+use gl::types::*;
+
+use crate::info::{self, ContextInfo};
+use crate::shader::{compile_shader, get_status_and_output};
+use crate::storage::{ShaderStorageBuffer, Std430};
+
+/// Fail with a message naming the context unless it can run compute shaders.
+pub fn check_support(info: &ContextInfo) -> Result<()> {
+    let extensions = ["GL_ARB_compute_shader", "GL_ARB_shader_storage_buffer_object"];
+    if info.version_at_least(4, 3) || extensions.iter().all(|ext| info.supports(ext)) {
+        return Ok(());
+    }
+
+    let missing: Vec<_> = extensions.iter().filter(|ext| !info.supports(ext)).copied().collect();
+    bail!(
+        "compute shaders need OpenGL 4.3, the context is {}.{} without {}; \
+         create it with `ContextConfig {{ gl_version: (4, 3), .. }}` or `AppConfig::with_gl_version(4, 3)`",
+        info.major,
+        info.minor,
+        missing.join(" and ")
+    )
+}
+
+/// [`check_support`] for the current context.
+pub fn require_compute() -> Result<()> {
+    let info = unsafe {
+        let (mut major, mut minor) = (0, 0);
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        ContextInfo { major, minor, extensions: info::extensions(), ..Default::default() }
+    };
+    check_support(&info)
+}
+
+/// Make writes of earlier dispatches visible to the accesses in `barriers`,
+/// a combination of `gl::*_BARRIER_BIT`.
+pub fn memory_barrier(barriers: GLbitfield) {
+    unsafe {
+        crate::gl_check!(gl::MemoryBarrier(barriers));
+    }
+}
+
+/// Before the next dispatch reads what the last one wrote to a storage buffer.
+pub fn storage_barrier() {
+    memory_barrier(gl::SHADER_STORAGE_BARRIER_BIT);
+}
+
+/// Before reading a storage buffer back or drawing from it as vertices or indirect commands.
+pub fn readback_barrier() {
+    memory_barrier(gl::BUFFER_UPDATE_BARRIER_BIT | gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT | gl::COMMAND_BARRIER_BIT);
+}
+
+pub fn all_barriers() {
+    memory_barrier(gl::ALL_BARRIER_BITS);
+}
+
+/// The arguments of `glDispatchComputeIndirect`, laid out like the GL expects them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, crate::storage::Std430)]
+pub struct DispatchIndirectCommand {
+    pub num_groups_x: u32,
+    pub num_groups_y: u32,
+    pub num_groups_z: u32,
+}
+
+/// A linked program with a single compute shader.
+pub struct ComputeProgram {
+    prog: GLuint,
+    work_group_size: [u32; 3],
+}
+
+impl ComputeProgram {
+    pub fn new(source: &str) -> Result<Self> {
+        require_compute()?;
+
+        let shader = compile_shader(source, gl::COMPUTE_SHADER).map_err(|log| anyhow!("compute shader compile failed: {log}"))?;
+        let prog = unsafe {
+            let prog = gl::CreateProgram();
+            gl::AttachShader(prog, shader);
+            gl::LinkProgram(prog);
+            gl::DeleteShader(shader);
+            prog
+        };
+        if let Err(log) = get_status_and_output(gl::GetProgramiv, gl::GetProgramInfoLog, prog, gl::LINK_STATUS) {
+            unsafe { gl::DeleteProgram(prog) };
+            bail!("compute program link failed: {log}");
+        }
+
+        let mut size = [0; 3];
+        unsafe { gl::GetProgramiv(prog, gl::COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr()) };
+        Ok(Self { prog, work_group_size: size.map(|n| n as u32) })
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.prog
+    }
+
+    /// The `local_size_x/y/z` of the shader.
+    pub fn work_group_size(&self) -> [u32; 3] {
+        self.work_group_size
+    }
+
+    /// Work groups needed to cover `items` invocations in each dimension.
+    pub fn groups_for(&self, items: [u32; 3]) -> [u32; 3] {
+        std::array::from_fn(|i| items[i].div_ceil(self.work_group_size[i]))
+    }
+
+    pub fn activate(&self) -> Result<()> {
+        unsafe { crate::gl_check!(gl::UseProgram(self.prog)); }
+        Ok(())
+    }
+
+    /// Read the buffer block `name` from the storage buffer bound at `binding`.
+    pub fn bind_storage_block(&self, name: &str, binding: GLuint) -> Result<()> {
+        let c_name = CString::new(name).unwrap();
+        let index = unsafe { gl::GetProgramResourceIndex(self.prog, gl::SHADER_STORAGE_BLOCK, c_name.as_ptr()) };
+        if index == gl::INVALID_INDEX {
+            bail!("no shader storage block {name} in the program");
+        }
+        unsafe { crate::gl_check!(gl::ShaderStorageBlockBinding(self.prog, index, binding)); }
+        Ok(())
+    }
+
+    pub fn set_u32(&self, name: &str, value: u32) -> Result<()> {
+        let loc = self.location(name)?;
+        unsafe { crate::gl_check!(gl::ProgramUniform1ui(self.prog, loc, value)); }
+        Ok(())
+    }
+
+    pub fn set_f32(&self, name: &str, value: f32) -> Result<()> {
+        let loc = self.location(name)?;
+        unsafe { crate::gl_check!(gl::ProgramUniform1f(self.prog, loc, value)); }
+        Ok(())
+    }
+
+    fn location(&self, name: &str) -> Result<GLint> {
+        let c_name = CString::new(name).unwrap();
+        let loc = unsafe { gl::GetUniformLocation(self.prog, c_name.as_ptr()) };
+        if loc == -1 {
+            bail!("no uniform {name} in the compute program");
+        }
+        Ok(loc)
+    }
+
+    /// Run `x * y * z` work groups, the results need a barrier before they are used.
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) -> Result<()> {
+        self.activate()?;
+        unsafe { crate::gl_check!(gl::DispatchCompute(x, y, z)); }
+        Ok(())
+    }
+
+    /// Run the work groups of the [`DispatchIndirectCommand`] at `index` in `commands`,
+    /// usually written by an earlier dispatch.
+    pub fn dispatch_indirect(&self, commands: &ShaderStorageBuffer<DispatchIndirectCommand>, index: usize) -> Result<()> {
+        if index >= commands.len() {
+            bail!("dispatch command {index} out of {}", commands.len());
+        }
+        self.activate()?;
+        unsafe {
+            gl::BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, commands.id());
+            crate::gl_check!(gl::DispatchComputeIndirect((index * DispatchIndirectCommand::SIZE) as _));
+            gl::BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, 0);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ComputeProgram {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.prog);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(major: i32, minor: i32, extensions: &[&str]) -> ContextInfo {
+        ContextInfo { major, minor, extensions: extensions.iter().map(|ext| ext.to_string()).collect(), ..Default::default() }
+    }
+
+    #[test]
+    fn support_needs_4_3_or_both_extensions() {
+        assert!(check_support(&info(4, 3, &[])).is_ok());
+        assert!(check_support(&info(4, 6, &[])).is_ok());
+        assert!(check_support(&info(3, 3, &["GL_ARB_compute_shader", "GL_ARB_shader_storage_buffer_object"])).is_ok());
+
+        let err = check_support(&info(3, 3, &["GL_ARB_compute_shader"])).unwrap_err().to_string();
+        assert!(err.contains("context is 3.3"), "{err}");
+        assert!(err.contains("without GL_ARB_shader_storage_buffer_object;"), "{err}");
+        assert!(err.contains("gl_version: (4, 3)"), "{err}");
+
+        let err = check_support(&info(4, 1, &[])).unwrap_err().to_string();
+        assert!(err.contains("GL_ARB_compute_shader and GL_ARB_shader_storage_buffer_object"), "{err}");
+    }
+
+    #[test]
+    fn indirect_commands_are_tightly_packed() {
+        let command = DispatchIndirectCommand { num_groups_x: 4, num_groups_y: 2, num_groups_z: 1 };
+        assert_eq!(DispatchIndirectCommand::SIZE, 12);
+        assert_eq!(crate::storage::stride::<DispatchIndirectCommand>(), 12);
+        assert_eq!(command.to_std430(), [4u32, 2, 1].map(u32::to_ne_bytes).concat());
+    }
+}
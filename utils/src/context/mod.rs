@@ -0,0 +1,229 @@
+use std::ffi::c_void;
+
+use gl::types::*;
+use glutin::{Api, Context, ContextBuilder, ContextError, ContextWrapper, GlProfile, GlRequest, PossiblyCurrent};
+use glutin::dpi::PhysicalSize;
+use glutin::event_loop::EventLoop;
+use glutin::window::{Window, WindowBuilder};
+
+use crate::debug::{self, DebugConfig};
+
+/// Environment variable that switches every sample to a headless context when set to
+/// anything but `0`/`false`.
+pub const HEADLESS_ENV: &str = "LEARNOPENGL_HEADLESS";
+
+/// Command line flag with the same meaning as [`HEADLESS_ENV`].
+pub const HEADLESS_FLAG: &str = "--headless";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextMode {
+    Windowed,
+    Headless,
+}
+
+impl ContextMode {
+    /// Pick the mode from the command line (`--headless`) or the `LEARNOPENGL_HEADLESS`
+    /// environment variable, defaulting to a window.
+    pub fn from_env() -> Self {
+        let flag = std::env::args().skip(1).any(|arg| arg == HEADLESS_FLAG);
+        let env = std::env::var(HEADLESS_ENV)
+            .map(|v| !matches!(v.as_str(), "" | "0" | "false"))
+            .unwrap_or(false);
+
+        if flag || env { Self::Headless } else { Self::Windowed }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContextConfig {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+    pub debug: bool,
+    pub vsync: bool,
+    pub gl_version: (u8, u8),
+    pub mode: ContextMode,
+    pub debug_output: DebugConfig,
+}
+
+impl ContextConfig {
+    pub fn new(width: u32, height: u32, debug: bool) -> Self {
+        Self {
+            width,
+            height,
+            title: "Test Window".to_owned(),
+            debug,
+            vsync: true,
+            gl_version: (3, 3),
+            mode: ContextMode::from_env(),
+            debug_output: DebugConfig::default(),
+        }
+    }
+}
+
+/// A current OpenGL context, either attached to a window or rendering off-screen.
+///
+/// Samples only talk to this type, so the same binary can run in both modes.
+pub enum GlContext {
+    Windowed(ContextWrapper<PossiblyCurrent, Window>),
+    Headless {
+        context: Context<PossiblyCurrent>,
+        size: PhysicalSize<u32>,
+        offscreen: Offscreen,
+    },
+}
+
+/// Framebuffer standing in for the window of a headless context, surfaceless
+/// contexts have no default framebuffer at all.
+pub struct Offscreen {
+    fbo: GLuint,
+    color: GLuint,
+    depth: GLuint,
+}
+
+impl Offscreen {
+    /// Create and bind the framebuffer.
+    ///
+    /// # Safety
+    /// Please ensure that gl is loaded.
+    unsafe fn new(size: PhysicalSize<u32>) -> Self {
+        let mut offscreen = Self { fbo: 0, color: 0, depth: 0 };
+        gl::GenFramebuffers(1, &mut offscreen.fbo);
+        gl::GenRenderbuffers(1, &mut offscreen.color);
+        gl::GenRenderbuffers(1, &mut offscreen.depth);
+        offscreen.alloc(size);
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, offscreen.fbo);
+        gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, offscreen.color);
+        gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, offscreen.depth);
+
+        let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+        assert_eq!(status, gl::FRAMEBUFFER_COMPLETE, "incomplete offscreen framebuffer");
+        offscreen
+    }
+
+    unsafe fn alloc(&self, size: PhysicalSize<u32>) {
+        let (w, h) = (size.width.max(1) as _, size.height.max(1) as _);
+        gl::BindRenderbuffer(gl::RENDERBUFFER, self.color);
+        gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, w, h);
+        gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth);
+        gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, w, h);
+        gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+    }
+}
+
+impl GlContext {
+    /// Build the context, make it current, load gl and apply the common setup.
+    pub fn create(config: &ContextConfig) -> (Self, EventLoop<()>) {
+        let el = EventLoop::new();
+
+        let builder = ContextBuilder::new()
+            .with_gl(GlRequest::Specific(Api::OpenGl, config.gl_version))
+            .with_gl_profile(GlProfile::Core)
+            .with_gl_debug_flag(config.debug)
+            .with_vsync(config.vsync);
+
+        let size = PhysicalSize::new(config.width, config.height);
+
+        let context = match config.mode {
+            ContextMode::Windowed => {
+                let wb = WindowBuilder::new()
+                    .with_inner_size(size)
+                    .with_title(&config.title);
+
+                let context = unsafe { builder.build_windowed(wb, &el).unwrap().make_current().unwrap() };
+                crate::load_with(|s| context.get_proc_address(s));
+                Self::Windowed(context)
+            }
+            ContextMode::Headless => {
+                let context = unsafe { builder.build_headless(&el, size).unwrap().make_current().unwrap() };
+                crate::load_with(|s| context.get_proc_address(s));
+                Self::Headless {
+                    context,
+                    size,
+                    offscreen: unsafe { Offscreen::new(size) },
+                }
+            }
+        };
+
+        if config.debug {
+            debug::init_logger();
+
+            if cfg!(not(target_os = "macos")) && unsafe { debug::supports_debug_output() } {
+                unsafe { debug::init_debug_output(&config.debug_output) }
+            } else {
+                log::warn!(target: debug::LOG_TARGET,
+                    "debug output is unavailable, build with the `gl-check` feature to check `glGetError`");
+            }
+        }
+
+        unsafe { gl::Viewport(0, 0, config.width as _, config.height as _); }
+
+        #[cfg(feature = "gl-trace")]
+        crate::trace::start_from_env();
+
+        (context, el)
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self, Self::Headless { .. })
+    }
+
+    /// The framebuffer that ends up on screen, samples rendering to their own
+    /// framebuffers bind this one instead of `0` to get back.
+    pub fn default_framebuffer(&self) -> GLuint {
+        match self {
+            Self::Windowed(_) => 0,
+            Self::Headless { offscreen, .. } => offscreen.fbo,
+        }
+    }
+
+    pub fn window(&self) -> Option<&Window> {
+        match self {
+            Self::Windowed(context) => Some(context.window()),
+            Self::Headless { .. } => None,
+        }
+    }
+
+    pub fn get_proc_address(&self, addr: &str) -> *const c_void {
+        match self {
+            Self::Windowed(context) => context.get_proc_address(addr),
+            Self::Headless { context, .. } => context.get_proc_address(addr),
+        }
+    }
+
+    /// Presents the back buffer. Headless contexts have nothing to present, so this is a no-op.
+    ///
+    /// Panics here if the debug output collected errors in `panic_on_error` mode.
+    pub fn swap_buffers(&self) -> Result<(), ContextError> {
+        debug::check();
+
+        #[cfg(feature = "gl-trace")]
+        crate::trace::end_frame();
+
+        match self {
+            Self::Windowed(context) => context.swap_buffers(),
+            Self::Headless { .. } => Ok(()),
+        }
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        match self {
+            Self::Windowed(context) => context.window().inner_size(),
+            Self::Headless { size, .. } => *size,
+        }
+    }
+
+    /// Resize the drawable and the viewport.
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        match self {
+            Self::Windowed(context) => context.resize(new_size),
+            Self::Headless { size, offscreen, .. } => {
+                *size = new_size;
+                unsafe { offscreen.alloc(new_size); }
+            }
+        }
+
+        unsafe { gl::Viewport(0, 0, new_size.width as _, new_size.height as _); }
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use std::mem::{replace, size_of_val};
+use std::mem::size_of_val;
 use gl::*;
 
 pub mod triangle;
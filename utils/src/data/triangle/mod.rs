@@ -1,5 +1,4 @@
-use nalgebra::{Point, Point3};
-use crate::data;
+use nalgebra::Point;
 
 pub struct Triangle<const N: usize> {
 
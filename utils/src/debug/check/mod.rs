@@ -0,0 +1,188 @@
+use std::cell::Cell;
+use std::fmt::{Debug, Display, Formatter};
+
+use gl::types::*;
+
+use super::{DebugMessage, MessageType, Severity, Source};
+
+/// Upper bound on `glGetError` calls per check, a lost context can keep reporting forever.
+const MAX_PENDING_ERRORS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlError {
+    InvalidEnum,
+    InvalidValue,
+    InvalidOperation,
+    InvalidFramebufferOperation,
+    OutOfMemory,
+    StackUnderflow,
+    StackOverflow,
+    Unknown(GLenum),
+}
+
+impl GlError {
+    pub fn from_gl(error: GLenum) -> Option<Self> {
+        match error {
+            gl::NO_ERROR => None,
+            gl::INVALID_ENUM => Some(Self::InvalidEnum),
+            gl::INVALID_VALUE => Some(Self::InvalidValue),
+            gl::INVALID_OPERATION => Some(Self::InvalidOperation),
+            gl::INVALID_FRAMEBUFFER_OPERATION => Some(Self::InvalidFramebufferOperation),
+            gl::OUT_OF_MEMORY => Some(Self::OutOfMemory),
+            gl::STACK_UNDERFLOW => Some(Self::StackUnderflow),
+            gl::STACK_OVERFLOW => Some(Self::StackOverflow),
+            other => Some(Self::Unknown(other)),
+        }
+    }
+
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            Self::InvalidEnum => gl::INVALID_ENUM,
+            Self::InvalidValue => gl::INVALID_VALUE,
+            Self::InvalidOperation => gl::INVALID_OPERATION,
+            Self::InvalidFramebufferOperation => gl::INVALID_FRAMEBUFFER_OPERATION,
+            Self::OutOfMemory => gl::OUT_OF_MEMORY,
+            Self::StackUnderflow => gl::STACK_UNDERFLOW,
+            Self::StackOverflow => gl::STACK_OVERFLOW,
+            Self::Unknown(other) => other,
+        }
+    }
+}
+
+impl Display for GlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidEnum => write!(f, "GL_INVALID_ENUM"),
+            Self::InvalidValue => write!(f, "GL_INVALID_VALUE"),
+            Self::InvalidOperation => write!(f, "GL_INVALID_OPERATION"),
+            Self::InvalidFramebufferOperation => write!(f, "GL_INVALID_FRAMEBUFFER_OPERATION"),
+            Self::OutOfMemory => write!(f, "GL_OUT_OF_MEMORY"),
+            Self::StackUnderflow => write!(f, "GL_STACK_UNDERFLOW"),
+            Self::StackOverflow => write!(f, "GL_STACK_OVERFLOW"),
+            Self::Unknown(other) => write!(f, "unknown error {other:#x}"),
+        }
+    }
+}
+
+/// Drain every pending `glGetError` flag.
+pub fn drain_errors() -> Vec<GlError> {
+    drain_with(|| unsafe { gl::GetError() })
+}
+
+fn drain_with(get_error: impl Fn() -> GLenum) -> Vec<GlError> {
+    (0..MAX_PENDING_ERRORS)
+        .map_while(|_| GlError::from_gl(get_error()))
+        .collect()
+}
+
+/// Drain and report the pending errors of `call`, issued at `file:line`.
+///
+/// The errors go through the same path as the debug callback, so `panic_on_error` applies
+/// to them as well.
+pub fn check_errors(call: &str, file: &str, line: u32) -> Vec<GlError> {
+    let errors = drain_errors();
+    report_errors(&errors, call, Some((file, line)));
+    errors
+}
+
+fn report_errors(errors: &[GlError], call: &str, site: Option<(&str, u32)>) {
+    // Without debug output the context installs no logger, the reports must not get lost.
+    super::init_logger();
+
+    for error in errors {
+        let (message, location) = match site {
+            Some((file, line)) => (format!("{error} after `{call}` at {file}:{line}"), Some(format!("{file}:{line}"))),
+            None => (format!("{error} after `{call}`"), None),
+        };
+        super::handle_message(DebugMessage {
+            source: Source::Api,
+            gltype: MessageType::Error,
+            id: error.to_gl(),
+            severity: Severity::High,
+            message,
+            location,
+        });
+    }
+}
+
+thread_local! {
+    static SITE: Cell<Option<(&'static str, u32)>> = const { Cell::new(None) };
+}
+
+/// The `file:line` of the [`gl_check!`](crate::gl_check) currently calling into gl, until dropped.
+#[doc(hidden)]
+pub struct CallSite(Option<(&'static str, u32)>);
+
+impl CallSite {
+    pub fn enter(file: &'static str, line: u32) -> Self {
+        Self(SITE.with(|site| site.replace(Some((file, line)))))
+    }
+}
+
+impl Drop for CallSite {
+    fn drop(&mut self) {
+        SITE.with(|site| site.set(self.0));
+    }
+}
+
+/// Run by the shims of [`loader`](crate::loader) after every gl call, `get_error` is the
+/// driver's own so draining doesn't go through the shims.
+#[cfg(feature = "gl-check")]
+pub(crate) fn check_call(name: &str, args: &[&dyn Debug], get_error: impl Fn() -> GLenum) {
+    let errors = drain_with(get_error);
+    if !errors.is_empty() {
+        report_errors(&errors, &crate::trace::format_call(name, args), SITE.with(Cell::get));
+    }
+}
+
+/// Call a gl function, with the `gl-check` feature errors of the call are reported with
+/// its `file:line`. The shims of [`loader`](crate::loader) check and trace every call,
+/// this only adds the location.
+///
+/// Without `gl-check` it expands to the bare call.
+#[cfg(feature = "gl-check")]
+#[macro_export]
+macro_rules! gl_check {
+    ($call:expr) => {{
+        let site = $crate::debug::check::CallSite::enter(file!(), line!());
+        let result = $call;
+        drop(site);
+        result
+    }};
+}
+
+/// Call a gl function, with the `gl-check` feature errors of the call are reported with
+/// its `file:line`. The shims of [`loader`](crate::loader) check and trace every call,
+/// this only adds the location.
+///
+/// Without `gl-check` it expands to the bare call.
+#[cfg(not(feature = "gl-check"))]
+#[macro_export]
+macro_rules! gl_check {
+    ($call:expr) => { $call };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_error_flags() {
+        assert_eq!(GlError::from_gl(gl::NO_ERROR), None);
+        assert_eq!(GlError::from_gl(gl::INVALID_OPERATION), Some(GlError::InvalidOperation));
+        assert_eq!(GlError::from_gl(0x1234), Some(GlError::Unknown(0x1234)));
+        assert_eq!(GlError::InvalidValue.to_string(), "GL_INVALID_VALUE");
+    }
+
+    #[test]
+    fn call_sites_nest() {
+        let outer = CallSite::enter("outer.rs", 1);
+        {
+            let _inner = CallSite::enter("inner.rs", 2);
+            assert_eq!(SITE.with(Cell::get), Some(("inner.rs", 2)));
+        }
+        assert_eq!(SITE.with(Cell::get), Some(("outer.rs", 1)));
+        drop(outer);
+        assert_eq!(SITE.with(Cell::get), None);
+    }
+}
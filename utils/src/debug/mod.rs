@@ -0,0 +1,395 @@
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+use gl::types::*;
+use once_cell::sync::Lazy;
+
+pub mod check;
+
+/// Log target used for every message coming out of the GL debug callback.
+pub const LOG_TARGET: &str = "gl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub fn from_gl(severity: GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => Self::High,
+            gl::DEBUG_SEVERITY_MEDIUM => Self::Medium,
+            gl::DEBUG_SEVERITY_LOW => Self::Low,
+            _ => Self::Notification,
+        }
+    }
+
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            Self::High => gl::DEBUG_SEVERITY_HIGH,
+            Self::Medium => gl::DEBUG_SEVERITY_MEDIUM,
+            Self::Low => gl::DEBUG_SEVERITY_LOW,
+            Self::Notification => gl::DEBUG_SEVERITY_NOTIFICATION,
+        }
+    }
+
+    fn log_level(self) -> log::Level {
+        match self {
+            Self::High => log::Level::Error,
+            Self::Medium => log::Level::Warn,
+            Self::Low => log::Level::Info,
+            Self::Notification => log::Level::Debug,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    Api,
+    WindowSystem,
+    ShaderCompiler,
+    ThirdParty,
+    Application,
+    Other,
+}
+
+impl Source {
+    pub fn from_gl(source: GLenum) -> Self {
+        match source {
+            gl::DEBUG_SOURCE_API => Self::Api,
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM => Self::WindowSystem,
+            gl::DEBUG_SOURCE_SHADER_COMPILER => Self::ShaderCompiler,
+            gl::DEBUG_SOURCE_THIRD_PARTY => Self::ThirdParty,
+            gl::DEBUG_SOURCE_APPLICATION => Self::Application,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageType {
+    Error,
+    DeprecatedBehavior,
+    UndefinedBehavior,
+    Portability,
+    Performance,
+    Marker,
+    PushGroup,
+    PopGroup,
+    Other,
+}
+
+impl MessageType {
+    pub fn from_gl(gltype: GLenum) -> Self {
+        match gltype {
+            gl::DEBUG_TYPE_ERROR => Self::Error,
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => Self::DeprecatedBehavior,
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => Self::UndefinedBehavior,
+            gl::DEBUG_TYPE_PORTABILITY => Self::Portability,
+            gl::DEBUG_TYPE_PERFORMANCE => Self::Performance,
+            gl::DEBUG_TYPE_MARKER => Self::Marker,
+            gl::DEBUG_TYPE_PUSH_GROUP => Self::PushGroup,
+            gl::DEBUG_TYPE_POP_GROUP => Self::PopGroup,
+            _ => Self::Other,
+        }
+    }
+}
+
+macro_rules! impl_display_as_debug {
+    ($($t:ty),*) => {
+        $(impl Display for $t {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{self:?}")
+            }
+        })*
+    };
+}
+
+impl_display_as_debug!(Severity, Source, MessageType);
+
+/// A decoded message from the GL debug output.
+#[derive(Debug, Clone)]
+pub struct DebugMessage {
+    pub source: Source,
+    pub gltype: MessageType,
+    pub id: GLuint,
+    pub severity: Severity,
+    pub message: String,
+    /// The `file:line` of the [`gl_check!`](crate::gl_check) that reported the message,
+    /// `None` for messages from the debug callback and errors of calls outside `gl_check!`.
+    pub location: Option<String>,
+}
+
+impl DebugMessage {
+    pub fn is_error(&self) -> bool {
+        self.gltype == MessageType::Error || self.severity == Severity::High
+    }
+}
+
+impl Display for DebugMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}/{}/{} #{}] {}", self.source, self.gltype, self.severity, self.id, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DebugConfig {
+    /// Messages below this severity are disabled through `glDebugMessageControl`.
+    pub min_severity: Severity,
+    /// Message ids that are dropped in the callback, e.g. noisy driver notifications.
+    pub ignored_ids: Vec<GLuint>,
+    /// How many times the same (source, type, id, location) is reported before it gets suppressed.
+    pub max_repeats: u32,
+    /// Deliver messages on the calling thread, so the backtrace points at the offending call.
+    pub synchronous: bool,
+    /// Collect errors and panic on the next [`check`](fn@check) instead of only logging them.
+    pub panic_on_error: bool,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        Self {
+            min_severity: Severity::Low,
+            ignored_ids: vec![],
+            max_repeats: 8,
+            synchronous: cfg!(debug_assertions),
+            panic_on_error: false,
+        }
+    }
+}
+
+/// Counts repeated messages so a message emitted every frame does not flood the log.
+#[derive(Debug, Default)]
+pub struct Deduplicator {
+    max_repeats: u32,
+    seen: HashMap<(Source, MessageType, GLuint, Option<String>), u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Report the message as usual.
+    Report,
+    /// Report the message and note that further repeats are suppressed.
+    Last,
+    /// Drop the message.
+    Suppress,
+}
+
+impl Deduplicator {
+    pub fn new(max_repeats: u32) -> Self {
+        Self { max_repeats, seen: HashMap::new() }
+    }
+
+    pub fn check(&mut self, msg: &DebugMessage) -> Repeat {
+        // The location is part of the key, the same error at another call site is a new report.
+        let key = (msg.source, msg.gltype, msg.id, msg.location.clone());
+        let count = self.seen.entry(key).or_insert(0);
+        *count += 1;
+
+        match (*count).cmp(&self.max_repeats) {
+            std::cmp::Ordering::Less => Repeat::Report,
+            std::cmp::Ordering::Equal => Repeat::Last,
+            std::cmp::Ordering::Greater => Repeat::Suppress,
+        }
+    }
+
+    /// How often a message was seen, summed over all locations.
+    pub fn count(&self, source: Source, gltype: MessageType, id: GLuint) -> u32 {
+        self.seen.iter()
+            .filter(|((s, t, i, _), _)| (*s, *t, *i) == (source, gltype, id))
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+struct DebugState {
+    config: DebugConfig,
+    dedup: Deduplicator,
+    pending_errors: Vec<DebugMessage>,
+}
+
+static STATE: Lazy<Mutex<DebugState>> = Lazy::new(|| {
+    let config = DebugConfig::default();
+    Mutex::new(DebugState {
+        dedup: Deduplicator::new(config.max_repeats),
+        config,
+        pending_errors: vec![],
+    })
+});
+
+/// Install a stderr logger (`RUST_LOG` aware, `warn` by default) unless one is already set.
+pub fn init_logger() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        let env = env_logger::Env::default().default_filter_or("warn");
+        env_logger::Builder::from_env(env).try_init().ok();
+    });
+}
+
+fn handle_message(msg: DebugMessage) {
+    let mut state = match STATE.lock() {
+        Ok(state) => state,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    if state.config.ignored_ids.contains(&msg.id) {
+        return;
+    }
+
+    let repeat = state.dedup.check(&msg);
+    if repeat == Repeat::Suppress {
+        return;
+    }
+
+    let level = if msg.gltype == MessageType::Error { log::Level::Error } else { msg.severity.log_level() };
+    let suffix = if repeat == Repeat::Last { " (further repeats suppressed)" } else { "" };
+
+    if msg.is_error() && state.config.synchronous {
+        let backtrace = Backtrace::force_capture();
+        log::log!(target: LOG_TARGET, level, "{msg}{suffix}\n{backtrace}");
+    } else {
+        log::log!(target: LOG_TARGET, level, "{msg}{suffix}");
+    }
+
+    if msg.is_error() && state.config.panic_on_error {
+        state.pending_errors.push(msg);
+    }
+}
+
+extern "system" fn callback(source: GLenum,
+                            gltype: GLenum,
+                            id: GLuint,
+                            severity: GLenum,
+                            length: GLsizei,
+                            message: *const GLchar,
+                            _user_param: *mut c_void)
+{
+    // Unwinding out of an `extern "system"` function is not allowed, so nothing may escape.
+    std::panic::catch_unwind(|| {
+        let bytes = match length {
+            len if len >= 0 => unsafe { std::slice::from_raw_parts(message as *const u8, len as usize) },
+            _ => unsafe { std::ffi::CStr::from_ptr(message).to_bytes() },
+        };
+
+        handle_message(DebugMessage {
+            source: Source::from_gl(source),
+            gltype: MessageType::from_gl(gltype),
+            id,
+            severity: Severity::from_gl(severity),
+            message: String::from_utf8_lossy(bytes).trim_end().to_owned(),
+            location: None,
+        });
+    }).ok();
+}
+
+/// Whether the current context can deliver debug output (GL 4.3 or `GL_KHR_debug`).
+///
+/// # Safety
+/// Please ensure that gl is loaded.
+pub unsafe fn supports_debug_output() -> bool {
+    if !gl::DebugMessageCallback::is_loaded() {
+        return false;
+    }
+
+    let mut version = (0, 0);
+    gl::GetIntegerv(gl::MAJOR_VERSION, &mut version.0);
+    gl::GetIntegerv(gl::MINOR_VERSION, &mut version.1);
+
+    version >= (4, 3) || crate::info::extensions().iter().any(|ext| ext == "GL_KHR_debug")
+}
+
+/// # Safety
+/// Please ensure that gl is loaded.
+pub unsafe fn init_debug_output(config: &DebugConfig) {
+    {
+        let mut state = STATE.lock().unwrap();
+        state.dedup = Deduplicator::new(config.max_repeats);
+        state.config = config.clone();
+        state.pending_errors.clear();
+    }
+
+    gl::Enable(gl::DEBUG_OUTPUT);
+    if config.synchronous {
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+    } else {
+        gl::Disable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+    }
+
+    // Enable everything, then switch off the severities we are not interested in.
+    gl::DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, gl::DONT_CARE, 0, std::ptr::null(), gl::TRUE);
+    for severity in [Severity::Notification, Severity::Low, Severity::Medium] {
+        if severity < config.min_severity {
+            gl::DebugMessageControl(gl::DONT_CARE, gl::DONT_CARE, severity.to_gl(), 0, std::ptr::null(), gl::FALSE);
+        }
+    }
+
+    gl::DebugMessageCallback(Some(callback), std::ptr::null());
+}
+
+/// Errors collected since the last call, only filled in `panic_on_error` mode.
+pub fn take_errors() -> Vec<DebugMessage> {
+    std::mem::take(&mut STATE.lock().unwrap().pending_errors)
+}
+
+/// Panic outside of the GL callback if an error was reported in `panic_on_error` mode.
+pub fn check() {
+    let errors = take_errors();
+    if !errors.is_empty() {
+        let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n");
+        panic!("GL debug output reported errors:\n{errors}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: GLuint) -> DebugMessage {
+        DebugMessage {
+            source: Source::Api,
+            gltype: MessageType::Performance,
+            id,
+            severity: Severity::Medium,
+            message: "test".to_owned(),
+            location: None,
+        }
+    }
+
+    #[test]
+    fn decodes_gl_enums() {
+        assert_eq!(Source::from_gl(gl::DEBUG_SOURCE_SHADER_COMPILER), Source::ShaderCompiler);
+        assert_eq!(MessageType::from_gl(gl::DEBUG_TYPE_ERROR), MessageType::Error);
+        assert_eq!(Severity::from_gl(gl::DEBUG_SEVERITY_HIGH), Severity::High);
+        assert_eq!(Severity::from_gl(Severity::Low.to_gl()), Severity::Low);
+        assert!(Severity::Notification < Severity::High);
+    }
+
+    #[test]
+    fn deduplicates_per_message_id() {
+        let mut dedup = Deduplicator::new(2);
+
+        assert_eq!(dedup.check(&message(1)), Repeat::Report);
+        assert_eq!(dedup.check(&message(1)), Repeat::Last);
+        assert_eq!(dedup.check(&message(1)), Repeat::Suppress);
+        assert_eq!(dedup.check(&message(2)), Repeat::Report);
+        assert_eq!(dedup.count(Source::Api, MessageType::Performance, 1), 3);
+    }
+
+    #[test]
+    fn deduplicates_per_location() {
+        let mut dedup = Deduplicator::new(1);
+        let at = |location: &str| DebugMessage { location: Some(location.to_owned()), ..message(1) };
+
+        assert_eq!(dedup.check(&at("a.rs:1")), Repeat::Last);
+        assert_eq!(dedup.check(&at("a.rs:1")), Repeat::Suppress);
+        assert_eq!(dedup.check(&at("a.rs:2")), Repeat::Last);
+        assert_eq!(dedup.check(&message(1)), Repeat::Last);
+        assert_eq!(dedup.count(Source::Api, MessageType::Performance, 1), 4);
+    }
+}
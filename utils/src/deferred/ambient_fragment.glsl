@@ -0,0 +1,24 @@
+#version 330 core
+#include "lighting.glsl"
+#include "gbuffer.glsl"
+
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform vec3 viewPos;
+
+// the directional and spot lights of the `Lights` block, point lights are drawn apart
+void main() {
+    Surface s;
+    if (!gbufferSurface(texCoords, viewPos, s)) {
+        discard;
+    }
+    vec3 color = vec3(0.0);
+    for (int i = 0; i < lightCounts.x; i++) {
+        color += directionalLight(directionalLights[i], s);
+    }
+    for (int i = 0; i < lightCounts.z; i++) {
+        color += spotLight(spotLights[i], s);
+    }
+    fragColor = vec4(color, 1.0);
+}
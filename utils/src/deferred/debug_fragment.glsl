@@ -0,0 +1,35 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform sampler2D gAlbedoSpec;
+uniform sampler2D gDepth;
+// the index of a `GBufferChannel`
+uniform int channel;
+uniform mat4 inverseProjection;
+// the view depth shown as white
+uniform float far;
+
+void main() {
+    vec3 color;
+    if (channel == 0) {
+        color = texture(gPosition, texCoords).xyz;
+    } else if (channel == 1) {
+        color = texture(gNormal, texCoords).xyz * 0.5 + 0.5;
+    } else if (channel == 2) {
+        color = texture(gAlbedoSpec, texCoords).rgb;
+    } else if (channel == 3) {
+        color = vec3(texture(gAlbedoSpec, texCoords).a);
+    } else if (channel == 4) {
+        // shininess on a log scale, 256 is white
+        color = vec3(log2(max(texture(gNormal, texCoords).w, 1.0)) / 8.0);
+    } else {
+        // linear view depth through the inverse projection, the far plane is white
+        float depth = texture(gDepth, texCoords).r;
+        vec4 position = inverseProjection * vec4(texCoords * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+        color = depth == 1.0 ? vec3(1.0) : vec3(clamp(-position.z / position.w / far, 0.0, 1.0));
+    }
+    fragColor = vec4(color, 1.0);
+}
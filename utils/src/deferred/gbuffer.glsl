@@ -0,0 +1,26 @@
+// Reading the G-buffer written by `gbuffer_fragment.glsl`, include after "lighting.glsl".
+// The packing is described on `utils::deferred::GBuffer`, change both together.
+
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform sampler2D gAlbedoSpec;
+
+// `false` where nothing was drawn, the normal is cleared to zero there.
+bool gbufferSurface(vec2 uv, vec3 viewPos, out Surface s) {
+    vec4 position = texture(gPosition, uv);
+    vec4 normal = texture(gNormal, uv);
+    if (dot(normal.xyz, normal.xyz) == 0.0) {
+        return false;
+    }
+    vec4 albedoSpec = texture(gAlbedoSpec, uv);
+    s = Surface(
+        normalize(normal.xyz),
+        position.xyz,
+        normalize(viewPos - position.xyz),
+        albedoSpec.rgb,
+        vec3(albedoSpec.a),
+        normal.w,
+        position.w > 0.5
+    );
+    return true;
+}
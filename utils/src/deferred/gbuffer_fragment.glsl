@@ -0,0 +1,19 @@
+#version 330 core
+#include "lighting.glsl"
+
+in vec3 worldPos;
+in vec3 normal;
+in vec2 texCoords;
+
+// the attachments of `GBuffer`, read back by `gbufferSurface`
+layout (location = 0) out vec4 gPosition;
+layout (location = 1) out vec4 gNormal;
+layout (location = 2) out vec4 gAlbedoSpec;
+
+uniform PhongMaterial material;
+
+void main() {
+    gPosition = vec4(worldPos, material.blinn ? 1.0 : 0.0);
+    gNormal = vec4(normalize(normal), material.shininess);
+    gAlbedoSpec = vec4(texture(material.diffuse, texCoords).rgb, texture(material.specular, texCoords).r);
+}
@@ -0,0 +1,19 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+
+uniform mat4 viewProjection;
+uniform mat4 model;
+
+out vec3 worldPos;
+out vec3 normal;
+out vec2 texCoords;
+
+void main() {
+    vec4 world = model * vec4(aPos, 1.0);
+    worldPos = world.xyz;
+    normal = mat3(transpose(inverse(model))) * aNormal;
+    texCoords = aTexCoords;
+    gl_Position = viewProjection * world;
+}
@@ -0,0 +1,688 @@
+//! Deferred shading: opaque geometry writes its surface into a [`GBuffer`] first, then
+//! the lights are added up per pixel, point lights only where they reach.
+//!
+//! ```ignore
+//! let mut deferred = DeferredRenderer::new(800, 600, 0, DeferredConfig::default())?;
+//!
+//! let shader = deferred.begin_geometry(&ctx.gl, &camera)?;
+//! material.apply(shader, "material", 0)?;
+//! shader.set_mat4fv("model", Arc::new(model))?;
+//! mesh.draw();
+//!
+//! post.begin(&ctx.gl);
+//! deferred.shade(&camera, &lights)?;
+//! deferred.begin_forward(&ctx.gl, Some(post.scene()));
+//! // draw transparent objects back to front, see `back_to_front`
+//! deferred.end_forward();
+//! post.finish(&ctx.gl)?;
+//! ```
+//!
+//! The light volumes and screen tiles are plain functions so they can be tested without
+//! a context.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use gl::types::*;
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+use once_cell::sync::Lazy;
+
+use crate::bounds::Sphere;
+use crate::camera::Camera;
+use crate::context::GlContext;
+use crate::framebuffer::{Framebuffer, FramebufferConfig};
+use crate::lighting::{self, Lights, LightsBuffer, PointLight};
+use crate::mesh::{Mesh, MeshData};
+use crate::postprocess::FULLSCREEN_VERTEX;
+use crate::shader::{resolve_includes, Shader};
+use crate::texture::{Texture, TextureFormat};
+
+/// `gbufferSurface`, include after `lighting.glsl`.
+pub const GLSL: &str = include_str!("gbuffer.glsl");
+
+/// Includes for [`resolve_includes`], together with [`lighting::INCLUDES`].
+pub const INCLUDES: &[(&str, &str)] = &[("gbuffer.glsl", GLSL)];
+
+/// Takes `viewProjection` and `model`, meshes with positions, normals and texture coordinates.
+pub const GEOMETRY_VERTEX: &str = include_str!("gbuffer_vertex.glsl");
+
+/// Writes a `PhongMaterial material` into the G-buffer, includes `lighting.glsl`.
+pub const GEOMETRY_FRAGMENT: &str = include_str!("gbuffer_fragment.glsl");
+
+pub const AMBIENT_FRAGMENT: &str = include_str!("ambient_fragment.glsl");
+pub const VOLUME_VERTEX: &str = include_str!("volume_vertex.glsl");
+pub const VOLUME_FRAGMENT: &str = include_str!("volume_fragment.glsl");
+pub const TILE_FRAGMENT: &str = include_str!("tile_fragment.glsl");
+pub const DEBUG_FRAGMENT: &str = include_str!("debug_fragment.glsl");
+
+/// Point lights per draw of a tile, `MAX_TILE_LIGHTS` of [`TILE_FRAGMENT`].
+pub const MAX_TILE_LIGHTS: usize = 16;
+
+/// Segments and rings of the sphere drawn for each light volume.
+const VOLUME_SEGMENTS: u32 = 16;
+const VOLUME_RINGS: u32 = 8;
+
+/// How the point lights are added to the lit image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightPass {
+    /// A sphere per light covering its range.
+    Volumes,
+    /// Lights binned into square tiles of `size` pixels, each tile drawn scissored
+    /// with up to [`MAX_TILE_LIGHTS`] lights at once.
+    Tiles { size: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeferredConfig {
+    pub light_pass: LightPass,
+    /// Brightness below which a point light stops, the edge of its volume.
+    pub cutoff: f32,
+}
+
+impl Default for DeferredConfig {
+    fn default() -> Self {
+        Self { light_pass: LightPass::Volumes, cutoff: 5.0 / 256.0 }
+    }
+}
+
+/// What the [`DeferredRenderer::debug_view`] shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GBufferChannel {
+    Position,
+    Normal,
+    Albedo,
+    Specular,
+    Shininess,
+    /// Linear view depth, the far plane is white.
+    Depth,
+}
+
+impl GBufferChannel {
+    pub const ALL: [Self; 6] = [Self::Position, Self::Normal, Self::Albedo, Self::Specular, Self::Shininess, Self::Depth];
+
+    /// The `channel` of [`DEBUG_FRAGMENT`].
+    pub fn index(self) -> i32 {
+        self as i32
+    }
+
+    /// The next channel, wrapping around, for a key cycling through them.
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() as usize + 1) % Self::ALL.len()]
+    }
+}
+
+/// Where `light` is brighter than `cutoff`, its diffuse or specular color at full
+/// strength times the attenuation. Infinite for lights that never fade out.
+pub fn light_volume(light: &PointLight, cutoff: f32) -> Sphere {
+    let brightest = light.color.diffuse.max().max(light.color.specular.max()).max(light.color.ambient.max());
+    let radius = if brightest > 0.0 { light.attenuation.range(cutoff / brightest) } else { 0.0 };
+    Sphere::new(light.position, radius)
+}
+
+/// How much to grow a sphere mesh of `segments` and `rings` so its faces are outside the
+/// sphere it approximates, the vertices lie on it and the faces cut inside.
+pub fn volume_scale(segments: u32, rings: u32) -> f32 {
+    let half_segment = std::f32::consts::PI / segments as f32;
+    let half_ring = std::f32::consts::PI / (2.0 * rings as f32);
+    1.0 / (half_segment.cos() * half_ring.cos())
+}
+
+/// A rectangle of pixels, from the bottom left like `glScissor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ScreenRect {
+    pub fn full(screen: (u32, u32)) -> Self {
+        Self { x: 0, y: 0, width: screen.0, height: screen.1 }
+    }
+
+    pub fn intersects(&self, other: &ScreenRect) -> bool {
+        self.x < other.x + other.width && other.x < self.x + self.width
+            && self.y < other.y + other.height && other.y < self.y + self.height
+    }
+
+    fn scissor(&self) {
+        unsafe {
+            gl::Scissor(self.x as _, self.y as _, self.width as _, self.height as _);
+        }
+    }
+}
+
+/// The pixels of a `screen` sized viewport `sphere` may cover seen from `camera`, `None`
+/// when it is outside the view. Reaching behind the camera covers the whole screen.
+pub fn screen_rect(sphere: &Sphere, camera: &Camera, screen: (u32, u32)) -> Option<ScreenRect> {
+    if !camera.frustum().intersects_sphere(sphere) || screen.0 == 0 || screen.1 == 0 {
+        return None;
+    }
+    if !sphere.radius.is_finite() {
+        return Some(ScreenRect::full(screen));
+    }
+
+    let view_projection = camera.view_projection();
+    let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+    for corner in 0..8 {
+        let offset = |bit: usize| if corner & (1 << bit) == 0 { -sphere.radius } else { sphere.radius };
+        let p = sphere.center + Vector3::new(offset(0), offset(1), offset(2));
+        let clip = view_projection * Vector4::new(p.x, p.y, p.z, 1.0);
+        if clip.w <= 1e-5 {
+            return Some(ScreenRect::full(screen));
+        }
+        for axis in 0..2 {
+            let ndc = clip[axis] / clip.w;
+            min[axis] = min[axis].min(ndc);
+            max[axis] = max[axis].max(ndc);
+        }
+    }
+
+    let pixels = |ndc: f32, size: u32| ((ndc + 1.0) / 2.0 * size as f32).clamp(0.0, size as f32);
+    let (x0, x1) = (pixels(min[0], screen.0).floor() as u32, pixels(max[0], screen.0).ceil() as u32);
+    let (y0, y1) = (pixels(min[1], screen.1).floor() as u32, pixels(max[1], screen.1).ceil() as u32);
+    (x1 > x0 && y1 > y0).then_some(ScreenRect { x: x0, y: y0, width: x1 - x0, height: y1 - y0 })
+}
+
+/// The screen cut into square tiles with the lights touching each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tiles {
+    pub size: u32,
+    pub columns: u32,
+    pub rows: u32,
+    screen: (u32, u32),
+    /// Indices of the lights, row by row from the bottom.
+    lights: Vec<Vec<usize>>,
+}
+
+impl Tiles {
+    /// Bin lights by their [`screen_rect`], `None` for the ones out of view.
+    pub fn bin(rects: &[Option<ScreenRect>], screen: (u32, u32), size: u32) -> Self {
+        let size = size.max(1);
+        let (columns, rows) = (screen.0.div_ceil(size), screen.1.div_ceil(size));
+        let mut lights = vec![vec![]; (columns * rows) as usize];
+        // an empty screen has no tiles, an empty rect no last pixel
+        let rects = if lights.is_empty() { &[] } else { rects };
+        for (light, rect) in rects.iter().enumerate() {
+            let Some(rect) = rect.filter(|rect| rect.width > 0 && rect.height > 0) else { continue };
+            let last_column = ((rect.x + rect.width - 1) / size).min(columns - 1);
+            let last_row = ((rect.y + rect.height - 1) / size).min(rows - 1);
+            for row in rect.y / size..=last_row {
+                for column in rect.x / size..=last_column {
+                    lights[(row * columns + column) as usize].push(light);
+                }
+            }
+        }
+        Self { size, columns, rows, screen, lights }
+    }
+
+    pub fn lights(&self, column: u32, row: u32) -> &[usize] {
+        &self.lights[(row * self.columns + column) as usize]
+    }
+
+    /// The pixels of a tile, the last column and row are cut off at the screen edge.
+    pub fn rect(&self, column: u32, row: u32) -> ScreenRect {
+        let (x, y) = (column * self.size, row * self.size);
+        ScreenRect { x, y, width: self.size.min(self.screen.0 - x), height: self.size.min(self.screen.1 - y) }
+    }
+
+    /// Every tile with at least one light.
+    pub fn lit(&self) -> impl Iterator<Item=(ScreenRect, &[usize])> + '_ {
+        (0..self.rows)
+            .flat_map(move |row| (0..self.columns).map(move |column| (column, row)))
+            .map(|(column, row)| (self.rect(column, row), self.lights(column, row)))
+            .filter(|(_, lights)| !lights.is_empty())
+    }
+}
+
+/// Indices of `positions` sorted from the furthest to the closest to `eye`, the order
+/// blended transparent objects are drawn in.
+pub fn back_to_front(eye: &Point3<f32>, positions: &[Point3<f32>]) -> Vec<usize> {
+    let mut order: Vec<_> = (0..positions.len()).collect();
+    let distance = |i: usize| nalgebra::distance_squared(eye, &positions[i]);
+    order.sort_by(|&a, &b| distance(b).total_cmp(&distance(a)));
+    order
+}
+
+/// Position, normal and albedo-specular render targets with a depth-stencil texture.
+///
+/// * `gPosition`: world position, `a` is `1` for Blinn-Phong specular
+/// * `gNormal`: world normal, `a` the shininess, cleared to zero where nothing is drawn
+/// * `gAlbedoSpec`: diffuse color, `a` the specular intensity
+pub struct GBuffer {
+    framebuffer: Framebuffer,
+}
+
+impl GBuffer {
+    pub fn config(width: u32, height: u32) -> FramebufferConfig {
+        FramebufferConfig::new(width, height)
+            .with_color(TextureFormat::Rgba16F)
+            .with_color(TextureFormat::Rgba16F)
+            .with_color(TextureFormat::Rgba8)
+            .with_depth_texture(TextureFormat::Depth24Stencil8)
+    }
+
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        Ok(Self { framebuffer: Framebuffer::new(Self::config(width, height))? })
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.framebuffer.size()
+    }
+
+    pub fn position(&self) -> &Texture {
+        self.framebuffer.color(0)
+    }
+
+    pub fn normal(&self) -> &Texture {
+        self.framebuffer.color(1)
+    }
+
+    pub fn albedo_spec(&self) -> &Texture {
+        self.framebuffer.color(2)
+    }
+
+    pub fn depth(&self) -> &Texture {
+        self.framebuffer.depth().unwrap()
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.framebuffer.resize(width, height);
+    }
+
+    /// Bind and clear every attachment to zero, without touching the clear color.
+    pub fn bind_and_clear(&self) {
+        self.framebuffer.bind();
+        let zero = [0.0f32; 4];
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            for i in 0..3 {
+                crate::gl_check!(gl::ClearBufferfv(gl::COLOR, i, zero.as_ptr()));
+            }
+            crate::gl_check!(gl::ClearBufferfi(gl::DEPTH_STENCIL, 0, 1.0, 0));
+        }
+    }
+
+    /// Bind `gPosition`, `gNormal` and `gAlbedoSpec` to the units `0` to `2`, `shader` must
+    /// be active.
+    fn apply(&self, shader: &Shader) -> Result<()> {
+        for (unit, (sampler, texture)) in [("gPosition", self.position()), ("gNormal", self.normal()), ("gAlbedoSpec", self.albedo_spec())].into_iter().enumerate() {
+            texture.bind(unit as u32);
+            shader.set_i32(sampler, (unit as i32,))?;
+        }
+        Ok(())
+    }
+}
+
+fn with_includes(source: &str) -> String {
+    resolve_includes(source, &[lighting::INCLUDES, INCLUDES].concat()).unwrap()
+}
+
+static GEOMETRY_RESOLVED: Lazy<String> = Lazy::new(|| with_includes(GEOMETRY_FRAGMENT));
+static AMBIENT_RESOLVED: Lazy<String> = Lazy::new(|| with_includes(AMBIENT_FRAGMENT));
+static VOLUME_RESOLVED: Lazy<String> = Lazy::new(|| with_includes(VOLUME_FRAGMENT));
+static TILE_RESOLVED: Lazy<String> = Lazy::new(|| with_includes(TILE_FRAGMENT));
+
+fn set_point_light(shader: &Shader, name: &str, light: &PointLight) -> Result<()> {
+    let (p, color, attenuation) = (light.position, light.color, light.attenuation);
+    shader.set_vec3(&format!("{name}.position"), (p.x, p.y, p.z))?;
+    shader.set_vec3(&format!("{name}.ambient"), (color.ambient.x, color.ambient.y, color.ambient.z))?;
+    shader.set_vec3(&format!("{name}.diffuse"), (color.diffuse.x, color.diffuse.y, color.diffuse.z))?;
+    shader.set_vec3(&format!("{name}.specular"), (color.specular.x, color.specular.y, color.specular.z))?;
+    shader.set_f32(&format!("{name}.constant"), (attenuation.constant,))?;
+    shader.set_f32(&format!("{name}.linear"), (attenuation.linear,))?;
+    shader.set_f32(&format!("{name}.quadratic"), (attenuation.quadratic,))
+}
+
+/// The G-buffer with the shaders of every pass, see the [module](self) docs for a frame.
+pub struct DeferredRenderer {
+    pub config: DeferredConfig,
+    gbuffer: GBuffer,
+    lights: LightsBuffer,
+    geometry: Shader<'static>,
+    ambient: Shader<'static>,
+    volume: Shader<'static>,
+    tile: Shader<'static>,
+    debug: Shader<'static>,
+    sphere: Mesh,
+    vao: GLuint,
+}
+
+impl DeferredRenderer {
+    /// A G-buffer of `width` x `height`, directional and spot lights go through a `Lights`
+    /// block at uniform buffer `binding`.
+    pub fn new(width: u32, height: u32, binding: GLuint, config: DeferredConfig) -> Result<Self> {
+        // the fullscreen passes draw without vertex buffers
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        let renderer = Self {
+            config,
+            gbuffer: GBuffer::new(width, height)?,
+            lights: LightsBuffer::new(binding),
+            geometry: Shader::new(GEOMETRY_VERTEX, GEOMETRY_RESOLVED.as_str()),
+            ambient: Shader::new(FULLSCREEN_VERTEX, AMBIENT_RESOLVED.as_str()),
+            volume: Shader::new(VOLUME_VERTEX, VOLUME_RESOLVED.as_str()),
+            tile: Shader::new(FULLSCREEN_VERTEX, TILE_RESOLVED.as_str()),
+            debug: Shader::new(FULLSCREEN_VERTEX, DEBUG_FRAGMENT),
+            sphere: Mesh::new(&MeshData::sphere(1.0, VOLUME_SEGMENTS, VOLUME_RINGS)),
+            vao,
+        };
+        renderer.lights.bind_to(&renderer.ambient)?;
+        Ok(renderer)
+    }
+
+    pub fn gbuffer(&self) -> &GBuffer {
+        &self.gbuffer
+    }
+
+    /// The shader of the geometry pass, for custom setups between the draws.
+    pub fn geometry_shader(&self) -> &Shader<'static> {
+        &self.geometry
+    }
+
+    /// Follow the size of `context`, bind and clear the G-buffer and activate the geometry
+    /// shader with the `viewProjection` of `camera`. Set `model` and `material` for each draw.
+    pub fn begin_geometry(&mut self, context: &GlContext, camera: &Camera) -> Result<&Shader<'static>> {
+        let size = context.size();
+        if size.width > 0 && size.height > 0 {
+            self.gbuffer.resize(size.width, size.height);
+        }
+        self.gbuffer.bind_and_clear();
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::Disable(gl::BLEND);
+        }
+        self.geometry.activate()?;
+        self.geometry.set_mat4fv("viewProjection", Arc::new(camera.view_projection()))?;
+        Ok(&self.geometry)
+    }
+
+    /// Add every light of the G-buffer into the bound framebuffer, which should be the size
+    /// of the G-buffer. Pixels without geometry keep what the framebuffer was cleared to.
+    ///
+    /// `lights` may hold any number of point lights, the directional and spot lights must
+    /// fit into the `Lights` block.
+    pub fn shade(&self, camera: &Camera, lights: &Lights) -> Result<()> {
+        let capabilities = [gl::DEPTH_TEST, gl::BLEND, gl::CULL_FACE, gl::SCISSOR_TEST]
+            .map(|cap| (cap, unsafe { gl::IsEnabled(cap) == gl::TRUE }));
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+            gl::BindVertexArray(self.vao);
+        }
+        let p = camera.position;
+
+        // directional and spot lights cover the screen
+        self.lights.upload(&Lights { point: vec![], ..lights.clone() })?;
+        self.ambient.activate()?;
+        self.gbuffer.apply(&self.ambient)?;
+        self.ambient.set_vec3("viewPos", (p.x, p.y, p.z))?;
+        draw_fullscreen();
+
+        let volumes: Vec<_> = lights.point.iter().map(|light| light_volume(light, self.config.cutoff)).collect();
+        match self.config.light_pass {
+            LightPass::Volumes => self.shade_volumes(camera, &lights.point, &volumes)?,
+            LightPass::Tiles { size } => {
+                let screen = self.gbuffer.size();
+                let rects: Vec<_> = volumes.iter().map(|volume| screen_rect(volume, camera, screen)).collect();
+                let tiles = Tiles::bin(&rects, screen, size);
+                self.tile.activate()?;
+                self.gbuffer.apply(&self.tile)?;
+                self.tile.set_vec3("viewPos", (p.x, p.y, p.z))?;
+                unsafe { gl::Enable(gl::SCISSOR_TEST) };
+                for (rect, indices) in tiles.lit() {
+                    rect.scissor();
+                    self.draw_tile_batches(indices.iter().map(|&i| &lights.point[i]))?;
+                }
+            }
+        }
+
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::CullFace(gl::BACK);
+            for (cap, enabled) in capabilities {
+                if enabled { gl::Enable(cap) } else { gl::Disable(cap) }
+            }
+        }
+        Ok(())
+    }
+
+    /// A sphere per light, the back faces so the camera can be inside. Lights without an
+    /// end cover the whole screen through the tile shader.
+    fn shade_volumes(&self, camera: &Camera, lights: &[PointLight], volumes: &[Sphere]) -> Result<()> {
+        let frustum = camera.frustum();
+        let (w, h) = self.gbuffer.size();
+        let p = camera.position;
+        let scale = volume_scale(VOLUME_SEGMENTS, VOLUME_RINGS);
+
+        self.volume.activate()?;
+        self.gbuffer.apply(&self.volume)?;
+        self.volume.set_mat4fv("viewProjection", Arc::new(camera.view_projection()))?;
+        self.volume.set_vec2("screenSize", (w as f32, h as f32))?;
+        self.volume.set_vec3("viewPos", (p.x, p.y, p.z))?;
+        unsafe {
+            gl::Enable(gl::CULL_FACE);
+            gl::CullFace(gl::FRONT);
+        }
+        let mut unbounded = vec![];
+        for (light, volume) in lights.iter().zip(volumes) {
+            if !volume.radius.is_finite() {
+                unbounded.push(light);
+                continue;
+            }
+            if volume.radius <= 0.0 || !frustum.intersects_sphere(volume) {
+                continue;
+            }
+            let c = volume.center;
+            self.volume.set_vec3("center", (c.x, c.y, c.z))?;
+            self.volume.set_f32("radius", (volume.radius * scale,))?;
+            set_point_light(&self.volume, "light", light)?;
+            self.sphere.draw();
+        }
+        unsafe {
+            gl::Disable(gl::CULL_FACE);
+            gl::BindVertexArray(self.vao);
+        }
+
+        if !unbounded.is_empty() {
+            self.tile.activate()?;
+            self.gbuffer.apply(&self.tile)?;
+            self.tile.set_vec3("viewPos", (p.x, p.y, p.z))?;
+            self.draw_tile_batches(unbounded.into_iter())?;
+        }
+        Ok(())
+    }
+
+    /// Fullscreen draws of [`MAX_TILE_LIGHTS`] lights each, the tile shader must be active.
+    fn draw_tile_batches<'a>(&self, lights: impl Iterator<Item=&'a PointLight>) -> Result<()> {
+        let lights: Vec<_> = lights.collect();
+        for batch in lights.chunks(MAX_TILE_LIGHTS) {
+            for (i, light) in batch.iter().enumerate() {
+                set_point_light(&self.tile, &format!("tileLights[{i}]"), light)?;
+            }
+            self.tile.set_i32("tileLightCount", (batch.len() as i32,))?;
+            draw_fullscreen();
+        }
+        Ok(())
+    }
+
+    /// Copy the depth of the G-buffer into `target`, the screen of `context` for `None`,
+    /// and set up blending for transparent objects drawn forward on top of the lit image.
+    /// They test against the opaque depth without writing their own.
+    pub fn begin_forward(&self, context: &GlContext, target: Option<&Framebuffer>) {
+        let (id, size) = match target {
+            Some(target) => (target.id(), target.size()),
+            None => (context.default_framebuffer(), (context.size().width, context.size().height)),
+        };
+        self.gbuffer.framebuffer().blit_to(id, size, gl::DEPTH_BUFFER_BIT, gl::NEAREST);
+        unsafe {
+            gl::Viewport(0, 0, size.0 as _, size.1 as _);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::FALSE);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+
+    pub fn end_forward(&self) {
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            gl::Disable(gl::BLEND);
+        }
+    }
+
+    /// Show `channel` of the G-buffer on the bound framebuffer instead of the lit image.
+    pub fn debug_view(&self, camera: &Camera, channel: GBufferChannel) -> Result<()> {
+        let depth_test = unsafe { gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE };
+        let near = camera.projection.near();
+        let far = camera.projection.far().unwrap_or(near * 1000.0);
+
+        self.debug.activate()?;
+        self.gbuffer.apply(&self.debug)?;
+        self.gbuffer.depth().bind(3);
+        self.debug.set_i32("gDepth", (3,))?;
+        self.debug.set_i32("channel", (channel.index(),))?;
+        let inverse_projection = camera.projection().try_inverse().unwrap_or_else(Matrix4::identity);
+        self.debug.set_mat4fv("inverseProjection", Arc::new(inverse_projection))?;
+        self.debug.set_f32("far", (far,))?;
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.vao);
+        }
+        draw_fullscreen();
+        unsafe {
+            gl::BindVertexArray(0);
+            if depth_test {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn draw_fullscreen() {
+    unsafe {
+        crate::gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+    }
+}
+
+impl Drop for DeferredRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::Projection;
+    use crate::lighting::{Attenuation, LightColor};
+
+    use super::*;
+
+    fn camera() -> Camera {
+        // looking down -z, 90 degrees so the screen edges are at `|x| == -z`
+        Camera::new(Point3::origin(), Projection::perspective(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0))
+    }
+
+    #[test]
+    fn volumes_end_at_the_cutoff() {
+        let attenuation = Attenuation::for_range(50.0);
+        let light = PointLight { position: Point3::new(1.0, 2.0, 3.0), color: LightColor::scaled(Vector3::repeat(1.0), 0.0, 0.5, 1.0), attenuation };
+        let volume = light_volume(&light, 5.0 / 256.0);
+        assert_eq!(volume.center, light.position);
+        // the brightest term, the specular one, has faded to the cutoff at the edge
+        assert!((attenuation.factor(volume.radius) - 5.0 / 256.0).abs() < 1e-5);
+
+        let dimmer = PointLight { color: LightColor::scaled(Vector3::repeat(1.0), 0.0, 0.25, 0.25), ..light };
+        assert!(light_volume(&dimmer, 5.0 / 256.0).radius < volume.radius);
+        let dark = PointLight { color: LightColor::scaled(Vector3::zeros(), 0.0, 0.0, 0.0), ..light };
+        assert_eq!(light_volume(&dark, 5.0 / 256.0).radius, 0.0);
+        let endless = PointLight { attenuation: Attenuation::NONE, ..light };
+        assert_eq!(light_volume(&endless, 5.0 / 256.0).radius, f32::INFINITY);
+    }
+
+    #[test]
+    fn grown_sphere_mesh_covers_the_volume() {
+        let scale = volume_scale(VOLUME_SEGMENTS, VOLUME_RINGS);
+        let sphere = MeshData::sphere(scale, VOLUME_SEGMENTS, VOLUME_RINGS);
+        for [a, b, c] in sphere.triangles() {
+            let normal = (b - a).cross(&(c - a)).normalize();
+            // distance of the face plane from the center
+            assert!(normal.dot(&a.coords) >= 1.0 - 1e-5);
+        }
+    }
+
+    #[test]
+    fn spheres_project_to_screen_rects() {
+        let camera = camera();
+        let screen = (200, 100);
+
+        let ahead = screen_rect(&Sphere::new(Point3::new(0.0, 0.0, -10.0), 1.0), &camera, screen).unwrap();
+        assert!(ahead.width < 40 && ahead.height < 20);
+        assert!(ahead.intersects(&ScreenRect { x: 100, y: 50, width: 1, height: 1 }));
+
+        // to the right edge at x == -z, half of it is cut off
+        let right = screen_rect(&Sphere::new(Point3::new(10.0, 0.0, -10.0), 1.0), &camera, screen).unwrap();
+        assert_eq!(right.x + right.width, 200);
+        assert!(right.x > 180);
+
+        assert_eq!(screen_rect(&Sphere::new(Point3::new(0.0, 0.0, 1.0), 5.0), &camera, screen), Some(ScreenRect::full(screen)));
+        assert_eq!(screen_rect(&Sphere::new(Point3::new(0.0, 0.0, 10.0), 1.0), &camera, screen), None);
+        assert_eq!(screen_rect(&Sphere::new(Point3::new(50.0, 0.0, -10.0), 1.0), &camera, screen), None);
+        assert_eq!(screen_rect(&Sphere::new(Point3::new(0.0, 0.0, 10.0), f32::INFINITY), &camera, screen), Some(ScreenRect::full(screen)));
+    }
+
+    #[test]
+    fn lights_are_binned_into_tiles() {
+        let rects = [
+            Some(ScreenRect { x: 0, y: 0, width: 10, height: 10 }),
+            Some(ScreenRect { x: 30, y: 10, width: 40, height: 1 }),
+            None,
+            Some(ScreenRect::full((100, 50))),
+        ];
+        let tiles = Tiles::bin(&rects, (100, 50), 32);
+        assert_eq!((tiles.columns, tiles.rows), (4, 2));
+        assert_eq!(tiles.lights(0, 0), [0, 1, 3]);
+        assert_eq!(tiles.lights(2, 0), [1, 3]);
+        assert_eq!(tiles.lights(3, 0), [3]);
+        assert_eq!(tiles.lights(0, 1), [3]);
+
+        // the last column and row are cut off by the screen
+        assert_eq!(tiles.rect(3, 1), ScreenRect { x: 96, y: 32, width: 4, height: 18 });
+        assert_eq!(tiles.lit().count(), 8);
+        assert_eq!(Tiles::bin(&rects[..3], (100, 50), 32).lit().count(), 3);
+
+        // nothing to bin without pixels
+        let empty = [Some(ScreenRect { x: 0, y: 0, width: 0, height: 10 }), Some(ScreenRect { x: 0, y: 0, width: 10, height: 0 })];
+        assert_eq!(Tiles::bin(&empty, (100, 50), 32).lit().count(), 0);
+        let minimized = Tiles::bin(&rects, (0, 0), 32);
+        assert_eq!((minimized.columns, minimized.rows, minimized.lit().count()), (0, 0, 0));
+    }
+
+    #[test]
+    fn transparent_objects_sort_back_to_front() {
+        let positions = [Point3::new(0.0, 0.0, -2.0), Point3::new(0.0, 0.0, -8.0), Point3::new(3.0, 0.0, 0.0)];
+        assert_eq!(back_to_front(&Point3::origin(), &positions), vec![1, 2, 0]);
+        assert_eq!(GBufferChannel::Depth.next(), GBufferChannel::Position);
+    }
+
+    #[test]
+    fn glsl_constants_match() {
+        assert!(TILE_FRAGMENT.contains(&format!("#define MAX_TILE_LIGHTS {MAX_TILE_LIGHTS}")));
+        let config = GBuffer::config(64, 32);
+        assert_eq!(config.colors, [TextureFormat::Rgba16F, TextureFormat::Rgba16F, TextureFormat::Rgba8]);
+        for source in [&*GEOMETRY_RESOLVED, &*AMBIENT_RESOLVED, &*VOLUME_RESOLVED, &*TILE_RESOLVED] {
+            assert!(!source.contains("#include"));
+        }
+    }
+}
@@ -0,0 +1,26 @@
+#version 330 core
+#include "lighting.glsl"
+#include "gbuffer.glsl"
+
+// Same as `MAX_TILE_LIGHTS` in `utils::deferred`.
+#define MAX_TILE_LIGHTS 16
+
+in vec2 texCoords;
+out vec4 fragColor;
+
+// a batch of the point lights of the scissored tile
+uniform PointLight tileLights[MAX_TILE_LIGHTS];
+uniform int tileLightCount;
+uniform vec3 viewPos;
+
+void main() {
+    Surface s;
+    if (!gbufferSurface(texCoords, viewPos, s)) {
+        discard;
+    }
+    vec3 color = vec3(0.0);
+    for (int i = 0; i < tileLightCount; i++) {
+        color += pointLight(tileLights[i], s);
+    }
+    fragColor = vec4(color, 1.0);
+}
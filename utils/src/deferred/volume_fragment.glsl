@@ -0,0 +1,17 @@
+#version 330 core
+#include "lighting.glsl"
+#include "gbuffer.glsl"
+
+out vec4 fragColor;
+
+uniform PointLight light;
+uniform vec2 screenSize;
+uniform vec3 viewPos;
+
+void main() {
+    Surface s;
+    if (!gbufferSurface(gl_FragCoord.xy / screenSize, viewPos, s)) {
+        discard;
+    }
+    fragColor = vec4(pointLight(light, s), 1.0);
+}
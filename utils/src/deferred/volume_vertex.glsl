@@ -0,0 +1,11 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+
+uniform mat4 viewProjection;
+uniform vec3 center;
+// already grown so the sphere mesh covers the whole volume
+uniform float radius;
+
+void main() {
+    gl_Position = viewProjection * vec4(center + aPos * radius, 1.0);
+}
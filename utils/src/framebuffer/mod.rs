@@ -0,0 +1,310 @@
+//! Framebuffers rendering into [`Texture`]s, for shadow maps and post-processing.
+//!
+//! Bind one with [`Framebuffer::bind`] and get back to the screen with
+//! [`Framebuffer::unbind`], which binds [`GlContext::default_framebuffer`] instead of `0`
+//! so headless contexts keep working.
+
+use anyhow::{bail, Result};
+use gl::types::*;
+
+use crate::context::GlContext;
+use crate::texture::{Texture, TextureFormat, TextureKind};
+
+/// The attachments of a [`Framebuffer`], all of the same size and [`TextureKind`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FramebufferConfig {
+    pub width: u32,
+    pub height: u32,
+    pub kind: TextureKind,
+    /// `GL_COLOR_ATTACHMENT0 + i`, drawn to by `layout(location = i)` outputs.
+    pub colors: Vec<TextureFormat>,
+    pub depth: Option<DepthAttachment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthAttachment {
+    /// A renderbuffer, depth testing only.
+    Renderbuffer(TextureFormat),
+    /// A depth texture that can be sampled afterwards, like a shadow map.
+    Texture(TextureFormat),
+}
+
+impl FramebufferConfig {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, kind: TextureKind::D2, colors: vec![], depth: None }
+    }
+
+    pub fn with_kind(mut self, kind: TextureKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_color(mut self, format: TextureFormat) -> Self {
+        self.colors.push(format);
+        self
+    }
+
+    pub fn with_depth_renderbuffer(mut self, format: TextureFormat) -> Self {
+        self.depth = Some(DepthAttachment::Renderbuffer(format));
+        self
+    }
+
+    pub fn with_depth_texture(mut self, format: TextureFormat) -> Self {
+        self.depth = Some(DepthAttachment::Texture(format));
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(format) = self.colors.iter().find(|format| format.is_depth()) {
+            bail!("{format:?} is not a color format");
+        }
+        if let Some(DepthAttachment::Texture(format) | DepthAttachment::Renderbuffer(format)) = self.depth {
+            if !format.is_depth() {
+                bail!("{format:?} is not a depth format");
+            }
+        }
+        if matches!(self.depth, Some(DepthAttachment::Renderbuffer(_))) && self.kind != TextureKind::D2 {
+            bail!("a depth renderbuffer has a single layer, use a depth texture for {:?}", self.kind);
+        }
+        if self.kind == TextureKind::Cube && self.width != self.height {
+            bail!("cube map faces are square, got {}x{}", self.width, self.height);
+        }
+        Ok(())
+    }
+}
+
+/// Name of a `glCheckFramebufferStatus` result.
+pub fn status_name(status: GLenum) -> &'static str {
+    match status {
+        gl::FRAMEBUFFER_COMPLETE => "GL_FRAMEBUFFER_COMPLETE",
+        gl::FRAMEBUFFER_UNDEFINED => "GL_FRAMEBUFFER_UNDEFINED",
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT",
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT",
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "GL_FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER",
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "GL_FRAMEBUFFER_INCOMPLETE_READ_BUFFER",
+        gl::FRAMEBUFFER_UNSUPPORTED => "GL_FRAMEBUFFER_UNSUPPORTED",
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE",
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "GL_FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS",
+        _ => "unknown framebuffer status",
+    }
+}
+
+pub struct Framebuffer {
+    id: GLuint,
+    config: FramebufferConfig,
+    colors: Vec<Texture>,
+    depth: Option<Texture>,
+    renderbuffer: GLuint,
+    layer: u32,
+    level: u32,
+}
+
+impl Framebuffer {
+    /// Create the attachments and check the framebuffer is complete, array and cube
+    /// framebuffers start on layer `0`. The framebuffer bound before stays bound.
+    pub fn new(config: FramebufferConfig) -> Result<Self> {
+        config.validate()?;
+
+        let mut max_colors = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max_colors) };
+        if config.colors.len() > max_colors as usize {
+            bail!("{} color attachments, the context supports {max_colors}", config.colors.len());
+        }
+
+        let (w, h) = (config.width, config.height);
+        let colors = config.colors.iter().map(|&format| Texture::new(config.kind, w, h, format)).collect();
+        let depth = match config.depth {
+            Some(DepthAttachment::Texture(format)) => Some(Texture::new(config.kind, w, h, format)),
+            _ => None,
+        };
+
+        let mut framebuffer = Self { id: 0, config, colors, depth, renderbuffer: 0, layer: 0, level: 0 };
+        // headless contexts draw into their own framebuffer, which must stay bound
+        let mut previous = 0;
+        unsafe {
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous);
+            gl::GenFramebuffers(1, &mut framebuffer.id);
+            assert_ne!(framebuffer.id, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.id);
+
+            if let Some(DepthAttachment::Renderbuffer(format)) = framebuffer.config.depth {
+                gl::GenRenderbuffers(1, &mut framebuffer.renderbuffer);
+                framebuffer.alloc_renderbuffer();
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, format.depth_attachment().unwrap(), gl::RENDERBUFFER, framebuffer.renderbuffer);
+            }
+
+            let buffers: Vec<_> = (0..framebuffer.colors.len() as GLenum).map(|i| gl::COLOR_ATTACHMENT0 + i).collect();
+            if buffers.is_empty() {
+                gl::DrawBuffer(gl::NONE);
+                gl::ReadBuffer(gl::NONE);
+            } else {
+                gl::DrawBuffers(buffers.len() as _, buffers.as_ptr());
+            }
+        }
+        framebuffer.attach_textures();
+
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, previous as _) };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            bail!("incomplete framebuffer: {}", status_name(status));
+        }
+        Ok(framebuffer)
+    }
+
+    /// Attach `self.layer` and `self.level` of every texture, the framebuffer must be bound.
+    fn attach_textures(&self) {
+        let attachments = self.colors.iter().enumerate().map(|(i, texture)| (gl::COLOR_ATTACHMENT0 + i as GLenum, texture));
+        let depth = self.depth.iter().map(|texture| (texture.format().depth_attachment().unwrap(), texture));
+        let level = self.level as GLint;
+        for (attachment, texture) in attachments.chain(depth) {
+            unsafe {
+                match self.config.kind {
+                    TextureKind::D2 => gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture.id(), level),
+                    TextureKind::D2Array { .. } => gl::FramebufferTextureLayer(gl::FRAMEBUFFER, attachment, texture.id(), level, self.layer as _),
+                    TextureKind::Cube => gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_CUBE_MAP_POSITIVE_X + self.layer, texture.id(), level),
+                }
+            }
+        }
+    }
+
+    unsafe fn alloc_renderbuffer(&self) {
+        if let Some(DepthAttachment::Renderbuffer(format)) = self.config.depth {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.renderbuffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, format.internal_format(), self.config.width.max(1) as _, self.config.height.max(1) as _);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn config(&self) -> &FramebufferConfig {
+        &self.config
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    pub fn color(&self, index: usize) -> &Texture {
+        &self.colors[index]
+    }
+
+    pub fn colors(&self) -> &[Texture] {
+        &self.colors
+    }
+
+    /// `None` without a depth attachment or with a renderbuffer.
+    pub fn depth(&self) -> Option<&Texture> {
+        self.depth.as_ref()
+    }
+
+    /// Keep the color textures after the framebuffer is gone, like a baked cube map.
+    pub fn into_colors(mut self) -> Vec<Texture> {
+        std::mem::take(&mut self.colors)
+    }
+
+    /// Draw into this framebuffer with a viewport covering the selected mip.
+    pub fn bind(&self) {
+        let (w, h) = ((self.config.width >> self.level).max(1), (self.config.height >> self.level).max(1));
+        unsafe {
+            crate::gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, self.id));
+            gl::Viewport(0, 0, w as _, h as _);
+        }
+    }
+
+    /// Draw to the screen of `context` again.
+    pub fn unbind(context: &GlContext) {
+        let size = context.size();
+        unsafe {
+            crate::gl_check!(gl::BindFramebuffer(gl::FRAMEBUFFER, context.default_framebuffer()));
+            gl::Viewport(0, 0, size.width as _, size.height as _);
+        }
+    }
+
+    /// Render into the array layer or cube face `layer` from now on, binds the framebuffer.
+    pub fn select_layer(&mut self, layer: u32) -> Result<()> {
+        self.select_mip(layer, self.level)
+    }
+
+    /// Render into mip `level` of `layer` from now on, binds the framebuffer. The textures
+    /// need their mips allocated first, see [`Texture::generate_mipmaps`].
+    pub fn select_mip(&mut self, layer: u32, level: u32) -> Result<()> {
+        if layer >= self.config.kind.layers() {
+            bail!("layer {layer} of a {:?} framebuffer", self.config.kind);
+        }
+        if level > 0 && self.renderbuffer != 0 {
+            bail!("a depth renderbuffer has no mips, use a depth texture");
+        }
+        self.layer = layer;
+        self.level = level;
+        self.bind();
+        self.attach_textures();
+        Ok(())
+    }
+
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Reallocate every attachment, their contents are lost.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) == self.size() {
+            return;
+        }
+        self.config.width = width;
+        self.config.height = height;
+        for texture in self.colors.iter_mut().chain(self.depth.as_mut()) {
+            texture.resize(width, height);
+        }
+        unsafe { self.alloc_renderbuffer() };
+    }
+
+    /// Copy `mask` (`GL_COLOR_BUFFER_BIT` and friends) into the framebuffer `target`
+    /// of `target_size`, scaled with `filter`.
+    pub fn blit_to(&self, target: GLuint, target_size: (u32, u32), mask: GLbitfield, filter: GLenum) {
+        let (w, h) = self.size();
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target);
+            crate::gl_check!(gl::BlitFramebuffer(0, 0, w as _, h as _, 0, 0, target_size.0 as _, target_size.1 as _, mask, filter));
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target);
+        }
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            if self.renderbuffer != 0 {
+                gl::DeleteRenderbuffers(1, &self.renderbuffer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configs_are_validated() {
+        let config = FramebufferConfig::new(64, 64).with_color(TextureFormat::Rgba16F).with_depth_renderbuffer(TextureFormat::Depth24Stencil8);
+        assert!(config.validate().is_ok());
+        assert!(FramebufferConfig::new(64, 64).with_color(TextureFormat::Depth24).validate().is_err());
+        assert!(FramebufferConfig::new(64, 64).with_depth_texture(TextureFormat::Rgba8).validate().is_err());
+
+        let cube = FramebufferConfig::new(64, 32).with_kind(TextureKind::Cube).with_depth_texture(TextureFormat::Depth24);
+        assert!(cube.validate().unwrap_err().to_string().contains("square"));
+        let array = FramebufferConfig::new(64, 64).with_kind(TextureKind::D2Array { layers: 4 }).with_depth_renderbuffer(TextureFormat::Depth24);
+        assert!(array.validate().is_err());
+
+        assert_eq!(status_name(gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT), "GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT");
+    }
+}
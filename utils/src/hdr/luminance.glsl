@@ -0,0 +1,12 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+
+// log luminance, so the average of the mip chain is the log of the geometric mean
+void main() {
+    vec3 color = texture(screen, texCoords).rgb;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    fragColor = vec4(log(max(luminance, 1e-4)), 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,309 @@
+//! HDR rendering: tonemapping operators, sRGB conversions and exposure control.
+//!
+//! Render the scene into a float target, e.g. a [`PostProcess`](crate::postprocess::PostProcess)
+//! with [`TextureFormat::Rgba16F`], so colors above `1` survive until a
+//! [`Pass::tonemap`](crate::postprocess::Pass::tonemap) maps them to the display range.
+//! Shaders `#include "tonemap.glsl"` (see [`INCLUDES`]), the operators are mirrored
+//! here so they can be tested without a context.
+
+use anyhow::Result;
+use gl::types::*;
+use nalgebra::{Matrix3, Vector3};
+
+use crate::framebuffer::{Framebuffer, FramebufferConfig};
+use crate::postprocess::{FULLSCREEN_VERTEX, luma};
+use crate::shader::Shader;
+use crate::texture::{Texture, TextureFormat};
+
+pub const GLSL: &str = include_str!("tonemap.glsl");
+
+pub const INCLUDES: &[(&str, &str)] = &[("tonemap.glsl", GLSL)];
+
+/// Writes the log luminance of `screen`, for [`AutoExposure`].
+pub const LUMINANCE: &str = include_str!("luminance.glsl");
+
+/// The operators of [`GLSL`], each maps exposed linear colors to linear `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tonemapper {
+    /// `1 - exp(-color)`
+    #[default]
+    Exposure,
+    /// `color / (1 + color)`
+    Reinhard,
+    /// Stephen Hill's fit of ACES, a filmic curve that desaturates bright colors.
+    AcesFitted,
+    /// John Hable's filmic curve from Uncharted 2.
+    Uncharted2,
+    /// Troy Sobotka's AgX, keeps hues of very bright colors.
+    Agx,
+}
+
+impl Tonemapper {
+    pub const ALL: [Self; 5] = [Self::Exposure, Self::Reinhard, Self::AcesFitted, Self::Uncharted2, Self::Agx];
+
+    /// The `TONEMAP_*` value of [`GLSL`].
+    pub fn index(self) -> i32 {
+        self as i32
+    }
+
+    /// The next operator, wrapping around, for a key cycling through them.
+    pub fn next(self) -> Self {
+        Self::ALL[(self.index() as usize + 1) % Self::ALL.len()]
+    }
+
+    pub fn apply(self, color: &Vector3<f32>) -> Vector3<f32> {
+        match self {
+            Self::Exposure => color.map(|c| 1.0 - (-c).exp()),
+            Self::Reinhard => reinhard(color),
+            Self::AcesFitted => aces_fitted(color),
+            Self::Uncharted2 => uncharted2(color),
+            Self::Agx => agx(color),
+        }
+    }
+}
+
+pub fn reinhard(color: &Vector3<f32>) -> Vector3<f32> {
+    color.map(|c| c / (1.0 + c))
+}
+
+pub fn aces_fitted(color: &Vector3<f32>) -> Vector3<f32> {
+    let input = Matrix3::new(
+        0.59719, 0.35458, 0.04823,
+        0.07600, 0.90834, 0.01566,
+        0.02840, 0.13383, 0.83777,
+    );
+    let output = Matrix3::new(
+        1.60475, -0.53108, -0.07367,
+        -0.10208, 1.10813, -0.00605,
+        -0.00327, -0.07276, 1.07602,
+    );
+    let fitted = (input * color).map(|v| (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.432951) + 0.238081));
+    (output * fitted).map(|c| c.clamp(0.0, 1.0))
+}
+
+fn uncharted2_curve(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+pub fn uncharted2(color: &Vector3<f32>) -> Vector3<f32> {
+    let white = uncharted2_curve(11.2);
+    color.map(|c| (uncharted2_curve(2.0 * c) / white).clamp(0.0, 1.0))
+}
+
+pub fn agx(color: &Vector3<f32>) -> Vector3<f32> {
+    let inset = Matrix3::new(
+        0.84247905, 0.0784336, 0.079223745,
+        0.042328242, 0.87846863, 0.07916613,
+        0.042375654, 0.0784336, 0.879143,
+    );
+    let outset = Matrix3::new(
+        1.196879, -0.09802088, -0.09902974,
+        -0.052896854, 1.1519032, -0.098961174,
+        -0.052971635, -0.09804345, 1.1510737,
+    );
+    let (min_ev, max_ev) = (-12.47393, 4.026069);
+    let contrast = |x: f32| {
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    };
+
+    let v = (inset * color).map(|v| (v.max(1e-10).log2().clamp(min_ev, max_ev) - min_ev) / (max_ev - min_ev));
+    (outset * v.map(contrast)).map(|c| c.max(0.0).powf(2.2))
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.max(0.0).powf(1.0 / 2.4) - 0.055 }
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// A color picked in sRGB, like `AppConfig::clear_color`, in the linear space lighting
+/// and HDR targets work in. Alpha stays as it is.
+pub fn linear_color((r, g, b, a): (f32, f32, f32, f32)) -> (f32, f32, f32, f32) {
+    (srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+}
+
+/// The geometric mean of the luminance, what [`AutoExposure`] reads from its mip chain.
+pub fn average_luminance(colors: &[Vector3<f32>]) -> f32 {
+    let log_sum: f32 = colors.iter().map(|c| luma(c).max(1e-4).ln()).sum();
+    (log_sum / colors.len().max(1) as f32).exp()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoExposureConfig {
+    /// Luminance the average is exposed to, middle gray by default.
+    pub key: f32,
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    /// How fast the exposure follows the scene, per second. Eyes adapt in a few seconds.
+    pub speed: f32,
+    /// Size of the luminance target, the scene is sampled down to it.
+    pub resolution: u32,
+}
+
+impl Default for AutoExposureConfig {
+    fn default() -> Self {
+        Self { key: 0.18, min_exposure: 0.05, max_exposure: 20.0, speed: 1.5, resolution: 256 }
+    }
+}
+
+impl AutoExposureConfig {
+    /// Exposure bringing `average_luminance` to the key.
+    pub fn target(&self, average_luminance: f32) -> f32 {
+        (self.key / average_luminance.max(1e-4)).clamp(self.min_exposure, self.max_exposure)
+    }
+
+    /// Move `current` towards `target` over `dt` seconds, frame rate independent.
+    pub fn adapt(&self, current: f32, target: f32, dt: f32) -> f32 {
+        current + (target - current) * (1.0 - (-dt * self.speed).exp())
+    }
+}
+
+/// Exposure following the average luminance of the scene, read back from the last mip
+/// of its log luminance.
+pub struct AutoExposure {
+    pub config: AutoExposureConfig,
+    target: Framebuffer,
+    shader: Shader<'static>,
+    vao: GLuint,
+    exposure: f32,
+}
+
+impl AutoExposure {
+    pub fn new(config: AutoExposureConfig) -> Result<Self> {
+        let target = Framebuffer::new(FramebufferConfig::new(config.resolution, config.resolution).with_color(TextureFormat::R16F))?;
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        Ok(Self { config, target, shader: Shader::new(FULLSCREEN_VERTEX, LUMINANCE), vao, exposure: 1.0 })
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Measure `scene` and adapt the exposure over `dt` seconds. Leaves the luminance
+    /// target bound, bind the next target afterwards.
+    ///
+    /// Reading the average back waits for the GPU to finish the frame so far.
+    pub fn update(&mut self, scene: &Texture, dt: f32) -> Result<f32> {
+        self.target.bind();
+        self.shader.activate()?;
+        scene.bind(0);
+        self.shader.set_i32("screen", (0,))?;
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            crate::gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+            gl::BindVertexArray(0);
+        }
+
+        let luminance = self.target.color(0);
+        luminance.generate_mipmaps();
+        let last_mip = (self.config.resolution.max(1) as f32).log2().floor() as GLint;
+        let mut log_average = 0.0f32;
+        unsafe {
+            luminance.bind(0);
+            crate::gl_check!(gl::GetTexImage(gl::TEXTURE_2D, last_mip, gl::RED, gl::FLOAT, &mut log_average as *mut f32 as *mut _));
+        }
+
+        let target = self.config.target(log_average.exp());
+        self.exposure = self.config.adapt(self.exposure, target, dt);
+        Ok(self.exposure)
+    }
+}
+
+impl Drop for AutoExposure {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(v: f32) -> Vector3<f32> {
+        Vector3::repeat(v)
+    }
+
+    fn assert_close(actual: Vector3<f32>, expected: [f32; 3]) {
+        assert!((actual - Vector3::from(expected)).abs().max() < 2e-4, "{actual:?} != {expected:?}");
+    }
+
+    // references from the published formulas, computed independently in double precision
+    #[test]
+    fn operators_match_reference_values() {
+        let color = Vector3::new(1.0, 0.5, 0.1);
+
+        assert_close(Tonemapper::Exposure.apply(&gray(1.0)), [0.63212; 3]);
+        assert_close(Tonemapper::Exposure.apply(&color), [0.63212, 0.39347, 0.09516]);
+
+        assert_close(reinhard(&gray(0.18)), [0.15254; 3]);
+        assert_close(reinhard(&gray(4.0)), [0.8; 3]);
+
+        assert_close(aces_fitted(&gray(0.18)), [0.10559; 3]);
+        assert_close(aces_fitted(&gray(1.0)), [0.61912; 3]);
+        assert_close(aces_fitted(&gray(4.0)), [0.90901; 3]);
+        assert_close(aces_fitted(&color), [0.63887, 0.38387, 0.0822]);
+
+        assert_close(uncharted2(&gray(0.18)), [0.12834; 3]);
+        assert_close(uncharted2(&gray(1.0)), [0.49292; 3]);
+        assert_close(uncharted2(&color), [0.49292, 0.3043, 0.07421]);
+
+        assert_close(agx(&gray(0.18)), [0.21447, 0.21453, 0.21454]);
+        assert_close(agx(&gray(1.0)), [0.58998, 0.59021, 0.59022]);
+        assert_close(agx(&color), [0.62415, 0.43908, 0.1747]);
+    }
+
+    #[test]
+    fn operators_are_monotonic_and_bounded() {
+        for tonemapper in Tonemapper::ALL {
+            assert!(tonemapper.apply(&gray(0.0)).max() < 1e-3, "{tonemapper:?}");
+            let mut previous = -1.0;
+            for i in 0..200 {
+                let mapped = tonemapper.apply(&gray(i as f32 * 0.25)).x;
+                assert!((0.0..=1.0).contains(&mapped) && mapped >= previous, "{tonemapper:?} at {i}");
+                previous = mapped;
+            }
+            assert!(tonemapper.apply(&gray(1000.0)).min() > 0.98, "{tonemapper:?}");
+        }
+        assert_eq!(Tonemapper::Agx.next(), Tonemapper::Exposure);
+        assert_eq!(Tonemapper::Reinhard.index(), 1);
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        assert!((linear_to_srgb(0.18) - 0.46135).abs() < 1e-4);
+        assert!((srgb_to_linear(0.5) - 0.21404).abs() < 1e-4);
+        for i in 0..=100 {
+            let c = i as f32 / 100.0;
+            assert!((srgb_to_linear(linear_to_srgb(c)) - c).abs() < 1e-5);
+        }
+        let (r, g, b, a) = linear_color((0.3, 0.3, 0.3, 0.5));
+        assert!((r - 0.07323).abs() < 1e-4 && r == g && g == b && a == 0.5);
+    }
+
+    #[test]
+    fn exposure_adapts_to_the_average() {
+        let config = AutoExposureConfig::default();
+        assert!((average_luminance(&[gray(0.18); 4]) - 0.18).abs() < 1e-5);
+        // geometric, a few bright pixels do not dominate
+        assert!((average_luminance(&[gray(0.01), gray(1.0)]) - 0.1).abs() < 1e-5);
+
+        assert!((config.target(0.18) - 1.0).abs() < 1e-5);
+        assert_eq!(config.target(1e-6), config.max_exposure);
+
+        let mut exposure = 1.0;
+        for _ in 0..60 {
+            exposure = config.adapt(exposure, 4.0, 1.0 / 60.0);
+        }
+        let expected = 4.0 - 3.0 * (-config.speed).exp();
+        assert!((exposure - expected).abs() < 1e-3);
+    }
+}
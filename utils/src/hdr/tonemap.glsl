@@ -0,0 +1,99 @@
+// HDR to display tonemapping operators, all taking exposed linear colors and returning
+// linear colors in [0, 1]. Mirrored in `utils::hdr`, change both together.
+
+#define TONEMAP_EXPOSURE 0
+#define TONEMAP_REINHARD 1
+#define TONEMAP_ACES_FITTED 2
+#define TONEMAP_UNCHARTED2 3
+#define TONEMAP_AGX 4
+
+vec3 tonemapExposure(vec3 color) {
+    return 1.0 - exp(-color);
+}
+
+vec3 reinhard(vec3 color) {
+    return color / (1.0 + color);
+}
+
+// Stephen Hill's fit of the ACES reference rendering and output transforms.
+vec3 acesFitted(vec3 color) {
+    // sRGB to the RRT input space, then back from the ODT output space
+    const mat3 inputMat = mat3(
+        0.59719, 0.07600, 0.02840,
+        0.35458, 0.90834, 0.13383,
+        0.04823, 0.01566, 0.83777
+    );
+    const mat3 outputMat = mat3(
+        1.60475, -0.10208, -0.00327,
+        -0.53108, 1.10813, -0.07276,
+        -0.07367, -0.00605, 1.07602
+    );
+    vec3 v = inputMat * color;
+    vec3 a = v * (v + 0.0245786) - 0.000090537;
+    vec3 b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return clamp(outputMat * (a / b), 0.0, 1.0);
+}
+
+vec3 uncharted2Curve(vec3 x) {
+    const float A = 0.15, B = 0.50, C = 0.10, D = 0.20, E = 0.02, F = 0.30;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+// John Hable's filmic curve with its exposure bias of 2 and white point of 11.2.
+vec3 uncharted2(vec3 color) {
+    vec3 white = uncharted2Curve(vec3(11.2));
+    return clamp(uncharted2Curve(2.0 * color) / white, 0.0, 1.0);
+}
+
+// Troy Sobotka's AgX with the polynomial fit of the default contrast look.
+vec3 agxContrast(vec3 x) {
+    vec3 x2 = x * x;
+    vec3 x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+vec3 agx(vec3 color) {
+    const mat3 inset = mat3(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104
+    );
+    const mat3 outset = mat3(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+    );
+    const float minEv = -12.47393;
+    const float maxEv = 4.026069;
+
+    vec3 v = inset * color;
+    v = clamp(log2(max(v, 1e-10)), minEv, maxEv);
+    v = agxContrast((v - minEv) / (maxEv - minEv));
+    // back to linear through the 2.2 power display curve
+    return pow(max(outset * v, 0.0), vec3(2.2));
+}
+
+vec3 tonemap(vec3 color, int tonemapper) {
+    if (tonemapper == TONEMAP_REINHARD) {
+        return reinhard(color);
+    } else if (tonemapper == TONEMAP_ACES_FITTED) {
+        return acesFitted(color);
+    } else if (tonemapper == TONEMAP_UNCHARTED2) {
+        return uncharted2(color);
+    } else if (tonemapper == TONEMAP_AGX) {
+        return agx(color);
+    }
+    return tonemapExposure(color);
+}
+
+vec3 linearToSrgb(vec3 color) {
+    vec3 low = color * 12.92;
+    vec3 high = 1.055 * pow(max(color, 0.0), vec3(1.0 / 2.4)) - 0.055;
+    return mix(high, low, lessThanEqual(color, vec3(0.0031308)));
+}
+
+vec3 srgbToLinear(vec3 color) {
+    vec3 low = color / 12.92;
+    vec3 high = pow((max(color, 0.0) + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, lessThanEqual(color, vec3(0.04045)));
+}
@@ -0,0 +1,194 @@
+use std::ffi::CStr;
+use std::fmt::{Display, Formatter};
+
+use anyhow::Result;
+use gl::types::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+    pub max_texture_size: i32,
+    pub max_3d_texture_size: i32,
+    pub max_cube_map_texture_size: i32,
+    pub max_texture_image_units: i32,
+    pub max_combined_texture_image_units: i32,
+    pub max_vertex_attribs: i32,
+    pub max_uniform_block_size: i32,
+    pub max_uniform_buffer_bindings: i32,
+    pub max_samples: i32,
+    pub max_color_attachments: i32,
+    pub max_draw_buffers: i32,
+    pub max_viewport_dims: [i32; 2],
+}
+
+/// Everything worth pasting into a bug report about the current context.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContextInfo {
+    pub vendor: String,
+    pub renderer: String,
+    pub version: String,
+    pub glsl_version: String,
+    pub major: i32,
+    pub minor: i32,
+    pub extensions: Vec<String>,
+    pub limits: Limits,
+}
+
+unsafe fn get_string(name: GLenum) -> String {
+    let s = gl::GetString(name);
+    if s.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(s as *const _).to_string_lossy().into_owned()
+}
+
+unsafe fn get_integer(name: GLenum) -> i32 {
+    let mut value = 0;
+    gl::GetIntegerv(name, &mut value);
+    value
+}
+
+/// # Safety
+/// Please ensure that gl is loaded.
+pub unsafe fn extensions() -> Vec<String> {
+    (0..get_integer(gl::NUM_EXTENSIONS) as GLuint)
+        .filter_map(|i| {
+            let ext = gl::GetStringi(gl::EXTENSIONS, i);
+            (!ext.is_null()).then(|| CStr::from_ptr(ext as *const _).to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
+impl Limits {
+    /// # Safety
+    /// Please ensure that gl is loaded.
+    pub unsafe fn query() -> Self {
+        let mut max_viewport_dims = [0; 2];
+        gl::GetIntegerv(gl::MAX_VIEWPORT_DIMS, max_viewport_dims.as_mut_ptr());
+
+        Self {
+            max_texture_size: get_integer(gl::MAX_TEXTURE_SIZE),
+            max_3d_texture_size: get_integer(gl::MAX_3D_TEXTURE_SIZE),
+            max_cube_map_texture_size: get_integer(gl::MAX_CUBE_MAP_TEXTURE_SIZE),
+            max_texture_image_units: get_integer(gl::MAX_TEXTURE_IMAGE_UNITS),
+            max_combined_texture_image_units: get_integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS),
+            max_vertex_attribs: get_integer(gl::MAX_VERTEX_ATTRIBS),
+            max_uniform_block_size: get_integer(gl::MAX_UNIFORM_BLOCK_SIZE),
+            max_uniform_buffer_bindings: get_integer(gl::MAX_UNIFORM_BUFFER_BINDINGS),
+            max_samples: get_integer(gl::MAX_SAMPLES),
+            max_color_attachments: get_integer(gl::MAX_COLOR_ATTACHMENTS),
+            max_draw_buffers: get_integer(gl::MAX_DRAW_BUFFERS),
+            max_viewport_dims,
+        }
+    }
+}
+
+impl ContextInfo {
+    /// # Safety
+    /// Please ensure that gl is loaded.
+    pub unsafe fn query() -> Self {
+        Self {
+            vendor: get_string(gl::VENDOR),
+            renderer: get_string(gl::RENDERER),
+            version: get_string(gl::VERSION),
+            glsl_version: get_string(gl::SHADING_LANGUAGE_VERSION),
+            major: get_integer(gl::MAJOR_VERSION),
+            minor: get_integer(gl::MINOR_VERSION),
+            extensions: extensions(),
+            limits: Limits::query(),
+        }
+    }
+
+    pub fn supports(&self, extension: &str) -> bool {
+        self.extensions.iter().any(|ext| ext == extension)
+    }
+
+    pub fn version_at_least(&self, major: i32, minor: i32) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// A two column table, `{:#}` also lists every extension.
+impl Display for ContextInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let l = &self.limits;
+        let rows = [
+            ("vendor", self.vendor.clone()),
+            ("renderer", self.renderer.clone()),
+            ("version", self.version.clone()),
+            ("glsl version", self.glsl_version.clone()),
+            ("extensions", self.extensions.len().to_string()),
+            ("max texture size", l.max_texture_size.to_string()),
+            ("max 3d texture size", l.max_3d_texture_size.to_string()),
+            ("max cube map size", l.max_cube_map_texture_size.to_string()),
+            ("texture units (fragment)", l.max_texture_image_units.to_string()),
+            ("texture units (combined)", l.max_combined_texture_image_units.to_string()),
+            ("vertex attribs", l.max_vertex_attribs.to_string()),
+            ("uniform block size", l.max_uniform_block_size.to_string()),
+            ("uniform buffer bindings", l.max_uniform_buffer_bindings.to_string()),
+            ("samples", l.max_samples.to_string()),
+            ("color attachments", l.max_color_attachments.to_string()),
+            ("draw buffers", l.max_draw_buffers.to_string()),
+            ("viewport dims", format!("{}x{}", l.max_viewport_dims[0], l.max_viewport_dims[1])),
+        ];
+
+        let key_width = rows.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        let value_width = rows.iter().map(|(_, v)| v.len()).max().unwrap_or(0);
+        let line = format!("+-{}-+-{}-+", "-".repeat(key_width), "-".repeat(value_width));
+
+        writeln!(f, "{line}")?;
+        for (key, value) in &rows {
+            writeln!(f, "| {key:<key_width$} | {value:<value_width$} |")?;
+        }
+        writeln!(f, "{line}")?;
+
+        if f.alternate() {
+            for ext in &self.extensions {
+                writeln!(f, "  {ext}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> ContextInfo {
+        ContextInfo {
+            vendor: "Mesa".to_owned(),
+            version: "3.3 (Core Profile) Mesa 22.2.1".to_owned(),
+            major: 3,
+            minor: 3,
+            extensions: vec!["GL_KHR_debug".to_owned(), "GL_ARB_compute_shader".to_owned()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn predicates() {
+        let info = info();
+        assert!(info.supports("GL_KHR_debug"));
+        assert!(!info.supports("GL_KHR"));
+        assert!(info.version_at_least(3, 3));
+        assert!(!info.version_at_least(4, 3));
+    }
+
+    #[test]
+    fn table_and_json() {
+        let info = info();
+
+        let table = info.to_string();
+        assert!(table.lines().any(|l| l.starts_with("| vendor") && l.contains("Mesa")));
+        assert!(!table.contains("GL_KHR_debug"));
+        assert!(format!("{info:#}").contains("GL_KHR_debug"));
+
+        let json: ContextInfo = serde_json::from_str(&info.to_json().unwrap()).unwrap();
+        assert_eq!(json, info);
+    }
+}
@@ -0,0 +1,97 @@
+//! [`InputDevice`] reading every gamepad gilrs knows about.
+
+use anyhow::{anyhow, Result};
+use gilrs::ev::filter::axis_dpad_to_button;
+use gilrs::{Axis, Button, EventType, Filter, Gilrs, GilrsBuilder};
+
+use crate::input::{InputDevice, InputEvent};
+
+use super::{GamepadAxis, GamepadButton};
+
+pub struct GilrsDevice {
+    gilrs: Gilrs,
+}
+
+impl GilrsDevice {
+    pub fn new() -> Result<Self> {
+        // The deadzone filter is replaced by our own processing, see `AxisSettings`.
+        let gilrs = GilrsBuilder::new()
+            .with_default_filters(false)
+            .build()
+            .map_err(|err| anyhow!("gilrs: {err}"))?;
+
+        for (_, gamepad) in gilrs.gamepads() {
+            log::info!("gamepad: {}", gamepad.name());
+        }
+        Ok(Self { gilrs })
+    }
+}
+
+impl InputDevice for GilrsDevice {
+    fn name(&self) -> &str {
+        "gilrs"
+    }
+
+    fn poll(&mut self, events: &mut Vec<InputEvent>) {
+        while let Some(event) = self.gilrs.next_event().filter_ev(&axis_dpad_to_button, &mut self.gilrs) {
+            let event = match event.event {
+                EventType::ButtonPressed(button, _) => button_event(button, true),
+                EventType::ButtonReleased(button, _) => button_event(button, false),
+                // Analog triggers report their travel as a button value.
+                EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+                    Some(InputEvent::GamepadAxis { axis: GamepadAxis::LeftTrigger, value })
+                }
+                EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+                    Some(InputEvent::GamepadAxis { axis: GamepadAxis::RightTrigger, value })
+                }
+                EventType::AxisChanged(axis, value, _) => convert_axis(axis).map(|axis| InputEvent::GamepadAxis { axis, value }),
+                EventType::Connected => {
+                    log::info!("gamepad connected: {}", self.gilrs.gamepad(event.id).name());
+                    None
+                }
+                EventType::Disconnected => Some(InputEvent::GamepadDisconnected),
+                _ => None,
+            };
+            events.extend(event);
+        }
+    }
+}
+
+fn button_event(button: Button, pressed: bool) -> Option<InputEvent> {
+    convert_button(button).map(|button| InputEvent::GamepadButton { button, pressed })
+}
+
+fn convert_button(button: Button) -> Option<GamepadButton> {
+    Some(match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+        Button::RightTrigger2 => GamepadButton::RightTrigger,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::Mode => GamepadButton::Mode,
+        Button::LeftThumb => GamepadButton::LeftThumb,
+        Button::RightThumb => GamepadButton::RightThumb,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn convert_axis(axis: Axis) -> Option<GamepadAxis> {
+    Some(match axis {
+        Axis::LeftStickX => GamepadAxis::LeftStickX,
+        Axis::LeftStickY => GamepadAxis::LeftStickY,
+        Axis::RightStickX => GamepadAxis::RightStickX,
+        Axis::RightStickY => GamepadAxis::RightStickY,
+        Axis::LeftZ => GamepadAxis::LeftTrigger,
+        Axis::RightZ => GamepadAxis::RightTrigger,
+        _ => return None,
+    })
+}
@@ -0,0 +1,251 @@
+//! Gamepad buttons and axes, with deadzone and response curve processing.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::ButtonState;
+
+#[cfg(feature = "gamepad")]
+mod gilrs;
+
+#[cfg(feature = "gamepad")]
+pub use self::gilrs::GilrsDevice;
+
+/// Buttons named by their position on the pad, `South` is A on an Xbox and Cross on a PlayStation pad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Sticks are in `[-1, 1]` with positive `y` up, triggers in `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    /// The other axis of the same stick.
+    pub fn partner(self) -> Option<Self> {
+        match self {
+            Self::LeftStickX => Some(Self::LeftStickY),
+            Self::LeftStickY => Some(Self::LeftStickX),
+            Self::RightStickX => Some(Self::RightStickY),
+            Self::RightStickY => Some(Self::RightStickX),
+            Self::LeftTrigger | Self::RightTrigger => None,
+        }
+    }
+
+    pub fn is_trigger(self) -> bool {
+        self.partner().is_none()
+    }
+}
+
+/// Maps the magnitude left after the deadzone, in `[0, 1]`, to the output magnitude.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ResponseCurve {
+    Linear,
+    /// `x^n`, more precision for small movements with `n > 1`.
+    Power(f32),
+    /// Smoothstep, slow at both ends.
+    SCurve,
+}
+
+impl ResponseCurve {
+    pub fn apply(self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => x,
+            Self::Power(n) => x.powf(n),
+            Self::SCurve => x * x * (3.0 - 2.0 * x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AxisSettings {
+    /// Magnitudes below this are `0`, worn sticks never rest at exactly zero.
+    pub deadzone: f32,
+    /// Magnitudes above this are `1`, many sticks never reach the corners.
+    pub saturation: f32,
+    pub curve: ResponseCurve,
+}
+
+impl AxisSettings {
+    pub const STICK: Self = Self { deadzone: 0.15, saturation: 0.95, curve: ResponseCurve::Power(2.0) };
+    pub const TRIGGER: Self = Self { deadzone: 0.05, saturation: 1.0, curve: ResponseCurve::Linear };
+
+    /// Rescale a magnitude from `[deadzone, saturation]` to `[0, 1]` and apply the curve.
+    pub fn magnitude(&self, raw: f32) -> f32 {
+        if raw <= self.deadzone {
+            return 0.0;
+        }
+        let range = (self.saturation - self.deadzone).max(f32::EPSILON);
+        self.curve.apply((raw - self.deadzone) / range)
+    }
+
+    /// A single axis, keeps the sign.
+    pub fn apply(&self, raw: f32) -> f32 {
+        self.magnitude(raw.abs()).copysign(raw)
+    }
+
+    /// Both axes of a stick with a radial deadzone, unlike two separate axes
+    /// this does not snap diagonal movement to the nearest axis.
+    pub fn apply_stick(&self, x: f32, y: f32) -> (f32, f32) {
+        let length = (x * x + y * y).sqrt();
+        let magnitude = self.magnitude(length);
+        if magnitude == 0.0 {
+            return (0.0, 0.0);
+        }
+        (x / length * magnitude, y / length * magnitude)
+    }
+}
+
+/// Buttons and axes of all connected gamepads, merged as if there was only one.
+#[derive(Debug, Clone)]
+pub struct GamepadState {
+    pub(super) buttons: ButtonState<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>,
+    /// Raw axes at the end of the previous frame, to detect an axis crossing a threshold.
+    prev_axes: HashMap<GamepadAxis, f32>,
+    pub stick: AxisSettings,
+    pub trigger: AxisSettings,
+}
+
+impl Default for GamepadState {
+    fn default() -> Self {
+        Self {
+            buttons: ButtonState::default(),
+            axes: HashMap::new(),
+            prev_axes: HashMap::new(),
+            stick: AxisSettings::STICK,
+            trigger: AxisSettings::TRIGGER,
+        }
+    }
+}
+
+impl GamepadState {
+    pub(super) fn set_button(&mut self, button: GamepadButton, pressed: bool) {
+        self.buttons.set(button, pressed);
+    }
+
+    pub(super) fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.axes.insert(axis, value.clamp(-1.0, 1.0));
+    }
+
+    pub(super) fn disconnect(&mut self) {
+        self.buttons.release_all();
+        self.axes.clear();
+    }
+
+    pub(super) fn end_frame(&mut self) {
+        self.buttons.end_frame();
+        self.prev_axes.clone_from(&self.axes);
+    }
+
+    pub fn button_down(&self, button: GamepadButton) -> bool {
+        self.buttons.down.contains(&button)
+    }
+
+    pub fn button_pressed(&self, button: GamepadButton) -> bool {
+        self.buttons.pressed.contains(&button)
+    }
+
+    pub fn button_released(&self, button: GamepadButton) -> bool {
+        self.buttons.released.contains(&button)
+    }
+
+    pub fn raw_axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    fn processed(&self, axes: &HashMap<GamepadAxis, f32>, axis: GamepadAxis) -> f32 {
+        let raw = |axis| axes.get(&axis).copied().unwrap_or(0.0);
+        match axis.partner() {
+            None => self.trigger.apply(raw(axis)),
+            Some(partner) => {
+                let (value, _) = self.stick.apply_stick(raw(axis), raw(partner));
+                value
+            }
+        }
+    }
+
+    /// Axis value after the deadzone and response curve.
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.processed(&self.axes, axis)
+    }
+
+    /// [`axis`](Self::axis) as it was during the previous frame.
+    pub fn prev_axis(&self, axis: GamepadAxis) -> f32 {
+        self.processed(&self.prev_axes, axis)
+    }
+
+    pub fn left_stick(&self) -> (f32, f32) {
+        self.stick.apply_stick(self.raw_axis(GamepadAxis::LeftStickX), self.raw_axis(GamepadAxis::LeftStickY))
+    }
+
+    pub fn right_stick(&self) -> (f32, f32) {
+        self.stick.apply_stick(self.raw_axis(GamepadAxis::RightStickX), self.raw_axis(GamepadAxis::RightStickY))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadzone_and_saturation() {
+        let settings = AxisSettings { deadzone: 0.2, saturation: 0.8, curve: ResponseCurve::Linear };
+
+        assert_eq!(settings.apply(0.1), 0.0);
+        assert_eq!(settings.apply(-0.2), 0.0);
+        assert!((settings.apply(0.5) - 0.5).abs() < 1e-6);
+        assert!((settings.apply(-0.5) + 0.5).abs() < 1e-6);
+        assert_eq!(settings.apply(0.9), 1.0);
+    }
+
+    #[test]
+    fn response_curves() {
+        assert_eq!(ResponseCurve::Linear.apply(0.5), 0.5);
+        assert_eq!(ResponseCurve::Power(2.0).apply(0.5), 0.25);
+        assert_eq!(ResponseCurve::SCurve.apply(0.5), 0.5);
+        assert!(ResponseCurve::SCurve.apply(0.1) < 0.1);
+        assert_eq!(ResponseCurve::Power(3.0).apply(1.5), 1.0);
+    }
+
+    #[test]
+    fn radial_stick_deadzone_keeps_direction() {
+        let settings = AxisSettings { deadzone: 0.2, saturation: 1.0, curve: ResponseCurve::Linear };
+
+        assert_eq!(settings.apply_stick(0.1, 0.1), (0.0, 0.0));
+
+        // an axial deadzone would snap this to the x axis
+        let (x, y) = settings.apply_stick(0.5, 0.1);
+        assert!((y / x - 0.2).abs() < 1e-6);
+
+        let (x, y) = settings.apply_stick(0.6, 0.6);
+        assert!((x - y).abs() < 1e-6 && x > 0.0);
+        assert!(((x * x + y * y).sqrt() - (0.6f32.hypot(0.6) - 0.2) / 0.8).abs() < 1e-5);
+    }
+}
@@ -1,14 +1,569 @@
-// re-export
-// pub use scancode::Scancode;
-//
-//
-// trait PatchedFrom {
-//     fn from(scancode: u8) -> Self {
-//
-//     }
-//
-// }
-//
-// impl PatchedFrom for Scancode {
-//
-// }
\ No newline at end of file
+//! Keyboard, mouse and gamepad state tracked per frame, plus named actions bound to inputs.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use glutin::event::{DeviceEvent, ElementState, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use serde::{Deserialize, Serialize};
+
+pub use gamepad::{AxisSettings, GamepadAxis, GamepadButton, GamepadState, ResponseCurve};
+
+pub mod gamepad;
+pub mod recording;
+
+/// Pixels per line for wheels that report lines.
+const PIXELS_PER_LINE: f32 = 20.0;
+/// Processed axis value above which an axis binding counts as held.
+const AXIS_THRESHOLD: f32 = 0.5;
+
+/// The subset of window and device events the input state cares about.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key { key: VirtualKeyCode, pressed: bool },
+    MouseButton { button: MouseButton, pressed: bool },
+    CursorMoved { x: f32, y: f32 },
+    CursorLeft,
+    /// Raw mouse motion, still reported when the cursor is grabbed.
+    MouseMotion { dx: f32, dy: f32 },
+    /// In lines, positive `y` scrolls up.
+    Scroll { x: f32, y: f32 },
+    Modifiers(ModifiersState),
+    Focused(bool),
+    GamepadButton { button: GamepadButton, pressed: bool },
+    /// Raw value, see [`GamepadState::axis`] for the processed one.
+    GamepadAxis { axis: GamepadAxis, value: f32 },
+    GamepadDisconnected,
+}
+
+impl InputEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::KeyboardInput { input, .. } => Self::Key {
+                key: input.virtual_keycode?,
+                pressed: input.state == ElementState::Pressed,
+            },
+            WindowEvent::MouseInput { button, state, .. } => Self::MouseButton {
+                button: *button,
+                pressed: *state == ElementState::Pressed,
+            },
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved { x: position.x as f32, y: position.y as f32 },
+            WindowEvent::CursorLeft { .. } => Self::CursorLeft,
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(x, y) => Self::Scroll { x: *x, y: *y },
+                MouseScrollDelta::PixelDelta(p) => Self::Scroll {
+                    x: p.x as f32 / PIXELS_PER_LINE,
+                    y: p.y as f32 / PIXELS_PER_LINE,
+                },
+            },
+            WindowEvent::ModifiersChanged(modifiers) => Self::Modifiers(*modifiers),
+            WindowEvent::Focused(focused) => Self::Focused(*focused),
+            _ => return None,
+        })
+    }
+
+    pub fn from_device_event(event: &DeviceEvent) -> Option<Self> {
+        match event {
+            DeviceEvent::MouseMotion { delta: (dx, dy) } => Some(Self::MouseMotion { dx: *dx as f32, dy: *dy as f32 }),
+            _ => None,
+        }
+    }
+}
+
+/// A source of input events outside the window system, polled once per frame.
+pub trait InputDevice {
+    fn name(&self) -> &str;
+
+    /// Append the events since the last poll.
+    fn poll(&mut self, events: &mut Vec<InputEvent>);
+}
+
+/// Held, pressed and released state of a set of buttons.
+///
+/// `pressed` and `released` only hold for the frame the change happened in.
+#[derive(Debug, Clone)]
+struct ButtonState<T> {
+    down: HashSet<T>,
+    pressed: HashSet<T>,
+    released: HashSet<T>,
+}
+
+impl<T> Default for ButtonState<T> {
+    fn default() -> Self {
+        Self { down: HashSet::new(), pressed: HashSet::new(), released: HashSet::new() }
+    }
+}
+
+impl<T: Copy + Eq + std::hash::Hash> ButtonState<T> {
+    fn set(&mut self, button: T, down: bool) {
+        if down {
+            // Key repeat sends more presses for a held key, those are not new presses.
+            if self.down.insert(button) {
+                self.pressed.insert(button);
+            }
+        } else if self.down.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+
+    fn release_all(&mut self) {
+        self.released.extend(self.down.drain());
+    }
+
+    fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+}
+
+/// Anything an action can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    Pad(GamepadButton),
+    /// One direction of an axis, held when pushed past half way.
+    PadAxis { axis: GamepadAxis, positive: bool },
+}
+
+impl Binding {
+    /// `W`, `Up`, `Space` name keys by their `VirtualKeyCode`, mouse buttons are
+    /// `MouseLeft`, `MouseRight`, `MouseMiddle` or `Mouse4`, gamepad buttons are
+    /// `PadSouth` or `PadStart` and axis directions `PadLeftStickY+` or `PadRightTrigger+`.
+    pub fn parse(name: &str) -> Result<Self> {
+        if let Some(pad) = name.strip_prefix("Pad") {
+            let enum_value = |name: &str| serde_json::Value::String(name.to_owned());
+
+            let axis = pad.strip_suffix('+').map(|axis| (axis, true))
+                .or_else(|| pad.strip_suffix('-').map(|axis| (axis, false)));
+            return match axis {
+                Some((axis, positive)) => serde_json::from_value(enum_value(axis))
+                    .map(|axis| Self::PadAxis { axis, positive })
+                    .with_context(|| format!("unknown gamepad axis `{name}`")),
+                None => serde_json::from_value(enum_value(pad))
+                    .map(Self::Pad)
+                    .with_context(|| format!("unknown gamepad button `{name}`")),
+            };
+        }
+
+        if let Some(button) = name.strip_prefix("Mouse") {
+            return Ok(Self::Mouse(match button {
+                "Left" => MouseButton::Left,
+                "Right" => MouseButton::Right,
+                "Middle" => MouseButton::Middle,
+                other => MouseButton::Other(other.parse().with_context(|| format!("unknown mouse button `{name}`"))?),
+            }));
+        }
+
+        serde_json::from_value(serde_json::Value::String(name.to_owned()))
+            .map(Self::Key)
+            .with_context(|| format!("unknown key `{name}`"))
+    }
+}
+
+impl Display for Binding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, "{key:?}"),
+            Self::Mouse(MouseButton::Other(n)) => write!(f, "Mouse{n}"),
+            Self::Mouse(button) => write!(f, "Mouse{button:?}"),
+            Self::Pad(button) => write!(f, "Pad{button:?}"),
+            Self::PadAxis { axis, positive } => write!(f, "Pad{axis:?}{}", if *positive { '+' } else { '-' }),
+        }
+    }
+}
+
+/// Named actions, each bound to any number of inputs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActionMap {
+    actions: HashMap<String, Vec<Binding>>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind(mut self, action: &str, binding: Binding) -> Self {
+        self.actions.entry(action.to_owned()).or_default().push(binding);
+        self
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// One action per line, `#` starts a comment:
+    ///
+    /// ```text
+    /// mix_up = Up, W
+    /// mix_down = Down, S
+    /// ```
+    pub fn parse(config: &str) -> Result<Self> {
+        let mut map = Self::new();
+
+        for (n, line) in config.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((action, bindings)) = line.split_once('=') else {
+                bail!("line {}: expected `action = Binding, ...`, got `{line}`", n + 1);
+            };
+
+            let action = action.trim();
+            for name in bindings.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                let binding = Binding::parse(name).with_context(|| format!("line {}", n + 1))?;
+                map = map.bind(action, binding);
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let config = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::parse(&config)
+    }
+}
+
+/// Input state for the current frame, fed with [`InputEvent`]s.
+///
+/// Call [`end_frame`](Self::end_frame) once per frame after the state was read.
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    keys: ButtonState<VirtualKeyCode>,
+    buttons: ButtonState<MouseButton>,
+    cursor: Option<(f32, f32)>,
+    cursor_delta: (f32, f32),
+    mouse_motion: (f32, f32),
+    scroll: (f32, f32),
+    modifiers: ModifiersState,
+    gamepad: GamepadState,
+    actions: ActionMap,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_actions(&mut self, actions: ActionMap) {
+        self.actions = actions;
+    }
+
+    pub fn actions(&self) -> &ActionMap {
+        &self.actions
+    }
+
+    pub fn handle(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::Key { key, pressed } => self.keys.set(key, pressed),
+            InputEvent::MouseButton { button, pressed } => self.buttons.set(button, pressed),
+            InputEvent::CursorMoved { x, y } => {
+                if let Some((px, py)) = self.cursor {
+                    self.cursor_delta.0 += x - px;
+                    self.cursor_delta.1 += y - py;
+                }
+                self.cursor = Some((x, y));
+            }
+            InputEvent::CursorLeft => self.cursor = None,
+            InputEvent::MouseMotion { dx, dy } => {
+                self.mouse_motion.0 += dx;
+                self.mouse_motion.1 += dy;
+            }
+            InputEvent::Scroll { x, y } => {
+                self.scroll.0 += x;
+                self.scroll.1 += y;
+            }
+            InputEvent::Modifiers(modifiers) => self.modifiers = modifiers,
+            // Releases are lost while unfocused, so nothing stays stuck down.
+            InputEvent::Focused(false) => {
+                self.keys.release_all();
+                self.buttons.release_all();
+            }
+            InputEvent::Focused(true) => {}
+            InputEvent::GamepadButton { button, pressed } => self.gamepad.set_button(button, pressed),
+            InputEvent::GamepadAxis { axis, value } => self.gamepad.set_axis(axis, value),
+            InputEvent::GamepadDisconnected => self.gamepad.disconnect(),
+        }
+    }
+
+    /// Convert and handle a window event, returns whether it was an input event.
+    pub fn handle_window_event(&mut self, event: &WindowEvent) -> bool {
+        InputEvent::from_window_event(event).map(|event| self.handle(&event)).is_some()
+    }
+
+    /// Forget the per frame state: presses, releases, deltas and scrolling.
+    pub fn end_frame(&mut self) {
+        self.keys.end_frame();
+        self.buttons.end_frame();
+        self.gamepad.end_frame();
+        self.cursor_delta = (0.0, 0.0);
+        self.mouse_motion = (0.0, 0.0);
+        self.scroll = (0.0, 0.0);
+    }
+
+    pub fn key_down(&self, key: VirtualKeyCode) -> bool {
+        self.keys.down.contains(&key)
+    }
+
+    pub fn key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys.pressed.contains(&key)
+    }
+
+    pub fn key_released(&self, key: VirtualKeyCode) -> bool {
+        self.keys.released.contains(&key)
+    }
+
+    pub fn button_down(&self, button: MouseButton) -> bool {
+        self.buttons.down.contains(&button)
+    }
+
+    pub fn button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons.pressed.contains(&button)
+    }
+
+    pub fn button_released(&self, button: MouseButton) -> bool {
+        self.buttons.released.contains(&button)
+    }
+
+    /// Cursor position in physical pixels, `None` outside the window.
+    pub fn cursor(&self) -> Option<(f32, f32)> {
+        self.cursor
+    }
+
+    /// Cursor movement during this frame, in physical pixels.
+    pub fn cursor_delta(&self) -> (f32, f32) {
+        self.cursor_delta
+    }
+
+    /// Raw mouse movement during this frame, not limited by the window borders.
+    pub fn mouse_motion(&self) -> (f32, f32) {
+        self.mouse_motion
+    }
+
+    /// Scrolling during this frame, in lines.
+    pub fn scroll(&self) -> (f32, f32) {
+        self.scroll
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    pub fn gamepad(&self) -> &GamepadState {
+        &self.gamepad
+    }
+
+    /// Change the deadzones and response curves.
+    pub fn gamepad_mut(&mut self) -> &mut GamepadState {
+        &mut self.gamepad
+    }
+
+    /// Poll `device` and handle its events.
+    pub fn poll(&mut self, device: &mut dyn InputDevice) {
+        let mut events = vec![];
+        device.poll(&mut events);
+        for event in &events {
+            self.handle(event);
+        }
+    }
+
+    /// How far a binding is pushed in `[0, 1]`, buttons are `0` or `1`.
+    pub fn binding_value(&self, binding: &Binding) -> f32 {
+        match *binding {
+            Binding::PadAxis { axis, positive } => axis_direction(self.gamepad.axis(axis), positive),
+            _ => if self.binding_down(binding) { 1.0 } else { 0.0 },
+        }
+    }
+
+    fn binding_down(&self, binding: &Binding) -> bool {
+        match *binding {
+            Binding::Key(key) => self.key_down(key),
+            Binding::Mouse(button) => self.button_down(button),
+            Binding::Pad(button) => self.gamepad.button_down(button),
+            Binding::PadAxis { axis, positive } => axis_direction(self.gamepad.axis(axis), positive) > AXIS_THRESHOLD,
+        }
+    }
+
+    fn binding_pressed(&self, binding: &Binding) -> bool {
+        match *binding {
+            Binding::Key(key) => self.key_pressed(key),
+            Binding::Mouse(button) => self.button_pressed(button),
+            Binding::Pad(button) => self.gamepad.button_pressed(button),
+            Binding::PadAxis { axis, positive } => {
+                self.binding_down(binding) && axis_direction(self.gamepad.prev_axis(axis), positive) <= AXIS_THRESHOLD
+            }
+        }
+    }
+
+    fn binding_released(&self, binding: &Binding) -> bool {
+        match *binding {
+            Binding::Key(key) => self.key_released(key),
+            Binding::Mouse(button) => self.button_released(button),
+            Binding::Pad(button) => self.gamepad.button_released(button),
+            Binding::PadAxis { axis, positive } => {
+                !self.binding_down(binding) && axis_direction(self.gamepad.prev_axis(axis), positive) > AXIS_THRESHOLD
+            }
+        }
+    }
+
+    pub fn action_down(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|b| self.binding_down(b))
+    }
+
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|b| self.binding_pressed(b))
+    }
+
+    pub fn action_released(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|b| self.binding_released(b))
+    }
+
+    /// The strongest of the action's bindings in `[0, 1]`, analog for gamepad axes.
+    pub fn action_value(&self, action: &str) -> f32 {
+        self.actions.bindings(action).iter().map(|b| self.binding_value(b)).fold(0.0, f32::max)
+    }
+}
+
+/// The part of `value` pointing in the given direction.
+fn axis_direction(value: f32, positive: bool) -> f32 {
+    if positive { value.max(0.0) } else { (-value).max(0.0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: VirtualKeyCode, pressed: bool) -> InputEvent {
+        InputEvent::Key { key, pressed }
+    }
+
+    #[test]
+    fn key_state_per_frame() {
+        let mut input = InputState::new();
+
+        input.handle(&key(VirtualKeyCode::W, true));
+        assert!(input.key_down(VirtualKeyCode::W) && input.key_pressed(VirtualKeyCode::W));
+
+        input.end_frame();
+        input.handle(&key(VirtualKeyCode::W, true)); // key repeat
+        assert!(input.key_down(VirtualKeyCode::W) && !input.key_pressed(VirtualKeyCode::W));
+
+        input.end_frame();
+        input.handle(&key(VirtualKeyCode::W, false));
+        assert!(!input.key_down(VirtualKeyCode::W) && input.key_released(VirtualKeyCode::W));
+
+        input.end_frame();
+        assert!(!input.key_released(VirtualKeyCode::W));
+    }
+
+    #[test]
+    fn focus_loss_releases_everything() {
+        let mut input = InputState::new();
+        input.handle(&key(VirtualKeyCode::A, true));
+        input.handle(&InputEvent::MouseButton { button: MouseButton::Left, pressed: true });
+
+        input.handle(&InputEvent::Focused(false));
+        assert!(!input.key_down(VirtualKeyCode::A) && input.key_released(VirtualKeyCode::A));
+        assert!(input.button_released(MouseButton::Left));
+    }
+
+    #[test]
+    fn cursor_and_scroll_deltas() {
+        let mut input = InputState::new();
+
+        input.handle(&InputEvent::CursorMoved { x: 10.0, y: 10.0 });
+        assert_eq!(input.cursor_delta(), (0.0, 0.0));
+        input.handle(&InputEvent::CursorMoved { x: 15.0, y: 8.0 });
+        input.handle(&InputEvent::CursorMoved { x: 20.0, y: 8.0 });
+        input.handle(&InputEvent::Scroll { x: 0.0, y: 1.0 });
+        input.handle(&InputEvent::Scroll { x: 0.0, y: 2.0 });
+
+        assert_eq!(input.cursor(), Some((20.0, 8.0)));
+        assert_eq!(input.cursor_delta(), (10.0, -2.0));
+        assert_eq!(input.scroll(), (0.0, 3.0));
+
+        input.end_frame();
+        assert_eq!(input.cursor_delta(), (0.0, 0.0));
+        assert_eq!(input.scroll(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn action_map_from_config() {
+        let map = ActionMap::parse("
+            # texture mix
+            mix_up = Up, W
+            mix_down = Down, MouseRight
+        ").unwrap();
+
+        assert_eq!(map.bindings("mix_up"), &[Binding::Key(VirtualKeyCode::Up), Binding::Key(VirtualKeyCode::W)]);
+        assert_eq!(map.bindings("mix_down")[1], Binding::Mouse(MouseButton::Right));
+        assert_eq!(Binding::parse("Mouse4").unwrap().to_string(), "Mouse4");
+        assert!(ActionMap::parse("jump = Spacebar").is_err());
+        assert!(ActionMap::parse("jump").is_err());
+
+        let mut input = InputState::new();
+        input.set_actions(map);
+        input.handle(&key(VirtualKeyCode::W, true));
+        assert!(input.action_pressed("mix_up") && input.action_down("mix_up"));
+        assert!(!input.action_down("mix_down") && !input.action_down("unknown"));
+    }
+
+    /// Replays a script of events, one batch per poll.
+    struct SyntheticDevice(Vec<Vec<InputEvent>>);
+
+    impl InputDevice for SyntheticDevice {
+        fn name(&self) -> &str {
+            "synthetic"
+        }
+
+        fn poll(&mut self, events: &mut Vec<InputEvent>) {
+            if !self.0.is_empty() {
+                events.extend(self.0.remove(0));
+            }
+        }
+    }
+
+    #[test]
+    fn gamepad_actions_from_synthetic_device() {
+        let axis = |axis, value| InputEvent::GamepadAxis { axis, value };
+        let mut device = SyntheticDevice(vec![
+            vec![axis(GamepadAxis::LeftStickY, 0.1), InputEvent::GamepadButton { button: GamepadButton::South, pressed: true }],
+            vec![axis(GamepadAxis::LeftStickY, 1.0)],
+            vec![axis(GamepadAxis::LeftStickY, -0.05)],
+            vec![InputEvent::GamepadDisconnected],
+        ]);
+
+        let mut input = InputState::new();
+        input.set_actions(ActionMap::parse("
+            forward = W, PadLeftStickY+
+            jump = Space, PadSouth
+        ").unwrap());
+        assert_eq!(input.actions().bindings("forward")[1].to_string(), "PadLeftStickY+");
+
+        // inside the deadzone
+        input.poll(&mut device);
+        assert_eq!(input.action_value("forward"), 0.0);
+        assert!(input.action_pressed("jump"));
+        input.end_frame();
+
+        input.poll(&mut device);
+        assert!(input.action_pressed("forward") && input.action_down("forward"));
+        assert_eq!(input.action_value("forward"), 1.0);
+        assert!(input.action_down("jump") && !input.action_pressed("jump"));
+        input.end_frame();
+
+        input.poll(&mut device);
+        assert!(input.action_released("forward") && !input.action_down("forward"));
+        input.end_frame();
+
+        input.poll(&mut device);
+        assert!(input.action_released("jump"));
+        assert_eq!(input.gamepad().left_stick(), (0.0, 0.0));
+    }
+}
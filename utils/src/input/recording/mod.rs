@@ -0,0 +1,178 @@
+//! Input sessions recorded with frame timings, replayed to render the exact same frames.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::InputEvent;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimedEvent {
+    /// Seconds since the recording started.
+    pub time: f32,
+    pub event: InputEvent,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Real duration of the frame in seconds, before pause and scaling.
+    pub dt: f32,
+    /// Events that arrived since the previous frame.
+    pub events: Vec<TimedEvent>,
+}
+
+impl RecordedFrame {
+    /// The frame duration fed to a [`ManualTime`](crate::time::ManualTime) clock on replay.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f32(self.dt)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    /// Size of the drawable, the same input on another size renders other frames.
+    pub width: u32,
+    pub height: u32,
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl Recording {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json()?).with_context(|| format!("writing recording {}", path.display()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).with_context(|| format!("reading recording {}", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("parsing recording {}", path.display()))
+    }
+}
+
+/// Collects events as they arrive and closes a frame with [`end_frame`](Self::end_frame).
+pub struct Recorder {
+    start: Instant,
+    pending: Vec<TimedEvent>,
+    recording: Recording,
+}
+
+impl Recorder {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            start: Instant::now(),
+            pending: vec![],
+            recording: Recording { width, height, frames: vec![] },
+        }
+    }
+
+    pub fn record(&mut self, event: InputEvent) {
+        self.pending.push(TimedEvent { time: self.start.elapsed().as_secs_f32(), event });
+    }
+
+    /// Close a frame of `dt` seconds with the events recorded since the last one.
+    pub fn end_frame(&mut self, dt: f32) {
+        let events = std::mem::take(&mut self.pending);
+        self.recording.frames.push(RecordedFrame { dt, events });
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+    }
+}
+
+/// Hands out the recorded frames in order.
+pub struct Player {
+    recording: Recording,
+    next: usize,
+}
+
+impl Player {
+    pub fn new(recording: Recording) -> Result<Self> {
+        if recording.frames.is_empty() {
+            bail!("the recording has no frames");
+        }
+        Ok(Self { recording, next: 0 })
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.recording.frames.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.frame_count()
+    }
+
+    pub fn next_frame(&mut self) -> Option<&RecordedFrame> {
+        let frame = self.recording.frames.get(self.next)?;
+        self.next += 1;
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glutin::event::VirtualKeyCode;
+
+    use crate::input::InputState;
+    use crate::time::{Clock, ManualTime};
+
+    use super::*;
+
+    /// Runs `frames` through a manual clock and input state, like the app driver does on replay.
+    fn simulate(recording: Recording) -> Vec<(f32, bool)> {
+        let time = ManualTime::new();
+        let mut clock = Clock::with_source(time.clone());
+        let mut input = InputState::new();
+        let mut player = Player::new(recording).unwrap();
+
+        let mut frames = vec![];
+        while let Some(frame) = player.next_frame() {
+            time.advance(frame.duration());
+            clock.tick();
+            for event in &frame.events {
+                input.handle(&event.event);
+            }
+            frames.push((clock.elapsed(), input.key_down(VirtualKeyCode::W)));
+            input.end_frame();
+        }
+        frames
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let mut recorder = Recorder::new(800, 600);
+        recorder.end_frame(0.016);
+        recorder.record(InputEvent::Key { key: VirtualKeyCode::W, pressed: true });
+        recorder.end_frame(0.017);
+        recorder.record(InputEvent::Key { key: VirtualKeyCode::W, pressed: false });
+        recorder.end_frame(0.015);
+
+        let recording = Recording::from_json(&recorder.finish().to_json().unwrap()).unwrap();
+        assert_eq!(recording.frames[1].events[0].event, InputEvent::Key { key: VirtualKeyCode::W, pressed: true });
+
+        let first = simulate(recording.clone());
+        assert_eq!(first.iter().map(|(_, w)| *w).collect::<Vec<_>>(), vec![false, true, false]);
+        assert!((first[2].0 - 0.048).abs() < 1e-6);
+        assert_eq!(first, simulate(recording));
+
+        assert!(Player::new(Recording::default()).is_err());
+    }
+}
@@ -1,93 +1,66 @@
 #![allow(dead_code)]
 
-use std::ffi::{c_void, CStr};
+// lets `#[derive(Std140)]` refer to `::learnopengl_utils` inside this crate too
+extern crate self as learnopengl_utils;
 
-use gl::DEBUG_TYPE_ERROR;
-use gl::types::*;
-use glutin::{Api, Context, ContextBuilder, ContextWrapper, GlProfile, GlRequest, PossiblyCurrent};
-use glutin::dpi::PhysicalSize;
 use glutin::event_loop::EventLoop;
-use glutin::window::{Window, WindowBuilder};
 
+pub use context::{ContextConfig, ContextMode, GlContext};
+pub use info::ContextInfo;
+pub use loader::load_with;
 pub use shader::compile_shader;
 pub use shader::get_status_and_output;
 pub use shader::Shader;
 
+pub mod app;
+pub mod bounds;
+pub mod camera;
+pub mod capture;
+pub mod compute;
+pub mod context;
+pub mod debug;
+pub mod deferred;
+pub mod framebuffer;
+pub mod hdr;
 pub mod shader;
 pub mod input;
+pub mod math;
+pub mod mesh;
+pub mod normal_mapping;
+pub mod pbr;
+pub mod postprocess;
 pub mod data;
-
+pub mod info;
+pub mod lighting;
+pub mod loader;
+pub mod scene;
+pub mod shadow;
+pub mod ssao;
+pub mod stats;
+pub mod storage;
+pub mod texture;
+pub mod time;
+pub mod trace;
+pub mod uniform;
+
+/// Install the debug callback with the default [`debug::DebugConfig`].
+///
 /// # Safety
 /// Please ensure that gl is loaded.
 pub unsafe fn init_error_callback() {
-    extern "system" fn callback(_source: GLenum,
-                                gltype: GLenum,
-                                _id: GLuint,
-                                severity: GLenum,
-                                _length: GLsizei,
-                                message: *const GLchar,
-                                _user_param: *mut c_void)
-    {
-        let msg = unsafe {
-            CStr::from_ptr(message as *mut _).to_str().unwrap().to_owned()
-        };
-        let tt = if gltype == DEBUG_TYPE_ERROR { " ** GL ERROR **" } else { "" };
-        eprintln!("GL CALLBACK: {tt} type = {gltype:#x}, severity = {severity}\n {msg}");
-    }
-    gl::DebugMessageCallback(Some(callback), std::ptr::null());
+    debug::init_debug_output(&debug::DebugConfig::default())
 }
 
-pub fn init_headless(width: u32, height: u32, debug: bool) -> (Context<PossiblyCurrent>, EventLoop<()>) {
-    let el = EventLoop::new();
-
-    let context = ContextBuilder::new()
-        .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
-        .with_gl_profile(GlProfile::Core)
-        .with_gl_debug_flag(debug)
-        .with_vsync(true)
-        .build_headless(&el, PhysicalSize::new(width, height))
-        .unwrap();
-
-
-    let windowed_context = unsafe { context.make_current().unwrap() };
-
-    gl::load_with(|s| windowed_context.get_proc_address(s));
-
-    if debug {
-        #[cfg(not(target_os = "macos"))]
-        unsafe { init_error_callback() }
-    }
-
-    unsafe { gl::Viewport(0, 0, width as _, height as _); }
-
-    (windowed_context, el)
+/// Headless context regardless of `--headless` / `LEARNOPENGL_HEADLESS`.
+pub fn init_headless(width: u32, height: u32, debug: bool) -> (GlContext, EventLoop<()>) {
+    let config = ContextConfig {
+        mode: ContextMode::Headless,
+        ..ContextConfig::new(width, height, debug)
+    };
+    GlContext::create(&config)
 }
 
-pub fn init(width: u32, height: u32, debug: bool) -> (ContextWrapper<PossiblyCurrent, Window>, EventLoop<()>) {
-    let el = EventLoop::new();
-
-    let wb = WindowBuilder::new()
-        .with_inner_size(PhysicalSize::new(width, height))
-        .with_title("Test Window");
-
-    let windowed_context = ContextBuilder::new()
-        .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
-        .with_gl_profile(GlProfile::Core)
-        .with_gl_debug_flag(debug)
-        .with_vsync(true)
-        .build_windowed(wb, &el)
-        .unwrap();
-
-    let windowed_context = unsafe { windowed_context.make_current().unwrap() };
-
-    gl::load_with(|s| windowed_context.get_proc_address(s));
-
-    if debug {
-        #[cfg(not(target_os = "macos"))]
-        unsafe { init_error_callback() }
-    }
-
-    unsafe { gl::Viewport(0, 0, width as _, height as _); }
-
-    (windowed_context, el)
+/// Windowed context, unless headless mode is requested on the command line or in the environment.
+pub fn init(width: u32, height: u32, debug: bool) -> (GlContext, EventLoop<()>) {
+    GlContext::create(&ContextConfig::new(width, height, debug))
 }
@@ -0,0 +1,138 @@
+// Phong and Blinn-Phong lighting from the `Lights` uniform block.
+// The layout and the math are mirrored in `utils::lighting`, change both together.
+
+#define MAX_DIRECTIONAL_LIGHTS 4
+#define MAX_POINT_LIGHTS 32
+#define MAX_SPOT_LIGHTS 8
+
+struct DirectionalLight {
+    vec3 direction;
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+struct PointLight {
+    vec3 position;
+    float constant;
+    vec3 ambient;
+    float linear;
+    vec3 diffuse;
+    float quadratic;
+    vec3 specular;
+};
+
+// Cutoffs are cosines of the cone half angles.
+struct SpotLight {
+    vec3 position;
+    float constant;
+    vec3 direction;
+    float linear;
+    vec3 ambient;
+    float quadratic;
+    vec3 diffuse;
+    float innerCutoff;
+    vec3 specular;
+    float outerCutoff;
+};
+
+layout(std140) uniform Lights {
+    // directional, point and spot lights in use
+    ivec4 lightCounts;
+    DirectionalLight directionalLights[MAX_DIRECTIONAL_LIGHTS];
+    PointLight pointLights[MAX_POINT_LIGHTS];
+    SpotLight spotLights[MAX_SPOT_LIGHTS];
+};
+
+struct PhongMaterial {
+    sampler2D diffuse;
+    sampler2D specular;
+    float shininess;
+    bool blinn;
+};
+
+// What the lights shine on, sampled once per fragment.
+struct Surface {
+    vec3 normal;
+    vec3 position;
+    vec3 viewDir;
+    vec3 diffuse;
+    vec3 specular;
+    float shininess;
+    bool blinn;
+};
+
+// Smallest `innerCutoff - outerCutoff`, equal cutoffs give a hard edge instead of a division by zero.
+const float CONE_EPSILON = 1e-4;
+
+// `1` inside the inner cone, `0` outside the outer one and smooth in between.
+float spotCone(float theta, float innerCutoff, float outerCutoff) {
+    return clamp((theta - outerCutoff) / max(innerCutoff - outerCutoff, CONE_EPSILON), 0.0, 1.0);
+}
+
+float attenuation(float constant, float linear, float quadratic, float distance) {
+    return 1.0 / (constant + linear * distance + quadratic * distance * distance);
+}
+
+float specularFactor(vec3 normal, vec3 lightDir, vec3 viewDir, float shininess, bool blinn) {
+    if (blinn) {
+        vec3 halfway = normalize(lightDir + viewDir);
+        return pow(max(dot(normal, halfway), 0.0), shininess);
+    }
+    vec3 reflectDir = reflect(-lightDir, normal);
+    return pow(max(dot(viewDir, reflectDir), 0.0), shininess);
+}
+
+// `lightDir` points from the surface towards the light.
+vec3 shade(Surface s, vec3 lightDir, vec3 ambient, vec3 diffuse, vec3 specular) {
+    float diff = max(dot(s.normal, lightDir), 0.0);
+    float spec = specularFactor(s.normal, lightDir, s.viewDir, s.shininess, s.blinn);
+    return ambient * s.diffuse + diffuse * diff * s.diffuse + specular * spec * s.specular;
+}
+
+vec3 directionalLight(DirectionalLight light, Surface s) {
+    return shade(s, normalize(-light.direction), light.ambient, light.diffuse, light.specular);
+}
+
+vec3 pointLight(PointLight light, Surface s) {
+    vec3 toLight = light.position - s.position;
+    float factor = attenuation(light.constant, light.linear, light.quadratic, length(toLight));
+    return factor * shade(s, normalize(toLight), light.ambient, light.diffuse, light.specular);
+}
+
+vec3 spotLight(SpotLight light, Surface s) {
+    vec3 toLight = light.position - s.position;
+    vec3 lightDir = normalize(toLight);
+    float theta = dot(lightDir, normalize(-light.direction));
+    float cone = spotCone(theta, light.innerCutoff, light.outerCutoff);
+    float factor = attenuation(light.constant, light.linear, light.quadratic, length(toLight));
+    // the ambient term stays outside the cone, so the scene is not pitch black around it
+    vec3 lit = shade(s, lightDir, vec3(0.0), light.diffuse, light.specular);
+    return factor * (light.ambient * s.diffuse + cone * lit);
+}
+
+vec3 allLights(Surface s) {
+    vec3 color = vec3(0.0);
+    for (int i = 0; i < lightCounts.x; i++) {
+        color += directionalLight(directionalLights[i], s);
+    }
+    for (int i = 0; i < lightCounts.y; i++) {
+        color += pointLight(pointLights[i], s);
+    }
+    for (int i = 0; i < lightCounts.z; i++) {
+        color += spotLight(spotLights[i], s);
+    }
+    return color;
+}
+
+Surface materialSurface(PhongMaterial material, vec2 texCoords, vec3 normal, vec3 position, vec3 viewPos) {
+    return Surface(
+        normalize(normal),
+        position,
+        normalize(viewPos - position),
+        texture(material.diffuse, texCoords).rgb,
+        texture(material.specular, texCoords).rgb,
+        material.shininess,
+        material.blinn
+    );
+}
@@ -0,0 +1,496 @@
+//! Directional, point and spot lights with Phong and Blinn-Phong materials.
+//!
+//! Shaders `#include "lighting.glsl"` (see [`resolve_includes`](crate::shader::resolve_includes)
+//! and [`INCLUDES`]) and read every light from the `Lights` uniform block, which
+//! [`LightsBuffer`] fills from a [`Lights`] list. The attenuation and specular math
+//! is mirrored here so it can be tested without a context.
+
+use anyhow::{bail, Result};
+use gl::types::*;
+use nalgebra::{Point3, Vector3, Vector4};
+
+use crate::shader::Shader;
+use crate::uniform::{Std140, UniformBuffer};
+
+pub const GLSL: &str = include_str!("lighting.glsl");
+
+/// Includes for [`resolve_includes`](crate::shader::resolve_includes).
+pub const INCLUDES: &[(&str, &str)] = &[("lighting.glsl", GLSL)];
+
+/// Name of the uniform block in [`GLSL`].
+pub const BLOCK_NAME: &str = "Lights";
+
+/// Array sizes of the uniform block, the `MAX_*_LIGHTS` defines of [`GLSL`].
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 32;
+pub const MAX_SPOT_LIGHTS: usize = 8;
+
+/// std140 size of the `Lights` block.
+pub const BLOCK_SIZE: usize = LightsBlock::SIZE;
+
+/// Smallest difference of the cutoff cosines, `CONE_EPSILON` in [`GLSL`].
+pub const CONE_EPSILON: f32 = 1e-4;
+
+/// The Phong terms of a light, multiplied with the material colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightColor {
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+}
+
+impl LightColor {
+    /// `color` with each term scaled by its strength.
+    pub fn scaled(color: Vector3<f32>, ambient: f32, diffuse: f32, specular: f32) -> Self {
+        Self { ambient: color * ambient, diffuse: color * diffuse, specular: color * specular }
+    }
+}
+
+impl Default for LightColor {
+    fn default() -> Self {
+        Self::scaled(Vector3::repeat(1.0), 0.05, 0.8, 1.0)
+    }
+}
+
+/// `1 / (constant + linear * d + quadratic * d²)` at distance `d`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    pub const NONE: Self = Self { constant: 1.0, linear: 0.0, quadratic: 0.0 };
+
+    /// Terms that light up to roughly the given distance, from the Ogre3D table.
+    pub const TABLE: [(f32, Self); 12] = [
+        (7.0, Self { constant: 1.0, linear: 0.7, quadratic: 1.8 }),
+        (13.0, Self { constant: 1.0, linear: 0.35, quadratic: 0.44 }),
+        (20.0, Self { constant: 1.0, linear: 0.22, quadratic: 0.20 }),
+        (32.0, Self { constant: 1.0, linear: 0.14, quadratic: 0.07 }),
+        (50.0, Self { constant: 1.0, linear: 0.09, quadratic: 0.032 }),
+        (65.0, Self { constant: 1.0, linear: 0.07, quadratic: 0.017 }),
+        (100.0, Self { constant: 1.0, linear: 0.045, quadratic: 0.0075 }),
+        (160.0, Self { constant: 1.0, linear: 0.027, quadratic: 0.0028 }),
+        (200.0, Self { constant: 1.0, linear: 0.022, quadratic: 0.0019 }),
+        (325.0, Self { constant: 1.0, linear: 0.014, quadratic: 0.0007 }),
+        (600.0, Self { constant: 1.0, linear: 0.007, quadratic: 0.0002 }),
+        (3250.0, Self { constant: 1.0, linear: 0.0014, quadratic: 0.000007 }),
+    ];
+
+    /// The first entry of [`TABLE`](Self::TABLE) reaching `range`.
+    pub fn for_range(range: f32) -> Self {
+        Self::TABLE.iter()
+            .find(|(distance, _)| *distance >= range)
+            .unwrap_or(&Self::TABLE[Self::TABLE.len() - 1])
+            .1
+    }
+
+    /// Same as `attenuation` in [`GLSL`].
+    pub fn factor(&self, distance: f32) -> f32 {
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+
+    /// Distance at which the factor drops to `threshold`, infinite if it never does.
+    pub fn range(&self, threshold: f32) -> f32 {
+        let c = self.constant - 1.0 / threshold;
+        if c >= 0.0 {
+            return 0.0;
+        }
+        if self.quadratic > 0.0 {
+            let discriminant = self.linear * self.linear - 4.0 * self.quadratic * c;
+            (-self.linear + discriminant.sqrt()) / (2.0 * self.quadratic)
+        } else if self.linear > 0.0 {
+            -c / self.linear
+        } else {
+            f32::INFINITY
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    /// Where the light travels to, from the light towards the scene.
+    pub direction: Vector3<f32>,
+    pub color: LightColor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Point3<f32>,
+    pub color: LightColor,
+    pub attenuation: Attenuation,
+}
+
+impl PointLight {
+    pub fn factor(&self, point: &Point3<f32>) -> f32 {
+        self.attenuation.factor(nalgebra::distance(&self.position, point))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub color: LightColor,
+    pub attenuation: Attenuation,
+    /// Half angle in radians of the fully lit cone.
+    pub inner_cutoff: f32,
+    /// Half angle in radians where the light has faded out, larger than `inner_cutoff`.
+    pub outer_cutoff: f32,
+}
+
+impl SpotLight {
+    /// `1` inside the inner cone, `0` outside the outer one and smooth in between.
+    pub fn cone_factor(&self, point: &Point3<f32>) -> f32 {
+        let theta = (point - self.position).normalize().dot(&self.direction.normalize());
+        let (inner, outer) = (self.inner_cutoff.cos(), self.outer_cutoff.cos());
+        // equal cutoffs give a hard edge instead of NaN
+        ((theta - outer) / (inner - outer).max(CONE_EPSILON)).clamp(0.0, 1.0)
+    }
+
+    /// Attenuation and cone together, what the diffuse and specular terms are scaled by.
+    pub fn factor(&self, point: &Point3<f32>) -> f32 {
+        self.cone_factor(point) * self.attenuation.factor(nalgebra::distance(&self.position, point))
+    }
+}
+
+/// Same as `specularFactor` in [`GLSL`], every direction normalized and pointing away from the surface.
+pub fn specular_factor(normal: &Vector3<f32>, light_dir: &Vector3<f32>, view_dir: &Vector3<f32>, shininess: f32, blinn: bool) -> f32 {
+    if blinn {
+        let halfway = (light_dir + view_dir).normalize();
+        return normal.dot(&halfway).max(0.0).powf(shininess);
+    }
+    // GLSL reflect(-l, n)
+    let reflected = -light_dir + 2.0 * normal.dot(light_dir) * normal;
+    view_dir.dot(&reflected).max(0.0).powf(shininess)
+}
+
+pub fn diffuse_factor(normal: &Vector3<f32>, light_dir: &Vector3<f32>) -> f32 {
+    normal.dot(light_dir).max(0.0)
+}
+
+/// Every light of a frame, uploaded together.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lights {
+    pub directional: Vec<DirectionalLight>,
+    pub point: Vec<PointLight>,
+    pub spot: Vec<SpotLight>,
+}
+
+impl Lights {
+    /// The contents of the `Lights` block, [`BLOCK_SIZE`] bytes.
+    pub fn to_std140(&self) -> Result<Vec<u8>> {
+        Ok(self.to_block()?.to_std140())
+    }
+
+    fn to_block(&self) -> Result<LightsBlock> {
+        if self.directional.len() > MAX_DIRECTIONAL_LIGHTS
+            || self.point.len() > MAX_POINT_LIGHTS
+            || self.spot.len() > MAX_SPOT_LIGHTS {
+            bail!("{} directional, {} point and {} spot lights do not fit in the uniform block, at most {MAX_DIRECTIONAL_LIGHTS}, {MAX_POINT_LIGHTS} and {MAX_SPOT_LIGHTS}",
+                  self.directional.len(), self.point.len(), self.spot.len());
+        }
+
+        let mut block = LightsBlock {
+            light_counts: Vector4::new(self.directional.len() as i32, self.point.len() as i32, self.spot.len() as i32, 0),
+            ..LightsBlock::default()
+        };
+        for (slot, light) in block.directional_lights.iter_mut().zip(&self.directional) {
+            *slot = DirectionalBlock {
+                direction: light.direction,
+                ambient: light.color.ambient,
+                diffuse: light.color.diffuse,
+                specular: light.color.specular,
+            };
+        }
+        for (slot, light) in block.point_lights.iter_mut().zip(&self.point) {
+            *slot = PointBlock {
+                position: light.position.coords,
+                constant: light.attenuation.constant,
+                ambient: light.color.ambient,
+                linear: light.attenuation.linear,
+                diffuse: light.color.diffuse,
+                quadratic: light.attenuation.quadratic,
+                specular: light.color.specular,
+            };
+        }
+        for (slot, light) in block.spot_lights.iter_mut().zip(&self.spot) {
+            *slot = SpotBlock {
+                position: light.position.coords,
+                constant: light.attenuation.constant,
+                direction: light.direction,
+                linear: light.attenuation.linear,
+                ambient: light.color.ambient,
+                quadratic: light.attenuation.quadratic,
+                diffuse: light.color.diffuse,
+                inner_cutoff: light.inner_cutoff.cos(),
+                specular: light.color.specular,
+                outer_cutoff: light.outer_cutoff.cos(),
+            };
+        }
+        Ok(block)
+    }
+}
+
+// The structs of the `Lights` block in [`GLSL`], unused slots stay zero.
+
+#[derive(Debug, Clone, Copy, Default, Std140)]
+struct DirectionalBlock {
+    direction: Vector3<f32>,
+    ambient: Vector3<f32>,
+    diffuse: Vector3<f32>,
+    specular: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Std140)]
+struct PointBlock {
+    position: Vector3<f32>,
+    constant: f32,
+    ambient: Vector3<f32>,
+    linear: f32,
+    diffuse: Vector3<f32>,
+    quadratic: f32,
+    specular: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Std140)]
+#[std140(rename_all = "camelCase")]
+struct SpotBlock {
+    position: Vector3<f32>,
+    constant: f32,
+    direction: Vector3<f32>,
+    linear: f32,
+    ambient: Vector3<f32>,
+    quadratic: f32,
+    diffuse: Vector3<f32>,
+    /// Cosines of the cone half angles.
+    inner_cutoff: f32,
+    specular: Vector3<f32>,
+    outer_cutoff: f32,
+}
+
+#[derive(Debug, Clone, Default, Std140)]
+#[std140(rename_all = "camelCase")]
+struct LightsBlock {
+    light_counts: Vector4<i32>,
+    directional_lights: [DirectionalBlock; MAX_DIRECTIONAL_LIGHTS],
+    point_lights: [PointBlock; MAX_POINT_LIGHTS],
+    spot_lights: [SpotBlock; MAX_SPOT_LIGHTS],
+}
+
+/// The uniform buffer behind the `Lights` block, bound to one binding point.
+pub struct LightsBuffer {
+    buffer: UniformBuffer<LightsBlock>,
+}
+
+impl LightsBuffer {
+    pub fn new(binding: GLuint) -> Self {
+        Self { buffer: UniformBuffer::new(binding) }
+    }
+
+    pub fn binding(&self) -> GLuint {
+        self.buffer.binding()
+    }
+
+    /// Check the `Lights` block of `shader` and point it at this buffer.
+    pub fn bind_to(&self, shader: &Shader) -> Result<()> {
+        self.buffer.bind_to(shader, BLOCK_NAME)
+    }
+
+    pub fn upload(&self, lights: &Lights) -> Result<()> {
+        self.buffer.set(&lights.to_block()?);
+        Ok(())
+    }
+}
+
+/// Diffuse and specular maps with a shininess, the `PhongMaterial` struct of [`GLSL`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhongMaterial {
+    /// 2D texture ids.
+    pub diffuse: GLuint,
+    pub specular: GLuint,
+    pub shininess: f32,
+    /// Blinn-Phong halfway vector specular instead of the reflected one.
+    pub blinn: bool,
+}
+
+impl PhongMaterial {
+    /// Bind the maps to the texture units `first_unit` and `first_unit + 1` and set the
+    /// fields of the `uniform` struct, `shader` must be active.
+    pub fn apply(&self, shader: &Shader, uniform: &str, first_unit: u32) -> Result<()> {
+        for (unit, texture) in [(first_unit, self.diffuse), (first_unit + 1, self.specular)] {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+            }
+        }
+        shader.set_i32(&format!("{uniform}.diffuse"), (first_unit as i32,))?;
+        shader.set_i32(&format!("{uniform}.specular"), (first_unit as i32 + 1,))?;
+        shader.set_f32(&format!("{uniform}.shininess"), (self.shininess,))?;
+        shader.set_i32(&format!("{uniform}.blinn"), (self.blinn as i32,))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn float_at(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn block_members_match_glsl_names() {
+        let fields = LightsBlock::fields();
+        for name in ["lightCounts", "directionalLights[3].specular", "pointLights[31].quadratic", "spotLights[0].innerCutoff"] {
+            assert!(fields.iter().any(|field| field.name == name), "{name}");
+        }
+        assert_eq!(fields.iter().find(|field| field.name == "spotLights[1].outerCutoff").unwrap().offset,
+                   16 + MAX_DIRECTIONAL_LIGHTS * 64 + MAX_POINT_LIGHTS * 64 + 80 + 76);
+    }
+
+    #[test]
+    fn glsl_array_sizes_match() {
+        let define = |name: &str| -> usize {
+            let line = GLSL.lines().find(|line| line.starts_with(&format!("#define {name} "))).unwrap();
+            line.rsplit(' ').next().unwrap().parse().unwrap()
+        };
+        assert_eq!(define("MAX_DIRECTIONAL_LIGHTS"), MAX_DIRECTIONAL_LIGHTS);
+        assert_eq!(define("MAX_POINT_LIGHTS"), MAX_POINT_LIGHTS);
+        assert_eq!(define("MAX_SPOT_LIGHTS"), MAX_SPOT_LIGHTS);
+    }
+
+    #[test]
+    fn attenuation_falls_off() {
+        let none = Attenuation::NONE;
+        assert_eq!(none.factor(1000.0), 1.0);
+        assert_eq!(none.range(0.01), f32::INFINITY);
+
+        let fifty = Attenuation::for_range(40.0);
+        assert_eq!(fifty, Attenuation::TABLE[4].1);
+        assert_eq!(fifty.factor(0.0), 1.0);
+        // 1 / (1 + 0.09 * 50 + 0.032 * 2500)
+        assert!(close(fifty.factor(50.0), 1.0 / 85.5));
+        assert!(close(fifty.range(1.0 / 85.5), 50.0));
+        assert_eq!(fifty.range(2.0), 0.0);
+
+        let linear = Attenuation { constant: 1.0, linear: 0.5, quadratic: 0.0 };
+        assert!(close(linear.range(0.2), 8.0));
+        assert_eq!(Attenuation::for_range(1e6), Attenuation::TABLE[11].1);
+
+        let light = PointLight { position: Point3::new(0.0, 3.0, 4.0), color: LightColor::default(), attenuation: fifty };
+        assert!(close(light.factor(&Point3::origin()), fifty.factor(5.0)));
+    }
+
+    #[test]
+    fn spot_cone_fades_between_cutoffs() {
+        let spot = SpotLight {
+            position: Point3::origin(),
+            direction: -Vector3::y(),
+            color: LightColor::default(),
+            attenuation: Attenuation::NONE,
+            inner_cutoff: 30f32.to_radians(),
+            outer_cutoff: 45f32.to_radians(),
+        };
+        let at_angle = |degrees: f32| {
+            let radians = degrees.to_radians();
+            spot.cone_factor(&(Point3::new(radians.sin(), -radians.cos(), 0.0) * 10.0))
+        };
+
+        assert_eq!(at_angle(0.0), 1.0);
+        assert_eq!(at_angle(29.0), 1.0);
+        assert_eq!(at_angle(46.0), 0.0);
+        let middle = (37.5f32.to_radians().cos() - FRAC_1_SQRT_2) / (30f32.to_radians().cos() - FRAC_1_SQRT_2);
+        assert!(close(at_angle(37.5), middle));
+        assert!(at_angle(35.0) > at_angle(40.0));
+
+        let far = Attenuation { constant: 1.0, linear: 1.0, quadratic: 0.0 };
+        assert!(close(SpotLight { attenuation: far, ..spot }.factor(&Point3::new(0.0, -3.0, 0.0)), 0.25));
+
+        // equal cutoffs are a hard edge
+        let hard = SpotLight { inner_cutoff: spot.outer_cutoff, ..spot };
+        let below = Point3::new(0.0, -10.0, 0.0);
+        assert_eq!(hard.cone_factor(&below), 1.0);
+        let edge = Point3::new(FRAC_1_SQRT_2, -FRAC_1_SQRT_2, 0.0);
+        assert!(hard.cone_factor(&edge).is_finite());
+        assert_eq!(hard.cone_factor(&Point3::new(1.0, -0.9, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn phong_and_blinn_specular() {
+        let normal = Vector3::y();
+        let light = Vector3::new(1.0, 1.0, 0.0).normalize();
+
+        // looking along the reflection gives the full highlight in both models
+        let mirror = Vector3::new(-1.0, 1.0, 0.0).normalize();
+        assert!(close(specular_factor(&normal, &light, &mirror, 32.0, false), 1.0));
+        assert!(close(specular_factor(&normal, &light, &mirror, 32.0, true), 1.0));
+
+        // straight above: reflection at 45 degrees, halfway vector at 22.5 degrees
+        let view = Vector3::y();
+        assert!(close(specular_factor(&normal, &light, &view, 2.0, false), 0.5));
+        assert!(close(specular_factor(&normal, &light, &view, 2.0, true), 22.5f32.to_radians().cos().powi(2)));
+
+        // Phong cuts off past 90 degrees from the reflection, Blinn-Phong does not
+        let grazing = Vector3::new(1.0, 0.1, 0.0).normalize();
+        assert_eq!(specular_factor(&normal, &light, &grazing, 8.0, false), 0.0);
+        assert!(specular_factor(&normal, &light, &grazing, 8.0, true) > 0.0);
+
+        assert!(close(diffuse_factor(&normal, &light), FRAC_1_SQRT_2));
+        assert_eq!(diffuse_factor(&normal, &-light), 0.0);
+    }
+
+    #[test]
+    fn lights_std140_layout() {
+        let attenuation = Attenuation { constant: 1.0, linear: 0.5, quadratic: 0.25 };
+        let lights = Lights {
+            directional: vec![DirectionalLight { direction: Vector3::new(0.0, -1.0, 0.0), color: LightColor::default() }],
+            point: vec![
+                PointLight { position: Point3::new(1.0, 2.0, 3.0), color: LightColor::default(), attenuation },
+                PointLight { position: Point3::new(4.0, 5.0, 6.0), color: LightColor::scaled(Vector3::x(), 0.1, 0.2, 0.3), attenuation },
+            ],
+            spot: vec![SpotLight {
+                position: Point3::origin(),
+                direction: -Vector3::z(),
+                color: LightColor::default(),
+                attenuation,
+                inner_cutoff: 0.0,
+                outer_cutoff: std::f32::consts::FRAC_PI_2,
+            }],
+        };
+
+        let bytes = lights.to_std140().unwrap();
+        assert_eq!(BLOCK_SIZE, 16 + MAX_DIRECTIONAL_LIGHTS * 64 + MAX_POINT_LIGHTS * 64 + MAX_SPOT_LIGHTS * 80);
+        assert_eq!(bytes.len(), BLOCK_SIZE);
+        assert_eq!(bytes[..16], [1i32, 2, 1, 0].map(i32::to_ne_bytes).concat());
+
+        // directionalLights[0].direction.y
+        assert_eq!(float_at(&bytes, 16 + 4), -1.0);
+        let points = 16 + MAX_DIRECTIONAL_LIGHTS * 64;
+        // pointLights[1]: position, constant, ambient, linear, diffuse, quadratic, specular
+        let second = points + 64;
+        assert_eq!(float_at(&bytes, second), 4.0);
+        assert_eq!(float_at(&bytes, second + 12), 1.0);
+        assert!(close(float_at(&bytes, second + 16), 0.1));
+        assert_eq!(float_at(&bytes, second + 28), 0.5);
+        assert_eq!(float_at(&bytes, second + 44), 0.25);
+        assert!(close(float_at(&bytes, second + 48), 0.3));
+
+        // spotLights[0]: direction.z, innerCutoff and outerCutoff as cosines
+        let spots = points + MAX_POINT_LIGHTS * 64;
+        assert_eq!(float_at(&bytes, spots + 16 + 8), -1.0);
+        assert_eq!(float_at(&bytes, spots + 60), 1.0);
+        assert!(close(float_at(&bytes, spots + 76), 0.0));
+
+        let too_many = Lights { spot: vec![lights.spot[0]; MAX_SPOT_LIGHTS + 1], ..Lights::default() };
+        assert!(too_many.to_std140().is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Loading the gl function pointers. With the `gl-check` or `gl-trace` feature every entry
+//! point is loaded through a generated shim, so bare `gl::` calls are checked with
+//! `glGetError` and recorded into the [`trace`](crate::trace) just like the ones wrapped in
+//! [`gl_check!`](crate::gl_check), which only adds the `file:line` of the call to error reports.
+
+use std::ffi::c_void;
+
+/// Load gl with `loader`, [`GlContext`](crate::GlContext) does this for every sample.
+pub fn load_with(loader: impl FnMut(&str) -> *const c_void) {
+    #[cfg(any(feature = "gl-check", feature = "gl-trace"))]
+    let loader = {
+        let mut loader = loader;
+        move |symbol: &str| shim::wrap(symbol, loader(symbol))
+    };
+    gl::load_with(loader);
+}
+
+#[cfg(any(feature = "gl-check", feature = "gl-trace"))]
+fn after_call(name: &str, args: &[&dyn std::fmt::Debug], result: &dyn std::fmt::Debug) {
+    #[cfg(feature = "gl-trace")]
+    crate::trace::record(name, args, result);
+
+    #[cfg(not(feature = "gl-trace"))]
+    let _ = result;
+
+    #[cfg(feature = "gl-check")]
+    if name != "GetError" {
+        crate::debug::check::check_call(name, args, shim::get_error);
+    }
+}
+
+#[cfg(any(feature = "gl-check", feature = "gl-trace"))]
+mod shim {
+    use std::ffi::{c_void, CStr};
+    use std::fmt::{Debug, Formatter};
+    use std::sync::atomic::{AtomicPtr, Ordering};
+
+    use gl::types;
+
+    mod __gl_imports {
+        pub use std::os::raw;
+    }
+
+    /// A NUL terminated name argument, printed as a string instead of its address.
+    struct Name(*const types::GLchar);
+
+    impl Debug for Name {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            if self.0.is_null() {
+                return write!(f, "{:?}", self.0);
+            }
+            write!(f, "{:?}", unsafe { CStr::from_ptr(self.0) })
+        }
+    }
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const UNLOADED: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+
+    #[allow(non_snake_case, unreachable_patterns, clippy::too_many_arguments, clippy::unused_unit, clippy::let_unit_value, clippy::missing_safety_doc)]
+    mod generated {
+        use super::*;
+
+        include!(concat!(env!("OUT_DIR"), "/gl_shim.rs"));
+    }
+
+    /// The shim of `symbol` forwarding to `real`, or `real` itself for functions the
+    /// registry doesn't know.
+    pub fn wrap(symbol: &str, real: *const c_void) -> *const c_void {
+        match generated::lookup(symbol) {
+            Some((slot, shim)) if !real.is_null() => {
+                generated::store(slot, real);
+                shim
+            }
+            _ => real,
+        }
+    }
+
+    /// `glGetError` of the driver, draining errors must not go through the shims.
+    #[cfg(feature = "gl-check")]
+    pub fn get_error() -> types::GLenum {
+        unsafe { generated::call_get_error() }
+    }
+}
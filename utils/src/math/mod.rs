@@ -0,0 +1,218 @@
+//! glm style helpers on nalgebra types and quaternion interpolation.
+//!
+//! Matrices follow glm: column vectors, right handed, clip space depth in `[-1, 1]`,
+//! and `translate(m, v)` returns `m * T(v)` so calls read in the same order as in C++.
+
+use nalgebra::{Matrix3, Matrix4, Point3, Quaternion, Unit, UnitQuaternion, Vector3};
+
+pub fn radians(degrees: f32) -> f32 {
+    degrees.to_radians()
+}
+
+pub fn degrees(radians: f32) -> f32 {
+    radians.to_degrees()
+}
+
+/// `glm::lookAt`
+pub fn look_at(eye: &Point3<f32>, center: &Point3<f32>, up: &Vector3<f32>) -> Matrix4<f32> {
+    Matrix4::look_at_rh(eye, center, up)
+}
+
+/// `glm::perspective`, `fov_y` in radians.
+pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Matrix4<f32> {
+    Matrix4::new_perspective(aspect, fov_y, near, far)
+}
+
+/// `glm::ortho`
+pub fn ortho(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix4<f32> {
+    Matrix4::new_orthographic(left, right, bottom, top, near, far)
+}
+
+/// `glm::translate`: `m * T(v)`.
+pub fn translate(m: &Matrix4<f32>, v: &Vector3<f32>) -> Matrix4<f32> {
+    m * Matrix4::new_translation(v)
+}
+
+/// `glm::rotate`: `m * R(angle, axis)`, the axis does not need to be normalized.
+pub fn rotate(m: &Matrix4<f32>, angle: f32, axis: &Vector3<f32>) -> Matrix4<f32> {
+    m * Matrix4::from_axis_angle(&Unit::new_normalize(*axis), angle)
+}
+
+/// `glm::scale`: `m * S(v)`.
+pub fn scale(m: &Matrix4<f32>, v: &Vector3<f32>) -> Matrix4<f32> {
+    m * Matrix4::new_nonuniform_scaling(v)
+}
+
+/// Inverse transpose of the upper 3x3, transforms normals under non-uniform scaling.
+///
+/// Singular matrices give the zero matrix.
+pub fn normal_matrix(model: &Matrix4<f32>) -> Matrix3<f32> {
+    let m: Matrix3<f32> = model.fixed_slice::<3, 3>(0, 0).into();
+    m.try_inverse().map(|inv| inv.transpose()).unwrap_or_else(Matrix3::zeros)
+}
+
+/// Spherical interpolation along the shorter arc, like `glm::slerp`.
+pub fn slerp(a: &UnitQuaternion<f32>, b: &UnitQuaternion<f32>, t: f32) -> UnitQuaternion<f32> {
+    // `q` and `-q` are the same rotation, flip one to take the short way.
+    let b = if a.coords.dot(&b.coords) < 0.0 {
+        UnitQuaternion::new_unchecked(-b.into_inner())
+    } else {
+        *b
+    };
+    a.try_slerp(&b, t, 1e-6).unwrap_or_else(|| a.nlerp(&b, t))
+}
+
+/// Control point between `prev` and `next` around `current` for [`squad`], like `glm::intermediate`.
+pub fn intermediate(
+    prev: &UnitQuaternion<f32>,
+    current: &UnitQuaternion<f32>,
+    next: &UnitQuaternion<f32>,
+) -> UnitQuaternion<f32> {
+    // the unit logarithm is zero for a repeated key, where the plain one is NaN. It is
+    // the whole angle though, the plain one and `exp` use half of it.
+    let inv = current.inverse();
+    let sum = ((inv * next).ln() + (inv * prev).ln()) * 0.5;
+    UnitQuaternion::new_normalize(current.into_inner() * (sum * -0.25).exp())
+}
+
+/// Spherical quadrangle interpolation from `q1` to `q2` with the control points `s1` and `s2`
+/// from [`intermediate`], smooth across keyframes unlike chained slerps. Like `glm::squad`.
+pub fn squad(
+    q1: &UnitQuaternion<f32>,
+    q2: &UnitQuaternion<f32>,
+    s1: &UnitQuaternion<f32>,
+    s2: &UnitQuaternion<f32>,
+    h: f32,
+) -> UnitQuaternion<f32> {
+    // glm uses the non shortest path slerp for both levels.
+    let mix = |a: &UnitQuaternion<f32>, b: &UnitQuaternion<f32>, t: f32| {
+        a.try_slerp(b, t, 1e-6).unwrap_or_else(|| a.nlerp(b, t))
+    };
+    mix(&mix(q1, q2, h), &mix(s1, s2, h), 2.0 * (1.0 - h) * h)
+}
+
+/// Quaternion from glm's `(w, x, y, z)` constructor order.
+pub fn quat(w: f32, x: f32, y: f32, z: f32) -> UnitQuaternion<f32> {
+    UnitQuaternion::new_normalize(Quaternion::new(w, x, y, z))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    use nalgebra::Vector4;
+
+    use super::*;
+
+    /// Compare with a glm matrix printed column by column, `glm::to_string` order.
+    fn assert_glm(m: &Matrix4<f32>, columns: [[f32; 4]; 4]) {
+        for (c, column) in columns.iter().enumerate() {
+            for (r, expected) in column.iter().enumerate() {
+                assert!((m[(r, c)] - expected).abs() < 1e-5, "[{c}][{r}] = {} != {expected}\n{m}", m[(r, c)]);
+            }
+        }
+    }
+
+    #[test]
+    fn projections_match_glm() {
+        // glm::perspective(glm::radians(45.0f), 4.0f / 3.0f, 0.1f, 100.0f)
+        assert_glm(&perspective(radians(45.0), 4.0 / 3.0, 0.1, 100.0), [
+            [1.81066, 0.0, 0.0, 0.0],
+            [0.0, 2.414213, 0.0, 0.0],
+            [0.0, 0.0, -1.002002, -1.0],
+            [0.0, 0.0, -0.2002002, 0.0],
+        ]);
+
+        // glm::ortho(-1.0f, 1.0f, -1.0f, 1.0f, 0.1f, 100.0f)
+        assert_glm(&ortho(-1.0, 1.0, -1.0, 1.0, 0.1, 100.0), [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, -0.02002002, 0.0],
+            [0.0, 0.0, -1.002002, 1.0],
+        ]);
+
+        // glm::lookAt(glm::vec3(1, 2, 3), glm::vec3(0), glm::vec3(0, 1, 0))
+        assert_glm(&look_at(&Point3::new(1.0, 2.0, 3.0), &Point3::origin(), &Vector3::y()), [
+            [0.948683, -0.169031, 0.267261, 0.0],
+            [0.0, 0.845154, 0.534522, 0.0],
+            [-0.316228, -0.507093, 0.801784, 0.0],
+            [0.0, 0.0, -3.741657, 1.0],
+        ]);
+    }
+
+    #[test]
+    fn transforms_compose_like_glm() {
+        // trans = glm::translate(trans, glm::vec3(1, 2, 3));
+        // trans = glm::rotate(trans, glm::radians(90.0f), glm::vec3(0, 0, 1));
+        // trans = glm::scale(trans, glm::vec3(2, 2, 2));
+        let trans = translate(&Matrix4::identity(), &Vector3::new(1.0, 2.0, 3.0));
+        let trans = rotate(&trans, radians(90.0), &Vector3::new(0.0, 0.0, 2.0));
+        let trans = scale(&trans, &Vector3::repeat(2.0));
+
+        assert_glm(&trans, [
+            [0.0, 2.0, 0.0, 0.0],
+            [-2.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 2.0, 0.0],
+            [1.0, 2.0, 3.0, 1.0],
+        ]);
+        assert_eq!(radians(180.0), PI);
+        assert_eq!(degrees(FRAC_PI_2), 90.0);
+    }
+
+    #[test]
+    fn normal_matrix_undoes_scaling() {
+        let model = scale(&Matrix4::new_translation(&Vector3::new(5.0, 0.0, 0.0)), &Vector3::new(1.0, 2.0, 4.0));
+        assert_eq!(normal_matrix(&model), Matrix3::from_diagonal(&Vector3::new(1.0, 0.5, 0.25)));
+        assert_eq!(normal_matrix(&Matrix4::zeros()), Matrix3::zeros());
+    }
+
+    #[test]
+    fn quaternion_interpolation_matches_glm() {
+        let identity = UnitQuaternion::identity();
+        let quarter = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), FRAC_PI_2);
+
+        // glm::slerp(glm::quat(1, 0, 0, 0), glm::angleAxis(glm::radians(90.0f), glm::vec3(0, 0, 1)), 0.5f)
+        // = quat(0.923880, {0, 0, 0.382683})
+        let half = slerp(&identity, &quarter, 0.5);
+        assert!((half.coords - Vector4::new(0.0, 0.0, 0.382683, 0.92388)).norm() < 1e-5);
+
+        // the same rotation with the opposite sign still takes the short way
+        let flipped = UnitQuaternion::new_unchecked(-quarter.into_inner());
+        assert!((slerp(&identity, &flipped, 0.5).angle() - FRAC_PI_2 / 2.0).abs() < 1e-5);
+
+        // evenly spaced keys around one axis: the control points are the keys themselves,
+        // and squad follows the slerp
+        let key = |degrees: f32| UnitQuaternion::from_axis_angle(&Vector3::z_axis(), radians(degrees));
+        let s1 = intermediate(&key(0.0), &key(30.0), &key(60.0));
+        let s2 = intermediate(&key(30.0), &key(60.0), &key(90.0));
+        assert!(s1.angle_to(&key(30.0)) < 1e-4 && s2.angle_to(&key(60.0)) < 1e-4);
+        let q = squad(&key(30.0), &key(60.0), &s1, &s2, 0.5);
+        assert!(q.angle_to(&key(45.0)) < 1e-4);
+
+        // a hold key repeats the previous one, the control points stay finite and squad
+        // stays on the axis between the keys
+        let hold = intermediate(&key(0.0), &key(0.0), &key(60.0));
+        assert!(hold.coords.iter().all(|c| c.is_finite()));
+        assert!(hold.angle_to(&key(-15.0)) < 1e-4);
+        let s2 = intermediate(&key(0.0), &key(60.0), &key(60.0));
+        assert!(s2.coords.iter().all(|c| c.is_finite()));
+        for h in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            let q = squad(&key(0.0), &key(60.0), &hold, &s2, h);
+            assert!(q.coords.iter().all(|c| c.is_finite()), "h {h}: {q:?}");
+            assert!(q.axis().is_none_or(|axis| (axis.into_inner() - Vector3::z()).norm() < 1e-4), "h {h}: {q:?}");
+        }
+        assert!(squad(&key(0.0), &key(60.0), &hold, &s2, 1.0).angle_to(&key(60.0)) < 1e-4);
+
+        // unevenly spaced keys: the control point leans away from the longer side,
+        // and squad still passes through the keys
+        let s1 = intermediate(&key(0.0), &key(10.0), &key(90.0));
+        assert!(s1.angle_to(&key(-7.5)) < 1e-4, "{}", s1.angle());
+        let s2 = intermediate(&key(10.0), &key(90.0), &key(100.0));
+        assert!(squad(&key(10.0), &key(90.0), &s1, &s2, 0.0).angle_to(&key(10.0)) < 1e-4);
+        assert!(squad(&key(10.0), &key(90.0), &s1, &s2, 1.0).angle_to(&key(90.0)) < 1e-4);
+        let middle = squad(&key(10.0), &key(90.0), &s1, &s2, 0.5);
+        assert!((middle.axis().unwrap().into_inner() - Vector3::z()).norm() < 1e-4);
+
+        assert_eq!(quat(1.0, 0.0, 0.0, 0.0), identity);
+    }
+}
@@ -0,0 +1,432 @@
+//! Vertex data on the CPU side, and [`Mesh`] once it is uploaded into buffers.
+
+use std::f32::consts::PI;
+use std::mem::size_of_val;
+
+use anyhow::{bail, Result};
+use gl::types::*;
+use nalgebra::{Point3, Vector2, Vector3, Vector4};
+
+/// Attribute locations of [`Mesh`], the `layout (location = ...)` of vertex shaders.
+pub const POSITION_LOCATION: GLuint = 0;
+pub const NORMAL_LOCATION: GLuint = 1;
+pub const TEX_COORDS_LOCATION: GLuint = 2;
+pub const TANGENT_LOCATION: GLuint = 3;
+
+/// An indexed triangle list, every attribute has one entry per position.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<Point3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+    pub tex_coords: Vec<Vector2<f32>>,
+    /// Direction of growing `u` with the handedness in `w`, empty until
+    /// [`generate_tangents`](Self::generate_tangents).
+    pub tangents: Vec<Vector4<f32>>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Positions of every triangle, counter clockwise when seen from the front.
+    pub fn triangles(&self) -> impl Iterator<Item=[Point3<f32>; 3]> + '_ {
+        self.indices.chunks_exact(3).map(|t| {
+            [self.positions[t[0] as usize], self.positions[t[1] as usize], self.positions[t[2] as usize]]
+        })
+    }
+
+    /// Axis aligned cube from `-0.5` to `0.5`, four vertices per face for flat normals.
+    pub fn cube() -> Self {
+        // normal, then the face's right and up axes, so `right x up == normal`
+        let faces = [
+            (Vector3::x(), -Vector3::z(), Vector3::y()),
+            (-Vector3::x(), Vector3::z(), Vector3::y()),
+            (Vector3::y(), Vector3::x(), -Vector3::z()),
+            (-Vector3::y(), Vector3::x(), Vector3::z()),
+            (Vector3::z(), Vector3::x(), Vector3::y()),
+            (-Vector3::z(), -Vector3::x(), Vector3::y()),
+        ];
+
+        let mut mesh = Self::default();
+        for (normal, right, up) in faces {
+            let base = mesh.positions.len() as u32;
+            for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                let corner = normal * 0.5 + right * (u - 0.5) + up * (v - 0.5);
+                mesh.positions.push(Point3::from(corner));
+                mesh.normals.push(normal);
+                mesh.tex_coords.push(Vector2::new(u, v));
+            }
+            mesh.indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        mesh
+    }
+
+    /// Square of `size` on the XZ plane facing up, centered on the origin.
+    pub fn plane(size: f32) -> Self {
+        let half = size / 2.0;
+        Self {
+            positions: vec![
+                Point3::new(-half, 0.0, half),
+                Point3::new(half, 0.0, half),
+                Point3::new(half, 0.0, -half),
+                Point3::new(-half, 0.0, -half),
+            ],
+            normals: vec![Vector3::y(); 4],
+            tex_coords: vec![
+                Vector2::new(0.0, 0.0),
+                Vector2::new(1.0, 0.0),
+                Vector2::new(1.0, 1.0),
+                Vector2::new(0.0, 1.0),
+            ],
+            tangents: vec![],
+            indices: vec![0, 1, 2, 0, 2, 3],
+        }
+    }
+
+    /// UV sphere of `radius` around the origin, `segments` around the y axis and `rings`
+    /// from the top to the bottom. The seam repeats its vertices with `u` of `0` and `1`.
+    pub fn sphere(radius: f32, segments: u32, rings: u32) -> Self {
+        let (segments, rings) = (segments.max(3), rings.max(2));
+        let mut mesh = Self::default();
+        for ring in 0..=rings {
+            let theta = PI * ring as f32 / rings as f32;
+            for segment in 0..=segments {
+                let phi = 2.0 * PI * segment as f32 / segments as f32;
+                // `u` grows to the right seen from outside
+                let normal = Vector3::new(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin());
+                mesh.positions.push(Point3::from(normal * radius));
+                mesh.normals.push(normal);
+                mesh.tex_coords.push(Vector2::new(segment as f32 / segments as f32, 1.0 - ring as f32 / rings as f32));
+            }
+        }
+
+        let row = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let (top_left, bottom_left) = (ring * row + segment, (ring + 1) * row + segment);
+                let (top_right, bottom_right) = (top_left + 1, bottom_left + 1);
+                // the quads touching a pole are triangles
+                if ring + 1 != rings {
+                    mesh.indices.extend([bottom_left, bottom_right, top_right]);
+                }
+                if ring != 0 {
+                    mesh.indices.extend([bottom_left, top_right, top_left]);
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Bitangent of vertex `i`, `w * cross(normal, tangent)` like MikkTSpace.
+    pub fn bitangent(&self, i: usize) -> Vector3<f32> {
+        let t = self.tangents[i];
+        self.normals[i].cross(&t.xyz()) * t.w
+    }
+
+    /// Fill [`tangents`](Self::tangents) from the texture coordinates: each triangle's
+    /// tangent and bitangent are projected onto the plane of the vertex normal and averaged
+    /// weighted by the angle of the corner. `w` is `-1` where the texture is mirrored.
+    ///
+    /// Vertices are taken as they are, nothing is welded by position, normal and uv and no
+    /// vertex is split where the handedness flips, so this matches MikkTSpace only on meshes
+    /// already split at seams and handedness flips, like the primitives here.
+    pub fn generate_tangents(&mut self) -> Result<()> {
+        let count = self.vertex_count();
+        if self.normals.len() != count || self.tex_coords.len() != count {
+            bail!("tangents need a normal and texture coordinates for each of the {count} vertices");
+        }
+        let project = |v: Vector3<f32>, n: &Vector3<f32>| (v - n * n.dot(&v)).try_normalize(1e-12).unwrap_or_else(Vector3::zeros);
+
+        let mut sums = vec![(Vector3::zeros(), Vector3::zeros()); count];
+        for t in self.indices.chunks_exact(3) {
+            let [a, b, c] = [t[0], t[1], t[2]].map(|i| i as usize);
+            let (e1, e2) = (self.positions[b] - self.positions[a], self.positions[c] - self.positions[a]);
+            let (d1, d2) = (self.tex_coords[b] - self.tex_coords[a], self.tex_coords[c] - self.tex_coords[a]);
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() < 1e-12 {
+                // no texture on the triangle to follow
+                continue;
+            }
+            let tangent = (e1 * d2.y - e2 * d1.y) / det;
+            let bitangent = (e2 * d1.x - e1 * d2.x) / det;
+
+            for (corner, previous, next) in [(a, c, b), (b, a, c), (c, b, a)] {
+                let p = self.positions[corner];
+                let (to_previous, to_next) = (self.positions[previous] - p, self.positions[next] - p);
+                let angle = to_previous.angle(&to_next);
+                if !angle.is_finite() {
+                    continue;
+                }
+                let normal = self.normals[corner].normalize();
+                sums[corner].0 += project(tangent, &normal) * angle;
+                sums[corner].1 += project(bitangent, &normal) * angle;
+            }
+        }
+
+        self.tangents = sums
+            .iter()
+            .zip(&self.normals)
+            .map(|((tangent, bitangent), normal)| {
+                let normal = normal.normalize();
+                // vertices without a textured triangle get any frame around the normal
+                let tangent = project(*tangent, &normal)
+                    .try_normalize(1e-12)
+                    .unwrap_or_else(|| any_perpendicular(&normal));
+                let w = if normal.cross(&tangent).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+                tangent.push(w)
+            })
+            .collect();
+        Ok(())
+    }
+}
+
+fn any_perpendicular(normal: &Vector3<f32>) -> Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+    (axis - normal * normal.dot(&axis)).normalize()
+}
+
+/// [`MeshData`] in buffers, one per attribute at the `*_LOCATION`s. Attributes without
+/// data are left disabled, so shaders read `(0, 0, 0, 1)` for them.
+pub struct Mesh {
+    vao: GLuint,
+    buffers: Vec<GLuint>,
+    index_count: usize,
+}
+
+impl Mesh {
+    pub fn new(data: &MeshData) -> Self {
+        let mut mesh = Self { vao: 0, buffers: vec![], index_count: data.indices.len() };
+        unsafe {
+            let mut previous_vao = 0;
+            gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut previous_vao);
+
+            gl::GenVertexArrays(1, &mut mesh.vao);
+            assert_ne!(mesh.vao, 0);
+            gl::BindVertexArray(mesh.vao);
+
+            mesh.attribute(POSITION_LOCATION, 3, &data.positions);
+            mesh.attribute(NORMAL_LOCATION, 3, &data.normals);
+            mesh.attribute(TEX_COORDS_LOCATION, 2, &data.tex_coords);
+            mesh.attribute(TANGENT_LOCATION, 4, &data.tangents);
+
+            // the element buffer binding is part of the vertex array
+            mesh.buffer(gl::ELEMENT_ARRAY_BUFFER, &data.indices);
+            gl::BindVertexArray(previous_vao as _);
+        }
+        mesh
+    }
+
+    unsafe fn buffer<T>(&mut self, target: GLenum, data: &[T]) {
+        let mut id = 0;
+        gl::GenBuffers(1, &mut id);
+        assert_ne!(id, 0);
+        gl::BindBuffer(target, id);
+        gl::BufferData(target, size_of_val(data) as _, data.as_ptr() as *const _, gl::STATIC_DRAW);
+        self.buffers.push(id);
+    }
+
+    /// Tightly packed `f32` vectors of `components`, the vertex array must be bound.
+    unsafe fn attribute<T>(&mut self, location: GLuint, components: GLint, data: &[T]) {
+        if data.is_empty() {
+            return;
+        }
+        self.buffer(gl::ARRAY_BUFFER, data);
+        gl::VertexAttribPointer(location, components, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
+        gl::EnableVertexAttribArray(location);
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    }
+
+    pub fn vao(&self) -> GLuint {
+        self.vao
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.index_count
+    }
+
+    /// Draw every triangle with the active program.
+    pub fn draw(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            crate::gl_check!(gl::DrawElements(gl::TRIANGLES, self.index_count as _, gl::UNSIGNED_INT, std::ptr::null()));
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(self.buffers.len() as _, self.buffers.as_ptr());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives_wind_towards_their_normals() {
+        for mesh in [MeshData::cube(), MeshData::plane(2.0)] {
+            assert_eq!(mesh.normals.len(), mesh.vertex_count());
+            assert_eq!(mesh.tex_coords.len(), mesh.vertex_count());
+
+            for (t, [a, b, c]) in mesh.indices.chunks_exact(3).zip(mesh.triangles()) {
+                let face = (b - a).cross(&(c - a)).normalize();
+                assert!((face - mesh.normals[t[0] as usize]).norm() < 1e-6);
+            }
+        }
+        assert_eq!(MeshData::cube().triangle_count(), 12);
+    }
+
+    #[test]
+    fn sphere_is_closed_and_faces_out() {
+        let sphere = MeshData::sphere(2.0, 16, 8);
+        assert_eq!(sphere.vertex_count(), 17 * 9);
+        // two triangles per quad, one at each pole
+        assert_eq!(sphere.triangle_count(), 16 * (2 * 8 - 2));
+        assert!(sphere.positions.iter().all(|p| (p.coords.norm() - 2.0).abs() < 1e-5));
+        assert!(sphere.normals.iter().zip(&sphere.positions).all(|(n, p)| (n * 2.0 - p.coords).norm() < 1e-5));
+
+        for (t, [a, b, c]) in sphere.indices.chunks_exact(3).zip(sphere.triangles()) {
+            let face = (b - a).cross(&(c - a));
+            assert!(face.norm() > 1e-6, "degenerate triangle {t:?}");
+            assert!(face.dot(&sphere.normals[t[0] as usize]) > 0.0);
+        }
+    }
+
+    /// Unit tangents perpendicular to the normals.
+    fn assert_frames(mesh: &MeshData) {
+        assert_eq!(mesh.tangents.len(), mesh.vertex_count());
+        for (i, (t, n)) in mesh.tangents.iter().zip(&mesh.normals).enumerate() {
+            assert!((t.xyz().norm() - 1.0).abs() < 1e-5 && t.xyz().dot(n).abs() < 1e-5, "vertex {i}: {t:?}");
+            assert!(t.w == 1.0 || t.w == -1.0);
+        }
+    }
+
+    #[test]
+    fn cube_and_plane_tangents_follow_the_texture() {
+        let mut cube = MeshData::cube();
+        cube.generate_tangents().unwrap();
+        assert_frames(&cube);
+        // each face is textured along its right and up axes
+        for (i, corners) in cube.positions.chunks_exact(4).enumerate() {
+            let (a, b, d) = (corners[0], corners[1], corners[3]);
+            let (right, up) = ((b - a).normalize(), (d - a).normalize());
+            assert!((cube.tangents[4 * i].xyz() - right).norm() < 1e-5, "face {i}");
+            assert!((cube.bitangent(4 * i + 2) - up).norm() < 1e-5, "face {i}");
+        }
+
+        let mut plane = MeshData::plane(2.0);
+        plane.generate_tangents().unwrap();
+        assert_frames(&plane);
+        assert!(plane.tangents.iter().all(|t| *t == Vector4::new(1.0, 0.0, 0.0, 1.0)));
+        assert!((0..4).all(|i| (plane.bitangent(i) + Vector3::z()).norm() < 1e-6));
+
+        // mirrored along u, the tangent turns around and the bitangent stays
+        for uv in &mut plane.tex_coords {
+            uv.x = 1.0 - uv.x;
+        }
+        plane.generate_tangents().unwrap();
+        assert!(plane.tangents.iter().all(|t| *t == Vector4::new(-1.0, 0.0, 0.0, -1.0)));
+        assert!((0..4).all(|i| (plane.bitangent(i) + Vector3::z()).norm() < 1e-6));
+    }
+
+    #[test]
+    fn sphere_tangents_follow_the_parameterization() {
+        let (segments, rings) = (32, 16);
+        let mut sphere = MeshData::sphere(1.0, segments, rings);
+        sphere.generate_tangents().unwrap();
+        assert_frames(&sphere);
+
+        for ring in 1..rings {
+            for segment in 0..=segments {
+                let i = (ring * (segments + 1) + segment) as usize;
+                let theta = PI * ring as f32 / rings as f32;
+                let phi = 2.0 * PI * segment as f32 / segments as f32;
+                // derivatives of the position along u and v
+                let du = Vector3::new(-phi.sin(), 0.0, -phi.cos());
+                let dv = -Vector3::new(theta.cos() * phi.cos(), -theta.sin(), -theta.cos() * phi.sin());
+                assert!(sphere.tangents[i].xyz().dot(&du) > 0.99, "ring {ring} segment {segment}: {:?}", sphere.tangents[i]);
+                assert!(sphere.bitangent(i).dot(&dv) > 0.99, "ring {ring} segment {segment}");
+                assert_eq!(sphere.tangents[i].w, 1.0);
+            }
+        }
+    }
+
+    /// Tangents of `MeshData::sphere(1.0, 6, 4)` from the MikkTSpace reference
+    /// implementation, zero for the pole vertices no triangle uses.
+    const MIKKTSPACE_SPHERE: [[f32; 4]; 35] = [
+        [0.0, 0.0, 0.0, 0.0],
+        [-0.5, 0.0, -0.866, 1.0],
+        [-1.0, 0.0, 0.0, 1.0],
+        [-0.5, 0.0, 0.866, 1.0],
+        [0.5, 0.0, 0.866, 1.0],
+        [1.0, 0.0, 0.0, 1.0],
+        [0.5, 0.0, -0.866, 1.0],
+        [-0.2673, 0.2673, -0.9258, 1.0],
+        [-0.866, 0.0, -0.5, 1.0],
+        [-0.866, 0.0, 0.5, 1.0],
+        [0.0, 0.0, 1.0, 1.0],
+        [0.866, 0.0, 0.5, 1.0],
+        [0.866, 0.0, -0.5, 1.0],
+        [0.2673, -0.2673, -0.9258, 1.0],
+        [0.0, 0.0, -1.0, 1.0],
+        [-0.866, 0.0, -0.5, 1.0],
+        [-0.866, 0.0, 0.5, 1.0],
+        [0.0, 0.0, 1.0, 1.0],
+        [0.866, 0.0, 0.5, 1.0],
+        [0.866, 0.0, -0.5, 1.0],
+        [0.0, 0.0, -1.0, 1.0],
+        [-0.2673, -0.2673, -0.9258, 1.0],
+        [-0.866, 0.0, -0.5, 1.0],
+        [-0.866, 0.0, 0.5, 1.0],
+        [0.0, 0.0, 1.0, 1.0],
+        [0.866, 0.0, 0.5, 1.0],
+        [0.866, 0.0, -0.5, 1.0],
+        [0.2673, 0.2673, -0.9258, 1.0],
+        [-0.5, 0.0, -0.866, 1.0],
+        [-1.0, 0.0, 0.0, 1.0],
+        [-0.5, 0.0, 0.866, 1.0],
+        [0.5, 0.0, 0.866, 1.0],
+        [1.0, 0.0, 0.0, 1.0],
+        [0.5, 0.0, -0.866, 1.0],
+        [0.0, 0.0, 0.0, 0.0],
+    ];
+
+    #[test]
+    fn sphere_tangents_match_mikktspace() {
+        let mut sphere = MeshData::sphere(1.0, 6, 4);
+        sphere.generate_tangents().unwrap();
+        assert_eq!(sphere.vertex_count(), MIKKTSPACE_SPHERE.len());
+
+        let mut used = vec![false; sphere.vertex_count()];
+        sphere.indices.iter().for_each(|&i| used[i as usize] = true);
+        for (i, (tangent, reference)) in sphere.tangents.iter().zip(MIKKTSPACE_SPHERE).enumerate() {
+            if used[i] {
+                assert!((tangent - Vector4::from(reference)).norm() < 1e-3, "vertex {i}: {tangent:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn tangents_need_texture_coordinates() {
+        let mut mesh = MeshData::cube();
+        mesh.tex_coords.clear();
+        assert!(mesh.generate_tangents().is_err());
+
+        // a triangle without texture still gets a frame
+        let mut mesh = MeshData::plane(1.0);
+        mesh.tex_coords = vec![Vector2::zeros(); 4];
+        mesh.generate_tangents().unwrap();
+        assert_frames(&mesh);
+    }
+}
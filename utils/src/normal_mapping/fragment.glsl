@@ -0,0 +1,27 @@
+#version 330 core
+#include "lighting.glsl"
+#include "normal_mapping.glsl"
+#include "parallax.glsl"
+
+in vec3 worldPos;
+in vec3 normal;
+in vec2 texCoords;
+in vec4 tangent;
+out vec4 fragColor;
+
+uniform PhongMaterial material;
+uniform sampler2D normalMap;
+uniform float normalStrength;
+uniform Parallax parallax;
+uniform vec3 viewPos;
+
+// Phong lighting of the `Lights` block with every map moved by the parallax
+void main() {
+    mat3 tbn = tangentFrame(normal, tangent);
+    // the frame is orthonormal, its transpose goes back to tangent space
+    vec3 viewDir = transpose(tbn) * (viewPos - worldPos);
+    vec2 uv = parallaxTexCoords(parallax, texCoords, viewDir);
+
+    vec3 n = mappedNormal(normalMap, uv, tbn, normalStrength);
+    fragColor = vec4(allLights(materialSurface(material, uv, n, worldPos, viewPos)), 1.0);
+}
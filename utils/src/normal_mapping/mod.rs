@@ -0,0 +1,296 @@
+//! Normal and parallax mapping in tangent space, for meshes with the tangents of
+//! [`MeshData::generate_tangents`](crate::mesh::MeshData::generate_tangents).
+//!
+//! ```ignore
+//! let mut data = MeshData::plane(2.0);
+//! data.generate_tangents()?;
+//! let mesh = Mesh::new(&data);
+//!
+//! let shader = normal_mapping::shader();
+//! shader.activate()?;
+//! material.apply(&shader, "material", 0)?;
+//! apply_normal_map(&shader, normal_map.id(), 1.0, 2)?;
+//! Parallax { mode: ParallaxMode::Occlusion, depth_map: depth.id(), ..Parallax::default() }.apply(&shader, "parallax", 3)?;
+//! ```
+//!
+//! The includes work in any fragment shader, [`FRAGMENT`] lights a `PhongMaterial` with
+//! them. The math is mirrored here so it can be tested without a context.
+
+use anyhow::{bail, Result};
+use gl::types::*;
+use nalgebra::{Matrix3, Vector2, Vector3, Vector4};
+use once_cell::sync::Lazy;
+
+use crate::lighting;
+use crate::shader::{resolve_includes, Shader};
+
+/// `tangentFrame`, `unpackNormal` and `mappedNormal`.
+pub const GLSL: &str = include_str!("normal_mapping.glsl");
+/// The `Parallax` struct and `parallaxTexCoords`.
+pub const PARALLAX_GLSL: &str = include_str!("parallax.glsl");
+
+/// Includes for [`resolve_includes`].
+pub const INCLUDES: &[(&str, &str)] = &[("normal_mapping.glsl", GLSL), ("parallax.glsl", PARALLAX_GLSL)];
+
+/// [`GEOMETRY_VERTEX`](crate::deferred::GEOMETRY_VERTEX) with the tangents at
+/// [`TANGENT_LOCATION`](crate::mesh::TANGENT_LOCATION) passed on as `tangent`.
+pub const VERTEX: &str = include_str!("vertex.glsl");
+
+/// Lights a `PhongMaterial material` with the `Lights` block through a `normalMap` and a
+/// `Parallax parallax`, includes `lighting.glsl`.
+pub const FRAGMENT: &str = include_str!("fragment.glsl");
+
+/// Most steps of the parallax ray march, `MAX_PARALLAX_LAYERS` of [`PARALLAX_GLSL`].
+pub const MAX_PARALLAX_LAYERS: u32 = 64;
+
+/// Columns tangent, bitangent and normal, the `tangentFrame` of [`GLSL`].
+pub fn tangent_frame(normal: &Vector3<f32>, tangent: &Vector4<f32>) -> Matrix3<f32> {
+    let n = normal.normalize();
+    let t = (tangent.xyz() - n * n.dot(&tangent.xyz())).normalize();
+    let b = n.cross(&t) * tangent.w;
+    Matrix3::from_columns(&[t, b, n])
+}
+
+/// A normal map texel stored as `n * 0.5 + 0.5`, with the bumps scaled by `strength`.
+pub fn unpack_normal(texel: Vector3<f32>, strength: f32) -> Vector3<f32> {
+    let n = texel * 2.0 - Vector3::repeat(1.0);
+    Vector3::new(n.x * strength, n.y * strength, n.z).normalize()
+}
+
+/// `parallaxBasic` of [`PARALLAX_GLSL`], `depth` samples the depth map.
+pub fn parallax_basic(depth: impl Fn(Vector2<f32>) -> f32, uv: Vector2<f32>, view_dir: &Vector3<f32>, scale: f32) -> Vector2<f32> {
+    uv - view_dir.xy() / view_dir.z * depth(uv) * scale
+}
+
+/// Steps of the ray march for a view direction, between `min_layers` looking straight at
+/// the surface and `max_layers` at grazing angles.
+pub fn parallax_layers(view_dir: &Vector3<f32>, min_layers: f32, max_layers: f32) -> f32 {
+    let t = view_dir.z.abs();
+    (max_layers + (min_layers - max_layers) * t).clamp(1.0, MAX_PARALLAX_LAYERS as f32)
+}
+
+/// `parallaxMarch` of [`PARALLAX_GLSL`], steep parallax mapping, or parallax occlusion
+/// mapping with `occlusion`.
+pub fn parallax_march(depth: impl Fn(Vector2<f32>) -> f32, uv: Vector2<f32>, view_dir: &Vector3<f32>, scale: f32, layers: f32, occlusion: bool) -> Vector2<f32> {
+    let layer_step = 1.0 / layers;
+    let uv_step = view_dir.xy() / view_dir.z * scale * layer_step;
+
+    let mut current = uv;
+    let mut current_depth = depth(current);
+    let mut layer_depth = 0.0;
+    for _ in 0..MAX_PARALLAX_LAYERS {
+        if layer_depth >= current_depth {
+            break;
+        }
+        current -= uv_step;
+        current_depth = depth(current);
+        layer_depth += layer_step;
+    }
+    if !occlusion {
+        return current;
+    }
+
+    let previous = current + uv_step;
+    let after = current_depth - layer_depth;
+    let before = depth(previous) - (layer_depth - layer_step);
+    let weight = after / (after - before);
+    current.lerp(&previous, weight)
+}
+
+/// The `PARALLAX_*` modes of [`PARALLAX_GLSL`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParallaxMode {
+    #[default]
+    None,
+    /// One offset by the depth under the fragment.
+    Basic,
+    /// The first depth layer below the depth map, stair steps at grazing angles.
+    Steep,
+    /// Steep parallax mapping interpolated between the last two layers.
+    Occlusion,
+}
+
+impl ParallaxMode {
+    pub const ALL: [Self; 4] = [Self::None, Self::Basic, Self::Steep, Self::Occlusion];
+
+    /// Value of the `mode` field.
+    pub fn glsl_value(self) -> i32 {
+        self as i32
+    }
+
+    pub fn define(self) -> &'static str {
+        match self {
+            Self::None => "PARALLAX_NONE",
+            Self::Basic => "PARALLAX_BASIC",
+            Self::Steep => "PARALLAX_STEEP",
+            Self::Occlusion => "PARALLAX_OCCLUSION",
+        }
+    }
+
+    /// The mode after this one, to cycle through them with a key.
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+/// The `Parallax` struct of [`PARALLAX_GLSL`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parallax {
+    pub mode: ParallaxMode,
+    /// 2D texture id, depth in red.
+    pub depth_map: GLuint,
+    /// Depth of `1` in texture coordinates.
+    pub scale: f32,
+    pub min_layers: f32,
+    /// At most [`MAX_PARALLAX_LAYERS`].
+    pub max_layers: f32,
+}
+
+impl Default for Parallax {
+    fn default() -> Self {
+        Self { mode: ParallaxMode::None, depth_map: 0, scale: 0.1, min_layers: 8.0, max_layers: 32.0 }
+    }
+}
+
+impl Parallax {
+    fn validate(&self) -> Result<()> {
+        if !(1.0..=MAX_PARALLAX_LAYERS as f32).contains(&self.min_layers) || !(self.min_layers..=MAX_PARALLAX_LAYERS as f32).contains(&self.max_layers) {
+            bail!("{} to {} layers, between 1 and {MAX_PARALLAX_LAYERS} are supported", self.min_layers, self.max_layers);
+        }
+        Ok(())
+    }
+
+    /// Texture coordinates to sample the other maps at, the `parallaxTexCoords` of
+    /// [`PARALLAX_GLSL`] with `depth` sampling the depth map.
+    pub fn tex_coords(&self, depth: impl Fn(Vector2<f32>) -> f32, uv: Vector2<f32>, view_dir: &Vector3<f32>) -> Vector2<f32> {
+        let view_dir = view_dir.normalize();
+        let layers = || parallax_layers(&view_dir, self.min_layers, self.max_layers);
+        match self.mode {
+            ParallaxMode::None => uv,
+            ParallaxMode::Basic => parallax_basic(depth, uv, &view_dir, self.scale),
+            ParallaxMode::Steep => parallax_march(depth, uv, &view_dir, self.scale, layers(), false),
+            ParallaxMode::Occlusion => parallax_march(depth, uv, &view_dir, self.scale, layers(), true),
+        }
+    }
+
+    /// Bind the depth map to the texture unit `unit` and set the fields of the `uniform`
+    /// struct, `shader` must be active.
+    pub fn apply(&self, shader: &Shader, uniform: &str, unit: u32) -> Result<()> {
+        self.validate()?;
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.depth_map);
+        }
+        shader.set_i32(&format!("{uniform}.depthMap"), (unit as i32,))?;
+        shader.set_i32(&format!("{uniform}.mode"), (self.mode.glsl_value(),))?;
+        shader.set_f32(&format!("{uniform}.scale"), (self.scale,))?;
+        shader.set_f32(&format!("{uniform}.minLayers"), (self.min_layers,))?;
+        shader.set_f32(&format!("{uniform}.maxLayers"), (self.max_layers,))
+    }
+}
+
+/// Bind the 2D texture `normal_map` to the texture unit `unit` for [`FRAGMENT`], `shader`
+/// must be active.
+pub fn apply_normal_map(shader: &Shader, normal_map: GLuint, strength: f32, unit: u32) -> Result<()> {
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0 + unit);
+        gl::BindTexture(gl::TEXTURE_2D, normal_map);
+    }
+    shader.set_i32("normalMap", (unit as i32,))?;
+    shader.set_f32("normalStrength", (strength,))
+}
+
+static FRAGMENT_RESOLVED: Lazy<String> = Lazy::new(|| resolve_includes(FRAGMENT, &[lighting::INCLUDES, INCLUDES].concat()).unwrap());
+
+/// [`VERTEX`] with [`FRAGMENT`], bind its `Lights` block before drawing.
+pub fn shader() -> Shader<'static> {
+    Shader::new(VERTEX, FRAGMENT_RESOLVED.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector2<f32>, b: Vector2<f32>) -> bool {
+        (a - b).norm() < 1e-5
+    }
+
+    #[test]
+    fn frame_maps_tangent_space() {
+        let normal = Vector3::new(0.0, 2.0, 0.0);
+        // a little off the surface, like after interpolation
+        let frame = tangent_frame(&normal, &Vector4::new(1.0, 0.1, 0.0, 1.0));
+        assert!((frame.transpose() * frame - Matrix3::identity()).norm() < 1e-5);
+        assert!((frame * Vector3::z() - Vector3::y()).norm() < 1e-6);
+        assert!((frame * Vector3::x() - Vector3::x()).norm() < 1e-6);
+        assert!((frame * Vector3::y() + Vector3::z()).norm() < 1e-6);
+
+        let mirrored = tangent_frame(&normal, &Vector4::new(1.0, 0.0, 0.0, -1.0));
+        assert!((mirrored * Vector3::y() - Vector3::z()).norm() < 1e-6);
+    }
+
+    #[test]
+    fn flat_texels_keep_the_normal() {
+        let flat = Vector3::new(0.5, 0.5, 1.0);
+        assert!((unpack_normal(flat, 1.0) - Vector3::z()).norm() < 1e-6);
+
+        let bump = Vector3::new(0.75, 0.5, 0.75);
+        assert!(unpack_normal(bump, 2.0).x > unpack_normal(bump, 1.0).x);
+        assert!((unpack_normal(bump, 0.0) - Vector3::z()).norm() < 1e-6);
+    }
+
+    #[test]
+    fn marching_a_flat_depth_lands_on_it() {
+        let view = Vector3::new(0.3, -0.2, 0.6).normalize();
+        let uv = Vector2::new(0.5, 0.5);
+        for depth in [0.0, 0.3, 0.55, 1.0] {
+            let exact = uv - view.xy() / view.z * depth * 0.1;
+            assert!(close(parallax_basic(|_| depth, uv, &view, 0.1), exact));
+            // the interpolation is exact on a plane, the steps overshoot by less than a layer
+            assert!(close(parallax_march(|_| depth, uv, &view, 0.1, 10.0, true), exact), "depth {depth}");
+            let steep = parallax_march(|_| depth, uv, &view, 0.1, 10.0, false);
+            let layer = (view.xy() / view.z * 0.1 / 10.0).norm();
+            assert!((steep - exact).norm() < layer + 1e-5);
+            assert!((steep - uv).norm() >= (exact - uv).norm() - 1e-5);
+        }
+    }
+
+    #[test]
+    fn occlusion_stops_at_a_wall() {
+        // a pit 0.5 deep left of u = 0.455, seen from the right so the ray runs right in
+        // steps of 0.0125
+        let depth = |uv: Vector2<f32>| if uv.x < 0.455 { 0.5 } else { 0.0 };
+        let view = Vector3::new(-1.0, 0.0, 1.0).normalize();
+        let parallax = Parallax { mode: ParallaxMode::Occlusion, scale: 0.2, min_layers: 16.0, max_layers: 16.0, ..Parallax::default() };
+        // the ray stops at the wall of the pit, well before its floor at u = 0.5
+        let steep = Parallax { mode: ParallaxMode::Steep, ..parallax }.tex_coords(depth, Vector2::new(0.4, 0.5), &view);
+        assert!(close(steep, Vector2::new(0.4625, 0.5)), "{steep:?}");
+        let hit = parallax.tex_coords(depth, Vector2::new(0.4, 0.5), &view);
+        assert!(hit.x > 0.45 && hit.x < 0.4625, "{hit:?}");
+
+        // outside the pit nothing moves
+        assert_eq!(parallax.tex_coords(depth, Vector2::new(0.6, 0.5), &view), Vector2::new(0.6, 0.5));
+        assert_eq!(Parallax::default().tex_coords(depth, Vector2::new(0.4, 0.5), &view), Vector2::new(0.4, 0.5));
+    }
+
+    #[test]
+    fn layers_grow_at_grazing_angles() {
+        assert_eq!(parallax_layers(&Vector3::z(), 8.0, 32.0), 8.0);
+        assert_eq!(parallax_layers(&Vector3::x(), 8.0, 32.0), 32.0);
+        assert_eq!(parallax_layers(&Vector3::x(), 8.0, 1000.0), MAX_PARALLAX_LAYERS as f32);
+
+        assert!(Parallax::default().validate().is_ok());
+        assert!(Parallax { max_layers: 65.0, ..Parallax::default() }.validate().is_err());
+        assert!(Parallax { min_layers: 40.0, ..Parallax::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn modes_match_the_glsl() {
+        for mode in ParallaxMode::ALL {
+            assert!(PARALLAX_GLSL.contains(&format!("#define {} {}", mode.define(), mode.glsl_value())));
+        }
+        assert_eq!(ParallaxMode::Occlusion.next(), ParallaxMode::None);
+        assert!(PARALLAX_GLSL.contains(&format!("#define MAX_PARALLAX_LAYERS {MAX_PARALLAX_LAYERS}")));
+        assert!(!FRAGMENT_RESOLVED.contains("#include"));
+    }
+}
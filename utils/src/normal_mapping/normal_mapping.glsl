@@ -0,0 +1,23 @@
+// Tangent space normal mapping with the tangents of `MeshData::generate_tangents`.
+// The math is mirrored in `utils::normal_mapping`, change both together.
+
+// Columns tangent, bitangent and normal, from tangent space to the space of the inputs.
+// The bitangent is rebuilt per fragment like MikkTSpace expects, `tangent.w` holds its sign.
+mat3 tangentFrame(vec3 normal, vec4 tangent) {
+    vec3 n = normalize(normal);
+    // interpolation bends the tangent away from the normal
+    vec3 t = normalize(tangent.xyz - n * dot(n, tangent.xyz));
+    vec3 b = tangent.w * cross(n, t);
+    return mat3(t, b, n);
+}
+
+// A normal map texel stored as `n * 0.5 + 0.5`, `strength` scales the bumps.
+vec3 unpackNormal(vec3 texel, float strength) {
+    vec3 n = texel * 2.0 - 1.0;
+    n.xy *= strength;
+    return normalize(n);
+}
+
+vec3 mappedNormal(sampler2D normalMap, vec2 texCoords, mat3 tbn, float strength) {
+    return normalize(tbn * unpackNormal(texture(normalMap, texCoords).rgb, strength));
+}
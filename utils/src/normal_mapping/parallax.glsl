@@ -0,0 +1,72 @@
+// Parallax mapping of a depth map, `0` at the surface and `1` deepest, mirrored in
+// `utils::normal_mapping`. `viewDir` points from the fragment to the eye in tangent space.
+
+#define PARALLAX_NONE 0
+#define PARALLAX_BASIC 1
+#define PARALLAX_STEEP 2
+#define PARALLAX_OCCLUSION 3
+
+#define MAX_PARALLAX_LAYERS 64
+
+struct Parallax {
+    int mode;
+    sampler2D depthMap;
+    // depth of `1` in texture coordinates
+    float scale;
+    // layers looking straight at the surface and at grazing angles
+    float minLayers;
+    float maxLayers;
+};
+
+// One offset by the depth under the fragment, fine for shallow maps seen from the front.
+vec2 parallaxBasic(sampler2D depthMap, vec2 uv, vec3 viewDir, float scale) {
+    float depth = texture(depthMap, uv).r;
+    return uv - viewDir.xy / viewDir.z * depth * scale;
+}
+
+// more layers at grazing angles, where the ray crosses more of the texture
+float parallaxLayers(vec3 viewDir, float minLayers, float maxLayers) {
+    return clamp(mix(maxLayers, minLayers, abs(viewDir.z)), 1.0, float(MAX_PARALLAX_LAYERS));
+}
+
+// Steps the view ray down through `layers` slices until it is below the depth map.
+// With `occlusion` the hit is interpolated between the last two slices.
+vec2 parallaxMarch(sampler2D depthMap, vec2 uv, vec3 viewDir, float scale, float layers, bool occlusion) {
+    float layerStep = 1.0 / layers;
+    vec2 uvStep = viewDir.xy / viewDir.z * scale * layerStep;
+    // the loop exits early, so the gradients are taken outside of it
+    vec2 dx = dFdx(uv);
+    vec2 dy = dFdy(uv);
+
+    vec2 current = uv;
+    float depth = textureGrad(depthMap, current, dx, dy).r;
+    float layerDepth = 0.0;
+    for (int i = 0; i < MAX_PARALLAX_LAYERS && layerDepth < depth; i++) {
+        current -= uvStep;
+        depth = textureGrad(depthMap, current, dx, dy).r;
+        layerDepth += layerStep;
+    }
+    if (!occlusion) {
+        return current;
+    }
+
+    vec2 previous = current + uvStep;
+    float after = depth - layerDepth;
+    float before = textureGrad(depthMap, previous, dx, dy).r - (layerDepth - layerStep);
+    float weight = after / (after - before);
+    return mix(current, previous, weight);
+}
+
+// Texture coordinates to sample the other maps at, those outside `[0, 1]` can be
+// discarded on textures that do not repeat.
+vec2 parallaxTexCoords(Parallax p, vec2 uv, vec3 viewDir) {
+    viewDir = normalize(viewDir);
+    if (p.mode == PARALLAX_BASIC) {
+        return parallaxBasic(p.depthMap, uv, viewDir, p.scale);
+    }
+    if (p.mode == PARALLAX_STEEP || p.mode == PARALLAX_OCCLUSION) {
+        float layers = parallaxLayers(viewDir, p.minLayers, p.maxLayers);
+        return parallaxMarch(p.depthMap, uv, viewDir, p.scale, layers, p.mode == PARALLAX_OCCLUSION);
+    }
+    return uv;
+}
@@ -0,0 +1,25 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec3 aNormal;
+layout (location = 2) in vec2 aTexCoords;
+layout (location = 3) in vec4 aTangent;
+
+uniform mat4 viewProjection;
+uniform mat4 model;
+
+out vec3 worldPos;
+out vec3 normal;
+out vec2 texCoords;
+out vec4 tangent;
+
+void main() {
+    vec4 world = model * vec4(aPos, 1.0);
+    worldPos = world.xyz;
+    normal = mat3(transpose(inverse(model))) * aNormal;
+    // tangents are surface directions and move with the model, a mirroring model flips
+    // the handedness
+    float handedness = determinant(mat3(model)) < 0.0 ? -aTangent.w : aTangent.w;
+    tangent = vec4(mat3(model) * aTangent.xyz, handedness);
+    texCoords = aTexCoords;
+    gl_Position = viewProjection * world;
+}
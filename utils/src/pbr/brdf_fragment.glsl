@@ -0,0 +1,37 @@
+#version 330 core
+#include "ibl.glsl"
+
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform uint sampleCount;
+
+// scale and bias to F0 of the specular split sum, for `NdotV` along x and the roughness
+// along y. Mirrored by `utils::pbr::integrate_brdf`.
+vec2 integrateBRDF(float NdotV, float roughness) {
+    vec3 view = vec3(sqrt(1.0 - NdotV * NdotV), 0.0, NdotV);
+    vec3 normal = vec3(0.0, 0.0, 1.0);
+    float k = roughness * roughness / 2.0;
+
+    float scale = 0.0;
+    float bias = 0.0;
+    for (uint i = 0u; i < sampleCount; i++) {
+        vec3 halfway = importanceSampleGGX(hammersley(i, sampleCount), normal, roughness);
+        vec3 light = normalize(2.0 * dot(view, halfway) * halfway - view);
+        float NdotL = max(light.z, 0.0);
+        float NdotH = max(halfway.z, 0.0);
+        float VdotH = max(dot(view, halfway), 0.0);
+        if (NdotL > 0.0) {
+            float G = geometrySmith(NdotV, NdotL, k);
+            float visibility = G * VdotH / (NdotH * NdotV);
+            float fresnel = pow(1.0 - VdotH, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+    return vec2(scale, bias) / float(sampleCount);
+}
+
+void main() {
+    fragColor = vec4(integrateBRDF(texCoords.x, texCoords.y), 0.0, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+
+// one face of the cube map, looking out from the center
+uniform mat4 viewProjection;
+
+out vec3 localPos;
+
+void main() {
+    localPos = aPos;
+    gl_Position = viewProjection * vec4(aPos, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 330 core
+in vec3 localPos;
+out vec4 fragColor;
+
+uniform sampler2D equirect;
+
+void main() {
+    vec3 v = normalize(localPos);
+    // longitude and latitude to [0, 1]
+    vec2 uv = vec2(atan(v.z, v.x) * 0.15915494, asin(v.y) * 0.31830989) + 0.5;
+    fragColor = vec4(texture(equirect, uv).rgb, 1.0);
+}
@@ -0,0 +1,29 @@
+#version 330 core
+in vec3 localPos;
+out vec4 fragColor;
+
+uniform samplerCube environment;
+// step of the azimuth and zenith angles in radians
+uniform float sampleDelta;
+
+const float PI = 3.14159265359;
+
+// the cosine weighted average of the hemisphere around each direction
+void main() {
+    vec3 normal = normalize(localPos);
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    vec3 irradiance = vec3(0.0);
+    float count = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += sampleDelta) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += sampleDelta) {
+            vec3 tangentSample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 direction = tangentSample.x * right + tangentSample.y * up + tangentSample.z * normal;
+            irradiance += texture(environment, direction).rgb * cos(theta) * sin(theta);
+            count++;
+        }
+    }
+    fragColor = vec4(PI * irradiance / count, 1.0);
+}
@@ -0,0 +1,733 @@
+//! Physically based shading with the Cook-Torrance metallic-roughness BRDF, lit by the
+//! [`Lights`](crate::lighting::Lights) block and by an environment through image based
+//! lighting.
+//!
+//! ```ignore
+//! let ibl = Ibl::load_or_bake("assets/ibl/studio", "assets/studio.hdr", &IblConfig::default())?;
+//! let shader = pbr::shader();
+//! shader.bind_uniform_block(lighting::BLOCK_NAME, 0)?;
+//!
+//! shader.activate()?;
+//! ibl.apply(&shader, 0)?;
+//! material.apply(&shader, "material", 3)?;
+//! shader.set_vec3("viewPos", camera.position().into())?;
+//! ```
+//!
+//! [`Ibl::bake`] renders the irradiance, the prefiltered specular mips and the BRDF
+//! lookup table on the GPU, [`Ibl::save`] writes them out as OpenEXR files for
+//! [`Ibl::load`]. The BRDF math is mirrored here so the GPU output can be checked against
+//! [`brdf_lut`], which also writes the table offline with [`save_brdf_lut`].
+
+use std::f32::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use gl::types::*;
+use image::{ImageBuffer, Rgb};
+use nalgebra::{Point3, Vector2, Vector3};
+use once_cell::sync::Lazy;
+
+use crate::deferred::GEOMETRY_VERTEX;
+use crate::framebuffer::{Framebuffer, FramebufferConfig};
+use crate::lighting;
+use crate::mesh::{Mesh, MeshData};
+use crate::postprocess::FULLSCREEN_VERTEX;
+use crate::shader::{resolve_includes, Shader};
+use crate::shadow::point_light_faces;
+use crate::texture::{Texture, TextureFormat, TextureKind};
+
+/// The `PbrMaterial` struct, the BRDF terms and `cookTorrance`.
+pub const GLSL: &str = include_str!("pbr.glsl");
+/// `hammersley` and `importanceSampleGGX`, includes `pbr.glsl`.
+pub const IBL_GLSL: &str = include_str!("ibl.glsl");
+
+/// Includes for [`resolve_includes`], together with [`lighting::INCLUDES`].
+pub const INCLUDES: &[(&str, &str)] = &[("pbr.glsl", GLSL), ("ibl.glsl", IBL_GLSL)];
+
+/// Lights a `PbrMaterial material` with the `Lights` block and [`Ibl::apply`] or
+/// [`apply_ambient`], drawn with [`GEOMETRY_VERTEX`]. Outputs linear HDR colors.
+pub const FRAGMENT: &str = include_str!("pbr_fragment.glsl");
+
+/// Draws the unit cube around `localPos` with a `viewProjection` per cube face.
+pub const CUBE_VERTEX: &str = include_str!("cube_vertex.glsl");
+pub const EQUIRECT_FRAGMENT: &str = include_str!("equirect_fragment.glsl");
+pub const IRRADIANCE_FRAGMENT: &str = include_str!("irradiance_fragment.glsl");
+pub const PREFILTER_FRAGMENT: &str = include_str!("prefilter_fragment.glsl");
+pub const BRDF_FRAGMENT: &str = include_str!("brdf_fragment.glsl");
+
+/// File names of the cube faces, in the order of `GL_TEXTURE_CUBE_MAP_POSITIVE_X + i`.
+pub const FACE_NAMES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+/// The `D` term, how many microfacets face along the halfway vector.
+pub fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+/// `k` of [`geometry_schlick_ggx`] for direct lights.
+pub fn direct_k(roughness: f32) -> f32 {
+    (roughness + 1.0) * (roughness + 1.0) / 8.0
+}
+
+/// `k` of [`geometry_schlick_ggx`] for image based lighting.
+pub fn ibl_k(roughness: f32) -> f32 {
+    roughness * roughness / 2.0
+}
+
+pub fn geometry_schlick_ggx(n_dot_v: f32, k: f32) -> f32 {
+    n_dot_v / (n_dot_v * (1.0 - k) + k)
+}
+
+/// The `G` term, microfacets shadowing each other towards the view and the light.
+pub fn geometry_smith(n_dot_v: f32, n_dot_l: f32, k: f32) -> f32 {
+    geometry_schlick_ggx(n_dot_v, k) * geometry_schlick_ggx(n_dot_l, k)
+}
+
+/// The `F` term, reflectance rising from `f0` to one at grazing angles.
+pub fn fresnel_schlick(cos_theta: f32, f0: Vector3<f32>) -> Vector3<f32> {
+    let factor = (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+    f0 + (Vector3::repeat(1.0) - f0) * factor
+}
+
+/// Van der Corput sequence in base 2, the bits of `i` mirrored behind the point.
+pub fn radical_inverse(i: u32) -> f32 {
+    (i.reverse_bits() as f64 * 2.3283064365386963e-10) as f32
+}
+
+/// Point `i` of `count` evenly spread over the unit square.
+pub fn hammersley(i: u32, count: u32) -> Vector2<f32> {
+    Vector2::new(i as f32 / count as f32, radical_inverse(i))
+}
+
+/// A halfway vector around `normal` distributed like the GGX lobe of `roughness`, from a
+/// point `xi` of the unit square.
+pub fn importance_sample_ggx(xi: Vector2<f32>, normal: &Vector3<f32>, roughness: f32) -> Vector3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = ((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let h = Vector3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+
+    let up = if normal.z.abs() < 0.999 { Vector3::z() } else { Vector3::x() };
+    let tangent = up.cross(normal).normalize();
+    let bitangent = normal.cross(&tangent);
+    (tangent * h.x + bitangent * h.y + normal * h.z).normalize()
+}
+
+/// Scale and bias to `F0` of the split sum specular, the `integrateBRDF` of
+/// [`BRDF_FRAGMENT`].
+pub fn integrate_brdf(n_dot_v: f32, roughness: f32, samples: u32) -> Vector2<f32> {
+    let view = Vector3::new((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+    let normal = Vector3::z();
+    let k = ibl_k(roughness);
+
+    let mut sum = Vector2::zeros();
+    for i in 0..samples {
+        let halfway = importance_sample_ggx(hammersley(i, samples), &normal, roughness);
+        let light = (2.0 * view.dot(&halfway) * halfway - view).normalize();
+        let n_dot_l = light.z.max(0.0);
+        let n_dot_h = halfway.z.max(0.0);
+        let v_dot_h = view.dot(&halfway).max(0.0);
+        if n_dot_l > 0.0 {
+            let visibility = geometry_smith(n_dot_v, n_dot_l, k) * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = (1.0 - v_dot_h).powi(5);
+            sum += Vector2::new((1.0 - fresnel) * visibility, fresnel * visibility);
+        }
+    }
+    sum / samples as f32
+}
+
+/// The BRDF lookup table of `size * size` texels, `NdotV` along x and the roughness along
+/// y, sampled at the texel centers. Scale and bias interleaved, rows bottom first like
+/// [`Texture::read_f32`] of the `Rg16F` texture [`brdf_lut_texture`] renders.
+pub fn brdf_lut(size: u32, samples: u32) -> Vec<f32> {
+    let center = |i: u32| (i as f32 + 0.5) / size as f32;
+    (0..size)
+        .flat_map(|y| (0..size).map(move |x| (x, y)))
+        .flat_map(|(x, y)| {
+            let value = integrate_brdf(center(x), center(y), samples);
+            [value.x, value.y]
+        })
+        .collect()
+}
+
+/// Compute [`brdf_lut`] on the CPU and write it as an OpenEXR file, scale in red and bias
+/// in green, for baking without a context.
+pub fn save_brdf_lut(path: impl AsRef<Path>, size: u32, samples: u32) -> Result<()> {
+    save_image(path.as_ref(), size, size, 2, &brdf_lut(size, samples))
+}
+
+/// Reflected light of a material lit by `radiance` along `light_dir`, the `cookTorrance`
+/// of [`GLSL`] for the factors of `material` without its maps.
+pub fn cook_torrance(material: &PbrMaterial, normal: &Vector3<f32>, view_dir: &Vector3<f32>, light_dir: &Vector3<f32>, radiance: Vector3<f32>) -> Vector3<f32> {
+    let halfway = (view_dir + light_dir).normalize();
+    let n_dot_v = normal.dot(view_dir).max(0.0);
+    let n_dot_l = normal.dot(light_dir).max(0.0);
+    let roughness = material.roughness;
+
+    let d = distribution_ggx(normal.dot(&halfway).max(0.0), roughness);
+    let g = geometry_smith(n_dot_v, n_dot_l, direct_k(roughness));
+    let f = fresnel_schlick(halfway.dot(view_dir).max(0.0), material.f0());
+
+    let specular = f * (d * g / (4.0 * n_dot_v * n_dot_l + 0.0001));
+    let k_d = (Vector3::repeat(1.0) - f) * (1.0 - material.metallic);
+    (k_d.component_mul(&material.albedo) / PI + specular).component_mul(&radiance) * n_dot_l
+}
+
+/// 2D texture ids of a [`PbrMaterial`], multiplied with its factors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PbrMaps {
+    /// sRGB colors.
+    pub albedo: GLuint,
+    /// Roughness in green and metallic in blue, like glTF.
+    pub metallic_roughness: GLuint,
+    /// Ambient occlusion in red.
+    pub ao: GLuint,
+}
+
+/// The `PbrMaterial` struct of [`GLSL`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PbrMaterial {
+    /// Linear base color.
+    pub albedo: Vector3<f32>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Ambient occlusion, only darkens the ambient and image based light.
+    pub ao: f32,
+    pub maps: Option<PbrMaps>,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self { albedo: Vector3::repeat(1.0), metallic: 0.0, roughness: 0.5, ao: 1.0, maps: None }
+    }
+}
+
+impl PbrMaterial {
+    /// Reflectance at normal incidence, 4% for dielectrics and the albedo for metals.
+    pub fn f0(&self) -> Vector3<f32> {
+        Vector3::repeat(0.04).lerp(&self.albedo, self.metallic)
+    }
+
+    /// Bind the maps to the texture units `first_unit` to `first_unit + 2` and set the
+    /// fields of the `uniform` struct, `shader` must be active. The samplers point at
+    /// those units even without maps, so they never share one with a cube map.
+    pub fn apply(&self, shader: &Shader, uniform: &str, first_unit: u32) -> Result<()> {
+        let samplers = ["albedoMap", "metallicRoughnessMap", "aoMap"];
+        if let Some(maps) = self.maps {
+            for (unit, texture) in (first_unit..).zip([maps.albedo, maps.metallic_roughness, maps.ao]) {
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit);
+                    gl::BindTexture(gl::TEXTURE_2D, texture);
+                }
+            }
+        }
+        for (unit, sampler) in (first_unit..).zip(samplers) {
+            shader.set_i32(&format!("{uniform}.{sampler}"), (unit as i32,))?;
+        }
+        let a = self.albedo;
+        shader.set_vec3(&format!("{uniform}.albedo"), (a.x, a.y, a.z))?;
+        shader.set_f32(&format!("{uniform}.metallic"), (self.metallic,))?;
+        shader.set_f32(&format!("{uniform}.roughness"), (self.roughness,))?;
+        shader.set_f32(&format!("{uniform}.ao"), (self.ao,))?;
+        shader.set_i32(&format!("{uniform}.hasMaps"), (self.maps.is_some() as i32,))
+    }
+}
+
+static FRAGMENT_RESOLVED: Lazy<String> = Lazy::new(|| with_includes(FRAGMENT));
+static PREFILTER_RESOLVED: Lazy<String> = Lazy::new(|| with_includes(PREFILTER_FRAGMENT));
+static BRDF_RESOLVED: Lazy<String> = Lazy::new(|| with_includes(BRDF_FRAGMENT));
+
+fn with_includes(source: &str) -> String {
+    resolve_includes(source, &[lighting::INCLUDES, INCLUDES].concat()).unwrap()
+}
+
+/// [`GEOMETRY_VERTEX`] with [`FRAGMENT`].
+pub fn shader() -> Shader<'static> {
+    Shader::new(GEOMETRY_VERTEX, FRAGMENT_RESOLVED.as_str())
+}
+
+/// Light the [`FRAGMENT`] shader with a constant `ambient` color instead of an [`Ibl`],
+/// `shader` must be active. Points the unused samplers at `first_unit` to `first_unit + 2`.
+pub fn apply_ambient(shader: &Shader, ambient: Vector3<f32>, first_unit: u32) -> Result<()> {
+    set_ibl_samplers(shader, first_unit)?;
+    shader.set_vec3("ambient", (ambient.x, ambient.y, ambient.z))?;
+    shader.set_i32("useIbl", (0,))
+}
+
+fn set_ibl_samplers(shader: &Shader, first_unit: u32) -> Result<()> {
+    for (unit, sampler) in (first_unit..).zip(["irradianceMap", "prefilterMap", "brdfLUT"]) {
+        shader.set_i32(sampler, (unit as i32,))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IblConfig {
+    /// Face size of the environment cube map converted from the equirectangular image.
+    pub environment_size: u32,
+    pub irradiance_size: u32,
+    /// Face size of the sharpest prefiltered mip.
+    pub prefilter_size: u32,
+    /// Prefiltered mips from roughness `0` to `1`.
+    pub prefilter_mips: u32,
+    pub brdf_lut_size: u32,
+    /// Importance samples per texel of the prefiltered mips and the BRDF lookup table.
+    pub samples: u32,
+    /// Step in radians of the irradiance convolution over the hemisphere.
+    pub irradiance_delta: f32,
+}
+
+impl Default for IblConfig {
+    fn default() -> Self {
+        Self {
+            environment_size: 512,
+            irradiance_size: 32,
+            prefilter_size: 128,
+            prefilter_mips: 5,
+            brdf_lut_size: 512,
+            samples: 1024,
+            irradiance_delta: 0.025,
+        }
+    }
+}
+
+impl IblConfig {
+    fn validate(&self) -> Result<()> {
+        if [self.environment_size, self.irradiance_size, self.prefilter_size, self.brdf_lut_size].contains(&0) {
+            bail!("every map needs at least one texel");
+        }
+        let max_mips = u32::BITS - self.prefilter_size.leading_zeros();
+        if !(1..=max_mips).contains(&self.prefilter_mips) {
+            bail!("{} prefiltered mips, a {} face has 1 to {max_mips}", self.prefilter_mips, self.prefilter_size);
+        }
+        if self.samples == 0 {
+            bail!("at least one sample is needed");
+        }
+        if self.irradiance_delta <= 0.0 {
+            bail!("the irradiance step must be positive, got {}", self.irradiance_delta);
+        }
+        Ok(())
+    }
+}
+
+/// Roughness of prefiltered mip `level` out of `mips`.
+pub fn mip_roughness(level: u32, mips: u32) -> f32 {
+    if mips <= 1 { 0.0 } else { level as f32 / (mips - 1) as f32 }
+}
+
+/// Load an equirectangular HDR image, like a `.hdr` or `.exr` file, as an `Rgb32F` texture
+/// with the top of the image up.
+pub fn load_equirect(path: impl AsRef<Path>) -> Result<Texture> {
+    let path = path.as_ref();
+    let image = image::open(path).with_context(|| format!("can not load {}", path.display()))?.flipv().into_rgb32f();
+    let bytes: Vec<u8> = image.as_raw().iter().copied().flat_map(f32::to_ne_bytes).collect();
+    Texture::from_data(image.width(), image.height(), TextureFormat::Rgb32F, &bytes)
+}
+
+/// Draws the unit cube, or the fullscreen triangle with [`FULLSCREEN_VERTEX`], into
+/// framebuffers with depth testing and face culling off.
+struct Baker {
+    cube: Mesh,
+    vao: GLuint,
+    depth_test: bool,
+    cull_face: bool,
+    seamless: bool,
+}
+
+impl Baker {
+    fn new() -> Self {
+        let mut vao = 0;
+        unsafe { gl::GenVertexArrays(1, &mut vao); }
+        let baker = Self {
+            cube: Mesh::new(&MeshData::cube()),
+            vao,
+            depth_test: unsafe { gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE },
+            cull_face: unsafe { gl::IsEnabled(gl::CULL_FACE) == gl::TRUE },
+            seamless: unsafe { gl::IsEnabled(gl::TEXTURE_CUBE_MAP_SEAMLESS) == gl::TRUE },
+        };
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::CULL_FACE);
+            // the environment is sampled across the face edges while prefiltering
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+        }
+        baker
+    }
+
+    /// Render every face of `level` into the cube `framebuffer`, `shader` must be active.
+    fn cube(&self, shader: &Shader, framebuffer: &mut Framebuffer, level: u32) -> Result<()> {
+        for (face, view_projection) in (0..).zip(point_light_faces(&Point3::origin(), 0.1, 10.0)) {
+            framebuffer.select_mip(face, level)?;
+            shader.set_mat4fv("viewProjection", Arc::new(view_projection))?;
+            self.cube.draw();
+        }
+        Ok(())
+    }
+
+    fn fullscreen(&self, framebuffer: &Framebuffer) {
+        framebuffer.bind();
+        unsafe {
+            let mut previous_vao = 0;
+            gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut previous_vao);
+            gl::BindVertexArray(self.vao);
+            crate::gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+            gl::BindVertexArray(previous_vao as _);
+        }
+    }
+}
+
+impl Drop for Baker {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            if self.depth_test {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+            if self.cull_face {
+                gl::Enable(gl::CULL_FACE);
+            }
+            if !self.seamless {
+                gl::Disable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+            }
+        }
+    }
+}
+
+fn cube_target(size: u32) -> Result<Framebuffer> {
+    Framebuffer::new(FramebufferConfig::new(size, size).with_kind(TextureKind::Cube).with_color(TextureFormat::Rgb16F))
+}
+
+fn only_color(framebuffer: Framebuffer) -> Texture {
+    framebuffer.into_colors().pop().unwrap()
+}
+
+fn render_brdf_lut(baker: &Baker, size: u32, samples: u32) -> Result<Texture> {
+    let framebuffer = Framebuffer::new(FramebufferConfig::new(size, size).with_color(TextureFormat::Rg16F))?;
+    let shader = Shader::new(FULLSCREEN_VERTEX, BRDF_RESOLVED.as_str());
+    shader.activate()?;
+    shader.set_u32("sampleCount", (samples,))?;
+    baker.fullscreen(&framebuffer);
+    Ok(only_color(framebuffer))
+}
+
+/// Render the `Rg16F` BRDF lookup table of [`brdf_lut`] on the GPU. Leaves its
+/// framebuffer bound, bind the next target afterwards.
+pub fn brdf_lut_texture(size: u32, samples: u32) -> Result<Texture> {
+    render_brdf_lut(&Baker::new(), size, samples)
+}
+
+/// The precomputed maps of an environment for the [`FRAGMENT`] shader.
+pub struct Ibl {
+    /// The environment itself with mips, for a skybox.
+    pub environment: Texture,
+    /// Cosine weighted average of the environment around each direction.
+    pub irradiance: Texture,
+    /// The environment blurred like the specular lobe, from roughness `0` at mip `0` to
+    /// `1` at the last mip.
+    pub prefiltered: Texture,
+    /// See [`brdf_lut`].
+    pub brdf_lut: Texture,
+    prefilter_mips: u32,
+}
+
+impl Ibl {
+    /// Render every map from an equirectangular environment, see [`load_equirect`].
+    /// Leaves a framebuffer bound, bind the next target afterwards.
+    pub fn bake(equirect: &Texture, config: &IblConfig) -> Result<Self> {
+        config.validate()?;
+        let baker = Baker::new();
+
+        let mut framebuffer = cube_target(config.environment_size)?;
+        let shader = Shader::new(CUBE_VERTEX, EQUIRECT_FRAGMENT);
+        shader.activate()?;
+        equirect.bind(0);
+        shader.set_i32("equirect", (0,))?;
+        baker.cube(&shader, &mut framebuffer, 0)?;
+        let environment = only_color(framebuffer);
+        environment.set_filter(gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR);
+        environment.generate_mipmaps();
+
+        let mut framebuffer = cube_target(config.irradiance_size)?;
+        let shader = Shader::new(CUBE_VERTEX, IRRADIANCE_FRAGMENT);
+        shader.activate()?;
+        environment.bind(0);
+        shader.set_i32("environment", (0,))?;
+        shader.set_f32("sampleDelta", (config.irradiance_delta,))?;
+        baker.cube(&shader, &mut framebuffer, 0)?;
+        let irradiance = only_color(framebuffer);
+
+        let mut framebuffer = cube_target(config.prefilter_size)?;
+        // allocates the mips to render into
+        framebuffer.color(0).generate_mipmaps();
+        let shader = Shader::new(CUBE_VERTEX, PREFILTER_RESOLVED.as_str());
+        shader.activate()?;
+        environment.bind(0);
+        shader.set_i32("environment", (0,))?;
+        shader.set_u32("sampleCount", (config.samples,))?;
+        shader.set_f32("resolution", (config.environment_size as f32,))?;
+        for level in 0..config.prefilter_mips {
+            shader.set_f32("roughness", (mip_roughness(level, config.prefilter_mips),))?;
+            baker.cube(&shader, &mut framebuffer, level)?;
+        }
+        let prefiltered = only_color(framebuffer);
+        prefiltered.set_filter(gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR);
+        prefiltered.set_max_level(config.prefilter_mips - 1);
+
+        let brdf_lut = render_brdf_lut(&baker, config.brdf_lut_size, config.samples)?;
+        Ok(Self { environment, irradiance, prefiltered, brdf_lut, prefilter_mips: config.prefilter_mips })
+    }
+
+    pub fn prefilter_mips(&self) -> u32 {
+        self.prefilter_mips
+    }
+
+    /// Write every map into `dir` as OpenEXR files, for [`Ibl::load`].
+    pub fn save(&self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir).with_context(|| format!("can not create {}", dir.display()))?;
+        save_texture(&self.brdf_lut, 0, 0, &dir.join("brdf_lut.exr"))?;
+        for (face, name) in (0..).zip(FACE_NAMES) {
+            save_texture(&self.environment, face, 0, &dir.join(format!("environment_{name}.exr")))?;
+            save_texture(&self.irradiance, face, 0, &dir.join(format!("irradiance_{name}.exr")))?;
+            for level in 0..self.prefilter_mips {
+                save_texture(&self.prefiltered, face, level, &dir.join(format!("prefiltered_{level}_{name}.exr")))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the maps [`Ibl::save`] wrote into `dir`.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let (size, _, data) = load_image(&dir.join("brdf_lut.exr"), 2)?;
+        let brdf_lut = Texture::new_2d(size, size, TextureFormat::Rg16F);
+        brdf_lut.write_f32(0, 0, &data)?;
+
+        let environment = load_cube(dir, "environment")?;
+        environment.set_filter(gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR);
+        environment.generate_mipmaps();
+        let irradiance = load_cube(dir, "irradiance")?;
+
+        let prefiltered = load_cube(dir, "prefiltered_0")?;
+        let mut prefilter_mips = 1;
+        while dir.join(format!("prefiltered_{prefilter_mips}_{}.exr", FACE_NAMES[0])).exists() {
+            for (face, name) in (0..).zip(FACE_NAMES) {
+                let (_, _, data) = load_image(&dir.join(format!("prefiltered_{prefilter_mips}_{name}.exr")), 3)?;
+                prefiltered.write_f32(face, prefilter_mips, &data)?;
+            }
+            prefilter_mips += 1;
+        }
+        prefiltered.set_filter(gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR);
+        prefiltered.set_max_level(prefilter_mips - 1);
+
+        Ok(Self { environment, irradiance, prefiltered, brdf_lut, prefilter_mips })
+    }
+
+    /// [`Ibl::load`] the maps from `dir`, or bake them from the equirectangular image at
+    /// `equirect` and save them there for the next start.
+    pub fn load_or_bake(dir: impl AsRef<Path>, equirect: impl AsRef<Path>, config: &IblConfig) -> Result<Self> {
+        let dir = dir.as_ref();
+        if dir.join("brdf_lut.exr").exists() {
+            return Self::load(dir);
+        }
+        let ibl = Self::bake(&load_equirect(equirect)?, config)?;
+        ibl.save(dir)?;
+        Ok(ibl)
+    }
+
+    /// Bind the maps to the texture units `first_unit` to `first_unit + 2` and light the
+    /// [`FRAGMENT`] shader with them, `shader` must be active.
+    ///
+    /// Enable `GL_TEXTURE_CUBE_MAP_SEAMLESS` to hide the face edges in the rough mips,
+    /// baking leaves it as it was.
+    pub fn apply(&self, shader: &Shader, first_unit: u32) -> Result<()> {
+        self.irradiance.bind(first_unit);
+        self.prefiltered.bind(first_unit + 1);
+        self.brdf_lut.bind(first_unit + 2);
+        set_ibl_samplers(shader, first_unit)?;
+        shader.set_f32("prefilterMaxLevel", ((self.prefilter_mips - 1) as f32,))?;
+        shader.set_i32("useIbl", (1,))
+    }
+}
+
+fn save_texture(texture: &Texture, layer: u32, level: u32, path: &Path) -> Result<()> {
+    let (w, h) = texture.mip_size(level);
+    save_image(path, w, h, texture.format().channels(), &texture.read_f32(layer, level)?)
+}
+
+/// Write `channels` interleaved floats per texel, rows bottom first, as an RGB image.
+fn save_image(path: &Path, width: u32, height: u32, channels: usize, data: &[f32]) -> Result<()> {
+    let image = ImageBuffer::from_fn(width, height, |x, y| {
+        let start = ((height - 1 - y) * width + x) as usize * channels;
+        let mut rgb = [0.0; 3];
+        for (value, &channel) in rgb.iter_mut().zip(&data[start..start + channels]) {
+            *value = channel;
+        }
+        Rgb(rgb)
+    });
+    image.save(path).with_context(|| format!("can not write {}", path.display()))
+}
+
+/// The first `channels` of an image, laid out like [`save_image`] takes them.
+fn load_image(path: &Path, channels: usize) -> Result<(u32, u32, Vec<f32>)> {
+    let image = image::open(path).with_context(|| format!("can not load {}", path.display()))?.flipv().into_rgb32f();
+    let data = image.pixels().flat_map(|p| p.0[..channels].to_vec()).collect();
+    Ok((image.width(), image.height(), data))
+}
+
+fn load_cube(dir: &Path, prefix: &str) -> Result<Texture> {
+    let mut cube = None;
+    for (face, name) in (0..).zip(FACE_NAMES) {
+        let (w, h, data) = load_image(&dir.join(format!("{prefix}_{name}.exr")), 3)?;
+        if w != h {
+            bail!("face {name} of {prefix} is {w}x{h}, cube map faces are square");
+        }
+        let texture = cube.get_or_insert_with(|| Texture::new_cube(w, TextureFormat::Rgb16F));
+        texture.write_f32(face, 0, &data)?;
+    }
+    Ok(cube.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    #[test]
+    fn hammersley_halves_the_intervals() {
+        let ys: Vec<f32> = (0..4).map(|i| hammersley(i, 4).y).collect();
+        assert_eq!(ys, [0.0, 0.5, 0.25, 0.75]);
+        assert_eq!(hammersley(3, 4).x, 0.75);
+        assert!(close(radical_inverse(u32::MAX), 1.0, 1e-6));
+    }
+
+    #[test]
+    fn ggx_is_normalized() {
+        // the projected microfacet area integrates to one over the hemisphere
+        for roughness in [0.3, 0.6, 1.0] {
+            let n = 2000;
+            let sum: f32 = (0..n)
+                .map(|i| {
+                    let theta = (i as f32 + 0.5) / n as f32 * PI / 2.0;
+                    distribution_ggx(theta.cos(), roughness) * theta.cos() * theta.sin() * 2.0 * PI * (PI / 2.0 / n as f32)
+                })
+                .sum();
+            assert!(close(sum, 1.0, 1e-2), "roughness {roughness}: {sum}");
+        }
+    }
+
+    #[test]
+    fn samples_follow_the_normal() {
+        let normal = Vector3::new(1.0, 2.0, 3.0).normalize();
+        for i in 0..64 {
+            let h = importance_sample_ggx(hammersley(i, 64), &normal, 0.5);
+            assert!(close(h.norm(), 1.0, 1e-5) && h.dot(&normal) > 0.0);
+        }
+        // a mirror has every halfway vector along the normal, also straight up
+        let h = importance_sample_ggx(Vector2::new(0.3, 0.7), &Vector3::z(), 0.0);
+        assert!((h - Vector3::z()).norm() < 1e-5);
+    }
+
+    #[test]
+    fn fresnel_and_f0() {
+        let f0 = Vector3::new(0.04, 0.5, 1.0);
+        assert_eq!(fresnel_schlick(1.0, f0), f0);
+        assert_eq!(fresnel_schlick(0.0, f0), Vector3::repeat(1.0));
+
+        let gold = PbrMaterial { albedo: Vector3::new(1.0, 0.8, 0.3), metallic: 1.0, ..PbrMaterial::default() };
+        assert_eq!(gold.f0(), gold.albedo);
+        assert_eq!(PbrMaterial::default().f0(), Vector3::repeat(0.04));
+    }
+
+    #[test]
+    fn cook_torrance_conserves_energy() {
+        let material = PbrMaterial { roughness: 0.7, ..PbrMaterial::default() };
+        let (normal, view) = (Vector3::z(), Vector3::new(0.6, 0.0, 0.8));
+        // reflected light over the hemisphere of light directions
+        let n = 200;
+        let mut sum = Vector3::zeros();
+        for i in 0..n {
+            let theta = (i as f32 + 0.5) / n as f32 * PI / 2.0;
+            for j in 0..2 * n {
+                let phi = (j as f32 + 0.5) / (2 * n) as f32 * 2.0 * PI;
+                let light = Vector3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                let solid_angle = theta.sin() * (PI / 2.0 / n as f32) * (PI / n as f32);
+                sum += cook_torrance(&material, &normal, &view, &light, Vector3::repeat(1.0)) * solid_angle;
+            }
+        }
+        assert!(sum.x > 0.5 && sum.x <= 1.0, "{sum:?}");
+
+        let back = cook_torrance(&material, &normal, &view, &-Vector3::z(), Vector3::repeat(1.0));
+        assert_eq!(back, Vector3::zeros());
+    }
+
+    #[test]
+    fn brdf_matches_quadrature() {
+        // scale and bias integrated over a fine grid of light directions instead of
+        // importance sampled
+        let reference = [
+            (0.5, 0.5, 0.7285, 0.0185),
+            (0.9, 0.3, 0.9771, 0.0001),
+            (0.25, 0.8, 0.5786, 0.0168),
+            (0.75, 1.0, 0.3501, 0.0006),
+        ];
+        for (n_dot_v, roughness, scale, bias) in reference {
+            let value = integrate_brdf(n_dot_v, roughness, 1024);
+            assert!(close(value.x, scale, 1e-2) && close(value.y, bias, 1e-2), "{n_dot_v} {roughness}: {value:?}");
+        }
+        // a smooth surface seen head on reflects exactly `F0`
+        let mirror = integrate_brdf(1.0, 0.0, 16);
+        assert!(close(mirror.x, 1.0, 1e-3) && close(mirror.y, 0.0, 1e-3), "{mirror:?}");
+    }
+
+    #[test]
+    fn brdf_lut_layout() {
+        let lut = brdf_lut(4, 64);
+        assert_eq!(lut.len(), 4 * 4 * 2);
+        // second row, third column
+        let value = integrate_brdf(2.5 / 4.0, 1.5 / 4.0, 64);
+        assert_eq!(&lut[(4 + 2) * 2..(4 + 2) * 2 + 2], &[value.x, value.y]);
+    }
+
+    #[test]
+    fn lut_file_roundtrip() {
+        let path = std::env::temp_dir().join(format!("learnopengl_brdf_lut_{}.exr", std::process::id()));
+        save_brdf_lut(&path, 8, 32).unwrap();
+        let (w, h, data) = load_image(&path, 2).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((w, h), (8, 8));
+        let expected = brdf_lut(8, 32);
+        assert!(data.iter().zip(&expected).all(|(a, b)| close(*a, *b, 1e-6)), "{data:?}");
+    }
+
+    #[test]
+    fn config_limits() {
+        assert!(IblConfig::default().validate().is_ok());
+        assert!(IblConfig { prefilter_size: 16, prefilter_mips: 5, ..IblConfig::default() }.validate().is_ok());
+        assert!(IblConfig { prefilter_size: 16, prefilter_mips: 6, ..IblConfig::default() }.validate().is_err());
+        assert!(IblConfig { prefilter_mips: 0, ..IblConfig::default() }.validate().is_err());
+        assert!(IblConfig { samples: 0, ..IblConfig::default() }.validate().is_err());
+        assert!(IblConfig { irradiance_size: 0, ..IblConfig::default() }.validate().is_err());
+
+        assert_eq!(mip_roughness(0, 5), 0.0);
+        assert_eq!(mip_roughness(4, 5), 1.0);
+        assert_eq!(mip_roughness(0, 1), 0.0);
+    }
+
+    #[test]
+    fn shaders_resolve() {
+        for source in [&FRAGMENT_RESOLVED, &PREFILTER_RESOLVED, &BRDF_RESOLVED] {
+            assert!(source.contains("float distributionGGX"));
+            assert!(!source.contains("#include"));
+        }
+        assert!(FRAGMENT_RESOLVED.contains("uniform Lights"));
+    }
+}
@@ -0,0 +1,95 @@
+// Cook-Torrance metallic-roughness BRDF with the GGX distribution.
+// The math is mirrored in `utils::pbr`, change both together.
+
+const float PI = 3.14159265359;
+
+// Factors multiplied with the maps when there are any, the albedo map is sRGB and the
+// metallic-roughness map packed like glTF: roughness in green, metallic in blue.
+struct PbrMaterial {
+    vec3 albedo;
+    float metallic;
+    float roughness;
+    float ao;
+    bool hasMaps;
+    sampler2D albedoMap;
+    sampler2D metallicRoughnessMap;
+    sampler2D aoMap;
+};
+
+struct PbrSurface {
+    vec3 normal;
+    vec3 position;
+    vec3 viewDir;
+    vec3 albedo;
+    float metallic;
+    float roughness;
+    float ao;
+    // reflectance at normal incidence, 4% for dielectrics and the albedo for metals
+    vec3 f0;
+};
+
+float distributionGGX(float NdotH, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// `k` is `(roughness + 1)² / 8` for direct lights and `roughness² / 2` for image based lighting
+float geometrySchlickGGX(float NdotV, float k) {
+    return NdotV / (NdotV * (1.0 - k) + k);
+}
+
+float geometrySmith(float NdotV, float NdotL, float k) {
+    return geometrySchlickGGX(NdotV, k) * geometrySchlickGGX(NdotL, k);
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// rough surfaces reflect less at grazing angles, for the ambient term
+vec3 fresnelSchlickRoughness(float cosTheta, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+// `radiance` arriving along `lightDir`, which points from the surface towards the light.
+vec3 cookTorrance(PbrSurface s, vec3 lightDir, vec3 radiance) {
+    vec3 halfway = normalize(s.viewDir + lightDir);
+    float NdotV = max(dot(s.normal, s.viewDir), 0.0);
+    float NdotL = max(dot(s.normal, lightDir), 0.0);
+    float k = (s.roughness + 1.0) * (s.roughness + 1.0) / 8.0;
+
+    float D = distributionGGX(max(dot(s.normal, halfway), 0.0), s.roughness);
+    float G = geometrySmith(NdotV, NdotL, k);
+    vec3 F = fresnelSchlick(max(dot(halfway, s.viewDir), 0.0), s.f0);
+
+    vec3 specular = D * G * F / (4.0 * NdotV * NdotL + 0.0001);
+    // what is not reflected is refracted and diffused, except by metals
+    vec3 kD = (vec3(1.0) - F) * (1.0 - s.metallic);
+    return (kD * s.albedo / PI + specular) * radiance * NdotL;
+}
+
+PbrSurface pbrSurface(PbrMaterial m, vec2 texCoords, vec3 normal, vec3 position, vec3 viewPos) {
+    vec3 albedo = m.albedo;
+    float metallic = m.metallic;
+    float roughness = m.roughness;
+    float ao = m.ao;
+    if (m.hasMaps) {
+        albedo *= texture(m.albedoMap, texCoords).rgb;
+        vec4 metallicRoughness = texture(m.metallicRoughnessMap, texCoords);
+        roughness *= metallicRoughness.g;
+        metallic *= metallicRoughness.b;
+        ao *= texture(m.aoMap, texCoords).r;
+    }
+    return PbrSurface(
+        normalize(normal),
+        position,
+        normalize(viewPos - position),
+        albedo,
+        metallic,
+        roughness,
+        ao,
+        mix(vec3(0.04), albedo, metallic)
+    );
+}
@@ -0,0 +1,66 @@
+#version 330 core
+#include "lighting.glsl"
+#include "pbr.glsl"
+
+in vec3 worldPos;
+in vec3 normal;
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform PbrMaterial material;
+uniform vec3 viewPos;
+
+// the maps of `Ibl`, or a constant `ambient` color without them
+uniform bool useIbl;
+uniform samplerCube irradianceMap;
+uniform samplerCube prefilterMap;
+uniform sampler2D brdfLUT;
+uniform float prefilterMaxLevel;
+uniform vec3 ambient;
+
+// The lights of the `Lights` block, their diffuse color is the radiance. The ambient and
+// specular colors are for Phong and ignored.
+vec3 directLight(PbrSurface s) {
+    vec3 color = vec3(0.0);
+    for (int i = 0; i < lightCounts.x; i++) {
+        color += cookTorrance(s, normalize(-directionalLights[i].direction), directionalLights[i].diffuse);
+    }
+    for (int i = 0; i < lightCounts.y; i++) {
+        PointLight light = pointLights[i];
+        vec3 toLight = light.position - s.position;
+        float factor = attenuation(light.constant, light.linear, light.quadratic, length(toLight));
+        color += cookTorrance(s, normalize(toLight), light.diffuse * factor);
+    }
+    for (int i = 0; i < lightCounts.z; i++) {
+        SpotLight light = spotLights[i];
+        vec3 toLight = light.position - s.position;
+        vec3 lightDir = normalize(toLight);
+        float theta = dot(lightDir, normalize(-light.direction));
+        float cone = spotCone(theta, light.innerCutoff, light.outerCutoff);
+        float factor = attenuation(light.constant, light.linear, light.quadratic, length(toLight));
+        color += cookTorrance(s, lightDir, light.diffuse * factor * cone);
+    }
+    return color;
+}
+
+vec3 ambientLight(PbrSurface s) {
+    if (!useIbl) {
+        return ambient * s.albedo * s.ao;
+    }
+    float NdotV = max(dot(s.normal, s.viewDir), 0.0);
+    vec3 F = fresnelSchlickRoughness(NdotV, s.f0, s.roughness);
+    vec3 kD = (1.0 - F) * (1.0 - s.metallic);
+    vec3 diffuse = texture(irradianceMap, s.normal).rgb * s.albedo;
+
+    vec3 reflected = reflect(-s.viewDir, s.normal);
+    vec3 prefiltered = textureLod(prefilterMap, reflected, s.roughness * prefilterMaxLevel).rgb;
+    vec2 brdf = texture(brdfLUT, vec2(NdotV, s.roughness)).rg;
+    vec3 specular = prefiltered * (F * brdf.x + brdf.y);
+    return (kD * diffuse + specular) * s.ao;
+}
+
+// linear HDR, tonemap afterwards
+void main() {
+    PbrSurface s = pbrSurface(material, texCoords, normal, worldPos, viewPos);
+    fragColor = vec4(directLight(s) + ambientLight(s), 1.0);
+}
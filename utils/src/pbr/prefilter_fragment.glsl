@@ -0,0 +1,37 @@
+#version 330 core
+#include "ibl.glsl"
+
+in vec3 localPos;
+out vec4 fragColor;
+
+uniform samplerCube environment;
+uniform float roughness;
+uniform uint sampleCount;
+// face size of the environment, to pick its mip from the sample density
+uniform float resolution;
+
+// the split sum assumes the view along the normal
+void main() {
+    vec3 normal = normalize(localPos);
+    vec3 view = normal;
+
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < sampleCount; i++) {
+        vec3 halfway = importanceSampleGGX(hammersley(i, sampleCount), normal, roughness);
+        vec3 light = normalize(2.0 * dot(view, halfway) * halfway - view);
+        float NdotL = dot(normal, light);
+        if (NdotL > 0.0) {
+            // sampling a blurrier mip where samples are sparse avoids bright dots
+            float NdotH = max(dot(normal, halfway), 0.0);
+            float pdf = distributionGGX(NdotH, roughness) / 4.0 + 0.0001;
+            float texel = 4.0 * PI / (6.0 * resolution * resolution);
+            float solidAngle = 1.0 / (float(sampleCount) * pdf + 0.0001);
+            float level = roughness == 0.0 ? 0.0 : 0.5 * log2(solidAngle / texel);
+
+            color += textureLod(environment, light, level).rgb * NdotL;
+            weight += NdotL;
+        }
+    }
+    fragColor = vec4(color / weight, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+uniform float threshold;
+// width of the soft transition below the threshold
+uniform float knee;
+
+void main() {
+    vec3 color = texture(screen, texCoords).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-4);
+    float contribution = max(soft, brightness - threshold) / max(brightness, 1e-4);
+    fragColor = vec4(color * contribution, 1.0);
+}
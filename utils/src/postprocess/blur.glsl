@@ -0,0 +1,19 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+uniform vec2 texelSize;
+uniform bool horizontal;
+// the center weight first, the same on both sides
+uniform float weights[5];
+
+void main() {
+    vec2 step = horizontal ? vec2(texelSize.x, 0.0) : vec2(0.0, texelSize.y);
+    vec3 color = texture(screen, texCoords).rgb * weights[0];
+    for (int i = 1; i < 5; i++) {
+        color += texture(screen, texCoords + step * float(i)).rgb * weights[i];
+        color += texture(screen, texCoords - step * float(i)).rgb * weights[i];
+    }
+    fragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,9 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+
+void main() {
+    fragColor = texture(screen, texCoords);
+}
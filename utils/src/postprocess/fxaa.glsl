@@ -0,0 +1,42 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+// tonemapped colors in [0, 1], FXAA works on their luma
+uniform sampler2D screen;
+uniform vec2 texelSize;
+
+const float SPAN_MAX = 8.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+void main() {
+    float nw = luma(texture(screen, texCoords + vec2(-1.0, -1.0) * texelSize).rgb);
+    float ne = luma(texture(screen, texCoords + vec2(1.0, -1.0) * texelSize).rgb);
+    float sw = luma(texture(screen, texCoords + vec2(-1.0, 1.0) * texelSize).rgb);
+    float se = luma(texture(screen, texCoords + vec2(1.0, 1.0) * texelSize).rgb);
+    vec4 center = texture(screen, texCoords);
+    float m = luma(center.rgb);
+
+    float lumaMin = min(m, min(min(nw, ne), min(sw, se)));
+    float lumaMax = max(m, max(max(nw, ne), max(sw, se)));
+
+    // blur along the edge, across the luma gradient
+    vec2 dir = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    float reduce = max((nw + ne + sw + se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * texelSize;
+
+    vec3 a = 0.5 * (texture(screen, texCoords + dir * (1.0 / 3.0 - 0.5)).rgb
+        + texture(screen, texCoords + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 b = a * 0.5 + 0.25 * (texture(screen, texCoords - dir * 0.5).rgb
+        + texture(screen, texCoords + dir * 0.5).rgb);
+
+    // the wider blend crossed another edge, keep the narrow one
+    float lumaB = luma(b);
+    fragColor = vec4(lumaB < lumaMin || lumaB > lumaMax ? a : b, center.a);
+}
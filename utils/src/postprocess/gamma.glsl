@@ -0,0 +1,11 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+uniform float gamma;
+
+void main() {
+    vec4 color = texture(screen, texCoords);
+    fragColor = vec4(pow(max(color.rgb, 0.0), vec3(1.0 / gamma)), color.a);
+}
@@ -0,0 +1,12 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+
+void main() {
+    vec4 color = texture(screen, texCoords);
+    // Rec. 709 luma, green looks brightest
+    float luma = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+    fragColor = vec4(vec3(luma), color.a);
+}
@@ -0,0 +1,10 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+
+void main() {
+    vec4 color = texture(screen, texCoords);
+    fragColor = vec4(1.0 - color.rgb, color.a);
+}
@@ -0,0 +1,19 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+uniform vec2 texelSize;
+// row major, the top row first
+uniform float kernel[9];
+
+void main() {
+    vec3 color = vec3(0.0);
+    for (int y = 0; y < 3; y++) {
+        for (int x = 0; x < 3; x++) {
+            vec2 offset = vec2(x - 1, 1 - y) * texelSize;
+            color += kernel[y * 3 + x] * texture(screen, texCoords + offset).rgb;
+        }
+    }
+    fragColor = vec4(color, texture(screen, texCoords).a);
+}
@@ -0,0 +1,546 @@
+//! Post-processing: the scene renders into an offscreen target, then an ordered list of
+//! fullscreen [`Pass`]es runs over it, ping-ponging between two framebuffers until the
+//! last enabled pass draws to the screen.
+//!
+//! ```ignore
+//! let mut post = PostProcess::new(800, 600, TextureFormat::Rgba16F)?;
+//! post.push(Pass::bloom(BloomConfig::default())?);
+//! post.push(Pass::tonemap(Tonemapper::AcesFitted, 1.0));
+//! post.push(Pass::srgb());
+//!
+//! post.begin(&ctx.gl);
+//! // draw the scene
+//! post.finish(&ctx.gl)?;
+//! ```
+
+use std::ffi::CString;
+
+use anyhow::{anyhow, Result};
+use gl::types::*;
+use nalgebra::{Vector2, Vector3, Vector4};
+use once_cell::sync::Lazy;
+
+use crate::context::GlContext;
+use crate::framebuffer::{Framebuffer, FramebufferConfig};
+use crate::hdr::{self, Tonemapper};
+use crate::shader::{resolve_includes, Shader};
+use crate::ssao::{self, Ssao, SsaoConfig};
+use crate::texture::{Texture, TextureFormat};
+
+/// Draws one triangle covering the screen from `gl_VertexID`, passes `texCoords` on.
+pub const FULLSCREEN_VERTEX: &str = include_str!("fullscreen_vertex.glsl");
+
+pub const COPY: &str = include_str!("copy.glsl");
+pub const GRAYSCALE: &str = include_str!("grayscale.glsl");
+pub const INVERSION: &str = include_str!("inversion.glsl");
+pub const KERNEL: &str = include_str!("kernel.glsl");
+pub const GAMMA: &str = include_str!("gamma.glsl");
+/// Includes `tonemap.glsl`, see [`hdr::INCLUDES`].
+pub const TONEMAP: &str = include_str!("tonemap.glsl");
+/// Includes `tonemap.glsl`, see [`hdr::INCLUDES`].
+pub const SRGB: &str = include_str!("srgb.glsl");
+pub const FXAA: &str = include_str!("fxaa.glsl");
+pub const BLOOM_BRIGHT: &str = include_str!("bloom_bright.glsl");
+pub const BLUR: &str = include_str!("blur.glsl");
+pub const BLOOM_COMBINE: &str = include_str!("bloom_combine.glsl");
+
+/// 3x3 kernels for [`Pass::kernel`], row major with the top row first.
+pub const SHARPEN_KERNEL: [f32; 9] = [-1.0, -1.0, -1.0, -1.0, 9.0, -1.0, -1.0, -1.0, -1.0];
+pub const BLUR_KERNEL: [f32; 9] = [
+    1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0,
+    2.0 / 16.0, 4.0 / 16.0, 2.0 / 16.0,
+    1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0,
+];
+pub const EDGE_KERNEL: [f32; 9] = [1.0, 1.0, 1.0, 1.0, -8.0, 1.0, 1.0, 1.0, 1.0];
+
+/// Weights of a gaussian blur `radius` texels to each side, the center first.
+///
+/// Normalized so the center and both sides sum to `1`.
+pub fn gaussian_weights(radius: usize, sigma: f32) -> Vec<f32> {
+    let weights: Vec<_> = (0..=radius).map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp()).collect();
+    let total = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+    weights.into_iter().map(|w| w / total).collect()
+}
+
+/// Same as the grayscale pass, Rec. 709 luma.
+pub fn luma(color: &Vector3<f32>) -> f32 {
+    color.dot(&Vector3::new(0.2126, 0.7152, 0.0722))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UniformValue {
+    F32(f32),
+    I32(i32),
+    Vec2(Vector2<f32>),
+    Vec3(Vector3<f32>),
+    Vec4(Vector4<f32>),
+    /// A `float[]`, set from its first element.
+    Floats(Vec<f32>),
+}
+
+impl UniformValue {
+    /// Set `name` of the active `program`, uniforms the compiler removed are skipped.
+    fn apply(&self, program: GLuint, name: &str) {
+        let c_name = CString::new(name).unwrap();
+        let loc = unsafe { gl::GetUniformLocation(program, c_name.as_ptr()) };
+        if loc == -1 {
+            return;
+        }
+        unsafe {
+            match self {
+                Self::F32(v) => gl::Uniform1f(loc, *v),
+                Self::I32(v) => gl::Uniform1i(loc, *v),
+                Self::Vec2(v) => gl::Uniform2f(loc, v.x, v.y),
+                Self::Vec3(v) => gl::Uniform3f(loc, v.x, v.y, v.z),
+                Self::Vec4(v) => gl::Uniform4f(loc, v.x, v.y, v.z, v.w),
+                Self::Floats(v) => gl::Uniform1fv(loc, v.len() as _, v.as_ptr()),
+            }
+        }
+    }
+}
+
+/// What a sampler of a pass reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassInput {
+    /// The output of the enabled pass before, or the scene for the first one.
+    Previous,
+    /// The scene color before any pass.
+    Scene,
+    /// The scene depth, in `[0, 1]`.
+    SceneDepth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomConfig {
+    /// Brightness where colors start to glow, above `1` only HDR colors do.
+    pub threshold: f32,
+    /// Width of the soft transition below the threshold.
+    pub knee: f32,
+    pub intensity: f32,
+    /// Horizontal and vertical blur pairs at half resolution.
+    pub iterations: u32,
+    pub sigma: f32,
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self { threshold: 1.0, knee: 0.5, intensity: 0.8, iterations: 5, sigma: 2.0 }
+    }
+}
+
+/// The bright pass and blur of a bloom pass, at half resolution.
+struct Bloom {
+    config: BloomConfig,
+    bright: Shader<'static>,
+    blur: Shader<'static>,
+    targets: [Framebuffer; 2],
+}
+
+impl Bloom {
+    fn new(config: BloomConfig, width: u32, height: u32) -> Result<Self> {
+        let target = || Framebuffer::new(FramebufferConfig::new((width / 2).max(1), (height / 2).max(1)).with_color(TextureFormat::Rgba16F));
+        Ok(Self {
+            config,
+            bright: Shader::new(FULLSCREEN_VERTEX, BLOOM_BRIGHT),
+            blur: Shader::new(FULLSCREEN_VERTEX, BLUR),
+            targets: [target()?, target()?],
+        })
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        for target in &mut self.targets {
+            target.resize((width / 2).max(1), (height / 2).max(1));
+        }
+    }
+
+    /// The blurred bright parts of `input`.
+    fn render(&self, input: &Texture) -> Result<&Texture> {
+        let (w, h) = self.targets[0].size();
+        let texel_size = UniformValue::Vec2(Vector2::new(1.0 / w as f32, 1.0 / h as f32));
+
+        self.targets[0].bind();
+        self.bright.activate()?;
+        input.bind(0);
+        let program = self.bright.id();
+        UniformValue::I32(0).apply(program, "screen");
+        UniformValue::F32(self.config.threshold).apply(program, "threshold");
+        UniformValue::F32(self.config.knee).apply(program, "knee");
+        draw_fullscreen();
+
+        self.blur.activate()?;
+        let program = self.blur.id();
+        UniformValue::I32(0).apply(program, "screen");
+        texel_size.apply(program, "texelSize");
+        UniformValue::Floats(gaussian_weights(4, self.config.sigma)).apply(program, "weights");
+        for i in 0..self.config.iterations * 2 {
+            let (source, target) = (&self.targets[(i % 2) as usize], &self.targets[(1 - i % 2) as usize]);
+            target.bind();
+            source.color(0).bind(0);
+            UniformValue::I32((i % 2 == 0) as i32).apply(program, "horizontal");
+            draw_fullscreen();
+        }
+        // an even number of blurs ends where the bright pass started
+        Ok(self.targets[0].color(0))
+    }
+}
+
+static TONEMAP_RESOLVED: Lazy<String> = Lazy::new(|| resolve_includes(TONEMAP, hdr::INCLUDES).unwrap());
+static SRGB_RESOLVED: Lazy<String> = Lazy::new(|| resolve_includes(SRGB, hdr::INCLUDES).unwrap());
+
+fn draw_fullscreen() {
+    unsafe {
+        crate::gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+    }
+}
+
+/// A fullscreen fragment shader with the samplers it reads and the uniforms it is given.
+///
+/// Besides those, `texelSize` is set to the size of one scene pixel.
+pub struct Pass {
+    name: String,
+    /// Skipped when `false`, the next pass reads what this one would have.
+    pub enabled: bool,
+    shader: Shader<'static>,
+    inputs: Vec<(String, PassInput)>,
+    uniforms: Vec<(String, UniformValue)>,
+    bloom: Option<Bloom>,
+    ssao: Option<Ssao>,
+}
+
+impl Pass {
+    /// A pass reading the previous output from `screen`, `fragment` reads `texCoords`.
+    pub fn new(name: &str, fragment: &'static str) -> Self {
+        Self {
+            name: name.to_owned(),
+            enabled: true,
+            shader: Shader::new(FULLSCREEN_VERTEX, fragment),
+            inputs: vec![("screen".to_owned(), PassInput::Previous)],
+            uniforms: vec![],
+            bloom: None,
+            ssao: None,
+        }
+    }
+
+    pub fn with_input(mut self, sampler: &str, input: PassInput) -> Self {
+        self.inputs.push((sampler.to_owned(), input));
+        self
+    }
+
+    pub fn with_uniform(mut self, name: &str, value: UniformValue) -> Self {
+        self.set_uniform(name, value);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn inputs(&self) -> &[(String, PassInput)] {
+        &self.inputs
+    }
+
+    /// Takes effect on the next frame.
+    pub fn set_uniform(&mut self, name: &str, value: UniformValue) {
+        match self.uniforms.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None => self.uniforms.push((name.to_owned(), value)),
+        }
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&UniformValue> {
+        self.uniforms.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    pub fn grayscale() -> Self {
+        Self::new("grayscale", GRAYSCALE)
+    }
+
+    pub fn inversion() -> Self {
+        Self::new("inversion", INVERSION)
+    }
+
+    /// Convolve with a 3x3 `kernel`, like [`SHARPEN_KERNEL`].
+    pub fn kernel(kernel: [f32; 9]) -> Self {
+        Self::new("kernel", KERNEL).with_uniform("kernel", UniformValue::Floats(kernel.to_vec()))
+    }
+
+    pub fn gamma(gamma: f32) -> Self {
+        Self::new("gamma", GAMMA).with_uniform("gamma", UniformValue::F32(gamma))
+    }
+
+    /// Encode linear colors with the exact sRGB curve, instead of [`Pass::gamma`].
+    pub fn srgb() -> Self {
+        Self::new("srgb", SRGB_RESOLVED.as_str())
+    }
+
+    /// HDR to `[0, 1]`, the colors are multiplied by `exposure` first. Switch operators
+    /// by setting `tonemapper` to a [`Tonemapper::index`].
+    pub fn tonemap(tonemapper: Tonemapper, exposure: f32) -> Self {
+        Self::new("tonemap", TONEMAP_RESOLVED.as_str())
+            .with_uniform("exposure", UniformValue::F32(exposure))
+            .with_uniform("tonemapper", UniformValue::I32(tonemapper.index()))
+    }
+
+    /// Needs colors in `[0, 1]`, so after tonemapping.
+    pub fn fxaa() -> Self {
+        Self::new("fxaa", FXAA)
+    }
+
+    /// Add a gaussian blur of the bright parts, best before tonemapping.
+    /// The chain must be resized with [`PostProcess::resize`] to size it.
+    pub fn bloom(config: BloomConfig) -> Result<Self> {
+        let mut pass = Self::new("bloom", BLOOM_COMBINE).with_uniform("intensity", UniformValue::F32(config.intensity));
+        pass.bloom = Some(Bloom::new(config, 1, 1)?);
+        Ok(pass)
+    }
+
+    pub fn bloom_config(&self) -> Option<&BloomConfig> {
+        self.bloom.as_ref().map(|bloom| &bloom.config)
+    }
+
+    pub fn set_bloom_config(&mut self, config: BloomConfig) {
+        if let Some(bloom) = &mut self.bloom {
+            bloom.config = config;
+            self.set_uniform("intensity", UniformValue::F32(config.intensity));
+        }
+    }
+
+    /// Darken the previous output by ambient occlusion from the scene depth, with the
+    /// normals reconstructed from it. Best before tonemapping, and the camera must be
+    /// given every frame through [`ssao_mut`](Self::ssao_mut).
+    pub fn ssao(config: SsaoConfig) -> Result<Self> {
+        let mut pass = Self::new("ssao", ssao::APPLY);
+        pass.ssao = Some(Ssao::new(config, 1, 1)?);
+        Ok(pass)
+    }
+
+    pub fn ssao_mut(&mut self) -> Option<&mut Ssao> {
+        self.ssao.as_mut()
+    }
+}
+
+/// Where a pass draws to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Target(usize),
+    Screen,
+}
+
+/// Alternate between the two targets and end on the screen.
+fn outputs(passes: usize) -> Vec<Output> {
+    (0..passes).map(|i| if i + 1 == passes { Output::Screen } else { Output::Target(i % 2) }).collect()
+}
+
+pub struct PostProcess {
+    scene: Framebuffer,
+    targets: [Framebuffer; 2],
+    passes: Vec<Pass>,
+    copy: Shader<'static>,
+    vao: GLuint,
+}
+
+impl PostProcess {
+    /// Targets of `width` x `height` in `format`, use a float format to keep HDR colors
+    /// until a tonemapping pass.
+    pub fn new(width: u32, height: u32, format: TextureFormat) -> Result<Self> {
+        let scene = Framebuffer::new(
+            FramebufferConfig::new(width, height)
+                .with_color(format)
+                .with_depth_texture(TextureFormat::Depth24Stencil8),
+        )?;
+        let target = || Framebuffer::new(FramebufferConfig::new(width, height).with_color(format));
+        let targets = [target()?, target()?];
+
+        // core profiles draw nothing without a vertex array, even an empty one
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        Ok(Self { scene, targets, passes: vec![], copy: Shader::new(FULLSCREEN_VERTEX, COPY), vao })
+    }
+
+    /// Append a pass, sized to the chain.
+    pub fn push(&mut self, mut pass: Pass) -> &mut Self {
+        let (w, h) = self.size();
+        if let Some(bloom) = &mut pass.bloom {
+            bloom.resize(w, h);
+        }
+        if let Some(ssao) = &mut pass.ssao {
+            ssao.resize(w, h);
+        }
+        self.passes.push(pass);
+        self
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// The first pass named `name`.
+    pub fn pass(&self, name: &str) -> Option<&Pass> {
+        self.passes.iter().find(|pass| pass.name == name)
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut Pass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        self.pass_mut(name).ok_or_else(|| anyhow!("no post-processing pass {name}"))?.enabled = enabled;
+        Ok(())
+    }
+
+    /// Flip a pass on or off, returns whether it is enabled now.
+    pub fn toggle(&mut self, name: &str) -> Result<bool> {
+        let pass = self.pass_mut(name).ok_or_else(|| anyhow!("no post-processing pass {name}"))?;
+        pass.enabled = !pass.enabled;
+        Ok(pass.enabled)
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.scene.size()
+    }
+
+    /// The scene target, with a sampled color and depth-stencil texture.
+    pub fn scene(&self) -> &Framebuffer {
+        &self.scene
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.scene.resize(width, height);
+        for target in &mut self.targets {
+            target.resize(width, height);
+        }
+        for bloom in self.passes.iter_mut().filter_map(|pass| pass.bloom.as_mut()) {
+            bloom.resize(width, height);
+        }
+        for ssao in self.passes.iter_mut().filter_map(|pass| pass.ssao.as_mut()) {
+            ssao.resize(width, height);
+        }
+    }
+
+    /// Follow the size of `context`, then bind and clear the scene target with the current
+    /// clear color.
+    pub fn begin(&mut self, context: &GlContext) {
+        let size = context.size();
+        if (size.width, size.height) != self.size() && size.width > 0 && size.height > 0 {
+            self.resize(size.width, size.height);
+        }
+        self.scene.bind();
+        unsafe {
+            crate::gl_check!(gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT));
+        }
+    }
+
+    /// Run the enabled passes and draw the result to the screen of `context`,
+    /// which is left bound.
+    pub fn finish(&self, context: &GlContext) -> Result<()> {
+        let depth_test = unsafe { gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE };
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.vao);
+        }
+
+        let (w, h) = self.size();
+        let texel_size = UniformValue::Vec2(Vector2::new(1.0 / w as f32, 1.0 / h as f32));
+        let scene = self.scene.color(0);
+        let enabled: Vec<_> = self.passes.iter().filter(|pass| pass.enabled).collect();
+
+        let mut previous = scene;
+        for (pass, output) in enabled.iter().zip(outputs(enabled.len())) {
+            let bloom = pass.bloom.as_ref().map(|bloom| bloom.render(previous)).transpose()?;
+            let ssao = pass.ssao.as_ref().map(|ssao| ssao.render(self.scene.depth().unwrap(), None)).transpose()?;
+
+            match output {
+                Output::Target(i) => self.targets[i].bind(),
+                Output::Screen => Framebuffer::unbind(context),
+            }
+            pass.shader.activate()?;
+            let program = pass.shader.id();
+
+            let mut unit = 0;
+            for (sampler, input) in &pass.inputs {
+                let texture = match input {
+                    PassInput::Previous => previous,
+                    PassInput::Scene => scene,
+                    PassInput::SceneDepth => self.scene.depth().unwrap(),
+                };
+                texture.bind(unit);
+                UniformValue::I32(unit as i32).apply(program, sampler);
+                unit += 1;
+            }
+            if let Some(bloom) = bloom {
+                bloom.bind(unit);
+                UniformValue::I32(unit as i32).apply(program, "bloom");
+                unit += 1;
+            }
+            if let Some(ssao) = ssao {
+                ssao.bind(unit);
+                UniformValue::I32(unit as i32).apply(program, "ssao");
+            }
+            texel_size.apply(program, "texelSize");
+            for (name, value) in &pass.uniforms {
+                value.apply(program, name);
+            }
+            draw_fullscreen();
+
+            if let Output::Target(i) = output {
+                previous = self.targets[i].color(0);
+            }
+        }
+
+        if enabled.is_empty() {
+            Framebuffer::unbind(context);
+            self.copy.activate()?;
+            scene.bind(0);
+            UniformValue::I32(0).apply(self.copy.id(), "screen");
+            draw_fullscreen();
+        }
+
+        unsafe {
+            gl::BindVertexArray(0);
+            if depth_test {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PostProcess {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_ping_pong_and_end_on_screen() {
+        assert_eq!(outputs(0), vec![]);
+        assert_eq!(outputs(1), vec![Output::Screen]);
+        assert_eq!(outputs(4), vec![Output::Target(0), Output::Target(1), Output::Target(0), Output::Screen]);
+    }
+
+    #[test]
+    fn kernels_and_weights() {
+        // sharpen and blur keep flat areas as they are, edge detection makes them black
+        assert_eq!(SHARPEN_KERNEL.iter().sum::<f32>(), 1.0);
+        assert!((BLUR_KERNEL.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        assert_eq!(EDGE_KERNEL.iter().sum::<f32>(), 0.0);
+
+        let weights = gaussian_weights(4, 2.0);
+        assert_eq!(weights.len(), 5);
+        assert!((weights[0] + 2.0 * weights[1..].iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        assert!(weights.windows(2).all(|w| w[0] > w[1]));
+        // a wider sigma spreads the weight out
+        assert!(gaussian_weights(4, 4.0)[0] < weights[0]);
+
+        assert!((luma(&Vector3::new(1.0, 1.0, 1.0)) - 1.0).abs() < 1e-6);
+        assert!(luma(&Vector3::y()) > luma(&Vector3::x()) && luma(&Vector3::x()) > luma(&Vector3::z()));
+    }
+}
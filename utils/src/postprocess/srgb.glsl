@@ -0,0 +1,12 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+
+#include "tonemap.glsl"
+
+void main() {
+    vec4 color = texture(screen, texCoords);
+    fragColor = vec4(linearToSrgb(color.rgb), color.a);
+}
@@ -0,0 +1,15 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+uniform float exposure;
+// one of the TONEMAP_* operators
+uniform int tonemapper;
+
+#include "tonemap.glsl"
+
+void main() {
+    vec4 color = texture(screen, texCoords);
+    fragColor = vec4(tonemap(color.rgb * exposure, tonemapper), color.a);
+}
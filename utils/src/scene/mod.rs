@@ -0,0 +1,427 @@
+//! A transform hierarchy: nodes with local TRS transforms and cached world matrices.
+
+use nalgebra::{Matrix4, Point3, Translation3, UnitQuaternion, Vector3};
+
+use crate::bounds::{Aabb, Frustum, Ray};
+
+/// Translation, rotation and scale, applied as `T * R * S`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vector3::new(0.0, 0.0, 0.0),
+        rotation: UnitQuaternion::new_unchecked(nalgebra::Quaternion::new(1.0, 0.0, 0.0, 0.0)),
+        scale: Vector3::new(1.0, 1.0, 1.0),
+    };
+
+    pub fn from_translation(x: f32, y: f32, z: f32) -> Self {
+        Self { translation: Vector3::new(x, y, z), ..Self::IDENTITY }
+    }
+
+    pub fn with_rotation(mut self, rotation: UnitQuaternion<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, x: f32, y: f32, z: f32) -> Self {
+        self.scale = Vector3::new(x, y, z);
+        self
+    }
+
+    pub fn with_uniform_scale(self, scale: f32) -> Self {
+        self.with_scale(scale, scale, scale)
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Translation3::from(self.translation).to_homogeneous()
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    /// Turn around `axis` by `angle` radians, after the current rotation.
+    pub fn rotate(&mut self, axis: &nalgebra::Unit<Vector3<f32>>, angle: f32) {
+        self.rotation = UnitQuaternion::from_axis_angle(axis, angle) * self.rotation;
+    }
+
+    pub fn look_at(&mut self, target: &Point3<f32>, up: &Vector3<f32>) {
+        let eye = Point3::from(self.translation);
+        self.rotation = UnitQuaternion::face_towards(&(target - eye), up);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone)]
+pub struct Node<T> {
+    pub name: String,
+    pub data: T,
+    /// Hidden nodes and their children are skipped by [`Scene::draw_order`].
+    pub visible: bool,
+    local: Transform,
+    world: Matrix4<f32>,
+    dirty: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl<T> Node<T> {
+    pub fn local(&self) -> &Transform {
+        &self.local
+    }
+
+    /// Valid after [`Scene::update`].
+    pub fn world(&self) -> &Matrix4<f32> {
+        &self.world
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// Nodes are stored in an arena and referred to by [`NodeId`], ids of removed nodes are not reused.
+#[derive(Debug, Clone)]
+pub struct Scene<T> {
+    nodes: Vec<Option<Node<T>>>,
+    roots: Vec<NodeId>,
+    /// Some node changed since the last update.
+    dirty: bool,
+}
+
+impl<T> Default for Scene<T> {
+    fn default() -> Self {
+        Self { nodes: vec![], roots: vec![], dirty: false }
+    }
+}
+
+impl<T> Scene<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: &str, local: Transform, data: T) -> NodeId {
+        self.insert(None, name, local, data)
+    }
+
+    pub fn add_child(&mut self, parent: NodeId, name: &str, local: Transform, data: T) -> NodeId {
+        assert!(self.contains(parent), "no node {parent:?}");
+        self.insert(Some(parent), name, local, data)
+    }
+
+    fn insert(&mut self, parent: Option<NodeId>, name: &str, local: Transform, data: T) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(Node {
+            name: name.to_owned(),
+            data,
+            visible: true,
+            local,
+            world: Matrix4::identity(),
+            dirty: true,
+            parent,
+            children: vec![],
+        }));
+        self.siblings_mut(parent).push(id);
+        self.dirty = true;
+        id
+    }
+
+    fn siblings_mut(&mut self, parent: Option<NodeId>) -> &mut Vec<NodeId> {
+        match parent {
+            Some(parent) => &mut self.node_mut(parent).children,
+            None => &mut self.roots,
+        }
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.nodes.get(id.0).is_some_and(Option::is_some)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node<T> {
+        self.nodes[id.0].as_ref().expect("node was removed")
+    }
+
+    /// Mutable access to everything but the transform, see [`local_mut`](Self::local_mut).
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node<T> {
+        self.nodes[id.0].as_mut().expect("node was removed")
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|n| n.as_ref().is_some_and(|n| n.name == name)).map(NodeId)
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// The local transform, marking the node's world matrix for an update.
+    pub fn local_mut(&mut self, id: NodeId) -> &mut Transform {
+        self.dirty = true;
+        let node = self.node_mut(id);
+        node.dirty = true;
+        &mut node.local
+    }
+
+    pub fn set_local(&mut self, id: NodeId, local: Transform) {
+        *self.local_mut(id) = local;
+    }
+
+    pub fn is_ancestor(&self, ancestor: NodeId, mut id: NodeId) -> bool {
+        while let Some(parent) = self.node(id).parent {
+            if parent == ancestor {
+                return true;
+            }
+            id = parent;
+        }
+        false
+    }
+
+    /// Move `id` under `parent`, keeping its local transform. Panics on cycles.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        if let Some(parent) = parent {
+            assert!(parent != id && !self.is_ancestor(id, parent), "{parent:?} is below {id:?}");
+        }
+
+        let old = self.node(id).parent;
+        self.siblings_mut(old).retain(|&child| child != id);
+        self.siblings_mut(parent).push(id);
+
+        let node = self.node_mut(id);
+        node.parent = parent;
+        node.dirty = true;
+        self.dirty = true;
+    }
+
+    /// Remove a node with all its descendants.
+    pub fn remove(&mut self, id: NodeId) -> T {
+        let parent = self.node(id).parent;
+        self.siblings_mut(parent).retain(|&child| child != id);
+
+        let node = self.nodes[id.0].take().expect("node was removed");
+        for child in node.children {
+            self.remove_subtree(child);
+        }
+        node.data
+    }
+
+    fn remove_subtree(&mut self, id: NodeId) {
+        if let Some(node) = self.nodes[id.0].take() {
+            for child in node.children {
+                self.remove_subtree(child);
+            }
+        }
+    }
+
+    /// Recompute the world matrices of changed nodes and their descendants,
+    /// returns how many were recomputed.
+    pub fn update(&mut self) -> usize {
+        if !self.dirty {
+            return 0;
+        }
+        self.dirty = false;
+
+        let mut updated = 0;
+        let mut stack: Vec<(NodeId, bool)> = self.roots.iter().rev().map(|&id| (id, false)).collect();
+        while let Some((id, parent_changed)) = stack.pop() {
+            let parent_world = self.node(id).parent.map(|parent| self.node(parent).world);
+
+            let node = self.node_mut(id);
+            let changed = node.dirty || parent_changed;
+            if changed {
+                let local = node.local.matrix();
+                node.world = match parent_world {
+                    Some(parent) => parent * local,
+                    None => local,
+                };
+                node.dirty = false;
+                updated += 1;
+            }
+            stack.extend(node.children.iter().rev().map(|&child| (child, changed)));
+        }
+        updated
+    }
+
+    /// World matrix of `id`, updating the scene first.
+    pub fn world(&mut self, id: NodeId) -> Matrix4<f32> {
+        self.update();
+        self.node(id).world
+    }
+
+    /// Visible nodes depth first, parents before children and siblings in insertion order.
+    pub fn draw_order(&mut self) -> Vec<NodeId> {
+        self.update();
+
+        let mut order = vec![];
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let node = self.node(id);
+            if node.visible {
+                order.push(id);
+                stack.extend(node.children.iter().rev());
+            }
+        }
+        order
+    }
+
+    /// Visible nodes in draw order with their world matrix.
+    pub fn iter(&mut self) -> impl Iterator<Item=(NodeId, &Matrix4<f32>, &T)> {
+        let order = self.draw_order();
+        let scene = &*self;
+        order.into_iter().map(move |id| {
+            let node = scene.node(id);
+            (id, &node.world, &node.data)
+        })
+    }
+
+    /// Visible nodes in draw order whose local `bounds`, placed by their world matrix,
+    /// touch `frustum`. Nodes without bounds are always kept.
+    pub fn cull(&mut self, frustum: &Frustum, bounds: impl Fn(&T) -> Option<Aabb>) -> Vec<NodeId> {
+        let mut order = self.draw_order();
+        order.retain(|&id| {
+            let node = self.node(id);
+            bounds(&node.data).is_none_or(|aabb| frustum.intersects_aabb(&aabb.transformed(&node.world)))
+        });
+        order
+    }
+
+    /// Closest visible node whose world bounds `ray` hits, with the distance along the ray.
+    pub fn pick(&mut self, ray: &Ray, bounds: impl Fn(&T) -> Option<Aabb>) -> Option<(NodeId, f32)> {
+        self.draw_order()
+            .into_iter()
+            .filter_map(|id| {
+                let node = self.node(id);
+                let aabb = bounds(&node.data)?.transformed(&node.world);
+                ray.intersect_aabb(&aabb).map(|t| (id, t))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    fn world_position(scene: &mut Scene<()>, id: NodeId) -> Point3<f32> {
+        scene.world(id).transform_point(&Point3::origin())
+    }
+
+    #[test]
+    fn transform_order_is_translate_rotate_scale() {
+        let t = Transform::from_translation(1.0, 0.0, 0.0)
+            .with_rotation(UnitQuaternion::from_axis_angle(&Vector3::z_axis(), FRAC_PI_2))
+            .with_uniform_scale(2.0);
+
+        let p = t.matrix().transform_point(&Point3::new(1.0, 0.0, 0.0));
+        assert!((p - Point3::new(1.0, 2.0, 0.0)).norm() < 1e-5);
+        assert_eq!(Transform::default().matrix(), Matrix4::identity());
+    }
+
+    #[test]
+    fn children_follow_parents() {
+        let mut scene = Scene::new();
+        let sun = scene.add("sun", Transform::from_translation(10.0, 0.0, 0.0), ());
+        let earth = scene.add_child(sun, "earth", Transform::from_translation(5.0, 0.0, 0.0), ());
+        let moon = scene.add_child(earth, "moon", Transform::from_translation(1.0, 0.0, 0.0), ());
+
+        assert!((world_position(&mut scene, moon) - Point3::new(16.0, 0.0, 0.0)).norm() < 1e-5);
+
+        scene.local_mut(sun).rotation = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), FRAC_PI_2);
+        assert!((world_position(&mut scene, moon) - Point3::new(10.0, 6.0, 0.0)).norm() < 1e-5);
+        assert_eq!(scene.find("earth"), Some(earth));
+    }
+
+    #[test]
+    fn only_dirty_subtrees_are_recomputed() {
+        let mut scene = Scene::new();
+        let a = scene.add("a", Transform::default(), ());
+        let a1 = scene.add_child(a, "a1", Transform::default(), ());
+        scene.add_child(a1, "a2", Transform::default(), ());
+        scene.add("b", Transform::default(), ());
+
+        assert_eq!(scene.update(), 4);
+        assert_eq!(scene.update(), 0);
+
+        scene.local_mut(a1).translation.x = 1.0;
+        assert_eq!(scene.update(), 2);
+    }
+
+    #[test]
+    fn draw_order_and_reparenting() {
+        let mut scene = Scene::new();
+        let a = scene.add("a", Transform::from_translation(1.0, 0.0, 0.0), ());
+        let b = scene.add("b", Transform::default(), ());
+        let a1 = scene.add_child(a, "a1", Transform::default(), ());
+        let b1 = scene.add_child(b, "b1", Transform::default(), ());
+        assert_eq!(scene.draw_order(), vec![a, a1, b, b1]);
+
+        scene.set_parent(b, Some(a1));
+        assert_eq!(scene.draw_order(), vec![a, a1, b, b1]);
+        assert_eq!(scene.roots(), &[a]);
+        assert!((world_position(&mut scene, b1) - Point3::new(1.0, 0.0, 0.0)).norm() < 1e-5);
+
+        scene.node_mut(a1).visible = false;
+        assert_eq!(scene.draw_order(), vec![a]);
+
+        scene.remove(a1);
+        assert_eq!(scene.len(), 1);
+        assert!(!scene.contains(b1));
+    }
+
+    #[test]
+    fn culling_and_picking_use_world_bounds() {
+        let unit = Aabb::new(Point3::new(-0.5, -0.5, -0.5), Point3::new(0.5, 0.5, 0.5));
+        let mut scene = Scene::new();
+        let near = scene.add("near", Transform::from_translation(0.0, 0.0, -5.0), Some(unit));
+        let far = scene.add("far", Transform::from_translation(0.0, 0.0, -20.0), Some(unit));
+        let group = scene.add("group", Transform::from_translation(0.0, 0.0, 10.0), None);
+        let behind = scene.add_child(group, "behind", Transform::default(), Some(unit));
+        // the child of a node behind the camera is moved in front of it
+        let scaled = scene.add_child(group, "scaled", Transform::from_translation(3.0, 0.0, -20.0).with_uniform_scale(4.0), Some(unit));
+
+        let projection = nalgebra::Perspective3::new(1.0, FRAC_PI_2, 0.1, 10.0).to_homogeneous();
+        let visible = scene.cull(&Frustum::from_matrix(&projection), |bounds| *bounds);
+        assert_eq!(visible, vec![near, group, scaled]);
+        assert!(!visible.contains(&far) && !visible.contains(&behind));
+
+        let ray = Ray::new(Point3::origin(), -Vector3::z());
+        assert_eq!(scene.pick(&ray, |bounds| *bounds), Some((near, 4.5)));
+        scene.node_mut(near).visible = false;
+        assert_eq!(scene.pick(&ray, |bounds| *bounds), Some((far, 19.5)));
+        // 4 units wide around x = 3
+        assert_eq!(scene.pick(&Ray::new(Point3::new(1.5, 0.0, 0.0), -Vector3::z()), |bounds| *bounds).map(|hit| hit.0), Some(scaled));
+    }
+
+    #[test]
+    #[should_panic]
+    fn cycles_are_rejected() {
+        let mut scene = Scene::new();
+        let a = scene.add("a", Transform::default(), ());
+        let b = scene.add_child(a, "b", Transform::default(), ());
+        scene.set_parent(a, Some(b));
+    }
+}
@@ -1,10 +1,10 @@
 use std::ffi::{CStr, CString};
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use gl::*;
 use gl::types::*;
-use nalgebra::{Matrix4, OMatrix};
+use nalgebra::Matrix4;
 
 pub fn compile_shader(source: &str, shader_type: GLenum) -> Result<GLuint, String> {
     let src = CString::new(source).unwrap();
@@ -53,6 +53,43 @@ pub fn get_status_and_output(
     }
 }
 
+/// Replace every `#include "name"` line with the matching source of `includes`,
+/// recursively.
+///
+/// `#line` directives keep error messages pointing at the right line: an included body
+/// starts at line 1 of source string `index + 1` of `includes`, `source` itself is string 0.
+pub fn resolve_includes(source: &str, includes: &[(&str, &str)]) -> Result<String> {
+    fn resolve(source: &str, string: usize, includes: &[(&str, &str)], stack: &mut Vec<String>, out: &mut String) -> Result<()> {
+        for (number, line) in source.lines().enumerate() {
+            let Some(name) = line.trim().strip_prefix("#include") else {
+                out.push_str(line);
+                out.push('\n');
+                continue;
+            };
+
+            let name = name.trim().trim_matches('"');
+            if stack.iter().any(|included| included == name) {
+                bail!("\"{name}\" includes itself");
+            }
+            let index = includes.iter()
+                .position(|(candidate, _)| *candidate == name)
+                .ok_or_else(|| anyhow!("unknown include \"{name}\""))?;
+
+            stack.push(name.to_string());
+            out.push_str(&format!("#line 1 {}\n", index + 1));
+            resolve(includes[index].1, index + 1, includes, stack, out)?;
+            stack.pop();
+            // `#line` sets the number of the line after it
+            out.push_str(&format!("#line {} {string}\n", number + 2));
+        }
+        Ok(())
+    }
+
+    let mut out = String::with_capacity(source.len());
+    resolve(source, 0, includes, &mut vec![], &mut out)?;
+    Ok(out)
+}
+
 pub struct Shader<'shader_src_life> {
     prog: GLuint,
 
@@ -61,10 +98,10 @@ pub struct Shader<'shader_src_life> {
 }
 
 macro_rules! build_uniform_setter {
-    (@setter 1, $setter:tt, $loc:ident, $d:ident) => { $setter($loc, $d.0) };
-    (@setter 2, $setter:tt, $loc:ident, $d:ident) => { $setter($loc, $d.0, $d.1) };
-    (@setter 3, $setter:tt, $loc:ident, $d:ident) => { $setter($loc, $d.0, $d.1, $d.2) };
-    (@setter 4, $setter:tt, $loc:ident, $d:ident) => { $setter($loc, $d.0, $d.1, $d.2, $d.3) };
+    (@setter 1, $setter:tt, $loc:ident, $d:ident) => { crate::gl_check!($setter($loc, $d.0)) };
+    (@setter 2, $setter:tt, $loc:ident, $d:ident) => { crate::gl_check!($setter($loc, $d.0, $d.1)) };
+    (@setter 3, $setter:tt, $loc:ident, $d:ident) => { crate::gl_check!($setter($loc, $d.0, $d.1, $d.2)) };
+    (@setter 4, $setter:tt, $loc:ident, $d:ident) => { crate::gl_check!($setter($loc, $d.0, $d.1, $d.2, $d.3)) };
 
     (@typer 1, $t:tt) => { ($t,) };
     (@typer 2, $t:tt) => { ($t, $t, ) };
@@ -120,8 +157,23 @@ impl<'a> Shader<'a> {
         }
     }
 
+    pub fn id(&self) -> GLuint {
+        self.prog
+    }
+
+    /// Read the uniform block `name` from the buffer bound at `binding`.
+    pub fn bind_uniform_block(&self, name: &str, binding: GLuint) -> Result<()> {
+        let c_name = CString::new(name).unwrap();
+        let index = unsafe { gl::GetUniformBlockIndex(self.prog, c_name.as_ptr()) };
+        if index == INVALID_INDEX {
+            bail!("no uniform block {name} in the program");
+        }
+        unsafe { crate::gl_check!(gl::UniformBlockBinding(self.prog, index, binding)); }
+        Ok(())
+    }
+
     pub fn activate(&self) -> Result<()> {
-        unsafe { gl::UseProgram(self.prog); }
+        unsafe { crate::gl_check!(gl::UseProgram(self.prog)); }
         Ok(())
     }
 
@@ -150,7 +202,7 @@ impl<'a> Shader<'a> {
 
         unsafe {
             let ptr = mat.as_ptr();
-            gl::UniformMatrix4fv(loc, 1, FALSE, ptr as *const _);
+            crate::gl_check!(gl::UniformMatrix4fv(loc, 1, FALSE, ptr as *const _));
         }
 
         Ok(())
@@ -163,4 +215,54 @@ impl<'a> Drop for Shader<'a> {
             gl::DeleteProgram(self.prog);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_are_resolved() {
+        let includes = [("common.glsl", "float twice(float x) { return 2.0 * x; }"), ("lights.glsl", "#include \"common.glsl\"\nvec3 light;")];
+        let source = resolve_includes("#version 330 core\n#include \"lights.glsl\"\nvoid main() {}", &includes).unwrap();
+        assert_eq!(source, "#version 330 core\n\
+                            #line 1 2\n\
+                            #line 1 1\n\
+                            float twice(float x) { return 2.0 * x; }\n\
+                            #line 2 2\n\
+                            vec3 light;\n\
+                            #line 3 0\n\
+                            void main() {}\n");
+
+        assert!(resolve_includes("#include \"missing.glsl\"", &includes).is_err());
+        assert!(resolve_includes("#include \"a\"", &[("a", "#include \"b\""), ("b", "#include \"a\"")]).is_err());
+    }
+
+    #[test]
+    fn included_lines_keep_their_numbers() {
+        let includes = [("common.glsl", "float a;\nfloat b;"), ("lights.glsl", "vec3 light;\n#include \"common.glsl\"\nvec3 dir;")];
+        let source = resolve_includes("#version 330 core\n#include \"lights.glsl\"\nvoid main() {}", &includes).unwrap();
+
+        // (line, source string) of every line, as the compiler counts them
+        let mut position = (1, 0);
+        let mut lines = vec![];
+        for line in source.lines() {
+            if let Some(directive) = line.strip_prefix("#line ") {
+                let mut numbers = directive.split(' ').map(|n| n.parse().unwrap());
+                position = (numbers.next().unwrap(), numbers.next().unwrap());
+                continue;
+            }
+            lines.push((line, position));
+            position.0 += 1;
+        }
+
+        assert_eq!(lines, [
+            ("#version 330 core", (1, 0)),
+            ("vec3 light;", (1, 2)),
+            ("float a;", (1, 1)),
+            ("float b;", (2, 1)),
+            ("vec3 dir;", (3, 2)),
+            ("void main() {}", (3, 0)),
+        ]);
+    }
+}
@@ -4,7 +4,7 @@ use learnopengl_utils::shader::Shader;
 use glutin::event_loop::ControlFlow;
 
 fn main() {
-    let (_window, ev) = init_headless(800, 600, true);
+    let (window, ev) = init_headless(800, 600, true);
 
 
     let shader = Shader::new(
@@ -17,7 +17,69 @@ fn main() {
 
     shader.activate().ok();
 
+    framebuffer_keeps_the_headless_target(&window);
+    brdf_lut_matches_cpu();
+    pbr_shader_compiles();
+    normal_mapping_shader_compiles();
+    replay_restores_state_only();
+
     ev.run(|_event, _, control_flow| {
         *control_flow = ControlFlow::Exit;
     })
-}
\ No newline at end of file
+}
+
+/// Creating a framebuffer must not unbind the offscreen target of the headless context.
+fn framebuffer_keeps_the_headless_target(window: &GlContext) {
+    let _framebuffer = framebuffer::Framebuffer::new(framebuffer::FramebufferConfig::new(16, 16).with_color(texture::TextureFormat::Rgba8)).unwrap();
+    let mut bound = 0;
+    unsafe { gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut bound) };
+    assert_eq!(bound as u32, window.default_framebuffer());
+}
+
+/// The GPU BRDF lookup table against the CPU reference of `pbr::brdf_lut`.
+fn brdf_lut_matches_cpu() {
+    let (size, samples) = (32, 256);
+    let gpu = pbr::brdf_lut_texture(size, samples).unwrap().read_f32(0, 0).unwrap();
+    let cpu = pbr::brdf_lut(size, samples);
+    assert_eq!(gpu.len(), cpu.len());
+    // the texture is half floats
+    let worst = gpu.iter().zip(&cpu).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+    assert!(worst < 5e-3, "the GPU lookup table is off by up to {worst}");
+}
+
+/// `Shader::new` panics if the lit PBR shader does not compile or link.
+fn pbr_shader_compiles() {
+    pbr::shader();
+}
+
+/// The tangent space shaders with parallax mapping compile and link.
+fn normal_mapping_shader_compiles() {
+    normal_mapping::shader();
+}
+
+/// Replaying a frame sets the recorded state and skips creating objects.
+fn replay_restores_state_only() {
+    use trace::{Call, Frame};
+
+    let call = |name: &str, args: &[&str]| Call { name: name.to_owned(), args: args.iter().map(|a| a.to_string()).collect(), result: None };
+    let frame = Frame {
+        index: 0,
+        calls: vec![
+            call("GenBuffers", &["1", "0x7ffd5a3c"]),
+            call("ClearColor", &["0.25", "0.5", "0.75", "1.0"]),
+            call("Enable", &[&gl::DEPTH_TEST.to_string()]),
+        ],
+    };
+
+    let report = unsafe { trace::replay_frame(&frame) }.unwrap();
+    assert_eq!(report.replayed, 2);
+    assert_eq!(report.skipped, frame.calls[..1]);
+
+    let mut color = [0.0f32; 4];
+    unsafe {
+        gl::GetFloatv(gl::COLOR_CLEAR_VALUE, color.as_mut_ptr());
+        assert_eq!(gl::IsEnabled(gl::DEPTH_TEST), gl::TRUE);
+        gl::Disable(gl::DEPTH_TEST);
+    }
+    assert_eq!(color, [0.25, 0.5, 0.75, 1.0]);
+}
@@ -0,0 +1,489 @@
+//! Shadow maps for directional, spot and point lights, with optional cascades.
+//!
+//! Each map renders the scene with [`DEPTH_VERTEX`] and [`DEPTH_FRAGMENT`]
+//! ([`POINT_DEPTH_FRAGMENT`] for point lights) between `begin_*` and
+//! [`Framebuffer::unbind`], then the lit pass `#include "shadow.glsl"` (see [`INCLUDES`])
+//! and reads the map through `apply`. The light matrices are plain functions so they
+//! can be tested without a context.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use nalgebra::{Isometry3, Matrix4, Point3, Vector3, Vector4};
+
+use crate::bounds::Aabb;
+use crate::camera::{Camera, Projection};
+use crate::framebuffer::{Framebuffer, FramebufferConfig};
+use crate::lighting::SpotLight;
+use crate::math;
+use crate::shader::Shader;
+use crate::texture::{Texture, TextureFormat, TextureKind};
+
+pub const GLSL: &str = include_str!("shadow.glsl");
+
+pub const INCLUDES: &[(&str, &str)] = &[("shadow.glsl", GLSL)];
+
+/// Takes `lightSpace` and `model`, passes `worldPos` on.
+pub const DEPTH_VERTEX: &str = include_str!("depth_vertex.glsl");
+
+pub const DEPTH_FRAGMENT: &str = include_str!("depth_fragment.glsl");
+
+/// Writes the distance to `lightPos` divided by `far`, for [`PointShadowMap`].
+pub const POINT_DEPTH_FRAGMENT: &str = include_str!("point_depth_fragment.glsl");
+
+/// Same as `MAX_CASCADES` in [`GLSL`].
+pub const MAX_CASCADES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowConfig {
+    /// Width and height of the map, of every face and cascade.
+    pub resolution: u32,
+    /// Depth bias for surfaces facing the light, in the `[0, 1]` depth of the map.
+    /// Point shadows compare world units instead.
+    pub bias: f32,
+    /// Bias for surfaces at grazing angles, where acne is worst.
+    pub slope_bias: f32,
+    /// Texels sampled on each side, `0` is a single hard lookup.
+    pub pcf_radius: u32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self { resolution: 2048, bias: 0.005, slope_bias: 0.05, pcf_radius: 1 }
+    }
+}
+
+impl ShadowConfig {
+    /// Same as `shadowBias` in [`GLSL`], `light_dir` points from the surface towards the light.
+    pub fn bias_for(&self, normal: &Vector3<f32>, light_dir: &Vector3<f32>) -> f32 {
+        (self.slope_bias * (1.0 - normal.dot(light_dir))).max(self.bias)
+    }
+}
+
+/// World to light space for a light at `position` shining along `direction`.
+pub fn light_view(position: &Point3<f32>, direction: &Vector3<f32>) -> Matrix4<f32> {
+    let direction = direction.normalize();
+    // any up works as long as it is not parallel to the direction
+    let up = if direction.y.abs() > 0.99 { Vector3::z() } else { Vector3::y() };
+    math::look_at(position, &(position + direction), &up)
+}
+
+/// Orthographic light space of a directional light that just covers `bounds`.
+pub fn fit_directional(direction: &Vector3<f32>, bounds: &Aabb) -> Matrix4<f32> {
+    let view = light_view(&bounds.center(), direction);
+    let (min, max) = light_space_extent(&view, &bounds.corners());
+    math::ortho(min.x, max.x, min.y, max.y, -max.z, -min.z) * view
+}
+
+fn light_space_extent(view: &Matrix4<f32>, points: &[Point3<f32>]) -> (Point3<f32>, Point3<f32>) {
+    let points: Vec<_> = points.iter().map(|p| view.transform_point(p)).collect();
+    let extent = Aabb::from_points(&points).unwrap();
+    (extent.min, extent.max)
+}
+
+/// Perspective light space of a spot light, its cone just fits the map.
+pub fn spot_light_space(light: &SpotLight, near: f32, far: f32) -> Matrix4<f32> {
+    math::perspective(2.0 * light.outer_cutoff, 1.0, near, far) * light_view(&light.position, &light.direction)
+}
+
+/// Light space of each cube face, in the order of `GL_TEXTURE_CUBE_MAP_POSITIVE_X + face`.
+pub fn point_light_faces(position: &Point3<f32>, near: f32, far: f32) -> [Matrix4<f32>; 6] {
+    // cube maps are looked up with the faces upside down
+    let faces = [
+        (Vector3::x(), -Vector3::y()),
+        (-Vector3::x(), -Vector3::y()),
+        (Vector3::y(), Vector3::z()),
+        (-Vector3::y(), -Vector3::z()),
+        (Vector3::z(), -Vector3::y()),
+        (-Vector3::z(), -Vector3::y()),
+    ];
+    let projection = math::perspective(std::f32::consts::FRAC_PI_2, 1.0, near, far);
+    faces.map(|(direction, up)| projection * math::look_at(position, &(position + direction), &up))
+}
+
+/// `count + 1` view distances from `near` to `far` bounding each cascade, blending an
+/// even split (`lambda = 0`) with a logarithmic one (`lambda = 1`).
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (0..=count)
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            let log = near * (far / near).powf(t);
+            let uniform = near + (far - near) * t;
+            lambda * log + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// World space corners of the part of the camera frustum between the view distances
+/// `near` and `far`, the four near corners first.
+pub fn slice_corners(camera: &Camera, near: f32, far: f32) -> [Point3<f32>; 8] {
+    let half_extent = |distance: f32| match camera.projection {
+        Projection::Perspective { fov_y, aspect, .. } => {
+            let h = distance * (fov_y / 2.0).tan();
+            (h * aspect, h)
+        }
+        Projection::Orthographic { height, aspect, .. } => (height * aspect / 2.0, height / 2.0),
+    };
+
+    let to_world = Isometry3::from_parts(camera.position.coords.into(), camera.orientation);
+    let mut corners = [Point3::origin(); 8];
+    for (i, distance) in [near, far].into_iter().enumerate() {
+        let (w, h) = half_extent(distance);
+        for (j, (x, y)) in [(-w, -h), (w, -h), (w, h), (-w, h)].into_iter().enumerate() {
+            corners[i * 4 + j] = to_world * Point3::new(x, y, -distance);
+        }
+    }
+    corners
+}
+
+/// Orthographic light space covering `corners` of a cascade, and everything in `bounds`
+/// between them and the light so casters outside the view still cast.
+///
+/// The extent is the bounding sphere of the corners snapped to whole texels of a map of
+/// `resolution`, so shadow edges do not shimmer while the camera moves or turns.
+pub fn fit_cascade(direction: &Vector3<f32>, corners: &[Point3<f32>; 8], bounds: &Aabb, resolution: u32) -> Matrix4<f32> {
+    let center = Point3::from(corners.iter().map(|p| p.coords).sum::<Vector3<f32>>() / 8.0);
+    let radius = corners.iter().map(|p| nalgebra::distance(p, &center)).fold(0.0, f32::max);
+    // quantized so the texel size stays fixed too
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let view = light_view(&center, direction);
+    let (min, max) = light_space_extent(&view, &bounds.corners());
+    let (near, far) = ((-max.z).min(-radius), (-min.z).max(radius));
+    let projection = math::ortho(-radius, radius, -radius, radius, near, far);
+
+    // move the projection so the world origin lands on a texel corner
+    let origin = (projection * view) * Vector4::w();
+    let texels = resolution as f32 / 2.0;
+    let offset = origin.xy() * texels;
+    let snap = (offset.map(f32::round) - offset) / texels;
+    let mut projection = projection;
+    projection[(0, 3)] += snap.x;
+    projection[(1, 3)] += snap.y;
+    projection * view
+}
+
+/// A depth map from a directional or spot light, the `ShadowMap` struct of [`GLSL`].
+pub struct ShadowMap {
+    framebuffer: Framebuffer,
+    config: ShadowConfig,
+    light_space: Matrix4<f32>,
+}
+
+/// A depth texture that reads as lit outside the map.
+fn shadow_framebuffer(config: &ShadowConfig, kind: TextureKind) -> Result<Framebuffer> {
+    let framebuffer = Framebuffer::new(
+        FramebufferConfig::new(config.resolution, config.resolution)
+            .with_kind(kind)
+            .with_depth_texture(TextureFormat::Depth24),
+    )?;
+    let depth = framebuffer.depth().unwrap();
+    if kind == TextureKind::Cube {
+        depth.set_wrap(gl::CLAMP_TO_EDGE);
+    } else {
+        depth.set_wrap(gl::CLAMP_TO_BORDER);
+        depth.set_border_color([1.0; 4]);
+    }
+    Ok(framebuffer)
+}
+
+fn clear_depth() {
+    unsafe {
+        gl::DepthMask(gl::TRUE);
+        crate::gl_check!(gl::Clear(gl::DEPTH_BUFFER_BIT));
+    }
+}
+
+impl ShadowMap {
+    pub fn new(config: ShadowConfig) -> Result<Self> {
+        let framebuffer = shadow_framebuffer(&config, TextureKind::D2)?;
+        Ok(Self { framebuffer, config, light_space: Matrix4::identity() })
+    }
+
+    pub fn config(&self) -> &ShadowConfig {
+        &self.config
+    }
+
+    pub fn texture(&self) -> &Texture {
+        self.framebuffer.depth().unwrap()
+    }
+
+    /// The matrix of the last `begin_*`, the `lightSpace` of the depth pass.
+    pub fn light_space(&self) -> Matrix4<f32> {
+        self.light_space
+    }
+
+    /// Bind and clear the map for a directional light covering `bounds`.
+    pub fn begin_directional(&mut self, direction: &Vector3<f32>, bounds: &Aabb) -> Matrix4<f32> {
+        self.begin(fit_directional(direction, bounds))
+    }
+
+    pub fn begin_spot(&mut self, light: &SpotLight, near: f32, far: f32) -> Matrix4<f32> {
+        self.begin(spot_light_space(light, near, far))
+    }
+
+    /// Bind and clear the map for any `light_space` matrix.
+    pub fn begin(&mut self, light_space: Matrix4<f32>) -> Matrix4<f32> {
+        self.light_space = light_space;
+        self.framebuffer.bind();
+        clear_depth();
+        light_space
+    }
+
+    /// Bind the map to `unit` and set the fields of the `uniform` struct, `shader` must be active.
+    pub fn apply(&self, shader: &Shader, uniform: &str, unit: u32) -> Result<()> {
+        self.texture().bind(unit);
+        shader.set_i32(&format!("{uniform}.map"), (unit as i32,))?;
+        shader.set_mat4fv(&format!("{uniform}.lightSpace"), Arc::new(self.light_space))?;
+        apply_config(shader, uniform, &self.config)
+    }
+}
+
+fn apply_config(shader: &Shader, uniform: &str, config: &ShadowConfig) -> Result<()> {
+    shader.set_f32(&format!("{uniform}.bias"), (config.bias,))?;
+    shader.set_f32(&format!("{uniform}.slopeBias"), (config.slope_bias,))?;
+    shader.set_i32(&format!("{uniform}.pcfRadius"), (config.pcf_radius as i32,))
+}
+
+/// A depth cube map around a point light, the `PointShadow` struct of [`GLSL`].
+pub struct PointShadowMap {
+    framebuffer: Framebuffer,
+    config: ShadowConfig,
+    position: Point3<f32>,
+    near: f32,
+    far: f32,
+}
+
+impl PointShadowMap {
+    /// Casters closer than `near` or further than `far` from the light are ignored.
+    pub fn new(config: ShadowConfig, near: f32, far: f32) -> Result<Self> {
+        let framebuffer = shadow_framebuffer(&config, TextureKind::Cube)?;
+        Ok(Self { framebuffer, config, position: Point3::origin(), near, far })
+    }
+
+    pub fn texture(&self) -> &Texture {
+        self.framebuffer.depth().unwrap()
+    }
+
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    /// Bind and clear cube face `face` for a light at `position` and return its `lightSpace`,
+    /// the depth pass also needs `lightPos` and `far`.
+    pub fn begin_face(&mut self, position: &Point3<f32>, face: u32) -> Result<Matrix4<f32>> {
+        self.framebuffer.select_layer(face)?;
+        self.position = *position;
+        clear_depth();
+        Ok(point_light_faces(position, self.near, self.far)[face as usize])
+    }
+
+    pub fn apply(&self, shader: &Shader, uniform: &str, unit: u32) -> Result<()> {
+        self.texture().bind(unit);
+        let p = self.position;
+        shader.set_i32(&format!("{uniform}.map"), (unit as i32,))?;
+        shader.set_vec3(&format!("{uniform}.position"), (p.x, p.y, p.z))?;
+        shader.set_f32(&format!("{uniform}.far"), (self.far,))?;
+        shader.set_f32(&format!("{uniform}.bias"), (self.config.bias,))?;
+        shader.set_i32(&format!("{uniform}.pcfRadius"), (self.config.pcf_radius as i32,))
+    }
+}
+
+/// Cascaded shadow maps of a directional light, each covering a slice of the camera
+/// frustum, the `CascadedShadow` struct of [`GLSL`].
+pub struct CascadedShadowMap {
+    framebuffer: Framebuffer,
+    config: ShadowConfig,
+    lambda: f32,
+    splits: Vec<f32>,
+    light_spaces: Vec<Matrix4<f32>>,
+}
+
+impl CascadedShadowMap {
+    /// `count` cascades split with `lambda`, see [`cascade_splits`].
+    pub fn new(config: ShadowConfig, count: usize, lambda: f32) -> Result<Self> {
+        if !(1..=MAX_CASCADES).contains(&count) {
+            bail!("{count} cascades, between 1 and {MAX_CASCADES} are supported");
+        }
+        let framebuffer = shadow_framebuffer(&config, TextureKind::D2Array { layers: count as u32 })?;
+        Ok(Self { framebuffer, config, lambda, splits: vec![], light_spaces: vec![Matrix4::identity(); count] })
+    }
+
+    pub fn count(&self) -> usize {
+        self.light_spaces.len()
+    }
+
+    pub fn texture(&self) -> &Texture {
+        self.framebuffer.depth().unwrap()
+    }
+
+    /// The view distances of the last [`update`](Self::update), see [`cascade_splits`].
+    pub fn splits(&self) -> &[f32] {
+        &self.splits
+    }
+
+    pub fn light_spaces(&self) -> &[Matrix4<f32>] {
+        &self.light_spaces
+    }
+
+    /// Fit every cascade to `camera`, the shadows reach as far as its far plane or the
+    /// furthest corner of `bounds`, whichever is closer.
+    pub fn update(&mut self, camera: &Camera, direction: &Vector3<f32>, bounds: &Aabb) {
+        let near = camera.projection.near();
+        let furthest = bounds.corners().iter().map(|p| nalgebra::distance(p, &camera.position)).fold(0.0, f32::max);
+        let far = camera.projection.far().map_or(furthest, |far| far.min(furthest)).max(near * 2.0);
+
+        self.splits = cascade_splits(near, far, self.count(), self.lambda);
+        self.light_spaces = self
+            .splits
+            .windows(2)
+            .map(|slice| fit_cascade(direction, &slice_corners(camera, slice[0], slice[1]), bounds, self.config.resolution))
+            .collect();
+    }
+
+    /// Bind and clear cascade `index` and return its `lightSpace`.
+    pub fn begin_cascade(&mut self, index: usize) -> Result<Matrix4<f32>> {
+        self.framebuffer.select_layer(index as u32)?;
+        clear_depth();
+        Ok(self.light_spaces[index])
+    }
+
+    pub fn apply(&self, shader: &Shader, uniform: &str, unit: u32) -> Result<()> {
+        self.texture().bind(unit);
+        shader.set_i32(&format!("{uniform}.maps"), (unit as i32,))?;
+        for (i, light_space) in self.light_spaces.iter().enumerate() {
+            shader.set_mat4fv(&format!("{uniform}.lightSpaces[{i}]"), Arc::new(*light_space))?;
+        }
+        let mut ends = [f32::MAX; MAX_CASCADES];
+        for (end, split) in ends.iter_mut().zip(self.splits.iter().skip(1)) {
+            *end = *split;
+        }
+        shader.set_vec4(&format!("{uniform}.splits"), (ends[0], ends[1], ends[2], ends[3]))?;
+        shader.set_i32(&format!("{uniform}.count"), (self.count() as i32,))?;
+        apply_config(shader, uniform, &self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector2;
+
+    use crate::lighting::{Attenuation, LightColor};
+
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn inside_clip(m: &Matrix4<f32>, p: &Point3<f32>) -> bool {
+        let ndc = m.transform_point(p);
+        ndc.iter().all(|c| (-1.0 - 1e-4..=1.0 + 1e-4).contains(c))
+    }
+
+    #[test]
+    fn directional_fit_covers_the_bounds() {
+        let bounds = Aabb::new(Point3::new(-4.0, -1.0, -3.0), Point3::new(6.0, 2.0, 5.0));
+        for direction in [Vector3::new(-1.0, -2.0, -0.5), -Vector3::y(), Vector3::new(0.3, 0.1, 1.0)] {
+            let m = fit_directional(&direction, &bounds);
+            assert!(bounds.corners().iter().all(|p| inside_clip(&m, p)));
+            // tight: some corner touches each side of the map
+            let ndc: Vec<_> = bounds.corners().iter().map(|p| m.transform_point(p)).collect();
+            for axis in 0..3 {
+                assert!(ndc.iter().any(|p| close(p[axis], 1.0)) && ndc.iter().any(|p| close(p[axis], -1.0)));
+            }
+            // nearer to the light is smaller depth
+            let center = bounds.center();
+            assert!(m.transform_point(&(center - direction)).z < m.transform_point(&(center + direction)).z);
+        }
+    }
+
+    #[test]
+    fn spot_and_point_light_spaces() {
+        let spot = SpotLight {
+            position: Point3::new(1.0, 4.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 0.0),
+            color: LightColor::default(),
+            attenuation: Attenuation::NONE,
+            inner_cutoff: 0.3,
+            outer_cutoff: 0.4,
+        };
+        let m = spot_light_space(&spot, 0.1, 20.0);
+        let below = m.transform_point(&Point3::new(1.0, 0.0, 0.0));
+        assert!(close(below.x, 0.0) && close(below.y, 0.0));
+        // the edge of the outer cone is the edge of the map
+        let edge = m.transform_point(&(spot.position + Vector3::new(0.4f32.tan(), -1.0, 0.0)));
+        assert!(close(edge.x.abs().max(edge.y.abs()), 1.0));
+
+        let light = Point3::new(2.0, 1.0, -1.0);
+        let faces = point_light_faces(&light, 0.1, 25.0);
+        let axes = [Vector3::x(), -Vector3::x(), Vector3::y(), -Vector3::y(), Vector3::z(), -Vector3::z()];
+        for (face, axis) in faces.iter().zip(axes) {
+            let p = face.transform_point(&(light + axis * 5.0));
+            assert!(close(p.x, 0.0) && close(p.y, 0.0) && p.z.abs() < 1.0);
+            assert!(!inside_clip(face, &(light - axis * 5.0)));
+        }
+        // +x face: +z shows up on the left, -y on top as the cube map lookup expects
+        let p = faces[0].transform_point(&(light + Vector3::new(5.0, -1.0, 1.0)));
+        assert!(p.x < 0.0 && p.y > 0.0);
+    }
+
+    #[test]
+    fn splits_blend_uniform_and_logarithmic() {
+        let uniform = cascade_splits(1.0, 101.0, 4, 0.0);
+        assert_eq!(uniform, vec![1.0, 26.0, 51.0, 76.0, 101.0]);
+        let log = cascade_splits(1.0, 10000.0, 4, 1.0);
+        for (split, expected) in log.iter().zip([1.0, 10.0, 100.0, 1000.0, 10000.0]) {
+            assert!((split - expected).abs() < expected * 1e-4);
+        }
+        let mixed = cascade_splits(0.1, 100.0, 3, 0.5);
+        assert!(mixed.windows(2).all(|w| w[0] < w[1]));
+        assert!(close(mixed[0], 0.1) && close(mixed[3], 100.0));
+    }
+
+    #[test]
+    fn cascades_cover_their_slice() {
+        let mut camera = Camera::new(Point3::new(0.0, 2.0, 8.0), Projection::perspective(1.0, 16.0 / 9.0, 0.1, 50.0));
+        camera.look_at(&Point3::new(1.0, 0.0, 0.0), &Vector3::y());
+
+        // the whole range matches the camera frustum
+        let corners = slice_corners(&camera, 0.1, 50.0);
+        let vp = camera.view_projection();
+        let ndc: Vec<_> = corners.iter().map(|p| vp.transform_point(p)).collect();
+        assert!(ndc[..4].iter().all(|p| close(p.z, -1.0)) && ndc[4..].iter().all(|p| close(p.z, 1.0)));
+        assert!(ndc.iter().all(|p| close(p.x.abs(), 1.0) && close(p.y.abs(), 1.0)));
+
+        let bounds = Aabb::new(Point3::new(-30.0, -1.0, -30.0), Point3::new(30.0, 10.0, 30.0));
+        let direction = Vector3::new(-0.4, -1.0, -0.2);
+        let splits = cascade_splits(0.1, 50.0, 3, 0.7);
+        let mut previous_size = 0.0;
+        for slice in splits.windows(2) {
+            let corners = slice_corners(&camera, slice[0], slice[1]);
+            let m = fit_cascade(&direction, &corners, &bounds, 1024);
+            assert!(corners.iter().all(|p| inside_clip(&m, p)));
+
+            // everything of the scene above the slice is in front of the near plane
+            let above = Point3::new(corners[0].x, 9.9, corners[0].z);
+            assert!(m.transform_point(&above).z >= -1.0);
+
+            // further cascades cover more
+            let size = 2.0 / m[(0, 0)];
+            assert!(size > previous_size);
+            previous_size = size;
+
+            // the world origin sits on a texel corner
+            let origin = m.transform_point(&Point3::origin());
+            let texels = Vector2::new(origin.x, origin.y) * 512.0;
+            assert!(texels.iter().all(|t| (t - t.round()).abs() < 1e-2));
+        }
+    }
+
+    #[test]
+    fn bias_grows_at_grazing_angles() {
+        let config = ShadowConfig::default();
+        let n = Vector3::y();
+        assert_eq!(config.bias_for(&n, &n), config.bias);
+        let grazing = Vector3::new(1.0, 0.05, 0.0).normalize();
+        assert!(config.bias_for(&n, &grazing) > 0.04);
+    }
+}
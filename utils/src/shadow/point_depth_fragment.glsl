@@ -0,0 +1,10 @@
+#version 330 core
+in vec3 worldPos;
+
+uniform vec3 lightPos;
+uniform float far;
+
+// linear distance instead of the perspective depth, so every face compares the same values
+void main() {
+    gl_FragDepth = length(worldPos - lightPos) / far;
+}
@@ -0,0 +1,126 @@
+// Shadow lookups with percentage closer filtering, the maps are rendered by `utils::shadow`.
+// The matrices and the bias are mirrored there, change both together.
+
+#define MAX_CASCADES 4
+
+// A directional or spot light, lit where the map is further away than the fragment.
+struct ShadowMap {
+    sampler2D map;
+    mat4 lightSpace;
+    float bias;
+    float slopeBias;
+    int pcfRadius;
+};
+
+// Holds the distance to the light divided by `far`.
+struct PointShadow {
+    samplerCube map;
+    vec3 position;
+    float far;
+    float bias;
+    int pcfRadius;
+};
+
+struct CascadedShadow {
+    sampler2DArray maps;
+    mat4 lightSpaces[MAX_CASCADES];
+    // view space distance where each cascade ends
+    vec4 splits;
+    int count;
+    float bias;
+    float slopeBias;
+    int pcfRadius;
+};
+
+float shadowBias(float bias, float slopeBias, vec3 normal, vec3 lightDir) {
+    return max(slopeBias * (1.0 - dot(normal, lightDir)), bias);
+}
+
+// `worldPos` in the [0, 1] texture and depth range of the map.
+vec3 shadowCoords(mat4 lightSpace, vec3 worldPos) {
+    vec4 clip = lightSpace * vec4(worldPos, 1.0);
+    return clip.xyz / clip.w * 0.5 + 0.5;
+}
+
+// The fraction of the `2 * radius + 1` square of texels around `coords` in shadow.
+float pcf(sampler2D map, vec3 coords, float bias, int radius) {
+    // beyond the far plane of the light
+    if (coords.z > 1.0) {
+        return 0.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(map, 0));
+    float shadow = 0.0;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            float closest = texture(map, coords.xy + vec2(x, y) * texel).r;
+            shadow += coords.z - bias > closest ? 1.0 : 0.0;
+        }
+    }
+    float side = float(2 * radius + 1);
+    return shadow / (side * side);
+}
+
+float pcfLayer(sampler2DArray maps, vec3 coords, int layer, float bias, int radius) {
+    if (coords.z > 1.0) {
+        return 0.0;
+    }
+    vec2 texel = 1.0 / vec2(textureSize(maps, 0).xy);
+    float shadow = 0.0;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            float closest = texture(maps, vec3(coords.xy + vec2(x, y) * texel, float(layer))).r;
+            shadow += coords.z - bias > closest ? 1.0 : 0.0;
+        }
+    }
+    float side = float(2 * radius + 1);
+    return shadow / (side * side);
+}
+
+// 1 in shadow and 0 lit, `lightDir` points from the surface towards the light.
+float shadow(ShadowMap s, vec3 worldPos, vec3 normal, vec3 lightDir) {
+    vec3 coords = shadowCoords(s.lightSpace, worldPos);
+    return pcf(s.map, coords, shadowBias(s.bias, s.slopeBias, normal, lightDir), s.pcfRadius);
+}
+
+// Directions spread around the lookup vector, most of them are sampled apart.
+const vec3 POINT_SHADOW_OFFSETS[20] = vec3[](
+    vec3(1, 1, 1), vec3(1, -1, 1), vec3(-1, -1, 1), vec3(-1, 1, 1),
+    vec3(1, 1, -1), vec3(1, -1, -1), vec3(-1, -1, -1), vec3(-1, 1, -1),
+    vec3(1, 1, 0), vec3(1, -1, 0), vec3(-1, -1, 0), vec3(-1, 1, 0),
+    vec3(1, 0, 1), vec3(-1, 0, 1), vec3(1, 0, -1), vec3(-1, 0, -1),
+    vec3(0, 1, 1), vec3(0, -1, 1), vec3(0, -1, -1), vec3(0, 1, -1)
+);
+
+float pointShadow(PointShadow s, vec3 worldPos, vec3 viewPos) {
+    vec3 fromLight = worldPos - s.position;
+    float current = length(fromLight);
+    if (s.pcfRadius == 0) {
+        float closest = texture(s.map, fromLight).r * s.far;
+        return current - s.bias > closest ? 1.0 : 0.0;
+    }
+
+    // softer further away from the viewer
+    float disk = float(s.pcfRadius) * (1.0 + length(viewPos - worldPos) / s.far) / 25.0;
+    float shadow = 0.0;
+    for (int i = 0; i < 20; i++) {
+        float closest = texture(s.map, fromLight + POINT_SHADOW_OFFSETS[i] * disk).r * s.far;
+        shadow += current - s.bias > closest ? 1.0 : 0.0;
+    }
+    return shadow / 20.0;
+}
+
+// `viewDepth` is the distance in front of the camera, `-viewPos.z` in view space.
+int cascadeIndex(CascadedShadow s, float viewDepth) {
+    for (int i = 0; i < s.count - 1; i++) {
+        if (viewDepth < s.splits[i]) {
+            return i;
+        }
+    }
+    return s.count - 1;
+}
+
+float cascadedShadow(CascadedShadow s, vec3 worldPos, float viewDepth, vec3 normal, vec3 lightDir) {
+    int layer = cascadeIndex(s, viewDepth);
+    vec3 coords = shadowCoords(s.lightSpaces[layer], worldPos);
+    return pcfLayer(s.maps, coords, layer, shadowBias(s.bias, s.slopeBias, normal, lightDir), s.pcfRadius);
+}
@@ -0,0 +1,11 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D screen;
+uniform sampler2D ssao;
+
+void main() {
+    vec4 color = texture(screen, texCoords);
+    fragColor = vec4(color.rgb * texture(ssao, texCoords).r, color.a);
+}
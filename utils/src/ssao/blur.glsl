@@ -0,0 +1,19 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+uniform sampler2D occlusion;
+uniform vec2 texelSize;
+// the noise size, averaging a whole tile removes its pattern
+uniform int size;
+
+void main() {
+    float sum = 0.0;
+    for (int y = 0; y < size; y++) {
+        for (int x = 0; x < size; x++) {
+            vec2 offset = vec2(x - size / 2, y - size / 2) * texelSize;
+            sum += texture(occlusion, texCoords + offset).r;
+        }
+    }
+    fragColor = vec4(vec3(sum / float(size * size)), 1.0);
+}
@@ -0,0 +1,329 @@
+//! Screen-space ambient occlusion: a hemisphere of samples around each pixel is compared
+//! against the depth buffer, then blurred over the size of the rotation noise.
+//!
+//! [`Ssao::render`] works from the depth and world normals of a
+//! [`GBuffer`](crate::deferred::GBuffer), or from any depth texture alone with normals
+//! reconstructed from it, which is what [`Pass::ssao`](crate::postprocess::Pass::ssao)
+//! does with the scene depth of a post-processing chain. The kernel and noise are
+//! generated here from a seed so they can be tested without a context.
+
+use std::ffi::CString;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use gl::types::*;
+use nalgebra::{Matrix4, Vector3};
+
+use crate::camera::Camera;
+use crate::framebuffer::{Framebuffer, FramebufferConfig};
+use crate::postprocess::FULLSCREEN_VERTEX;
+use crate::shader::Shader;
+use crate::texture::{Texture, TextureFormat};
+
+pub const OCCLUSION: &str = include_str!("occlusion.glsl");
+pub const BLUR: &str = include_str!("blur.glsl");
+/// Multiplies `screen` by the `ssao` texture.
+pub const APPLY: &str = include_str!("apply.glsl");
+
+/// Size of the `samples` array of [`OCCLUSION`].
+pub const MAX_SAMPLES: usize = 64;
+
+/// SplitMix64, small and seedable so the kernel is the same on every run.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+/// `count` points in the unit hemisphere around `+z`, closer to the center at the start
+/// so the occlusion weighs nearby geometry more.
+pub fn kernel(count: usize, seed: u64) -> Vec<Vector3<f32>> {
+    let mut rng = Rng::new(seed);
+    (0..count)
+        .map(|i| {
+            let direction = loop {
+                let v = Vector3::new(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0), rng.next_f32());
+                // rejected outside the ball, so the directions are not biased to the corners
+                let length = v.norm();
+                if length > 1e-3 && length <= 1.0 {
+                    break v / length;
+                }
+            };
+            let t = i as f32 / count as f32;
+            let scale = 0.1 + 0.9 * t * t;
+            direction * rng.next_f32() * scale
+        })
+        .collect()
+}
+
+/// `size * size` unit vectors in the xy plane, rotating the kernel around the normal.
+pub fn noise(size: u32, seed: u64) -> Vec<Vector3<f32>> {
+    let mut rng = Rng::new(seed);
+    (0..size * size)
+        .map(|_| {
+            let angle = rng.range(0.0, std::f32::consts::TAU);
+            Vector3::new(angle.cos(), angle.sin(), 0.0)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsaoConfig {
+    /// View space distance the hemisphere reaches.
+    pub radius: f32,
+    /// Depth a sample must be behind the surface to occlude, against acne on flat surfaces.
+    pub bias: f32,
+    /// At most [`MAX_SAMPLES`].
+    pub samples: usize,
+    /// Width and height of the rotation noise, and of the blur removing its pattern.
+    pub noise_size: u32,
+    /// Contrast, the occlusion is raised to it.
+    pub power: f32,
+    pub seed: u64,
+}
+
+impl Default for SsaoConfig {
+    fn default() -> Self {
+        Self { radius: 0.5, bias: 0.025, samples: 64, noise_size: 4, power: 1.0, seed: 0 }
+    }
+}
+
+impl SsaoConfig {
+    fn validate(&self) -> Result<()> {
+        if !(1..=MAX_SAMPLES).contains(&self.samples) {
+            bail!("{} samples, between 1 and {MAX_SAMPLES} are supported", self.samples);
+        }
+        if self.noise_size == 0 {
+            bail!("the noise needs at least one pixel");
+        }
+        Ok(())
+    }
+}
+
+fn uniform_location(program: GLuint, name: &str) -> GLint {
+    let c_name = CString::new(name).unwrap();
+    unsafe { gl::GetUniformLocation(program, c_name.as_ptr()) }
+}
+
+/// The occlusion and blur targets with the kernel and noise of a [`SsaoConfig`].
+pub struct Ssao {
+    config: SsaoConfig,
+    occlusion: Shader<'static>,
+    blur: Shader<'static>,
+    noise: Texture,
+    targets: [Framebuffer; 2],
+    projection: Matrix4<f32>,
+    view: Matrix4<f32>,
+    vao: GLuint,
+}
+
+impl Ssao {
+    pub fn new(config: SsaoConfig, width: u32, height: u32) -> Result<Self> {
+        config.validate()?;
+        let target = || Framebuffer::new(FramebufferConfig::new(width, height).with_color(TextureFormat::R8));
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+        let mut ssao = Self {
+            config,
+            occlusion: Shader::new(FULLSCREEN_VERTEX, OCCLUSION),
+            blur: Shader::new(FULLSCREEN_VERTEX, BLUR),
+            noise: Texture::new_2d(1, 1, TextureFormat::Rgb32F),
+            targets: [target()?, target()?],
+            projection: Matrix4::identity(),
+            view: Matrix4::identity(),
+            vao,
+        };
+        ssao.set_config(config)?;
+        Ok(ssao)
+    }
+
+    pub fn config(&self) -> &SsaoConfig {
+        &self.config
+    }
+
+    /// Regenerate the kernel and noise.
+    pub fn set_config(&mut self, config: SsaoConfig) -> Result<()> {
+        config.validate()?;
+        self.config = config;
+
+        let size = config.noise_size;
+        // another seed than the kernel, so the two are not correlated
+        let data: Vec<u8> = noise(size, config.seed.wrapping_add(1)).iter().flat_map(|v| v.iter().copied()).flat_map(f32::to_ne_bytes).collect();
+        self.noise = Texture::from_data(size, size, TextureFormat::Rgb32F, &data)?;
+        self.noise.set_wrap(gl::REPEAT);
+        self.noise.set_filter(gl::NEAREST, gl::NEAREST);
+
+        // uniforms stay in the program, the kernel only changes with the config
+        let samples = kernel(config.samples, config.seed);
+        self.occlusion.activate()?;
+        unsafe {
+            crate::gl_check!(gl::Uniform3fv(uniform_location(self.occlusion.id(), "samples"), samples.len() as _, samples.as_ptr() as *const _));
+        }
+        self.occlusion.set_i32("sampleCount", (samples.len() as i32,))
+    }
+
+    /// Use the projection and view of `camera` from now on, every frame it moves.
+    /// The projection must have OpenGL's `[-1, 1]` depth, reversed z is not supported.
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.projection = camera.projection();
+        self.view = camera.view();
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.targets[0].size()
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        for target in &mut self.targets {
+            target.resize(width, height);
+        }
+    }
+
+    /// The blurred occlusion, `1` where nothing is occluded, from `depth` and the world
+    /// space `normals` when there are any. Leaves the blur target bound, bind the next
+    /// target afterwards.
+    pub fn render(&self, depth: &Texture, normals: Option<&Texture>) -> Result<&Texture> {
+        let (w, h) = self.size();
+        let noise_size = self.config.noise_size as f32;
+        let mut previous_vao = 0;
+        unsafe {
+            gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut previous_vao);
+            gl::BindVertexArray(self.vao);
+        }
+
+        self.targets[0].bind();
+        let shader = &self.occlusion;
+        shader.activate()?;
+        depth.bind(0);
+        normals.unwrap_or(depth).bind(1);
+        self.noise.bind(2);
+        shader.set_i32("depth", (0,))?;
+        shader.set_i32("normals", (1,))?;
+        shader.set_i32("hasNormals", (normals.is_some() as i32,))?;
+        shader.set_i32("noise", (2,))?;
+        shader.set_mat4fv("projection", Arc::new(self.projection))?;
+        shader.set_mat4fv("inverseProjection", Arc::new(self.projection.try_inverse().unwrap_or_else(Matrix4::identity)))?;
+        shader.set_mat4fv("view", Arc::new(self.view))?;
+        shader.set_vec2("noiseScale", (w as f32 / noise_size, h as f32 / noise_size))?;
+        shader.set_f32("radius", (self.config.radius,))?;
+        shader.set_f32("bias", (self.config.bias,))?;
+        shader.set_f32("power", (self.config.power,))?;
+        draw_fullscreen();
+
+        self.targets[1].bind();
+        self.blur.activate()?;
+        self.targets[0].color(0).bind(0);
+        self.blur.set_i32("occlusion", (0,))?;
+        self.blur.set_vec2("texelSize", (1.0 / w as f32, 1.0 / h as f32))?;
+        self.blur.set_i32("size", (self.config.noise_size as i32,))?;
+        draw_fullscreen();
+
+        unsafe {
+            gl::BindVertexArray(previous_vao as _);
+        }
+        Ok(self.targets[1].color(0))
+    }
+}
+
+fn draw_fullscreen() {
+    unsafe {
+        crate::gl_check!(gl::DrawArrays(gl::TRIANGLES, 0, 3));
+    }
+}
+
+impl Drop for Ssao {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng_is_deterministic_and_uniform() {
+        let (mut a, mut b) = (Rng::new(7), Rng::new(7));
+        assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+
+        let mut rng = Rng::new(42);
+        let mut buckets = [0; 10];
+        for _ in 0..10000 {
+            let x = rng.next_f32();
+            assert!((0.0..1.0).contains(&x));
+            buckets[(x * 10.0) as usize] += 1;
+        }
+        assert!(buckets.iter().all(|&n| (900..1100).contains(&n)), "{buckets:?}");
+    }
+
+    #[test]
+    fn kernel_fills_the_hemisphere() {
+        let samples = kernel(64, 0);
+        assert_eq!(samples, kernel(64, 0));
+        assert_ne!(samples, kernel(64, 1));
+
+        assert!(samples.iter().all(|s| s.z >= 0.0 && s.norm() <= 1.0));
+        // the scale keeps the first samples close to the center
+        let average = |s: &[Vector3<f32>]| s.iter().map(|v| v.norm()).sum::<f32>() / s.len() as f32;
+        assert!(average(&samples[..16]) < 0.2 && average(&samples[48..]) > 0.2);
+        assert!(samples[..16].iter().all(|s| s.norm() <= 0.1 + 0.9 / 16.0));
+
+        // directions uniform over the hemisphere: symmetric around the normal, and the
+        // mean cosine to it is a half
+        let big = kernel(512, 3);
+        let mean = big.iter().map(|s| s.normalize()).sum::<Vector3<f32>>() / big.len() as f32;
+        assert!(mean.x.abs() < 0.05 && mean.y.abs() < 0.05 && (mean.z - 0.5).abs() < 0.05, "{mean:?}");
+        // and each quadrant around the normal gets its share
+        for quadrant in 0..4 {
+            let (sx, sy) = (if quadrant & 1 == 0 { 1.0 } else { -1.0 }, if quadrant & 2 == 0 { 1.0 } else { -1.0 });
+            let count = big.iter().filter(|s| s.x * sx > 0.0 && s.y * sy > 0.0).count();
+            assert!((96..160).contains(&count), "quadrant {quadrant}: {count}");
+        }
+    }
+
+    #[test]
+    fn noise_rotates_in_the_plane() {
+        let noise = noise(4, 9);
+        assert_eq!(noise.len(), 16);
+        assert_eq!(noise, super::noise(4, 9));
+        assert!(noise.iter().all(|v| v.z == 0.0 && (v.norm() - 1.0).abs() < 1e-5));
+        // the rotations differ, not one angle repeated
+        let mean = noise.iter().sum::<Vector3<f32>>() / 16.0;
+        assert!(mean.norm() < 0.6);
+    }
+
+    #[test]
+    fn config_limits_and_glsl_size() {
+        assert!(SsaoConfig::default().validate().is_ok());
+        assert!(SsaoConfig { samples: MAX_SAMPLES + 1, ..SsaoConfig::default() }.validate().is_err());
+        assert!(SsaoConfig { samples: 0, ..SsaoConfig::default() }.validate().is_err());
+        assert!(SsaoConfig { noise_size: 0, ..SsaoConfig::default() }.validate().is_err());
+        assert!(OCCLUSION.contains(&format!("#define MAX_SAMPLES {MAX_SAMPLES}")));
+    }
+}
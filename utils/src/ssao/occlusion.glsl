@@ -0,0 +1,60 @@
+#version 330 core
+in vec2 texCoords;
+out vec4 fragColor;
+
+// Same as `MAX_SAMPLES` in `utils::ssao`.
+#define MAX_SAMPLES 64
+
+uniform sampler2D depth;
+// world space normals like the G-buffer's, reconstructed from the depth without them
+uniform sampler2D normals;
+uniform bool hasNormals;
+uniform sampler2D noise;
+
+uniform mat4 projection;
+uniform mat4 inverseProjection;
+uniform mat4 view;
+// screen size divided by the noise size, the noise repeats across the screen
+uniform vec2 noiseScale;
+
+// the hemisphere around +z, rotated onto the normal
+uniform vec3 samples[MAX_SAMPLES];
+uniform int sampleCount;
+uniform float radius;
+uniform float bias;
+uniform float power;
+
+vec3 viewPosition(vec2 uv) {
+    vec4 ndc = vec4(uv * 2.0 - 1.0, texture(depth, uv).r * 2.0 - 1.0, 1.0);
+    vec4 position = inverseProjection * ndc;
+    return position.xyz / position.w;
+}
+
+void main() {
+    if (texture(depth, texCoords).r == 1.0) {
+        fragColor = vec4(1.0);
+        return;
+    }
+    vec3 position = viewPosition(texCoords);
+    vec3 normal = hasNormals
+        ? normalize(mat3(view) * texture(normals, texCoords).xyz)
+        : normalize(cross(dFdx(position), dFdy(position)));
+
+    // Gram-Schmidt the random vector into a tangent, each pixel of the noise tile turns
+    // the kernel differently
+    vec3 random = texture(noise, texCoords * noiseScale).xyz;
+    vec3 tangent = normalize(random - normal * dot(random, normal));
+    mat3 tbn = mat3(tangent, cross(normal, tangent), normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < sampleCount; i++) {
+        vec3 samplePos = position + tbn * samples[i] * radius;
+        vec4 offset = projection * vec4(samplePos, 1.0);
+        vec2 uv = offset.xy / offset.w * 0.5 + 0.5;
+        float sampleDepth = viewPosition(uv).z;
+        // geometry far in front of the surface does not darken it
+        float range = smoothstep(0.0, 1.0, radius / abs(position.z - sampleDepth));
+        occlusion += (sampleDepth >= samplePos.z + bias ? 1.0 : 0.0) * range;
+    }
+    fragColor = vec4(vec3(pow(1.0 - occlusion / float(sampleCount), power)), 1.0);
+}
@@ -0,0 +1,378 @@
+//! Frame time statistics, GPU timer queries and an on-screen overlay.
+
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use gl::types::*;
+
+pub mod overlay;
+
+pub use overlay::StatsOverlay;
+
+/// Fixed capacity history, the oldest value is overwritten once full.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T> {
+    data: Vec<T>,
+    capacity: usize,
+    next: usize,
+}
+
+impl<T: Copy> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self { data: Vec::with_capacity(capacity), capacity, next: 0 }
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.data.len() < self.capacity {
+            self.data.push(value);
+        } else {
+            self.data[self.next] = value;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn last(&self) -> Option<T> {
+        (!self.data.is_empty()).then(|| self.data[(self.next + self.capacity - 1) % self.capacity])
+    }
+
+    /// Oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item=T> + '_ {
+        let split = if self.data.len() < self.capacity { 0 } else { self.next };
+        self.data[split..].iter().chain(self.data[..split].iter()).copied()
+    }
+}
+
+/// Nearest-rank percentile of an ascending slice, `p` in `[0, 100]`.
+pub fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Summary of a series of durations, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+}
+
+impl Summary {
+    pub fn of(values: impl Iterator<Item=f32>) -> Self {
+        let mut sorted: Vec<f32> = values.collect();
+        if sorted.is_empty() {
+            return Self::default();
+        }
+        sorted.sort_by(f32::total_cmp);
+
+        Self {
+            count: sorted.len(),
+            mean: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            p50: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+        }
+    }
+
+    pub fn fps(&self) -> f32 {
+        if self.mean > 0.0 { 1000.0 / self.mean } else { 0.0 }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2}ms (p50 {:.2}, p95 {:.2}, p99 {:.2}, min {:.2}, max {:.2})",
+               self.mean, self.p50, self.p95, self.p99, self.min, self.max)
+    }
+}
+
+/// Collects per frame timings: the whole frame, named CPU phases and GPU time.
+///
+/// Phase and GPU samples are kept until the next [`record_frame`](Self::record_frame),
+/// which stores them next to the frame time, so every series has one entry per frame.
+pub struct FrameStats {
+    history: usize,
+    frames: u64,
+    frame_times: RingBuffer<f32>,
+    gpu_times: RingBuffer<Option<f32>>,
+    phases: Vec<(String, RingBuffer<Option<f32>>)>,
+    pending_gpu: Option<f32>,
+    pending_phases: Vec<Option<f32>>,
+}
+
+impl FrameStats {
+    pub const DEFAULT_HISTORY: usize = 240;
+
+    pub fn new(history: usize) -> Self {
+        Self {
+            history,
+            frames: 0,
+            frame_times: RingBuffer::new(history),
+            gpu_times: RingBuffer::new(history),
+            phases: vec![],
+            pending_gpu: None,
+            pending_phases: vec![],
+        }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Record a full frame of `dt` seconds, together with the phases recorded since the last one.
+    pub fn record_frame(&mut self, dt: f32) {
+        self.frames += 1;
+        self.frame_times.push(dt * 1000.0);
+        self.gpu_times.push(self.pending_gpu.take());
+        for ((_, times), pending) in self.phases.iter_mut().zip(&mut self.pending_phases) {
+            times.push(pending.take());
+        }
+    }
+
+    pub fn record_gpu(&mut self, ms: f32) {
+        self.pending_gpu = Some(ms);
+    }
+
+    /// Add `ms` to phase `name` of the current frame.
+    pub fn record_phase(&mut self, name: &str, ms: f32) {
+        let index = match self.phases.iter().position(|(n, _)| n == name) {
+            Some(index) => index,
+            None => {
+                // Earlier frames did not have the phase.
+                let mut times = RingBuffer::new(self.history);
+                for _ in 0..self.frame_times.len() {
+                    times.push(None);
+                }
+                self.phases.push((name.to_owned(), times));
+                self.pending_phases.push(None);
+                self.phases.len() - 1
+            }
+        };
+        *self.pending_phases[index].get_or_insert(0.0) += ms;
+    }
+
+    /// Run `f` and record its CPU time as phase `name`.
+    pub fn time_phase<R>(&mut self, name: &str, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.record_phase(name, start.elapsed().as_secs_f32() * 1000.0);
+        result
+    }
+
+    pub fn frame_times(&self) -> &RingBuffer<f32> {
+        &self.frame_times
+    }
+
+    pub fn frame(&self) -> Summary {
+        Summary::of(self.frame_times.iter())
+    }
+
+    pub fn gpu(&self) -> Option<Summary> {
+        self.gpu_times.iter().any(|ms| ms.is_some()).then(|| Summary::of(self.gpu_times.iter().flatten()))
+    }
+
+    pub fn phase(&self, name: &str) -> Option<Summary> {
+        self.phases.iter().find(|(n, _)| n == name).map(|(_, times)| Summary::of(times.iter().flatten()))
+    }
+
+    pub fn phase_names(&self) -> impl Iterator<Item=&str> {
+        self.phases.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// One row per frame of the history, newest last, empty cells for missing samples.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frame,frame_ms,gpu_ms");
+        for (name, _) in &self.phases {
+            csv += &format!(",{name}_ms");
+        }
+        csv += "\n";
+
+        let first = self.frames - self.frame_times.len() as u64;
+        let mut columns: Vec<_> = std::iter::once(&self.gpu_times)
+            .chain(self.phases.iter().map(|(_, times)| times))
+            .map(RingBuffer::iter)
+            .collect();
+
+        for (row, frame_ms) in self.frame_times.iter().enumerate() {
+            csv += &format!("{},{frame_ms:.3}", first + row as u64);
+            for column in &mut columns {
+                match column.next().flatten() {
+                    Some(ms) => csv += &format!(",{ms:.3}"),
+                    None => csv += ",",
+                }
+            }
+            csv += "\n";
+        }
+        csv
+    }
+
+    pub fn save_csv(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_csv()).with_context(|| format!("writing stats {}", path.display()))
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_HISTORY)
+    }
+}
+
+impl Display for FrameStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let frame = self.frame();
+        write!(f, "fps {:.1} | frame {frame}", frame.fps())?;
+        if let Some(gpu) = self.gpu() {
+            write!(f, " | gpu {:.2}ms", gpu.mean)?;
+        }
+        for (name, times) in &self.phases {
+            write!(f, " | {name} {:.2}ms", Summary::of(times.iter().flatten()).mean)?;
+        }
+        Ok(())
+    }
+}
+
+/// `GL_TIME_ELAPSED` queries in a ring, results are read a few frames later to avoid stalls.
+pub struct GpuTimer {
+    queries: Vec<GLuint>,
+    pending: Vec<bool>,
+    current: usize,
+}
+
+impl GpuTimer {
+    const LATENCY: usize = 4;
+
+    pub fn new() -> Self {
+        let mut queries = vec![0; Self::LATENCY];
+        unsafe { gl::GenQueries(queries.len() as _, queries.as_mut_ptr()); }
+        Self { queries, pending: vec![false; Self::LATENCY], current: 0 }
+    }
+
+    pub fn begin(&mut self) {
+        unsafe { gl::BeginQuery(gl::TIME_ELAPSED, self.queries[self.current]); }
+    }
+
+    /// End the query of this frame and return the oldest finished one, in milliseconds.
+    pub fn end(&mut self) -> Option<f32> {
+        unsafe { gl::EndQuery(gl::TIME_ELAPSED); }
+        self.pending[self.current] = true;
+        self.current = (self.current + 1) % self.queries.len();
+
+        // The slot about to be reused is the oldest one.
+        let (query, pending) = (self.queries[self.current], self.pending[self.current]);
+        if !pending {
+            return None;
+        }
+
+        let mut available = 0;
+        unsafe { gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available); }
+        if available == 0 {
+            return None;
+        }
+
+        let mut ns: GLuint64 = 0;
+        unsafe { gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut ns); }
+        self.pending[self.current] = false;
+        Some(ns as f32 / 1.0e6)
+    }
+}
+
+impl Default for GpuTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteQueries(self.queries.len() as _, self.queries.as_ptr()); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_buffer_wraps() {
+        let mut ring = RingBuffer::new(3);
+        assert_eq!(ring.last(), None);
+
+        for i in 0..5 {
+            ring.push(i);
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.iter().collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(ring.last(), Some(4));
+    }
+
+    #[test]
+    fn percentiles() {
+        let sorted: Vec<f32> = (1..=100).map(|i| i as f32).collect();
+        assert_eq!(percentile(&sorted, 50.0), 50.0);
+        assert_eq!(percentile(&sorted, 95.0), 95.0);
+        assert_eq!(percentile(&sorted, 99.0), 99.0);
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+
+        let summary = Summary::of([4.0, 1.0, 3.0, 2.0].into_iter());
+        assert_eq!((summary.min, summary.max, summary.mean, summary.p50), (1.0, 4.0, 2.5, 2.0));
+        assert_eq!(summary.fps(), 400.0);
+    }
+
+    #[test]
+    fn csv_export() {
+        let mut stats = FrameStats::new(2);
+        stats.record_frame(0.010);
+        stats.record_frame(0.020);
+        stats.record_phase("render", 1.5);
+        stats.record_frame(0.030);
+
+        assert_eq!(stats.to_csv(), "frame,frame_ms,gpu_ms,render_ms\n1,20.000,,\n2,30.000,,1.500\n");
+    }
+
+    #[test]
+    fn csv_rows_stay_aligned() {
+        // Like `App`: phases every frame, the first frame time is skipped and each frame
+        // time is recorded at the start of the next frame.
+        let mut stats = FrameStats::new(8);
+        for frame in 0..4 {
+            if frame > 0 {
+                stats.record_frame(frame as f32 / 1000.0);
+            }
+            stats.record_phase("update", frame as f32 + 0.25);
+            stats.record_gpu(frame as f32 + 0.5);
+        }
+        stats.record_frame(0.004);
+
+        assert_eq!(stats.to_csv(), "frame,frame_ms,gpu_ms,update_ms\n\
+                                    0,1.000,0.500,0.250\n\
+                                    1,2.000,1.500,1.250\n\
+                                    2,3.000,2.500,2.250\n\
+                                    3,4.000,3.500,3.250\n");
+        assert_eq!(stats.phase("update").unwrap().count, 4);
+    }
+}
@@ -0,0 +1,285 @@
+use std::mem::{size_of, size_of_val};
+
+use gl::types::*;
+
+use crate::Shader;
+
+use super::FrameStats;
+
+type Color = [f32; 4];
+
+const BACKGROUND: Color = [0.0, 0.0, 0.0, 0.6];
+const TEXT: Color = [1.0, 1.0, 1.0, 1.0];
+const GOOD: Color = [0.2, 0.9, 0.3, 0.9];
+const SLOW: Color = [0.95, 0.8, 0.2, 0.9];
+const BAD: Color = [0.95, 0.25, 0.2, 0.9];
+const TARGET: Color = [1.0, 1.0, 1.0, 0.4];
+
+/// Frame budgets of 60 and 30 fps, in milliseconds.
+const BUDGET_60: f32 = 1000.0 / 60.0;
+const BUDGET_30: f32 = 1000.0 / 30.0;
+
+const MARGIN: f32 = 8.0;
+const PADDING: f32 = 6.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+/// Size of a font pixel in screen pixels.
+const FONT_SCALE: f32 = 2.0;
+const LINE_HEIGHT: f32 = 7.0 * FONT_SCALE;
+
+/// 3x5 bitmap font, one row per byte from top to bottom, bit 2 is the left column.
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c.to_ascii_lowercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        'a' => [0b000, 0b110, 0b011, 0b101, 0b111],
+        'b' => [0b100, 0b110, 0b101, 0b101, 0b110],
+        'c' => [0b000, 0b011, 0b100, 0b100, 0b011],
+        'd' => [0b001, 0b011, 0b101, 0b101, 0b011],
+        'e' => [0b000, 0b010, 0b111, 0b100, 0b011],
+        'f' => [0b011, 0b100, 0b110, 0b100, 0b100],
+        'g' => [0b011, 0b101, 0b011, 0b001, 0b110],
+        'h' => [0b100, 0b110, 0b101, 0b101, 0b101],
+        'i' => [0b010, 0b000, 0b010, 0b010, 0b010],
+        'j' => [0b001, 0b000, 0b001, 0b101, 0b010],
+        'k' => [0b100, 0b101, 0b110, 0b110, 0b101],
+        'l' => [0b110, 0b010, 0b010, 0b010, 0b111],
+        'm' => [0b000, 0b111, 0b111, 0b101, 0b101],
+        'n' => [0b000, 0b110, 0b101, 0b101, 0b101],
+        'o' => [0b000, 0b010, 0b101, 0b101, 0b010],
+        'p' => [0b000, 0b110, 0b101, 0b110, 0b100],
+        'q' => [0b000, 0b011, 0b101, 0b011, 0b001],
+        'r' => [0b000, 0b011, 0b100, 0b100, 0b100],
+        's' => [0b000, 0b011, 0b110, 0b001, 0b110],
+        't' => [0b010, 0b111, 0b010, 0b010, 0b001],
+        'u' => [0b000, 0b101, 0b101, 0b101, 0b011],
+        'v' => [0b000, 0b101, 0b101, 0b101, 0b010],
+        'w' => [0b000, 0b101, 0b101, 0b111, 0b111],
+        'x' => [0b000, 0b101, 0b010, 0b010, 0b101],
+        'y' => [0b000, 0b101, 0b011, 0b001, 0b110],
+        'z' => [0b000, 0b111, 0b001, 0b100, 0b111],
+        _ => return None,
+    })
+}
+
+/// Triangles in NDC, built from pixel coordinates with the origin at the top left.
+struct Batch {
+    width: f32,
+    height: f32,
+    /// x, y, r, g, b, a per vertex
+    vertices: Vec<f32>,
+}
+
+impl Batch {
+    fn new(width: u32, height: u32) -> Self {
+        Self { width: width.max(1) as f32, height: height.max(1) as f32, vertices: vec![] }
+    }
+
+    fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        let (x0, y0) = (x / self.width * 2.0 - 1.0, 1.0 - y / self.height * 2.0);
+        let (x1, y1) = ((x + w) / self.width * 2.0 - 1.0, 1.0 - (y + h) / self.height * 2.0);
+
+        for (vx, vy) in [(x0, y0), (x1, y0), (x1, y1), (x0, y0), (x1, y1), (x0, y1)] {
+            self.vertices.extend_from_slice(&[vx, vy]);
+            self.vertices.extend_from_slice(&color);
+        }
+    }
+
+    fn text(&mut self, x: f32, y: f32, text: &str, color: Color) {
+        for (i, c) in text.chars().enumerate() {
+            let Some(rows) = glyph(c) else { continue; };
+            let gx = x + i as f32 * 4.0 * FONT_SCALE;
+
+            for (row, bits) in rows.iter().enumerate() {
+                for col in 0..3 {
+                    if bits & (0b100 >> col) != 0 {
+                        self.rect(gx + col as f32 * FONT_SCALE, y + row as f32 * FONT_SCALE, FONT_SCALE, FONT_SCALE, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn vertex_count(&self) -> usize {
+        self.vertices.len() / 6
+    }
+}
+
+fn text_lines(stats: &FrameStats) -> Vec<String> {
+    let frame = stats.frame();
+    let mut lines = vec![
+        format!("fps {:.0}", frame.fps()),
+        format!("ms {:.1} p95 {:.1} p99 {:.1}", frame.mean, frame.p95, frame.p99),
+    ];
+    if let Some(gpu) = stats.gpu() {
+        lines.push(format!("gpu {:.2} ms", gpu.mean));
+    }
+    for name in stats.phase_names() {
+        if let Some(phase) = stats.phase(name) {
+            lines.push(format!("{name} {:.2} ms", phase.mean));
+        }
+    }
+    lines
+}
+
+fn build(stats: &FrameStats, width: u32, height: u32) -> Batch {
+    let mut batch = Batch::new(width, height);
+
+    let lines = text_lines(stats);
+    let graph_width = stats.frame_times().capacity() as f32;
+    let text_width = lines.iter().map(|l| l.len() as f32 * 4.0 * FONT_SCALE).fold(0.0, f32::max);
+    let panel_width = graph_width.max(text_width) + 2.0 * PADDING;
+    let panel_height = lines.len() as f32 * LINE_HEIGHT + GRAPH_HEIGHT + 3.0 * PADDING;
+
+    batch.rect(MARGIN, MARGIN, panel_width, panel_height, BACKGROUND);
+
+    for (i, line) in lines.iter().enumerate() {
+        batch.text(MARGIN + PADDING, MARGIN + PADDING + i as f32 * LINE_HEIGHT, line, TEXT);
+    }
+
+    // Frame time bars, the graph tops out at a 30 fps frame.
+    let graph_x = MARGIN + PADDING;
+    let graph_bottom = MARGIN + panel_height - PADDING;
+    for (i, ms) in stats.frame_times().iter().enumerate() {
+        let h = (ms / BUDGET_30).min(1.0) * GRAPH_HEIGHT;
+        let color = if ms <= BUDGET_60 * 1.05 { GOOD } else if ms <= BUDGET_30 * 1.05 { SLOW } else { BAD };
+        batch.rect(graph_x + i as f32, graph_bottom - h, 1.0, h, color);
+    }
+
+    let target_y = graph_bottom - BUDGET_60 / BUDGET_30 * GRAPH_HEIGHT;
+    batch.rect(graph_x, target_y, graph_width, 1.0, TARGET);
+
+    batch
+}
+
+/// Text and frame time graph in the top left corner, drawn over whatever is rendered.
+pub struct StatsOverlay {
+    shader: Shader<'static>,
+    vao: GLuint,
+    vbo: GLuint,
+}
+
+impl StatsOverlay {
+    pub fn new() -> Self {
+        let shader = Shader::new(
+            include_str!("overlay.vs.glsl"),
+            include_str!("overlay.fg.glsl"),
+        );
+
+        let (mut vao, mut vbo) = (0, 0);
+        unsafe {
+            let mut previous_vao = 0;
+            gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut previous_vao);
+
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            let stride = 6 * size_of::<f32>() as GLsizei;
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, stride, (2 * size_of::<f32>()) as *const _);
+            gl::EnableVertexAttribArray(1);
+
+            gl::BindVertexArray(previous_vao as _);
+        }
+
+        Self { shader, vao, vbo }
+    }
+
+    /// Draw `stats` over a `width`x`height` framebuffer, leaving the bound state as it was.
+    pub fn draw(&self, stats: &FrameStats, width: u32, height: u32) {
+        let batch = build(stats, width, height);
+
+        unsafe {
+            let (mut program, mut vao, mut buffer) = (0, 0, 0);
+            let mut blend_func = [0; 4];
+            let mut polygon_mode = [0; 2];
+            gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut program);
+            gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut vao);
+            gl::GetIntegerv(gl::ARRAY_BUFFER_BINDING, &mut buffer);
+            gl::GetIntegerv(gl::BLEND_SRC_RGB, &mut blend_func[0]);
+            gl::GetIntegerv(gl::BLEND_DST_RGB, &mut blend_func[1]);
+            gl::GetIntegerv(gl::BLEND_SRC_ALPHA, &mut blend_func[2]);
+            gl::GetIntegerv(gl::BLEND_DST_ALPHA, &mut blend_func[3]);
+            gl::GetIntegerv(gl::POLYGON_MODE, polygon_mode.as_mut_ptr());
+            let capabilities = [gl::DEPTH_TEST, gl::BLEND, gl::CULL_FACE]
+                .map(|cap| (cap, gl::IsEnabled(cap) == gl::TRUE));
+
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+
+            self.shader.activate().ok();
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                size_of_val(batch.vertices.as_slice()) as _,
+                batch.vertices.as_ptr() as *const _,
+                gl::STREAM_DRAW);
+            gl::DrawArrays(gl::TRIANGLES, 0, batch.vertex_count() as _);
+
+            gl::UseProgram(program as _);
+            gl::BindVertexArray(vao as _);
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffer as _);
+            gl::BlendFuncSeparate(blend_func[0] as _, blend_func[1] as _, blend_func[2] as _, blend_func[3] as _);
+            gl::PolygonMode(gl::FRONT_AND_BACK, polygon_mode[0] as _);
+            for (cap, enabled) in capabilities {
+                if enabled { gl::Enable(cap) } else { gl::Disable(cap) }
+            }
+        }
+    }
+}
+
+impl Default for StatsOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for StatsOverlay {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.vbo);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlay_geometry_stays_on_screen() {
+        let mut stats = FrameStats::new(120);
+        for i in 0..200 {
+            stats.record_frame(0.010 + (i % 7) as f32 * 0.005);
+        }
+        stats.record_phase("render", 2.0);
+
+        let batch = build(&stats, 800, 600);
+        assert_eq!(batch.vertices.len() % 6, 0);
+        assert!(batch.vertex_count() > 120 * 6);
+        assert!(batch.vertices.chunks(6).all(|v| (-1.0..=1.0).contains(&v[0]) && (-1.0..=1.0).contains(&v[1])));
+
+        for line in text_lines(&stats) {
+            assert!(line.chars().all(|c| c == ' ' || glyph(c).is_some()), "missing glyph in {line:?}");
+        }
+    }
+}
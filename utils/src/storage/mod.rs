@@ -0,0 +1,366 @@
+//! Shader storage buffers holding arrays laid out with `std430`, needs OpenGL 4.3.
+//!
+//! `std430` is `std140` without rounding arrays and structs up to a `vec4`,
+//! so `float[]` and `vec2[]` are tightly packed.
+
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use anyhow::{bail, Result};
+use gl::types::*;
+use nalgebra::{
+    Matrix2, Matrix2x3, Matrix2x4, Matrix3, Matrix3x2, Matrix3x4, Matrix4, Matrix4x2, Matrix4x3,
+    Point3, Vector2, Vector3, Vector4,
+};
+
+pub use learnopengl_utils_derive::Std430;
+
+pub use crate::uniform::{align_to, push_member, struct_offsets, LayoutField};
+
+/// A type with a `std430` layout, see [`Std140`](crate::uniform::Std140).
+pub trait Std430 {
+    const ALIGN: usize;
+    const SIZE: usize;
+
+    fn write_std430(&self, out: &mut [u8]);
+
+    fn read_std430(bytes: &[u8]) -> Self where Self: Sized;
+
+    fn fields() -> Vec<LayoutField> {
+        vec![LayoutField { name: String::new(), offset: 0, size: Self::SIZE }]
+    }
+
+    fn to_std430(&self) -> Vec<u8> {
+        let mut bytes = vec![0; Self::SIZE];
+        self.write_std430(&mut bytes);
+        bytes
+    }
+}
+
+/// Distance between the elements of a `T[]`.
+pub const fn stride<T: Std430>() -> usize {
+    align_to(T::SIZE, T::ALIGN)
+}
+
+/// Alignment of a struct with members of `(align, size)`, the largest one.
+pub const fn struct_align(members: &[(usize, usize)]) -> usize {
+    let mut align = 1;
+    let mut i = 0;
+    while i < members.len() {
+        if members[i].0 > align {
+            align = members[i].0;
+        }
+        i += 1;
+    }
+    align
+}
+
+pub const fn struct_size(members: &[(usize, usize)]) -> usize {
+    let mut end = 0;
+    let mut i = 0;
+    while i < members.len() {
+        end = align_to(end, members[i].0) + members[i].1;
+        i += 1;
+    }
+    align_to(end, struct_align(members))
+}
+
+macro_rules! impl_scalar {
+    ($($t:ty),*) => {$(
+        impl Std430 for $t {
+            const ALIGN: usize = 4;
+            const SIZE: usize = 4;
+
+            fn write_std430(&self, out: &mut [u8]) {
+                out.copy_from_slice(&self.to_ne_bytes());
+            }
+
+            fn read_std430(bytes: &[u8]) -> Self {
+                Self::from_ne_bytes(bytes.try_into().unwrap())
+            }
+        }
+    )*};
+}
+
+impl_scalar!(f32, i32, u32);
+
+impl Std430 for bool {
+    const ALIGN: usize = 4;
+    const SIZE: usize = 4;
+
+    fn write_std430(&self, out: &mut [u8]) {
+        (*self as u32).write_std430(out)
+    }
+
+    fn read_std430(bytes: &[u8]) -> Self {
+        u32::read_std430(bytes) != 0
+    }
+}
+
+macro_rules! impl_vector {
+    ($($vector:ident, $n:literal, $align:literal);*) => {$(
+        impl<T: Std430 + nalgebra::Scalar> Std430 for $vector<T> {
+            const ALIGN: usize = $align;
+            const SIZE: usize = $n * 4;
+
+            fn write_std430(&self, out: &mut [u8]) {
+                for (i, value) in self.iter().enumerate() {
+                    value.write_std430(&mut out[i * 4..i * 4 + 4]);
+                }
+            }
+
+            fn read_std430(bytes: &[u8]) -> Self {
+                Self::from_fn(|i, _| T::read_std430(&bytes[i * 4..i * 4 + 4]))
+            }
+        }
+    )*};
+}
+
+impl_vector!(Vector2, 2, 8; Vector3, 3, 16; Vector4, 4, 16);
+
+impl Std430 for Point3<f32> {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 12;
+
+    fn write_std430(&self, out: &mut [u8]) {
+        self.coords.write_std430(out)
+    }
+
+    fn read_std430(bytes: &[u8]) -> Self {
+        Self::from(Vector3::read_std430(bytes))
+    }
+}
+
+// `matCxR` is laid out like `vecR[C]`, so two row columns are only 8 bytes apart.
+macro_rules! impl_matrix {
+    ($($matrix:ident, $rows:literal, $columns:literal);*) => {$(
+        impl Std430 for $matrix<f32> {
+            const ALIGN: usize = if $rows == 2 { 8 } else { 16 };
+            const SIZE: usize = $columns * Self::ALIGN;
+
+            fn write_std430(&self, out: &mut [u8]) {
+                for (c, column) in self.column_iter().enumerate() {
+                    for (r, value) in column.iter().enumerate() {
+                        let offset = c * Self::ALIGN + r * 4;
+                        value.write_std430(&mut out[offset..offset + 4]);
+                    }
+                }
+            }
+
+            fn read_std430(bytes: &[u8]) -> Self {
+                Self::from_fn(|r, c| {
+                    let offset = c * Self::ALIGN + r * 4;
+                    f32::read_std430(&bytes[offset..offset + 4])
+                })
+            }
+        }
+    )*};
+}
+
+impl_matrix!(
+    Matrix2, 2, 2; Matrix3, 3, 3; Matrix4, 4, 4;
+    Matrix3x2, 3, 2; Matrix4x2, 4, 2;
+    Matrix2x3, 2, 3; Matrix4x3, 4, 3;
+    Matrix2x4, 2, 4; Matrix3x4, 3, 4
+);
+
+impl<T: Std430, const N: usize> Std430 for [T; N] {
+    const ALIGN: usize = T::ALIGN;
+    const SIZE: usize = N * stride::<T>();
+
+    fn write_std430(&self, out: &mut [u8]) {
+        out.copy_from_slice(&to_std430_array(self))
+    }
+
+    fn read_std430(bytes: &[u8]) -> Self {
+        let stride = stride::<T>();
+        std::array::from_fn(|i| T::read_std430(&bytes[i * stride..i * stride + T::SIZE]))
+    }
+
+    fn fields() -> Vec<LayoutField> {
+        let element = T::fields();
+        if let [field] = element.as_slice() {
+            if field.name.is_empty() {
+                return vec![LayoutField { name: "[0]".into(), offset: 0, size: Self::SIZE }];
+            }
+        }
+
+        let mut fields = vec![];
+        for i in 0..N {
+            push_member(&mut fields, &format!("[{i}]"), i * stride::<T>(), element.clone());
+        }
+        fields
+    }
+}
+
+/// The bytes of a `T[]` holding `values`.
+pub fn to_std430_array<T: Std430>(values: &[T]) -> Vec<u8> {
+    let stride = stride::<T>();
+    let mut bytes = vec![0; values.len() * stride];
+    for (value, out) in values.iter().zip(bytes.chunks_exact_mut(stride)) {
+        value.write_std430(&mut out[..T::SIZE]);
+    }
+    bytes
+}
+
+pub fn from_std430_array<T: Std430>(bytes: &[u8]) -> Vec<T> {
+    bytes.chunks_exact(stride::<T>()).map(|element| T::read_std430(&element[..T::SIZE])).collect()
+}
+
+/// A `T[]` bound to a shader storage binding point.
+pub struct ShaderStorageBuffer<T: Std430> {
+    id: GLuint,
+    binding: GLuint,
+    len: usize,
+    _value: PhantomData<T>,
+}
+
+impl<T: Std430> ShaderStorageBuffer<T> {
+    /// `len` zeroed elements.
+    pub fn new(binding: GLuint, len: usize) -> Result<Self> {
+        Self::create(binding, len, &vec![0; len * stride::<T>()])
+    }
+
+    pub fn from_slice(binding: GLuint, values: &[T]) -> Result<Self> {
+        Self::create(binding, values.len(), &to_std430_array(values))
+    }
+
+    fn create(binding: GLuint, len: usize, bytes: &[u8]) -> Result<Self> {
+        crate::compute::require_compute()?;
+
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            assert_ne!(id, 0);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, id);
+            crate::gl_check!(gl::BufferData(gl::SHADER_STORAGE_BUFFER, bytes.len() as _, bytes.as_ptr() as *const _, gl::DYNAMIC_COPY));
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, id);
+        }
+        Ok(Self { id, binding, len, _value: PhantomData })
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn binding(&self) -> GLuint {
+        self.binding
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Replace the elements from `first` on.
+    pub fn write(&self, first: usize, values: &[T]) -> Result<()> {
+        if first + values.len() > self.len {
+            bail!("writing {} elements at {first} overflows a buffer of {}", values.len(), self.len);
+        }
+        let bytes = to_std430_array(values);
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+            crate::gl_check!(gl::BufferSubData(gl::SHADER_STORAGE_BUFFER, (first * stride::<T>()) as _, bytes.len() as _, bytes.as_ptr() as *const _));
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+        Ok(())
+    }
+
+    /// Copy elements back from the GPU, writes of a dispatch are only visible
+    /// after a [`readback_barrier`](crate::compute::readback_barrier).
+    pub fn read_range(&self, range: Range<usize>) -> Result<Vec<T>> {
+        if range.start > range.end || range.end > self.len {
+            bail!("reading {range:?} from a buffer of {}", self.len);
+        }
+        let stride = stride::<T>();
+        let mut bytes = vec![0u8; range.len() * stride];
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+            crate::gl_check!(gl::GetBufferSubData(gl::SHADER_STORAGE_BUFFER, (range.start * stride) as _, bytes.len() as _, bytes.as_mut_ptr() as *mut _));
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+        Ok(from_std430_array(&bytes))
+    }
+
+    pub fn read(&self) -> Result<Vec<T>> {
+        self.read_range(0..self.len)
+    }
+}
+
+impl<T: Std430> Drop for ShaderStorageBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::uniform::Std140;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Std430, Std140)]
+    struct Particle {
+        position: Vector3<f32>,
+        life: f32,
+        velocity: Vector2<f32>,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Std430, Std140)]
+    struct Pair {
+        a: f32,
+        b: f32,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Std430)]
+    struct Packed {
+        a: f32,
+        b: [f32; 3],
+        c: Vector2<f32>,
+        d: Matrix2<f32>,
+    }
+
+    #[test]
+    fn std430_packs_arrays_and_structs() {
+        assert_eq!(<[f32; 3] as Std430>::SIZE, 12);
+        assert_eq!(<[Vector2<f32>; 2] as Std430>::SIZE, 16);
+        assert_eq!(<[Vector3<f32>; 2] as Std430>::SIZE, 32);
+        assert_eq!(<Matrix2<f32> as Std430>::SIZE, 16);
+        assert_eq!(<Matrix3<f32> as Std430>::SIZE, 48);
+
+        // std140 rounds every struct up to a vec4
+        assert_eq!(<Pair as Std430>::SIZE, 8);
+        assert_eq!(<Pair as Std140>::SIZE, 16);
+        assert_eq!(<[Pair; 3] as Std430>::SIZE, 24);
+        assert_eq!(<Particle as Std430>::SIZE, 32);
+        assert_eq!(<Particle as Std140>::SIZE, 32);
+
+        let offsets: Vec<_> = <Packed as Std430>::fields().into_iter().map(|f| (f.name, f.offset)).collect();
+        assert_eq!(offsets, vec![("a".into(), 0), ("b[0]".into(), 4), ("c".into(), 16), ("d".into(), 24)]);
+        assert_eq!(<Packed as Std430>::SIZE, 40);
+    }
+
+    #[test]
+    fn arrays_round_trip() {
+        let particles = [
+            Particle { position: Vector3::new(1.0, 2.0, 3.0), life: 4.0, velocity: Vector2::new(5.0, 6.0) },
+            Particle { position: Vector3::new(-1.0, -2.0, -3.0), life: 0.5, velocity: Vector2::zeros() },
+        ];
+        let bytes = to_std430_array(&particles);
+        assert_eq!(bytes.len(), 64);
+        assert_eq!(bytes[32 + 12..32 + 16], 0.5f32.to_ne_bytes());
+        assert_eq!(from_std430_array::<Particle>(&bytes), particles);
+
+        let packed = Packed { a: 1.0, b: [2.0, 3.0, 4.0], c: Vector2::new(5.0, 6.0), d: Matrix2::new(7.0, 8.0, 9.0, 10.0) };
+        let bytes = packed.to_std430();
+        // column major, the second column right after the first
+        assert_eq!(bytes[24..40], [7.0f32, 9.0, 8.0, 10.0].map(f32::to_ne_bytes).concat());
+        assert_eq!(Packed::read_std430(&bytes), packed);
+    }
+}
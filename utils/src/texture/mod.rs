@@ -0,0 +1,384 @@
+//! Textures owning their gl name, in the formats images and render targets need.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use gl::types::*;
+
+/// Internal format together with the transfer format and type used to upload it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    R8,
+    Rg8,
+    Rgb8,
+    Rgba8,
+    Srgb8Alpha8,
+    R16F,
+    Rg16F,
+    Rgb16F,
+    Rgba16F,
+    R32F,
+    Rgb32F,
+    Rgba32F,
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
+}
+
+impl TextureFormat {
+    pub fn internal_format(self) -> GLenum {
+        match self {
+            Self::R8 => gl::R8,
+            Self::Rg8 => gl::RG8,
+            Self::Rgb8 => gl::RGB8,
+            Self::Rgba8 => gl::RGBA8,
+            Self::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+            Self::R16F => gl::R16F,
+            Self::Rg16F => gl::RG16F,
+            Self::Rgb16F => gl::RGB16F,
+            Self::Rgba16F => gl::RGBA16F,
+            Self::R32F => gl::R32F,
+            Self::Rgb32F => gl::RGB32F,
+            Self::Rgba32F => gl::RGBA32F,
+            Self::Depth24 => gl::DEPTH_COMPONENT24,
+            Self::Depth32F => gl::DEPTH_COMPONENT32F,
+            Self::Depth24Stencil8 => gl::DEPTH24_STENCIL8,
+        }
+    }
+
+    /// The `format` of `glTexImage*`.
+    pub fn format(self) -> GLenum {
+        match self {
+            Self::R8 | Self::R16F | Self::R32F => gl::RED,
+            Self::Rg8 | Self::Rg16F => gl::RG,
+            Self::Rgb8 | Self::Rgb16F | Self::Rgb32F => gl::RGB,
+            Self::Rgba8 | Self::Srgb8Alpha8 | Self::Rgba16F | Self::Rgba32F => gl::RGBA,
+            Self::Depth24 | Self::Depth32F => gl::DEPTH_COMPONENT,
+            Self::Depth24Stencil8 => gl::DEPTH_STENCIL,
+        }
+    }
+
+    /// The `type` of `glTexImage*`, uploads of float formats take `f32`.
+    pub fn pixel_type(self) -> GLenum {
+        match self {
+            Self::R8 | Self::Rg8 | Self::Rgb8 | Self::Rgba8 | Self::Srgb8Alpha8 => gl::UNSIGNED_BYTE,
+            Self::Depth24 => gl::UNSIGNED_INT,
+            Self::Depth24Stencil8 => gl::UNSIGNED_INT_24_8,
+            _ => gl::FLOAT,
+        }
+    }
+
+    /// Values per pixel of an upload in [`format`](Self::format).
+    pub fn channels(self) -> usize {
+        match self.format() {
+            gl::RED | gl::DEPTH_COMPONENT | gl::DEPTH_STENCIL => 1,
+            gl::RG => 2,
+            gl::RGB => 3,
+            _ => 4,
+        }
+    }
+
+    /// Bytes per pixel of an upload in [`format`](Self::format) and [`pixel_type`](Self::pixel_type).
+    pub fn upload_size(self) -> usize {
+        let bytes = if self.pixel_type() == gl::UNSIGNED_BYTE { 1 } else { 4 };
+        self.channels() * bytes
+    }
+
+    pub fn is_depth(self) -> bool {
+        matches!(self, Self::Depth24 | Self::Depth32F | Self::Depth24Stencil8)
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::R16F | Self::Rg16F | Self::Rgb16F | Self::Rgba16F | Self::R32F | Self::Rgb32F | Self::Rgba32F | Self::Depth32F)
+    }
+
+    /// Where a framebuffer attaches it, `None` for color formats.
+    pub fn depth_attachment(self) -> Option<GLenum> {
+        match self {
+            Self::Depth24 | Self::Depth32F => Some(gl::DEPTH_ATTACHMENT),
+            Self::Depth24Stencil8 => Some(gl::DEPTH_STENCIL_ATTACHMENT),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureKind {
+    D2,
+    D2Array { layers: u32 },
+    /// Six square faces in the order of `GL_TEXTURE_CUBE_MAP_POSITIVE_X + face`.
+    Cube,
+}
+
+impl TextureKind {
+    pub fn target(self) -> GLenum {
+        match self {
+            Self::D2 => gl::TEXTURE_2D,
+            Self::D2Array { .. } => gl::TEXTURE_2D_ARRAY,
+            Self::Cube => gl::TEXTURE_CUBE_MAP,
+        }
+    }
+
+    /// Array layers or cube faces, `1` for a plain 2D texture.
+    pub fn layers(self) -> u32 {
+        match self {
+            Self::D2 => 1,
+            Self::D2Array { layers } => layers,
+            Self::Cube => 6,
+        }
+    }
+}
+
+pub struct Texture {
+    id: GLuint,
+    kind: TextureKind,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+}
+
+impl Texture {
+    /// Uninitialized storage, linear filtering and clamped to the edge.
+    /// Depth formats are filtered with `GL_NEAREST`.
+    pub fn new(kind: TextureKind, width: u32, height: u32, format: TextureFormat) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            assert_ne!(id, 0);
+        }
+        let texture = Self { id, kind, format, width, height };
+        texture.alloc(std::ptr::null());
+
+        let filter = if format.is_depth() { gl::NEAREST } else { gl::LINEAR };
+        texture.set_filter(filter, filter);
+        texture.set_wrap(gl::CLAMP_TO_EDGE);
+        texture
+    }
+
+    pub fn new_2d(width: u32, height: u32, format: TextureFormat) -> Self {
+        Self::new(TextureKind::D2, width, height, format)
+    }
+
+    pub fn new_2d_array(width: u32, height: u32, layers: u32, format: TextureFormat) -> Self {
+        Self::new(TextureKind::D2Array { layers }, width, height, format)
+    }
+
+    pub fn new_cube(size: u32, format: TextureFormat) -> Self {
+        Self::new(TextureKind::Cube, size, size, format)
+    }
+
+    /// A 2D texture holding `data`, rows bottom first and tightly packed.
+    pub fn from_data(width: u32, height: u32, format: TextureFormat, data: &[u8]) -> Result<Self> {
+        let expected = width as usize * height as usize * format.upload_size();
+        if data.len() != expected {
+            bail!("{width}x{height} {format:?} needs {expected} bytes, got {}", data.len());
+        }
+        let texture = Self::new_2d(width, height, format);
+        texture.alloc(data.as_ptr() as *const _);
+        Ok(texture)
+    }
+
+    /// Load an image as `Rgba8`, or `Srgb8Alpha8` for colors authored in sRGB,
+    /// flipped to fit the opengl coordinates and with mipmaps.
+    pub fn from_image(path: impl AsRef<Path>, srgb: bool) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path).with_context(|| format!("can not load {}", path.display()))?.flipv().into_rgba8();
+        let format = if srgb { TextureFormat::Srgb8Alpha8 } else { TextureFormat::Rgba8 };
+
+        let texture = Self::from_data(image.width(), image.height(), format, image.as_raw())?;
+        texture.set_wrap(gl::REPEAT);
+        texture.set_filter(gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR);
+        texture.generate_mipmaps();
+        Ok(texture)
+    }
+
+    fn alloc(&self, data: *const std::ffi::c_void) {
+        let f = self.format;
+        let (w, h) = (self.width.max(1) as GLsizei, self.height.max(1) as GLsizei);
+        unsafe {
+            gl::BindTexture(self.target(), self.id);
+            // rows of RGB8 and R8 uploads are not 4 byte aligned
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            match self.kind {
+                TextureKind::D2 => {
+                    crate::gl_check!(gl::TexImage2D(gl::TEXTURE_2D, 0, f.internal_format() as _, w, h, 0, f.format(), f.pixel_type(), data));
+                }
+                TextureKind::D2Array { layers } => {
+                    crate::gl_check!(gl::TexImage3D(gl::TEXTURE_2D_ARRAY, 0, f.internal_format() as _, w, h, layers as _, 0, f.format(), f.pixel_type(), data));
+                }
+                TextureKind::Cube => {
+                    for face in 0..6 {
+                        crate::gl_check!(gl::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + face, 0, f.internal_format() as _, w, h, 0, f.format(), f.pixel_type(), data));
+                    }
+                }
+            }
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn kind(&self) -> TextureKind {
+        self.kind
+    }
+
+    pub fn target(&self) -> GLenum {
+        self.kind.target()
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Reallocate the storage, the old contents are lost.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.alloc(std::ptr::null());
+        }
+    }
+
+    pub fn set_filter(&self, min: GLenum, mag: GLenum) {
+        self.set_parameter(gl::TEXTURE_MIN_FILTER, min as _);
+        self.set_parameter(gl::TEXTURE_MAG_FILTER, mag as _);
+    }
+
+    pub fn set_wrap(&self, wrap: GLenum) {
+        self.set_parameter(gl::TEXTURE_WRAP_S, wrap as _);
+        self.set_parameter(gl::TEXTURE_WRAP_T, wrap as _);
+        self.set_parameter(gl::TEXTURE_WRAP_R, wrap as _);
+    }
+
+    /// Sampled outside `[0, 1]` with `GL_CLAMP_TO_BORDER`.
+    pub fn set_border_color(&self, color: [f32; 4]) {
+        unsafe {
+            gl::BindTexture(self.target(), self.id);
+            gl::TexParameterfv(self.target(), gl::TEXTURE_BORDER_COLOR, color.as_ptr());
+        }
+    }
+
+    /// Compare depth textures against the reference coordinate, for `sampler2DShadow`
+    /// and the other shadow samplers.
+    pub fn set_depth_compare(&self, compare: bool) {
+        let mode = if compare { gl::COMPARE_REF_TO_TEXTURE } else { gl::NONE };
+        self.set_parameter(gl::TEXTURE_COMPARE_MODE, mode as _);
+        self.set_parameter(gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as _);
+    }
+
+    fn set_parameter(&self, name: GLenum, value: GLint) {
+        unsafe {
+            gl::BindTexture(self.target(), self.id);
+            crate::gl_check!(gl::TexParameteri(self.target(), name, value));
+        }
+    }
+
+    /// Sample no mip above `level`, for chains that are only filled up to it.
+    pub fn set_max_level(&self, level: u32) {
+        self.set_parameter(gl::TEXTURE_MAX_LEVEL, level as _);
+    }
+
+    /// Width and height of mip `level`.
+    pub fn mip_size(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// The target of cube face `layer`, or of the whole texture for 2D ones.
+    fn image_target(&self, layer: u32) -> Result<GLenum> {
+        match self.kind {
+            TextureKind::D2 if layer == 0 => Ok(gl::TEXTURE_2D),
+            TextureKind::Cube if layer < 6 => Ok(gl::TEXTURE_CUBE_MAP_POSITIVE_X + layer),
+            kind => bail!("layer {layer} of a {kind:?} texture can not be read or written alone"),
+        }
+    }
+
+    /// Mip `level` of cube face `layer` (`0` for 2D textures) as floats in the channels of
+    /// the format, rows bottom first.
+    pub fn read_f32(&self, layer: u32, level: u32) -> Result<Vec<f32>> {
+        let target = self.image_target(layer)?;
+        let (w, h) = self.mip_size(level);
+        let mut data = vec![0.0f32; w as usize * h as usize * self.format.channels()];
+        unsafe {
+            gl::BindTexture(self.target(), self.id);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            crate::gl_check!(gl::GetTexImage(target, level as _, self.format.format(), gl::FLOAT, data.as_mut_ptr() as *mut _));
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        }
+        Ok(data)
+    }
+
+    /// Replace mip `level` of cube face `layer` with `data` laid out like [`read_f32`](Self::read_f32).
+    pub fn write_f32(&self, layer: u32, level: u32, data: &[f32]) -> Result<()> {
+        let target = self.image_target(layer)?;
+        let (w, h) = self.mip_size(level);
+        let expected = w as usize * h as usize * self.format.channels();
+        if data.len() != expected {
+            bail!("mip {level} of {:?} needs {expected} floats, got {}", self.format, data.len());
+        }
+        let f = self.format;
+        unsafe {
+            gl::BindTexture(self.target(), self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            crate::gl_check!(gl::TexImage2D(target, level as _, f.internal_format() as _, w as _, h as _, 0, f.format(), gl::FLOAT, data.as_ptr() as *const _));
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+        Ok(())
+    }
+
+    pub fn generate_mipmaps(&self) {
+        unsafe {
+            gl::BindTexture(self.target(), self.id);
+            crate::gl_check!(gl::GenerateMipmap(self.target()));
+        }
+    }
+
+    /// Bind to texture unit `unit`, the value of the sampler uniform.
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(self.target(), self.id);
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_table() {
+        assert_eq!(TextureFormat::Rgb8.upload_size(), 3);
+        assert_eq!(TextureFormat::Rgba16F.upload_size(), 16);
+        assert_eq!(TextureFormat::Rg16F.channels(), 2);
+        assert_eq!(TextureFormat::R32F.upload_size(), 4);
+        assert_eq!(TextureFormat::Rgba16F.pixel_type(), gl::FLOAT);
+        assert_eq!(TextureFormat::Srgb8Alpha8.format(), gl::RGBA);
+
+        assert!(TextureFormat::Depth32F.is_depth() && TextureFormat::Depth32F.is_float());
+        assert!(!TextureFormat::Rgba8.is_depth() && !TextureFormat::Rgba8.is_float());
+        assert_eq!(TextureFormat::Depth24.depth_attachment(), Some(gl::DEPTH_ATTACHMENT));
+        assert_eq!(TextureFormat::Depth24Stencil8.depth_attachment(), Some(gl::DEPTH_STENCIL_ATTACHMENT));
+        assert_eq!(TextureFormat::Rgba8.depth_attachment(), None);
+
+        assert_eq!(TextureKind::Cube.layers(), 6);
+        assert_eq!(TextureKind::D2Array { layers: 4 }.target(), gl::TEXTURE_2D_ARRAY);
+    }
+}
@@ -0,0 +1,290 @@
+//! Frame timing on a monotonic clock: variable `dt`, fixed steps with interpolation and frame caps.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Monotonic time since some fixed origin.
+pub trait TimeSource {
+    fn now(&self) -> Duration;
+}
+
+pub struct MonotonicTime {
+    origin: Instant,
+}
+
+impl MonotonicTime {
+    pub fn new() -> Self {
+        Self { origin: Instant::now() }
+    }
+}
+
+impl Default for MonotonicTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for MonotonicTime {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// A clock that only moves when told to, clones share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct ManualTime {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualTime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, dt: Duration) {
+        self.now.set(self.now.get() + dt);
+    }
+
+    pub fn set(&self, now: Duration) {
+        self.now.set(now);
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+/// Per frame timing with pause and time scaling.
+pub struct Clock {
+    source: Box<dyn TimeSource>,
+    last: Duration,
+    raw_dt: f32,
+    dt: f32,
+    elapsed: f64,
+    scale: f32,
+    paused: bool,
+    /// Longest frame accepted, so a breakpoint or a dragged window does not explode the simulation.
+    max_dt: f32,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self::with_source(MonotonicTime::new())
+    }
+
+    pub fn with_source(source: impl TimeSource + 'static) -> Self {
+        let last = source.now();
+        Self {
+            source: Box::new(source),
+            last,
+            raw_dt: 0.0,
+            dt: 0.0,
+            elapsed: 0.0,
+            scale: 1.0,
+            paused: false,
+            max_dt: 0.25,
+        }
+    }
+
+    /// Start a new frame and return its scaled `dt` in seconds.
+    pub fn tick(&mut self) -> f32 {
+        let now = self.source.now();
+        self.raw_dt = (now - self.last).as_secs_f32().min(self.max_dt);
+        self.last = now;
+
+        self.dt = if self.paused { 0.0 } else { self.raw_dt * self.scale };
+        self.elapsed += self.dt as f64;
+        self.dt
+    }
+
+    /// Scaled duration of the last frame in seconds, `0` while paused.
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    /// Real duration of the last frame in seconds, ignoring pause and scale.
+    pub fn raw_dt(&self) -> f32 {
+        self.raw_dt
+    }
+
+    /// Scaled time accumulated over all frames, in seconds.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed as f32
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// `0.5` for slow motion, `2.0` for fast forward.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
+    pub fn set_max_dt(&mut self, max_dt: f32) {
+        self.max_dt = max_dt;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Real time of the underlying source.
+    pub fn now(&self) -> Duration {
+        self.source.now()
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fixed-step accumulator, run [`accumulate`](Self::accumulate) steps per frame and
+/// interpolate the rendered state with [`alpha`](Self::alpha).
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    step: f64,
+    accumulator: f64,
+    /// Upper bound of steps per frame, the excess time is dropped.
+    max_steps: u32,
+}
+
+impl FixedTimestep {
+    pub fn new(hz: f32) -> Self {
+        Self { step: 1.0 / hz as f64, accumulator: 0.0, max_steps: 8 }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn step(&self) -> f32 {
+        self.step as f32
+    }
+
+    /// Add a frame of `dt` seconds and return how many fixed steps to run.
+    pub fn accumulate(&mut self, dt: f32) -> u32 {
+        self.accumulator += dt as f64;
+
+        let steps = (self.accumulator / self.step).floor() as u32;
+        self.accumulator -= steps as f64 * self.step;
+
+        if steps > self.max_steps {
+            self.accumulator = 0.0;
+            return self.max_steps;
+        }
+        steps
+    }
+
+    /// How far the current frame is between the last two steps, in `[0, 1)`.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step) as f32
+    }
+}
+
+/// Caps the frame rate by sleeping the remainder of each frame.
+#[derive(Debug, Clone)]
+pub struct FrameLimiter {
+    frame_time: Duration,
+    next_frame: Option<Duration>,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: f32) -> Self {
+        Self { frame_time: Duration::from_secs_f64(1.0 / fps as f64), next_frame: None }
+    }
+
+    /// How long to wait at `now` for the next frame to start on time.
+    pub fn remaining(&mut self, now: Duration) -> Duration {
+        let next = self.next_frame.unwrap_or(now);
+        let wait = next.saturating_sub(now);
+
+        // Fell behind by more than a frame: restart the schedule instead of rushing to catch up.
+        self.next_frame = Some(if now > next + self.frame_time { now } else { next } + self.frame_time);
+        wait
+    }
+
+    pub fn wait(&mut self, clock: &Clock) {
+        let wait = self.remaining(clock.now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn clock_scales_and_pauses() {
+        let time = ManualTime::new();
+        let mut clock = Clock::with_source(time.clone());
+
+        time.advance(ms(100));
+        assert_eq!(clock.tick(), 0.1);
+
+        clock.set_scale(0.5);
+        time.advance(ms(100));
+        assert_eq!(clock.tick(), 0.05);
+        assert_eq!(clock.raw_dt(), 0.1);
+
+        clock.pause();
+        time.advance(ms(100));
+        assert_eq!(clock.tick(), 0.0);
+
+        clock.resume();
+        time.advance(ms(1000));
+        assert_eq!(clock.tick(), 0.125, "long frames are clamped to max_dt before scaling");
+        assert!((clock.elapsed() - 0.275).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fixed_steps_and_alpha() {
+        let mut fixed = FixedTimestep::new(10.0);
+
+        assert_eq!(fixed.accumulate(0.05), 0);
+        assert!((fixed.alpha() - 0.5).abs() < 1e-5);
+
+        assert_eq!(fixed.accumulate(0.2), 2);
+        assert!((fixed.alpha() - 0.5).abs() < 1e-5);
+
+        let mut fixed = FixedTimestep::new(10.0).with_max_steps(3);
+        assert_eq!(fixed.accumulate(1.0), 3);
+        assert_eq!(fixed.alpha(), 0.0, "time beyond max_steps is dropped");
+    }
+
+    #[test]
+    fn limiter_waits_for_the_next_frame() {
+        let mut limiter = FrameLimiter::new(10.0);
+
+        assert_eq!(limiter.remaining(ms(0)), ms(0));
+        assert_eq!(limiter.remaining(ms(30)), ms(70));
+        assert_eq!(limiter.remaining(ms(200)), ms(0));
+        // way behind schedule, the next frame is one frame time after now
+        assert_eq!(limiter.remaining(ms(500)), ms(0));
+        assert_eq!(limiter.remaining(ms(550)), ms(50));
+    }
+}
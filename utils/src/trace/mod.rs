@@ -0,0 +1,550 @@
+//! Per-frame recording of every gl call with its arguments and return value.
+//!
+//! Recording needs the `gl-trace` feature, which loads gl through the shims of
+//! [`loader`](crate::loader), so bare `gl::` calls are in the trace as well. A capture is started either from code with
+//! [`start`] or by setting `LEARNOPENGL_TRACE=<file>` (and optionally
+//! `LEARNOPENGL_TRACE_FRAMES=<n>`), frames are closed by [`GlContext::swap_buffers`](crate::GlContext::swap_buffers).
+
+use std::fmt::{Debug, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+pub const TRACE_ENV: &str = "LEARNOPENGL_TRACE";
+pub const TRACE_FRAMES_ENV: &str = "LEARNOPENGL_TRACE_FRAMES";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Call {
+    pub name: String,
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+}
+
+impl Display for Call {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.name, self.args.join(", "))?;
+        if let Some(result) = &self.result {
+            write!(f, " -> {result}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Call {
+    /// Parse a call printed by `Display`.
+    fn parse(line: &str) -> Result<Self> {
+        let Some((name, rest)) = line.split_once('(') else { bail!("`{line}` is not a call") };
+
+        let (mut args, mut arg) = (vec![], String::new());
+        let (mut depth, mut quoted, mut escaped) = (0, false, false);
+        let mut chars = rest.char_indices();
+        let end = loop {
+            let Some((i, c)) = chars.next() else { bail!("`{line}` misses its closing parenthesis") };
+            if quoted {
+                quoted = escaped || c != '"';
+                escaped = !escaped && c == '\\';
+            } else {
+                match c {
+                    '"' => quoted = true,
+                    '(' | '[' | '{' => depth += 1,
+                    ')' if depth == 0 => break i,
+                    ')' | ']' | '}' => depth -= 1,
+                    ',' if depth == 0 => {
+                        args.push(std::mem::take(&mut arg).trim().to_owned());
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            arg.push(c);
+        };
+        if !arg.trim().is_empty() || !args.is_empty() {
+            args.push(arg.trim().to_owned());
+        }
+
+        let result = match rest[end + 1..].trim() {
+            "" => None,
+            tail => match tail.strip_prefix("->") {
+                Some(result) => Some(result.trim().to_owned()),
+                None => bail!("`{line}` has trailing `{tail}`"),
+            },
+        };
+        Ok(Self { name: name.trim().to_owned(), args, result })
+    }
+
+    /// Equal but for pointer arguments and results.
+    fn same_values(&self, other: &Call) -> bool {
+        let same = |l: &String, r: &String| l == r || (l.starts_with("0x") && r.starts_with("0x"));
+        self.name == other.name
+            && self.args.len() == other.args.len()
+            && self.args.iter().zip(&other.args).all(|(l, r)| same(l, r))
+            && match (&self.result, &other.result) {
+                (Some(l), Some(r)) => same(l, r),
+                (l, r) => l == r,
+            }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub index: u64,
+    pub calls: Vec<Call>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+    pub frames: Vec<Frame>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffMode {
+    /// Only compare which functions were called.
+    NamesOnly,
+    /// Compare names, arguments and results.
+    Full,
+    /// Like `Full`, but pointers (printed as `0x...`) always match: client memory lives
+    /// at other addresses in another run.
+    IgnorePointers,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    Changed { frame: usize, index: usize, left: Call, right: Call },
+    /// The call only exists in the left trace.
+    Missing { frame: usize, index: usize, call: Call },
+    /// The call only exists in the right trace.
+    Extra { frame: usize, index: usize, call: Call },
+}
+
+impl Trace {
+    pub fn calls(&self) -> impl Iterator<Item=&Call> {
+        self.frames.iter().flat_map(|frame| frame.calls.iter())
+    }
+
+    /// Drop the calls `keep` returns `false` for from every frame, e.g. polling whose count
+    /// depends on the driver before diffing.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Call) -> bool) {
+        for frame in &mut self.frames {
+            frame.calls.retain(&mut keep);
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for frame in &self.frames {
+            text += &format!("# frame {}\n", frame.index);
+            for call in &frame.calls {
+                text += &format!("{call}\n");
+            }
+        }
+        text
+    }
+
+    /// Parse the output of [`to_text`](Self::to_text).
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut trace = Trace::default();
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(index) = line.strip_prefix("# frame ") {
+                let index = index.trim().parse().with_context(|| format!("line {}: bad frame index", number + 1))?;
+                trace.frames.push(Frame { index, calls: vec![] });
+                continue;
+            }
+            let call = Call::parse(line).with_context(|| format!("line {}", number + 1))?;
+            match trace.frames.last_mut() {
+                Some(frame) => frame.calls.push(call),
+                None => bail!("line {}: call before the first frame", number + 1),
+            }
+        }
+        Ok(trace)
+    }
+
+    /// Save as JSON when the path ends with `.json`, as plain text otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = match path.extension() {
+            Some(ext) if ext == "json" => self.to_json()?,
+            _ => self.to_text(),
+        };
+        std::fs::write(path, content).with_context(|| format!("writing trace {}", path.display()))
+    }
+
+    /// Load a trace written by [`save`](Self::save), the extension picks the format the same way.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).with_context(|| format!("reading trace {}", path.display()))?;
+        match path.extension() {
+            Some(ext) if ext == "json" => Self::from_json(&content),
+            _ => Self::from_text(&content),
+        }
+        .with_context(|| format!("parsing trace {}", path.display()))
+    }
+
+    /// Compare two traces frame by frame and call by call.
+    pub fn diff(&self, other: &Trace, mode: DiffMode) -> Vec<Mismatch> {
+        let mut mismatches = vec![];
+        let empty = Frame::default();
+
+        for frame in 0..self.frames.len().max(other.frames.len()) {
+            let left = &self.frames.get(frame).unwrap_or(&empty).calls;
+            let right = &other.frames.get(frame).unwrap_or(&empty).calls;
+
+            for index in 0..left.len().max(right.len()) {
+                match (left.get(index), right.get(index)) {
+                    (Some(l), Some(r)) => {
+                        let same = match mode {
+                            DiffMode::NamesOnly => l.name == r.name,
+                            DiffMode::Full => l == r,
+                            DiffMode::IgnorePointers => l.same_values(r),
+                        };
+                        if !same {
+                            mismatches.push(Mismatch::Changed { frame, index, left: l.clone(), right: r.clone() });
+                        }
+                    }
+                    (Some(call), None) => mismatches.push(Mismatch::Missing { frame, index, call: call.clone() }),
+                    (None, Some(call)) => mismatches.push(Mismatch::Extra { frame, index, call: call.clone() }),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+
+        mismatches
+    }
+}
+
+/// `gl :: DrawElements` and `DrawElements` are the same call, whatever the import style.
+fn normalize_name(name: &str) -> String {
+    let name: String = name.split_whitespace().collect();
+    name.strip_prefix("gl::").map(str::to_owned).unwrap_or(name)
+}
+
+fn format_args(args: &[&dyn Debug]) -> Vec<String> {
+    args.iter().map(|arg| format!("{arg:?}")).collect()
+}
+
+/// `Name(arg, ...)`, used in error reports.
+pub fn format_call(name: &str, args: &[&dyn Debug]) -> String {
+    format!("{}({})", normalize_name(name), format_args(args).join(", "))
+}
+
+#[derive(Default)]
+struct Recorder {
+    /// Frames left to capture, `None` when idle.
+    remaining: Option<usize>,
+    output: Option<PathBuf>,
+    frame_index: u64,
+    current: Vec<Call>,
+    frames: Vec<Frame>,
+}
+
+static RECORDER: Lazy<Mutex<Recorder>> = Lazy::new(Default::default);
+
+/// Capture the next `frames` frames.
+pub fn start(frames: usize) {
+    let mut recorder = RECORDER.lock().unwrap();
+    recorder.remaining = Some(frames);
+    recorder.current.clear();
+    recorder.frames.clear();
+}
+
+/// Capture the next `frames` frames and save them to `path` once done.
+pub fn start_to_file(path: impl Into<PathBuf>, frames: usize) {
+    start(frames);
+    RECORDER.lock().unwrap().output = Some(path.into());
+}
+
+/// Start a capture if `LEARNOPENGL_TRACE` is set.
+pub fn start_from_env() {
+    if let Ok(path) = std::env::var(TRACE_ENV) {
+        let frames = std::env::var(TRACE_FRAMES_ENV).ok().and_then(|n| n.parse().ok()).unwrap_or(1);
+        start_to_file(path, frames);
+    }
+}
+
+pub fn is_capturing() -> bool {
+    RECORDER.lock().unwrap().remaining.is_some()
+}
+
+/// Stop capturing and return everything recorded so far, including an unfinished frame.
+pub fn stop() -> Trace {
+    let mut recorder = RECORDER.lock().unwrap();
+    if !recorder.current.is_empty() {
+        let frame = Frame { index: recorder.frame_index, calls: std::mem::take(&mut recorder.current) };
+        recorder.frames.push(frame);
+    }
+    recorder.remaining = None;
+    recorder.output = None;
+    Trace { frames: std::mem::take(&mut recorder.frames) }
+}
+
+#[doc(hidden)]
+pub fn record(name: &str, args: &[&dyn Debug], result: &dyn Debug) {
+    let mut recorder = RECORDER.lock().unwrap();
+    if recorder.remaining.is_none() {
+        return;
+    }
+
+    let result = format!("{result:?}");
+    recorder.current.push(Call {
+        name: normalize_name(name),
+        args: format_args(args),
+        result: (result != "()").then_some(result),
+    });
+}
+
+/// Close the current frame. The capture stops after its last frame, which is saved when
+/// it was started with [`start_to_file`] and kept for [`stop`] otherwise.
+pub fn end_frame() {
+    let mut recorder = RECORDER.lock().unwrap();
+    let index = recorder.frame_index;
+    recorder.frame_index += 1;
+
+    let Some(remaining) = recorder.remaining else { return; };
+
+    if remaining > 0 {
+        let frame = Frame { index, calls: std::mem::take(&mut recorder.current) };
+        recorder.frames.push(frame);
+    }
+    if remaining > 1 {
+        recorder.remaining = Some(remaining - 1);
+        return;
+    }
+
+    recorder.remaining = None;
+    if let Some(path) = recorder.output.take() {
+        let trace = Trace { frames: std::mem::take(&mut recorder.frames) };
+        match trace.save(&path) {
+            Ok(()) => log::info!("gl trace saved to {}", path.display()),
+            Err(err) => log::error!("{err:#}"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub replayed: usize,
+    /// Calls that are not replayable: object creation, uploads and every other call taking
+    /// client memory.
+    pub skipped: Vec<Call>,
+}
+
+fn arg<T: std::str::FromStr>(call: &Call, i: usize) -> Result<T> {
+    let raw = call.args.get(i).with_context(|| format!("`{call}` has no argument {i}"))?;
+    match raw.parse() {
+        Ok(v) => Ok(v),
+        Err(_) => bail!("`{call}`: cannot parse argument {i} `{raw}`"),
+    }
+}
+
+/// Pointer arguments are only replayable as buffer offsets, e.g. for `DrawElements`.
+fn offset_arg(call: &Call, i: usize) -> Result<*const std::ffi::c_void> {
+    let raw = call.args.get(i).with_context(|| format!("`{call}` has no argument {i}"))?;
+    let offset = usize::from_str_radix(raw.trim_start_matches("0x"), 16)
+        .with_context(|| format!("`{call}`: cannot parse pointer `{raw}`"))?;
+    Ok(offset as *const _)
+}
+
+/// Issue the state and draw calls of `frame` against the current context.
+///
+/// This is a state-only replay: calls creating or filling objects (`Gen*`, `BufferData`,
+/// `ShaderSource`, `CompileShader`, `LinkProgram`, `TexImage*`, ...) are skipped, the trace
+/// only has the addresses of their client data. Object names are replayed verbatim, so they
+/// only line up in a context that created its objects in the same order as the recorded one,
+/// like another run of the same sample.
+///
+/// # Safety
+/// Please ensure that gl is loaded.
+pub unsafe fn replay_frame(frame: &Frame) -> Result<ReplayReport> {
+    let mut report = ReplayReport::default();
+
+    for call in &frame.calls {
+        let c = call;
+        match call.name.as_str() {
+            "ClearColor" => gl::ClearColor(arg(c, 0)?, arg(c, 1)?, arg(c, 2)?, arg(c, 3)?),
+            "ClearDepth" => gl::ClearDepth(arg(c, 0)?),
+            "Clear" => gl::Clear(arg(c, 0)?),
+            "Viewport" => gl::Viewport(arg(c, 0)?, arg(c, 1)?, arg(c, 2)?, arg(c, 3)?),
+            "Enable" => gl::Enable(arg(c, 0)?),
+            "Disable" => gl::Disable(arg(c, 0)?),
+            "PolygonMode" => gl::PolygonMode(arg(c, 0)?, arg(c, 1)?),
+            "DepthFunc" => gl::DepthFunc(arg(c, 0)?),
+            "CullFace" => gl::CullFace(arg(c, 0)?),
+            "BlendFunc" => gl::BlendFunc(arg(c, 0)?, arg(c, 1)?),
+            "LineWidth" => gl::LineWidth(arg(c, 0)?),
+            "UseProgram" => gl::UseProgram(arg(c, 0)?),
+            "BindVertexArray" => gl::BindVertexArray(arg(c, 0)?),
+            "ActiveTexture" => gl::ActiveTexture(arg(c, 0)?),
+            "BindTexture" => gl::BindTexture(arg(c, 0)?, arg(c, 1)?),
+            "Uniform1i" => gl::Uniform1i(arg(c, 0)?, arg(c, 1)?),
+            "Uniform1f" => gl::Uniform1f(arg(c, 0)?, arg(c, 1)?),
+            "DrawArrays" => gl::DrawArrays(arg(c, 0)?, arg(c, 1)?, arg(c, 2)?),
+            "DrawElements" => gl::DrawElements(arg(c, 0)?, arg(c, 1)?, arg(c, 2)?, offset_arg(c, 3)?),
+            _ => {
+                report.skipped.push(call.clone());
+                continue;
+            }
+        }
+        report.replayed += 1;
+    }
+
+    Ok(report)
+}
+
+/// Replay every frame of `trace`, see [`replay_frame`].
+///
+/// # Safety
+/// Please ensure that gl is loaded.
+pub unsafe fn replay(trace: &Trace) -> Result<ReplayReport> {
+    let mut report = ReplayReport::default();
+    for frame in &trace.frames {
+        let frame_report = replay_frame(frame)?;
+        report.replayed += frame_report.replayed;
+        report.skipped.extend(frame_report.skipped);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: &[&str]) -> Call {
+        Call { name: name.to_owned(), args: args.iter().map(|a| a.to_string()).collect(), result: None }
+    }
+
+    fn trace(calls: Vec<Call>) -> Trace {
+        Trace { frames: vec![Frame { index: 0, calls }] }
+    }
+
+    /// The recorder is global, tests using it take turns.
+    static RECORDER_TESTS: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn normalizes_call_names() {
+        let args: [&dyn Debug; 2] = [&4u32, &std::ptr::null::<u8>()];
+        assert_eq!(format_call("gl :: DrawArrays", &args), "DrawArrays(4, 0x0)");
+        assert_eq!(normalize_name("Clear"), "Clear");
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut t = trace(vec![call("Clear", &["16384"])]);
+        t.frames[0].calls.push(Call { result: Some("3".to_owned()), ..call("CreateProgram", &[]) });
+
+        let json = t.to_json().unwrap();
+        assert_eq!(Trace::from_json(&json).unwrap(), t);
+        assert_eq!(t.to_text(), "# frame 0\nClear(16384)\nCreateProgram() -> 3\n");
+    }
+
+    #[test]
+    fn diffs_call_streams() {
+        let left = trace(vec![call("Clear", &["16384"]), call("DrawArrays", &["4", "0", "3"])]);
+        let right = trace(vec![call("Clear", &["16640"]), call("DrawArrays", &["4", "0", "3"]), call("Flush", &[])]);
+
+        assert_eq!(left.diff(&right, DiffMode::NamesOnly), vec![
+            Mismatch::Extra { frame: 0, index: 2, call: call("Flush", &[]) },
+        ]);
+
+        let full = left.diff(&right, DiffMode::Full);
+        assert_eq!(full.len(), 2);
+        assert!(matches!(&full[0], Mismatch::Changed { index: 0, .. }));
+        assert!(right.diff(&left, DiffMode::Full).iter().any(|m| matches!(m, Mismatch::Missing { index: 2, .. })));
+    }
+
+    #[test]
+    fn diffs_ignoring_pointers() {
+        let left = trace(vec![call("BufferData", &["34962", "48", "0x55d0c4a3e2f0", "35044"]), call("DrawArrays", &["4", "0", "3"])]);
+        let mut right = trace(vec![call("BufferData", &["34962", "48", "0x5612f0a01a80", "35044"]), call("DrawArrays", &["4", "0", "3"])]);
+        assert_eq!(left.diff(&right, DiffMode::Full).len(), 1);
+        assert!(left.diff(&right, DiffMode::IgnorePointers).is_empty());
+
+        right.frames[0].calls[1].args[2] = "6".to_owned();
+        assert!(matches!(left.diff(&right, DiffMode::IgnorePointers)[..], [Mismatch::Changed { index: 1, .. }]));
+
+        right.retain(|call| call.name != "DrawArrays");
+        assert!(matches!(left.diff(&right, DiffMode::IgnorePointers)[..], [Mismatch::Missing { index: 1, .. }]));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut t = trace(vec![
+            call("Clear", &["16384"]),
+            call("Flush", &[]),
+            call("GetUniformLocation", &["3", r#""a, (b)\"""#]),
+            call("DebugMessageCallback", &["Some(0x55d0c4a3e2f0)", "0x0"]),
+        ]);
+        t.frames[0].calls[2].result = Some("-1".to_owned());
+        t.frames.push(Frame { index: 1, calls: vec![call("DrawArrays", &["4", "0", "3"])] });
+
+        assert_eq!(Trace::from_text(&t.to_text()).unwrap(), t);
+        assert!(Trace::from_text("Clear(16384)\n").is_err());
+        assert!(Trace::from_text("# frame 0\nClear(16384\n").is_err());
+    }
+
+    #[test]
+    fn loads_what_it_saves() {
+        let t = trace(vec![call("Clear", &["16384"]), Call { result: Some("3".to_owned()), ..call("CreateProgram", &[]) }]);
+        for name in ["learnopengl_trace.json", "learnopengl_trace.txt"] {
+            let path = std::env::temp_dir().join(name);
+            t.save(&path).unwrap();
+            assert_eq!(Trace::load(&path).unwrap(), t, "{name}");
+            std::fs::remove_file(&path).ok();
+        }
+    }
+
+    #[test]
+    fn replay_skips_resource_calls() {
+        let frame = Frame {
+            index: 0,
+            calls: vec![
+                call("GenBuffers", &["1", "0x7ffd5a3c"]),
+                call("BufferData", &["34962", "48", "0x55d0c4a3e2f0", "35044"]),
+                call("ShaderSource", &["1", "1", "0x7ffd5a40", "0x0"]),
+                call("CompileShader", &["1"]),
+                call("LinkProgram", &["3"]),
+                call("TexImage2D", &["3553", "0", "6407", "512", "512", "0", "6407", "5121", "0x7f31c0001010"]),
+            ],
+        };
+        // none of them reach gl, which isn't loaded here
+        let report = unsafe { replay_frame(&frame) }.unwrap();
+        assert_eq!(report.replayed, 0);
+        assert_eq!(report.skipped, frame.calls);
+    }
+
+    #[test]
+    fn capture_stops_after_its_frames() {
+        let _turn = RECORDER_TESTS.lock().unwrap();
+        start(2);
+        for _ in 0..5 {
+            record("gl::Flush", &[], &());
+            end_frame();
+        }
+        assert!(!is_capturing());
+        let trace = stop();
+        assert_eq!(trace.frames.len(), 2);
+        assert_eq!(trace.frames[1].index, trace.frames[0].index + 1);
+        assert!(trace.frames.iter().all(|frame| frame.calls.len() == 1));
+    }
+
+    #[test]
+    fn parses_replay_arguments() {
+        let c = call("DrawElements", &["4", "6", "5125", "0x18"]);
+        assert_eq!(arg::<u32>(&c, 0).unwrap(), 4);
+        assert_eq!(offset_arg(&c, 3).unwrap() as usize, 0x18);
+        assert!(arg::<f32>(&c, 4).is_err());
+    }
+}
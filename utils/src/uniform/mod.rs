@@ -0,0 +1,518 @@
+//! Uniform buffers holding `std140` blocks.
+//!
+//! [`Std140`] is implemented for scalars, nalgebra vectors and matrices and arrays, and
+//! derived for structs of those:
+//!
+//! ```ignore
+//! #[derive(Std140)]
+//! #[std140(rename_all = "camelCase")]
+//! struct Matrices {
+//!     view: Matrix4<f32>,
+//!     projection: Matrix4<f32>,
+//!     view_pos: Vector3<f32>,
+//! }
+//!
+//! let matrices = UniformBuffer::<Matrices>::new(0);
+//! matrices.bind_to(&shader, "Matrices")?;
+//! matrices.set(&Matrices { .. });
+//! ```
+
+use std::ffi::CString;
+use std::marker::PhantomData;
+
+use anyhow::{bail, Result};
+use gl::types::*;
+use nalgebra::{
+    Matrix2, Matrix2x3, Matrix2x4, Matrix3, Matrix3x2, Matrix3x4, Matrix4, Matrix4x2, Matrix4x3,
+    Point3, Vector2, Vector3, Vector4,
+};
+
+pub use learnopengl_utils_derive::Std140;
+
+use crate::shader::Shader;
+
+/// A member of a block, named like OpenGL names it: `light.position`, `values[0]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutField {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// A type with a `std140` layout.
+pub trait Std140 {
+    /// Base alignment in bytes.
+    const ALIGN: usize;
+    /// Size in bytes, including the trailing padding of structs and arrays.
+    const SIZE: usize;
+
+    /// Write the value into `out`, which is exactly [`SIZE`](Self::SIZE) bytes long.
+    fn write_std140(&self, out: &mut [u8]);
+
+    /// Read a value back from [`SIZE`](Self::SIZE) bytes.
+    fn read_std140(bytes: &[u8]) -> Self where Self: Sized;
+
+    /// The members with their offsets, for checking against a program.
+    /// Plain values are one member without a name.
+    fn fields() -> Vec<LayoutField> {
+        vec![LayoutField { name: String::new(), offset: 0, size: Self::SIZE }]
+    }
+
+    fn to_std140(&self) -> Vec<u8> {
+        let mut bytes = vec![0; Self::SIZE];
+        self.write_std140(&mut bytes);
+        bytes
+    }
+}
+
+pub const fn align_to(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+/// Alignment of a struct with members of `(align, size)`, rounded up to a `vec4`.
+pub const fn struct_align(members: &[(usize, usize)]) -> usize {
+    let mut align = 16;
+    let mut i = 0;
+    while i < members.len() {
+        if members[i].0 > align {
+            align = members[i].0;
+        }
+        i += 1;
+    }
+    align
+}
+
+pub const fn struct_offsets<const N: usize>(members: &[(usize, usize)]) -> [usize; N] {
+    let mut offsets = [0; N];
+    let mut end = 0;
+    let mut i = 0;
+    while i < N {
+        offsets[i] = align_to(end, members[i].0);
+        end = offsets[i] + members[i].1;
+        i += 1;
+    }
+    offsets
+}
+
+/// Size of a struct, padded to its alignment so the next member starts after it.
+pub const fn struct_size(members: &[(usize, usize)]) -> usize {
+    let mut end = 0;
+    let mut i = 0;
+    while i < members.len() {
+        end = align_to(end, members[i].0) + members[i].1;
+        i += 1;
+    }
+    align_to(end, struct_align(members))
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if name.is_empty() || name.starts_with('[') {
+        format!("{prefix}{name}")
+    } else {
+        format!("{prefix}.{name}")
+    }
+}
+
+/// Add the `fields` of a struct member at `offset`, used by the derive.
+pub fn push_member(out: &mut Vec<LayoutField>, name: &str, offset: usize, fields: Vec<LayoutField>) {
+    out.extend(fields.into_iter().map(|field| LayoutField {
+        name: join(name, &field.name),
+        offset: offset + field.offset,
+        size: field.size,
+    }));
+}
+
+macro_rules! impl_scalar {
+    ($($t:ty),*) => {$(
+        impl Std140 for $t {
+            const ALIGN: usize = 4;
+            const SIZE: usize = 4;
+
+            fn write_std140(&self, out: &mut [u8]) {
+                out.copy_from_slice(&self.to_ne_bytes());
+            }
+
+            fn read_std140(bytes: &[u8]) -> Self {
+                Self::from_ne_bytes(bytes.try_into().unwrap())
+            }
+        }
+    )*};
+}
+
+impl_scalar!(f32, i32, u32);
+
+/// GLSL `bool`, four bytes.
+impl Std140 for bool {
+    const ALIGN: usize = 4;
+    const SIZE: usize = 4;
+
+    fn write_std140(&self, out: &mut [u8]) {
+        (*self as u32).write_std140(out)
+    }
+
+    fn read_std140(bytes: &[u8]) -> Self {
+        u32::read_std140(bytes) != 0
+    }
+}
+
+macro_rules! impl_vector {
+    ($($vector:ident, $n:literal, $align:literal);*) => {$(
+        impl<T: Std140 + nalgebra::Scalar> Std140 for $vector<T> {
+            const ALIGN: usize = $align;
+            const SIZE: usize = $n * 4;
+
+            fn write_std140(&self, out: &mut [u8]) {
+                for (i, value) in self.iter().enumerate() {
+                    value.write_std140(&mut out[i * 4..i * 4 + 4]);
+                }
+            }
+
+            fn read_std140(bytes: &[u8]) -> Self {
+                Self::from_fn(|i, _| T::read_std140(&bytes[i * 4..i * 4 + 4]))
+            }
+        }
+    )*};
+}
+
+impl_vector!(Vector2, 2, 8; Vector3, 3, 16; Vector4, 4, 16);
+
+impl Std140 for Point3<f32> {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 12;
+
+    fn write_std140(&self, out: &mut [u8]) {
+        self.coords.write_std140(out)
+    }
+
+    fn read_std140(bytes: &[u8]) -> Self {
+        Self::from(Vector3::read_std140(bytes))
+    }
+}
+
+// Columns are padded to a `vec4`, so `matCxR` is laid out like `vec4[C]`.
+macro_rules! impl_matrix {
+    ($($matrix:ident, $rows:literal, $columns:literal);*) => {$(
+        impl Std140 for $matrix<f32> {
+            const ALIGN: usize = 16;
+            const SIZE: usize = $columns * 16;
+
+            fn write_std140(&self, out: &mut [u8]) {
+                for (c, column) in self.column_iter().enumerate() {
+                    for (r, value) in column.iter().enumerate() {
+                        let offset = c * 16 + r * 4;
+                        value.write_std140(&mut out[offset..offset + 4]);
+                    }
+                }
+            }
+
+            fn read_std140(bytes: &[u8]) -> Self {
+                Self::from_fn(|r, c| f32::read_std140(&bytes[c * 16 + r * 4..c * 16 + r * 4 + 4]))
+            }
+        }
+    )*};
+}
+
+impl_matrix!(
+    Matrix2, 2, 2; Matrix3, 3, 3; Matrix4, 4, 4;
+    Matrix3x2, 3, 2; Matrix4x2, 4, 2;
+    Matrix2x3, 2, 3; Matrix4x3, 4, 3;
+    Matrix2x4, 2, 4; Matrix3x4, 3, 4
+);
+
+/// Elements are padded to a `vec4`.
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGN: usize = align_to(T::ALIGN, 16);
+    const SIZE: usize = N * align_to(T::SIZE, Self::ALIGN);
+
+    fn write_std140(&self, out: &mut [u8]) {
+        let stride = align_to(T::SIZE, Self::ALIGN);
+        for (i, element) in self.iter().enumerate() {
+            element.write_std140(&mut out[i * stride..i * stride + T::SIZE]);
+        }
+    }
+
+    fn read_std140(bytes: &[u8]) -> Self {
+        let stride = align_to(T::SIZE, Self::ALIGN);
+        std::array::from_fn(|i| T::read_std140(&bytes[i * stride..i * stride + T::SIZE]))
+    }
+
+    /// OpenGL names arrays of plain values once, `values[0]`, and arrays of structs per element.
+    fn fields() -> Vec<LayoutField> {
+        let element = T::fields();
+        if let [field] = element.as_slice() {
+            if field.name.is_empty() {
+                return vec![LayoutField { name: "[0]".into(), offset: 0, size: Self::SIZE }];
+            }
+        }
+
+        let stride = align_to(T::SIZE, Self::ALIGN);
+        let mut fields = vec![];
+        for i in 0..N {
+            push_member(&mut fields, &format!("[{i}]"), i * stride, element.clone());
+        }
+        fields
+    }
+}
+
+/// Size and member offsets of a uniform block as the driver laid it out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockLayout {
+    pub size: usize,
+    pub members: Vec<(String, usize)>,
+}
+
+impl BlockLayout {
+    /// Read the layout of the uniform block `block` of `shader`.
+    pub fn reflect(shader: &Shader, block: &str) -> Result<Self> {
+        let program = shader.id();
+        let c_block = CString::new(block).unwrap();
+        let index = unsafe { gl::GetUniformBlockIndex(program, c_block.as_ptr()) };
+        if index == gl::INVALID_INDEX {
+            bail!("no uniform block {block} in the program");
+        }
+
+        let (mut size, mut count) = (0, 0);
+        unsafe {
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_ACTIVE_UNIFORMS, &mut count);
+        }
+        let mut indices = vec![0 as GLint; count as usize];
+        unsafe {
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_ACTIVE_UNIFORM_INDICES, indices.as_mut_ptr());
+        }
+
+        let prefix = format!("{block}.");
+        let mut members = vec![];
+        for index in indices {
+            let index = index as GLuint;
+            let mut name = [0u8; 256];
+            let (mut length, mut offset) = (0, 0);
+            unsafe {
+                gl::GetActiveUniformName(program, index, name.len() as _, &mut length, name.as_mut_ptr() as *mut _);
+                gl::GetActiveUniformsiv(program, 1, &index, gl::UNIFORM_OFFSET, &mut offset);
+            }
+            let name = String::from_utf8_lossy(&name[..length as usize]);
+            // members of blocks with an instance name are prefixed with the block name
+            let name = name.strip_prefix(&prefix).unwrap_or(&name).to_string();
+            members.push((name, offset as usize));
+        }
+        Ok(Self { size: size as usize, members })
+    }
+
+    /// Fail with every difference between this layout and the one of `T`.
+    pub fn check<T: Std140>(&self) -> Result<()> {
+        let mut errors = vec![];
+        if self.size != T::SIZE {
+            errors.push(format!("the block is {} bytes, {} expects {}", self.size, std::any::type_name::<T>(), T::SIZE));
+        }
+        for field in T::fields() {
+            match self.members.iter().find(|(name, _)| *name == field.name) {
+                None => errors.push(format!("{} is not in the block", field.name)),
+                Some((_, offset)) if *offset != field.offset => {
+                    errors.push(format!("{} is at offset {offset}, expected {}", field.name, field.offset))
+                }
+                Some(_) => {}
+            }
+        }
+        if !errors.is_empty() {
+            bail!("uniform block layout mismatch:\n{}", errors.join("\n"));
+        }
+        Ok(())
+    }
+}
+
+/// A buffer of one `T`, bound to a uniform binding point.
+pub struct UniformBuffer<T: Std140> {
+    id: GLuint,
+    binding: GLuint,
+    _value: PhantomData<T>,
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    pub fn new(binding: GLuint) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            assert_ne!(id, 0);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+            gl::BufferData(gl::UNIFORM_BUFFER, T::SIZE as _, std::ptr::null(), gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, id);
+        }
+        Self { id, binding, _value: PhantomData }
+    }
+
+    pub fn id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn binding(&self) -> GLuint {
+        self.binding
+    }
+
+    pub fn set(&self, value: &T) {
+        let bytes = value.to_std140();
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+            crate::gl_check!(gl::BufferSubData(gl::UNIFORM_BUFFER, 0, bytes.len() as _, bytes.as_ptr() as *const _));
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+
+    /// Check that `block` of `shader` is laid out like `T`, then point it at this buffer.
+    pub fn bind_to(&self, shader: &Shader, block: &str) -> Result<()> {
+        BlockLayout::reflect(shader, block)?.check::<T>()?;
+        shader.bind_uniform_block(block, self.binding)
+    }
+}
+
+impl<T: Std140> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Std140)]
+    struct F {
+        d: i32,
+        e: Vector2<u32>,
+    }
+
+    #[derive(Std140)]
+    struct O {
+        j: Vector3<u32>,
+        k: Vector2<f32>,
+        l: [f32; 2],
+        m: Vector2<f32>,
+        n: [Matrix3<f32>; 2],
+    }
+
+    /// The example block of the OpenGL 4.6 specification, section 7.6.2.2.
+    #[derive(Std140)]
+    struct Example {
+        a: f32,
+        b: Vector2<f32>,
+        c: Vector3<f32>,
+        f: F,
+        g: f32,
+        h: [f32; 2],
+        /// `mat2x3`: two columns of three rows
+        i: Matrix3x2<f32>,
+        o: [O; 2],
+    }
+
+    #[derive(Std140)]
+    #[std140(rename_all = "camelCase")]
+    struct Renamed {
+        view_pos: Vector3<f32>,
+        #[std140(rename = "lightCount")]
+        count: i32,
+    }
+
+    fn offsets<T: Std140>() -> Vec<(String, usize)> {
+        T::fields().into_iter().map(|field| (field.name, field.offset)).collect()
+    }
+
+    fn owned(fields: &[(&str, usize)]) -> Vec<(String, usize)> {
+        fields.iter().map(|(name, offset)| (name.to_string(), *offset)).collect()
+    }
+
+    #[test]
+    fn plain_types() {
+        assert_eq!((f32::ALIGN, f32::SIZE), (4, 4));
+        assert_eq!((Vector2::<f32>::ALIGN, Vector2::<f32>::SIZE), (8, 8));
+        assert_eq!((Vector3::<f32>::ALIGN, Vector3::<f32>::SIZE), (16, 12));
+        assert_eq!((Vector4::<i32>::ALIGN, Vector4::<i32>::SIZE), (16, 16));
+        assert_eq!((Matrix3::<f32>::ALIGN, Matrix3::<f32>::SIZE), (16, 48));
+        assert_eq!((Matrix4::<f32>::ALIGN, Matrix4::<f32>::SIZE), (16, 64));
+        assert_eq!(<[f32; 3]>::SIZE, 48);
+        assert_eq!(<[Vector3<f32>; 2]>::SIZE, 32);
+        assert_eq!(<[Matrix4<f32>; 2]>::SIZE, 128);
+    }
+
+    #[test]
+    fn specification_example_offsets() {
+        assert_eq!((F::ALIGN, F::SIZE), (16, 16));
+        assert_eq!((O::ALIGN, O::SIZE), (16, 176));
+        assert_eq!((Example::ALIGN, Example::SIZE), (16, 480));
+
+        let example = offsets::<Example>();
+        assert_eq!(example[..9], owned(&[
+            ("a", 0), ("b", 8), ("c", 16), ("f.d", 32), ("f.e", 40), ("g", 48), ("h[0]", 64), ("i", 96),
+            ("o[0].j", 128),
+        ]));
+        assert_eq!(example[9..], owned(&[
+            ("o[0].k", 144), ("o[0].l[0]", 160), ("o[0].m", 192), ("o[0].n[0]", 208),
+            ("o[1].j", 304), ("o[1].k", 320), ("o[1].l[0]", 336), ("o[1].m", 368), ("o[1].n[0]", 384),
+        ]));
+    }
+
+    #[test]
+    fn values_are_written_at_their_offsets() {
+        let m = Matrix3x2::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0);
+        let mut example = Example {
+            a: 1.0,
+            b: Vector2::new(2.0, 3.0),
+            c: Vector3::new(4.0, 5.0, 6.0),
+            f: F { d: -7, e: Vector2::new(1, 0) },
+            g: 8.0,
+            h: [9.0, 10.0],
+            i: m,
+            o: [O { j: Vector3::zeros(), k: Vector2::zeros(), l: [0.0; 2], m: Vector2::zeros(), n: [Matrix3::zeros(); 2] },
+                O { j: Vector3::new(1, 2, 3), k: Vector2::zeros(), l: [0.0, 11.0], m: Vector2::zeros(), n: [Matrix3::zeros(), Matrix3::identity()] }],
+        };
+        example.o[0].m.y = 12.0;
+
+        let bytes = example.to_std140();
+        let float = |offset: usize| f32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let int = |offset: usize| i32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+        assert_eq!(bytes.len(), 480);
+        assert_eq!([float(0), float(8), float(12), float(16), float(24)], [1.0, 2.0, 3.0, 4.0, 6.0]);
+        assert_eq!([int(32), int(40), int(44)], [-7, 1, 0]);
+        assert_eq!([float(48), float(64), float(80)], [8.0, 9.0, 10.0]);
+        // column major, columns 16 bytes apart
+        assert_eq!([float(96), float(100), float(104), float(112), float(116), float(120)], [1.0, 3.0, 5.0, 2.0, 4.0, 6.0]);
+        assert_eq!(float(196), 12.0);
+        assert_eq!([int(304), int(308), int(312)], [1, 2, 3]);
+        assert_eq!(float(352), 11.0);
+        // o[1].n[1] is the identity, from 432
+        assert_eq!([float(432), float(448 + 4), float(464 + 8), float(436)], [1.0, 1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn values_read_back() {
+        let renamed = Renamed { view_pos: Vector3::new(1.0, 2.0, 3.0), count: 4 };
+        let read = Renamed::read_std140(&renamed.to_std140());
+        assert_eq!((read.view_pos, read.count), (renamed.view_pos, renamed.count));
+
+        let matrices = [Matrix3x2::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0), Matrix3x2::identity()];
+        assert_eq!(<[Matrix3x2<f32>; 2]>::read_std140(&matrices.to_std140()), matrices);
+    }
+
+    #[test]
+    fn renamed_fields() {
+        assert_eq!(offsets::<Renamed>(), owned(&[("viewPos", 0), ("lightCount", 12)]));
+        assert_eq!(Renamed::SIZE, 16);
+        assert_eq!(Renamed { view_pos: Vector3::x(), count: 3 }.to_std140()[12..], 3i32.to_ne_bytes());
+    }
+
+    #[test]
+    fn layout_check_reports_differences() {
+        let reflected = BlockLayout { size: 16, members: owned(&[("viewPos", 0), ("lightCount", 12)]) };
+        reflected.check::<Renamed>().unwrap();
+
+        let shifted = BlockLayout { size: 32, members: owned(&[("viewPos", 0), ("lightCount", 16)]) };
+        let error = shifted.check::<Renamed>().unwrap_err().to_string();
+        assert!(error.contains("32 bytes") && error.contains("lightCount is at offset 16, expected 12"), "{error}");
+
+        let missing = BlockLayout { size: 16, members: owned(&[("viewPos", 0)]) };
+        assert!(missing.check::<Renamed>().unwrap_err().to_string().contains("lightCount is not in the block"));
+    }
+}